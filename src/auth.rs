@@ -0,0 +1,337 @@
+//! Bearer-token authentication and role checks for the HTTP API.
+//!
+//! Tokens are only stored as SHA-256 hashes, in the TOML file named by
+//! `[auth] tokens_file`:
+//!
+//! ```text
+//! [[tokens]]
+//! name = "first-admin"
+//! role = "admin"
+//! sha256 = "<printf %s "$TOKEN" | sha256sum>"
+//! ```
+//!
+//! Requests to /api send `Authorization: Bearer <token>`; the streaming
+//! endpoints, which browsers open without custom headers, also take
+//! `?access_token=<token>`. Admins create and revoke further tokens through
+//! /api/tokens, which rewrites the file.
+//!
+//! Without a tokens file the API is only served on loopback, unless
+//! `allow_unauthenticated = true` opens it to every interface.
+
+use anyhow::{bail, Context, Result};
+use axum::extract::{Path as UrlPath, Request, State};
+use axum::http::header::{AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE};
+use axum::http::{HeaderValue, Method, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{delete, get};
+use axum::{Json, Router};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// API access control (`[auth]`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// Hashed tokens; without it the API is open, so it only listens on loopback.
+    pub tokens_file: Option<PathBuf>,
+    /// Listen on every interface even without `tokens_file`, for deployments
+    /// that put their own authentication in front of the collector.
+    pub allow_unauthenticated: bool,
+    /// Origins whose pages may call the API from a browser, e.g.
+    /// `https://soc.example.com`. The bundled UI is same-origin and needs none.
+    pub cors_origins: Vec<String>,
+}
+
+/// What a token allows; each role includes the ones before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Stream, look up and search events, read settings and statistics.
+    Viewer,
+    /// Also change the collector's filters and sources, and try parsing rules.
+    Operator,
+    /// Also manage tokens.
+    Admin,
+}
+
+/// A token as kept in the tokens file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TokenEntry {
+    pub name: String,
+    pub role: Role,
+    /// Hex-encoded SHA-256 of the token.
+    pub sha256: String,
+    pub created: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokensFile {
+    #[serde(default)]
+    tokens: Vec<TokenEntry>,
+}
+
+/// The SHA-256 of `token`, hex encoded, as stored in the tokens file.
+pub fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compares without stopping at the first difference, so timing reveals nothing.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The valid tokens, backed by the tokens file.
+#[derive(Debug)]
+pub struct TokenStore {
+    path: PathBuf,
+    tokens: RwLock<Vec<TokenEntry>>,
+}
+
+impl TokenStore {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path).with_context(|| format!("Failed to read tokens file {}", path.display()))?;
+        let file: TokensFile =
+            toml::from_str(&text).with_context(|| format!("Invalid tokens file {}", path.display()))?;
+        for (i, token) in file.tokens.iter().enumerate() {
+            check_name(&token.name)?;
+            if token.sha256.len() != 64 || !token.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                bail!("Token {} in {} needs a hex SHA-256 hash", token.name, path.display());
+            }
+            if file.tokens[..i].iter().any(|t| t.name == token.name) {
+                bail!("Token name {} appears twice in {}", token.name, path.display());
+            }
+        }
+        let tokens = file.tokens.into_iter().map(|t| TokenEntry { sha256: t.sha256.to_ascii_lowercase(), ..t });
+        Ok(Self { path: path.to_path_buf(), tokens: RwLock::new(tokens.collect()) })
+    }
+
+    /// The name and role of the token, if it is valid.
+    pub fn authenticate(&self, token: &str) -> Option<(String, Role)> {
+        let hash = hash_token(token);
+        let tokens = self.tokens.read().unwrap();
+        tokens.iter().find(|t| same(&t.sha256, &hash)).map(|t| (t.name.clone(), t.role))
+    }
+
+    pub fn list(&self) -> Vec<TokenEntry> {
+        self.tokens.read().unwrap().clone()
+    }
+
+    /// Issues a new token and saves its hash; the token itself is not kept.
+    /// Returns None if a token with that name already exists.
+    pub fn create(&self, name: &str, role: Role) -> Result<Option<String>> {
+        check_name(name)?;
+        let mut bytes = [0u8; 32];
+        getrandom::fill(&mut bytes).map_err(|e| anyhow::anyhow!("No secure random numbers: {}", e))?;
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        let mut tokens = self.tokens.write().unwrap();
+        if tokens.iter().any(|t| t.name == name) {
+            return Ok(None);
+        }
+        let mut updated = tokens.clone();
+        updated.push(TokenEntry { name: name.to_string(), role, sha256: hash_token(&token), created: Some(Utc::now()) });
+        self.save(&updated)?;
+        *tokens = updated;
+        Ok(Some(token))
+    }
+
+    /// Revokes the token named `name`; returns false if there is none.
+    ///
+    /// The last admin token cannot be revoked, as nobody could issue tokens after.
+    pub fn revoke(&self, name: &str) -> Result<bool> {
+        let mut tokens = self.tokens.write().unwrap();
+        let Some(index) = tokens.iter().position(|t| t.name == name) else {
+            return Ok(false);
+        };
+        if tokens[index].role == Role::Admin && tokens.iter().filter(|t| t.role == Role::Admin).count() == 1 {
+            bail!("{} is the last admin token", name);
+        }
+        let mut updated = tokens.clone();
+        updated.remove(index);
+        self.save(&updated)?;
+        *tokens = updated;
+        Ok(true)
+    }
+
+    /// Replaces the tokens file atomically.
+    fn save(&self, tokens: &[TokenEntry]) -> Result<()> {
+        let text = toml::to_string(&TokensFile { tokens: tokens.to_vec() })?;
+        let partial = self.path.with_extension("partial");
+        fs::write(&partial, text).with_context(|| format!("Failed to write {}", partial.display()))?;
+        fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+}
+
+pub fn check_name(name: &str) -> Result<()> {
+    let valid = |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.');
+    if name.is_empty() || name.len() > 64 || !name.chars().all(valid) {
+        bail!("Token names are 1-64 letters, digits, '-', '_' or '.', not {:?}", name);
+    }
+    Ok(())
+}
+
+/// The role a request needs, or `None` for the UI's static files.
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+    if path != "/api" && !path.starts_with("/api/") {
+        return None;
+    }
+    if path == "/api/tokens" || path.starts_with("/api/tokens/") {
+        return Some(Role::Admin);
+    }
+    // POST /api/config replaces the collector's filter and restarts its source.
+    if path == "/api/config" && method != Method::GET && method != Method::HEAD {
+        return Some(Role::Operator);
+    }
+    // POST /api/parse/test compiles and runs caller-supplied patterns.
+    if path == "/api/parse/test" {
+        return Some(Role::Operator);
+    }
+    Some(Role::Viewer)
+}
+
+/// Endpoints opened by `EventSource` or `WebSocket`, which cannot send headers.
+fn takes_query_token(path: &str) -> bool {
+    matches!(path, "/api/events" | "/api/dead-letters" | "/api/ws")
+}
+
+fn bearer(request: &Request) -> Option<String> {
+    if let Some(value) = request.headers().get(AUTHORIZATION) {
+        let value = value.to_str().ok()?;
+        let (scheme, token) = value.split_once(' ')?;
+        return scheme.eq_ignore_ascii_case("bearer").then(|| token.trim().to_string());
+    }
+    if !takes_query_token(request.uri().path()) {
+        return None;
+    }
+    let query = request.uri().query()?;
+    let token = query.split('&').find_map(|pair| pair.strip_prefix("access_token="))?;
+    percent_encoding::percent_decode_str(token).decode_utf8().ok().map(|t| t.into_owned())
+}
+
+fn refuse(status: StatusCode, message: &str) -> Response {
+    let mut response = (status, Json(serde_json::json!({ "error": message }))).into_response();
+    if status == StatusCode::UNAUTHORIZED {
+        response.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    }
+    response
+}
+
+/// Middleware letting a request through only with a token of the role it needs.
+pub async fn require_token(State(store): State<Arc<TokenStore>>, request: Request, next: Next) -> Response {
+    let Some(needed) = required_role(request.method(), request.uri().path()) else {
+        return next.run(request).await;
+    };
+    let Some(token) = bearer(&request) else {
+        return refuse(StatusCode::UNAUTHORIZED, "A bearer token is required");
+    };
+    match store.authenticate(&token) {
+        None => refuse(StatusCode::UNAUTHORIZED, "Invalid token"),
+        Some((_, role)) if role < needed => refuse(StatusCode::FORBIDDEN, "This token's role does not allow that"),
+        Some(_) => next.run(request).await,
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewToken {
+    name: String,
+    role: Role,
+}
+
+#[derive(Serialize)]
+struct IssuedToken {
+    name: String,
+    role: Role,
+    /// Shown this once; only its hash is kept.
+    token: String,
+}
+
+async fn list_tokens(State(store): State<Arc<TokenStore>>) -> Json<Vec<TokenEntry>> {
+    Json(store.list())
+}
+
+async fn create_token(State(store): State<Arc<TokenStore>>, Json(new): Json<NewToken>) -> Response {
+    if let Err(e) = check_name(&new.name) {
+        return refuse(StatusCode::BAD_REQUEST, &format!("{:#}", e));
+    }
+    match store.create(&new.name, new.role) {
+        Ok(Some(token)) => (StatusCode::CREATED, Json(IssuedToken { name: new.name, role: new.role, token })).into_response(),
+        Ok(None) => refuse(StatusCode::CONFLICT, &format!("A token named {} already exists", new.name)),
+        Err(e) => refuse(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+    }
+}
+
+async fn revoke_token(State(store): State<Arc<TokenStore>>, UrlPath(name): UrlPath<String>) -> Response {
+    match store.revoke(&name) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => refuse(StatusCode::NOT_FOUND, &format!("No token named {}", name)),
+        Err(e) => refuse(StatusCode::CONFLICT, &format!("{:#}", e)),
+    }
+}
+
+/// Only the listed origins get CORS headers; an empty list allows none.
+pub fn cors_layer(origins: &[String]) -> Result<CorsLayer> {
+    let origins = origins
+        .iter()
+        .map(|origin| {
+            if origin == "*" {
+                bail!("List CORS origins explicitly instead of \"*\"");
+            }
+            HeaderValue::from_str(origin.trim_end_matches('/')).with_context(|| format!("Invalid CORS origin {}", origin))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::GET, Method::POST, Method::DELETE])
+        .allow_headers([AUTHORIZATION, CONTENT_TYPE]))
+}
+
+/// The port the API and UI are served on.
+pub const PORT: u16 = 9357;
+
+/// Where the API listens: on every interface when tokens are required or
+/// unauthenticated access was explicitly allowed, otherwise on loopback only.
+pub fn listen_address(config: &AuthConfig) -> SocketAddr {
+    let ip = if config.tokens_file.is_some() || config.allow_unauthenticated {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        Ipv4Addr::LOCALHOST
+    };
+    SocketAddr::from((ip, PORT))
+}
+
+/// Puts `app` behind token checks, adds the token management endpoints, and
+/// restricts CORS to the configured origins.
+pub fn protect(app: Router, config: &AuthConfig) -> Result<Router> {
+    let cors = cors_layer(&config.cors_origins)?;
+    let app = match &config.tokens_file {
+        Some(path) => {
+            let store = Arc::new(TokenStore::load(path)?);
+            let tokens = Router::new()
+                .route("/api/tokens", get(list_tokens).post(create_token))
+                .route("/api/tokens/{name}", delete(revoke_token))
+                .with_state(store.clone());
+            app.merge(tokens).layer(middleware::from_fn_with_state(store, require_token))
+        }
+        None if config.allow_unauthenticated => {
+            eprintln!("API authentication is disabled and the API is open on every interface (allow_unauthenticated)");
+            app
+        }
+        None => {
+            eprintln!("API authentication is disabled, so only 127.0.0.1 is served; set [auth] tokens_file to serve other hosts");
+            app
+        }
+    };
+    // Outermost, so that preflight requests are answered before the token check.
+    Ok(app.layer(cors))
+}
@@ -0,0 +1,251 @@
+use crate::model::FieldValue;
+use crate::syslog;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// A template rule: literal text with `{name}` captures, plus the action and outcome
+/// recorded when it matches. Rules are tried in order, so more specific ones come first.
+struct Rule {
+    template: &'static str,
+    action: &'static str,
+    outcome: &'static str,
+}
+
+const fn rule(template: &'static str, action: &'static str, outcome: &'static str) -> Rule {
+    Rule { template, action, outcome }
+}
+
+const SSHD_RULES: &[Rule] = &[
+    rule(
+        "Accepted {auth_method} for {user} from {source_ip} port {source_port} {protocol}: {key_type} {key_fingerprint}",
+        "ssh_login",
+        "success",
+    ),
+    rule("Accepted {auth_method} for {user} from {source_ip} port {source_port} {protocol}", "ssh_login", "success"),
+    rule(
+        "Failed {auth_method} for invalid user {user} from {source_ip} port {source_port} {protocol}",
+        "ssh_invalid_user",
+        "failure",
+    ),
+    rule("Failed {auth_method} for {user} from {source_ip} port {source_port} {protocol}", "ssh_login", "failure"),
+    rule("Invalid user {user} from {source_ip} port {source_port}", "ssh_invalid_user", "failure"),
+    rule("Invalid user {user} from {source_ip}", "ssh_invalid_user", "failure"),
+    rule("Disconnected from user {user} {source_ip} port {source_port}", "ssh_logout", "success"),
+];
+
+const SU_RULES: &[Rule] = &[
+    rule("(to {target_user}) {user} on {tty}", "su", "success"),
+    rule("Successful su for {target_user} by {user}", "su", "success"),
+    rule("FAILED SU (to {target_user}) {user} on {tty}", "su", "failure"),
+    rule("FAILED su for {target_user} by {user}", "su", "failure"),
+];
+
+const USERMOD_RULES: &[Rule] = &[
+    rule("add '{user}' to shadow group '{group}'", "user_group_added", "success"),
+    rule("add '{user}' to group '{group}'", "user_group_added", "success"),
+    rule("delete '{user}' from shadow group '{group}'", "user_group_removed", "success"),
+    rule("delete '{user}' from group '{group}'", "user_group_removed", "success"),
+    rule("lock user '{user}' password", "user_locked", "success"),
+    rule("unlock user '{user}' password", "user_unlocked", "success"),
+    rule("change user '{user}' {change}", "user_modified", "success"),
+    rule("delete user '{user}'", "user_deleted", "success"),
+];
+
+/// PAM messages look the same whichever program logged them.
+const PAM_RULES: &[Rule] = &[
+    rule("pam_unix({pam_service}:session): session opened for user {user}(uid={uid}) by {by}", "session_open", "success"),
+    rule("pam_unix({pam_service}:session): session opened for user {user} by {by}", "session_open", "success"),
+    rule("pam_unix({pam_service}:session): session closed for user {user}", "session_close", "success"),
+    rule("pam_unix({pam_service}:chauthtok): password changed for {user}", "password_changed", "success"),
+    rule("pam_unix({pam_service}:auth): authentication failure; {details}", "auth_failure", "failure"),
+];
+
+/// A line from `/var/log/auth.log` or `/var/log/secure`, split into its syslog header
+/// and the program's message.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthLogLine<'a> {
+    pub timestamp: DateTime<Utc>,
+    pub hostname: &'a str,
+    pub program: &'a str,
+    pub pid: Option<&'a str>,
+    pub message: &'a str,
+}
+
+/// Splits an auth log line into header and message.
+///
+/// Both the traditional `Mmm dd hh:mm:ss` header and the RFC 3339 header used by
+/// newer rsyslog defaults are accepted.
+pub fn parse_line(line: &str) -> Option<AuthLogLine<'_>> {
+    let line = line.trim_end();
+    let (timestamp, rest) = match syslog::parse_bsd_timestamp(line) {
+        Some(parsed) => parsed,
+        None => {
+            let (ts, rest) = line.split_once(' ')?;
+            (DateTime::parse_from_rfc3339(ts).ok()?.with_timezone(&Utc), rest)
+        }
+    };
+
+    let rest = rest.trim_start_matches(' ');
+    let (hostname, rest) = rest.split_once(' ')?;
+    let (tag, message) = syslog::split_tag(rest);
+    let (program, pid) = tag?;
+
+    Some(AuthLogLine { timestamp, hostname, program, pid, message })
+}
+
+/// Extracts structured fields from an authentication message logged by `program`.
+///
+/// On a match, `action` and `outcome` are set along with whatever the rule
+/// captured (`user`, `source_ip`, `source_port`, `auth_method`, `tty`, `command`, ...).
+/// Returns `false`, leaving `fields` untouched, when the message is not recognised.
+pub fn extract(program: &str, message: &str, fields: &mut HashMap<String, FieldValue>) -> bool {
+    let message = message.trim();
+
+    if message.starts_with("pam_") {
+        return apply_rules(PAM_RULES, message, fields);
+    }
+
+    match program {
+        "sshd" => apply_rules(SSHD_RULES, message, fields),
+        "su" => apply_rules(SU_RULES, message, fields),
+        "usermod" | "userdel" => apply_rules(USERMOD_RULES, message, fields),
+        "sudo" => extract_sudo(message, fields),
+        "useradd" | "groupadd" => extract_useradd(message, fields),
+        _ => false,
+    }
+}
+
+/// Types a captured value by field name: ports and ids are numbers, `source_ip` an IP.
+fn typed(name: &str, value: &str) -> FieldValue {
+    match name {
+        "source_port" | "uid" | "gid" => FieldValue::uint(value),
+        "source_ip" => FieldValue::ip(value),
+        _ => value.into(),
+    }
+}
+
+fn apply_rules(rules: &[Rule], message: &str, fields: &mut HashMap<String, FieldValue>) -> bool {
+    for rule in rules {
+        if let Some(captures) = match_template(rule.template, message) {
+            for (name, value) in captures {
+                fields.insert(name.to_string(), typed(name, value));
+            }
+            fields.insert("action".to_string(), rule.action.into());
+            fields.insert("outcome".to_string(), rule.outcome.into());
+            return true;
+        }
+    }
+    false
+}
+
+/// Matches `text` against a template such as `Accepted {auth_method} for {user}`.
+///
+/// Literal segments must match exactly. A capture extends to the first occurrence of
+/// the literal that follows it, or to the end of the text if it is the last segment.
+fn match_template<'t, 'a>(template: &'t str, text: &'a str) -> Option<Vec<(&'t str, &'a str)>> {
+    let mut captures = Vec::new();
+    let mut rest = text;
+    let mut template = template;
+    let mut pending: Option<&'t str> = None;
+
+    loop {
+        let literal_end = template.find('{').unwrap_or(template.len());
+        let literal = &template[..literal_end];
+
+        match pending.take() {
+            Some(name) if literal.is_empty() => {
+                captures.push((name, rest));
+                rest = "";
+            }
+            Some(name) => {
+                let pos = rest.find(literal)?;
+                captures.push((name, &rest[..pos]));
+                rest = &rest[pos + literal.len()..];
+            }
+            None => rest = rest.strip_prefix(literal)?,
+        }
+
+        if literal_end == template.len() {
+            break;
+        }
+        let close = literal_end + template[literal_end..].find('}')?;
+        pending = Some(&template[literal_end + 1..close]);
+        template = &template[close + 1..];
+    }
+
+    rest.is_empty().then_some(captures)
+}
+
+/// `alice : TTY=pts/0 ; PWD=/home/alice ; USER=root ; COMMAND=/usr/bin/id`
+///
+/// Failures put a reason before the key/value list, e.g.
+/// `alice : 3 incorrect password attempts ; TTY=pts/0 ; ...`.
+fn extract_sudo(message: &str, fields: &mut HashMap<String, FieldValue>) -> bool {
+    let Some((user, rest)) = message.split_once(" : ") else {
+        return false;
+    };
+
+    let mut reason = None;
+    let mut parsed = HashMap::new();
+    for (i, part) in rest.split(" ; ").enumerate() {
+        match part.split_once('=') {
+            Some((key, value)) if key.chars().all(|c| c.is_ascii_uppercase()) => {
+                parsed.insert(key, value);
+            }
+            _ if i == 0 => reason = Some(part),
+            _ => {}
+        }
+    }
+    let Some(command) = parsed.get("COMMAND") else {
+        return false;
+    };
+
+    fields.insert("user".to_string(), user.trim().into());
+    fields.insert("command".to_string(), (*command).into());
+    for (key, name) in [("TTY", "tty"), ("PWD", "cwd"), ("USER", "target_user")] {
+        if let Some(value) = parsed.get(key) {
+            fields.insert(name.to_string(), (*value).into());
+        }
+    }
+    fields.insert("action".to_string(), "sudo".into());
+    match reason {
+        Some(reason) => {
+            fields.insert("outcome".to_string(), "failure".into());
+            fields.insert("reason".to_string(), reason.into());
+        }
+        None => {
+            fields.insert("outcome".to_string(), "success".into());
+        }
+    }
+    true
+}
+
+/// `new user: name=bob, UID=1001, GID=1001, home=/home/bob, shell=/bin/bash, from=/dev/pts/0`
+/// and `new group: name=bob, GID=1001`.
+fn extract_useradd(message: &str, fields: &mut HashMap<String, FieldValue>) -> bool {
+    let (action, name_field, rest) = if let Some(rest) = message.strip_prefix("new user: ") {
+        ("user_created", "user", rest)
+    } else if let Some(rest) = message.strip_prefix("new group: ") {
+        ("group_created", "group", rest)
+    } else {
+        return false;
+    };
+
+    for part in rest.split(", ") {
+        if let Some((key, value)) = part.split_once('=') {
+            let key = match key {
+                "name" => name_field,
+                "UID" => "uid",
+                "GID" => "gid",
+                "home" => "home",
+                "shell" => "shell",
+                "from" => "tty",
+                _ => continue,
+            };
+            fields.insert(key.to_string(), typed(key, value));
+        }
+    }
+    fields.insert("action".to_string(), action.into());
+    fields.insert("outcome".to_string(), "success".into());
+    true
+}
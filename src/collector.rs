@@ -1,131 +1,179 @@
+use crate::extract::ExtractionRules;
 use crate::model::AuditEvent;
-use crate::source::AuditSource;
+use crate::normalize::Normalizer;
+use crate::parser::ParserRegistry;
+use crate::source::{AuditSource, Peer};
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use crossbeam_channel::Sender;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
+/// Counters describing what a collector has done so far.
+///
+/// Shared via `Arc` so several collectors can report into the same totals.
+#[derive(Debug, Default)]
+pub struct CollectorStats {
+    pub received: AtomicU64,
+    pub parsed: AtomicU64,
+    pub parse_failures: AtomicU64,
+}
+
+/// A point-in-time copy of `CollectorStats`, suitable for the API.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+pub struct CollectorStatsSnapshot {
+    pub received: u64,
+    pub parsed: u64,
+    pub parse_failures: u64,
+}
+
+impl CollectorStats {
+    pub fn snapshot(&self) -> CollectorStatsSnapshot {
+        CollectorStatsSnapshot {
+            received: self.received.load(Ordering::Relaxed),
+            parsed: self.parsed.load(Ordering::Relaxed),
+            parse_failures: self.parse_failures.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// A raw record that could not be parsed, kept for inspection instead of being dropped.
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub received_at: DateTime<Utc>,
+    /// Name of the parser that rejected the record.
+    pub parser: String,
+    pub error: String,
+    /// The raw record, lossily decoded as UTF-8.
+    pub raw: String,
+    pub peer: Option<String>,
+}
 
 /// The Collector orchestrates reading from the source, parsing, and sending to the pipeline.
 pub struct Collector {
     source: Arc<dyn AuditSource>,
     sender: Sender<AuditEvent>,
+    registry: Arc<ParserRegistry>,
+    dead_letter: Option<Sender<DeadLetter>>,
+    stats: Arc<CollectorStats>,
+    extraction: Option<Arc<ExtractionRules>>,
+    normalizer: Option<Arc<Normalizer>>,
 }
 
 impl Collector {
     /// Creates a new Collector with a source and a destination channel.
     pub fn new(source: Arc<dyn AuditSource>, sender: Sender<AuditEvent>) -> Self {
-        Self { source, sender }
+        Self::with_registry(source, sender, Arc::new(ParserRegistry::default()))
+    }
+
+    /// Creates a Collector that looks up its parser in a custom registry.
+    pub fn with_registry(
+        source: Arc<dyn AuditSource>,
+        sender: Sender<AuditEvent>,
+        registry: Arc<ParserRegistry>,
+    ) -> Self {
+        Self {
+            source,
+            sender,
+            registry,
+            dead_letter: None,
+            stats: Arc::new(CollectorStats::default()),
+            extraction: None,
+            normalizer: None,
+        }
+    }
+
+    /// Routes records that fail to parse to `sender` instead of only counting them.
+    pub fn with_dead_letter(mut self, sender: Sender<DeadLetter>) -> Self {
+        self.dead_letter = Some(sender);
+        self
+    }
+
+    /// Reports into shared counters instead of private ones.
+    pub fn with_stats(mut self, stats: Arc<CollectorStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Applies user-defined extraction rules to every parsed event.
+    pub fn with_extraction(mut self, rules: Arc<ExtractionRules>) -> Self {
+        self.extraction = Some(rules);
+        self
+    }
+
+    /// Rewrites every event into the common schema, after extraction rules have run.
+    pub fn with_normalizer(mut self, normalizer: Arc<Normalizer>) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    pub fn stats(&self) -> Arc<CollectorStats> {
+        self.stats.clone()
     }
 
     /// Runs the collector loop. This consumes the current thread.
     pub fn run(&self) -> Result<()> {
+        let format = self.source.format();
+        let parser = self
+            .registry
+            .parser_for(&format)
+            .context("Failed to select parser for source")?;
+        let source_name = self.source.name();
+
         loop {
             // 1. Receive Raw Data
-            let raw_data = self.source.receive().context("Failed to receive audit data")?;
-            
+            let (raw_data, peer) = self.source.receive_from().context("Failed to receive audit data")?;
+
             if raw_data.is_empty() {
                 continue;
             }
+            self.stats.received.fetch_add(1, Ordering::Relaxed);
 
-            // 2. Parse Data (Simulated for this exercise since raw_data structure varies)
-            // In a real netlink implementation, we would parse the nlmsghdr first.
-            // Here we assume raw_data is the payload string for simplicity of the Mock.
-            if let Ok(event) = self.parse_event(&raw_data) {
-                // 3. Send to Pipeline
-                if let Err(_) = self.sender.send(event) {
-                    println!("Receiver dropped, stopping collector.");
-                    break;
+            // 2. Parse Data with the parser matching the source's declared format
+            let mut event = match parser.parse(&raw_data) {
+                Ok(event) => event,
+                Err(e) => {
+                    self.stats.parse_failures.fetch_add(1, Ordering::Relaxed);
+                    self.send_dead_letter(parser.name(), &e, &raw_data, peer);
+                    continue;
                 }
+            };
+            self.stats.parsed.fetch_add(1, Ordering::Relaxed);
+            event.id = event.compute_id(&source_name, peer.as_ref().map(|p| p.addr.ip()), &raw_data);
+
+            // Tag events from network sources with the sending peer
+            if let Some(peer) = peer {
+                event.fields.insert("peer".to_string(), peer.addr.to_string().into());
+                event.fields.insert("transport".to_string(), peer.transport.into());
             }
-        }
-        Ok(())
-    }
 
-    /// Parses raw bytes into an AuditEvent.
-    /// 
-    /// Assumptions for this simplified implementation:
-    /// - Input bytes are ASCII string representation of an audit log line.
-    /// - Format: `type=1300 ... key=value ...`
-    fn parse_event(&self, raw: &[u8]) -> Result<AuditEvent> {
-        let s = String::from_utf8_lossy(raw);
-        
-        // Defaults
-        let mut type_id = 0;
-        let mut serial = 0;
-        let mut fields = std::collections::HashMap::new();
-
-        // Check if it's JSON (macOS log stream --style json)
-        if s.trim().starts_with('{') {
-             // Try parsing as MacLogEntry
-             if let Ok(entry) = serde_json::from_str::<crate::model::MacLogEntry>(&s) {
-                 type_id = 1; // Generic Type
-                 
-                 if let Some(msg) = entry.event_message {
-                     fields.insert("message".to_string(), msg);
-                 }
-                 if let Some(proc) = entry.process_image_path.clone() {
-                     fields.insert("process".to_string(), proc);
-                 }
-                 if let Some(pid) = entry.process_id {
-                     fields.insert("pid".to_string(), pid.to_string());
-                 }
-                 if let Some(tid) = entry.thread_id {
-                     fields.insert("thread_id".to_string(), tid.to_string());
-                 }
-                 if let Some(sub) = entry.subsystem {
-                     fields.insert("subsystem".to_string(), sub);
-                 }
-                 if let Some(cat) = entry.category {
-                     fields.insert("category".to_string(), cat);
-                 }
-                 if let Some(lib) = entry.process_image_path.as_ref() { 
-                      // Sometimes image path is the library if it's loaded dylib vs executable? 
-                      // Actually processImagePath is usually the main executable.
-                      fields.insert("library".to_string(), lib.clone());
-                 }
-                 
-                 return Ok(AuditEvent {
-                     timestamp: chrono::Utc::now(), // Ideally parse entry.timestamp
-                     record_type: type_id,
-                     sequence: serial,
-                     fields,
-                 });
-             }
-        }
+            if let Some(rules) = &self.extraction {
+                rules.apply(&source_name, &mut event);
+            }
+            if let Some(normalizer) = &self.normalizer {
+                normalizer.normalize(&format, &mut event);
+            }
 
-        // Legacy/Linux Audit Format: type=1300 ... key=value ...
-        if s.contains("type=") && s.contains("msg=audit") {
-            for part in s.split_whitespace() {
-                if let Some((k, v)) = part.split_once('=') {
-                     fields.insert(k.to_string(), v.to_string());
-                     if k == "type" {
-                         let digits: String = v.chars().filter(|c| c.is_digit(10)).collect();
-                         type_id = digits.parse().unwrap_or(0);
-                     }
-                     if k == "msg" {
-                        // serial parsing logic
-                        if let Some(start) = v.find(':') {
-                            if let Some(end) = v.find(')') {
-                                if start < end {
-                                    let seq_str = &v[start+1..end];
-                                    serial = seq_str.parse().unwrap_or(0);
-                                }
-                            }
-                        }
-                     }
-                }
+            // 3. Send to Pipeline
+            if self.sender.send(event).is_err() {
+                println!("Receiver dropped, stopping collector.");
+                break;
             }
-        } else if !s.trim().starts_with('{') {
-            // Fallback for non-JSON generic logs
-            type_id = 1; 
-            fields.insert("message".to_string(), s.to_string());
         }
+        Ok(())
+    }
 
-        Ok(AuditEvent {
-            timestamp: chrono::Utc::now(),
-            record_type: type_id,
-            sequence: serial,
-            fields,
-        })
+    fn send_dead_letter(&self, parser: &str, error: &anyhow::Error, raw: &[u8], peer: Option<Peer>) {
+        if let Some(dead_letter) = &self.dead_letter {
+            // A full or closed dead-letter channel must never stop collection.
+            let _ = dead_letter.try_send(DeadLetter {
+                received_at: Utc::now(),
+                parser: parser.to_string(),
+                error: format!("{:#}", error),
+                raw: String::from_utf8_lossy(raw).into_owned(),
+                peer: peer.map(|p| p.addr.to_string()),
+            });
+        }
     }
 }
@@ -0,0 +1,63 @@
+use crate::auth::AuthConfig;
+use crate::extract::ExtractRuleConfig;
+use crate::host::HostConfig;
+use crate::normalize::NormalizeConfig;
+use crate::output::OutputsConfig;
+use crate::source::{FileSourceConfig, SyslogConfig};
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Default location of the configuration file, relative to the working directory.
+pub const DEFAULT_CONFIG_PATH: &str = "audit_collector.toml";
+
+/// Static application configuration loaded from a TOML file at startup.
+///
+/// Runtime settings that the UI can change (such as `FilterConfig`) are not part of this.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    /// Optional syslog receiver. Disabled when absent.
+    pub syslog: Option<SyslogConfig>,
+    /// Additional log files to follow (`[[file]]`).
+    pub file: Vec<FileSourceConfig>,
+    /// Grok/regex extraction rules (`[[extract]]`), applied in order.
+    pub extract: Vec<ExtractRuleConfig>,
+    /// Custom grok patterns available to `extract` rules.
+    pub grok_patterns: HashMap<String, String>,
+    /// Mapping into the common event schema (`[normalize]`).
+    pub normalize: NormalizeConfig,
+    /// Host metadata settings (`[host]`).
+    pub host: HostConfig,
+    /// Output sinks, including the SSE stream (`[output]`).
+    pub output: OutputsConfig,
+    /// API tokens and allowed browser origins (`[auth]`).
+    pub auth: AuthConfig,
+}
+
+impl AppConfig {
+    /// Loads the configuration from a TOML file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+
+    /// Parses the configuration from TOML text.
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// Loads the file given on the command line, or `audit_collector.toml` if it exists,
+    /// falling back to defaults otherwise.
+    pub fn from_args() -> Result<Self> {
+        match std::env::args().nth(1) {
+            Some(path) => Self::load(Path::new(&path)),
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+                Self::load(Path::new(DEFAULT_CONFIG_PATH))
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::model::{AuditEvent, FieldValue};
+use crate::parser::{ParserRegistry, SourceFormat};
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Built-in grok patterns. Definitions may reference each other with `%{NAME}`.
+const BUILTIN_PATTERNS: &[(&str, &str)] = &[
+    ("USERNAME", r"[a-zA-Z0-9._-]+"),
+    ("USER", r"%{USERNAME}"),
+    ("INT", r"[+-]?[0-9]+"),
+    ("BASE10NUM", r"[+-]?(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+)"),
+    ("NUMBER", r"%{BASE10NUM}"),
+    ("POSINT", r"\b[1-9][0-9]*\b"),
+    ("NONNEGINT", r"\b[0-9]+\b"),
+    ("WORD", r"\b\w+\b"),
+    ("NOTSPACE", r"\S+"),
+    ("SPACE", r"\s*"),
+    ("DATA", r".*?"),
+    ("GREEDYDATA", r".*"),
+    ("QUOTEDSTRING", r#""(?:[^"\\]|\\.)*""#),
+    ("UUID", r"[A-Fa-f0-9]{8}-(?:[A-Fa-f0-9]{4}-){3}[A-Fa-f0-9]{12}"),
+    ("MAC", r"(?:[A-Fa-f0-9]{2}[:-]){5}[A-Fa-f0-9]{2}"),
+    ("IPV4", r"(?:(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])\.){3}(?:25[0-5]|2[0-4][0-9]|1[0-9]{2}|[1-9]?[0-9])"),
+    ("IPV6", r"(?:[0-9A-Fa-f]{0,4}:){2,7}(?:%{IPV4}|[0-9A-Fa-f]{0,4})"),
+    ("IP", r"(?:%{IPV6}|%{IPV4})"),
+    ("HOSTNAME", r"\b[0-9A-Za-z][0-9A-Za-z-]{0,62}(?:\.[0-9A-Za-z][0-9A-Za-z-]{0,62})*\.?\b"),
+    ("IPORHOST", r"(?:%{IP}|%{HOSTNAME})"),
+    ("HOSTPORT", r"%{IPORHOST}:%{POSINT}"),
+    ("UNIXPATH", r"(?:/[^/\s]*)+"),
+    ("PATH", r"%{UNIXPATH}"),
+    ("URIPATH", r"(?:/[A-Za-z0-9$.+!*'(){},~:;=@#%&_\-]*)+"),
+    ("EMAILADDRESS", r"[a-zA-Z0-9._%+-]+@[a-zA-Z0-9.-]+"),
+    ("LOGLEVEL", r"(?i:trace|debug|info|notice|warn(?:ing)?|err(?:or)?|crit(?:ical)?|fatal|severe|emerg(?:ency)?|alert)"),
+    ("MONTH", r"\b(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)[a-z]*\b"),
+    ("MONTHDAY", r"(?:0[1-9]|[12][0-9]|3[01]|[1-9])"),
+    ("YEAR", r"[0-9]{4}"),
+    ("TIME", r"[0-9]{2}:[0-9]{2}:[0-9]{2}(?:[.,][0-9]+)?"),
+    ("TIMESTAMP_ISO8601", r"[0-9]{4}-[0-9]{2}-[0-9]{2}[T ][0-9]{2}:[0-9]{2}(?::[0-9]{2}(?:[.,][0-9]+)?)?(?:Z|[+-][0-9]{2}:?[0-9]{2})?"),
+    ("HTTPDATE", r"%{MONTHDAY}/%{MONTH}/%{YEAR}:%{TIME} %{INT}"),
+    ("SYSLOGTIMESTAMP", r"%{MONTH} +%{MONTHDAY} %{TIME}"),
+];
+
+/// Nesting limit for pattern references, which also catches cycles.
+const MAX_DEPTH: usize = 16;
+
+/// Longest regex a grok pattern may expand to. Patterns that reference each
+/// other twice per level double at every step, well before `MAX_DEPTH`.
+const MAX_EXPANDED_LEN: usize = 64 * 1024;
+
+/// Target type for an extracted value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    String,
+    Int,
+    Float,
+    Bool,
+    Ip,
+    Timestamp,
+}
+
+impl FieldType {
+    fn from_name(name: &str) -> Result<Self> {
+        match name {
+            "string" => Ok(FieldType::String),
+            "int" => Ok(FieldType::Int),
+            "float" => Ok(FieldType::Float),
+            "bool" => Ok(FieldType::Bool),
+            "ip" => Ok(FieldType::Ip),
+            "timestamp" => Ok(FieldType::Timestamp),
+            other => bail!("Unknown type '{}'", other),
+        }
+    }
+
+    /// Converts `value` to this type, or returns `None` if it does not convert.
+    fn coerce(self, value: &str) -> Option<FieldValue> {
+        let trimmed = value.trim();
+        match self {
+            FieldType::String => Some(value.into()),
+            FieldType::Int => trimmed
+                .parse::<u64>()
+                .map(FieldValue::UInt)
+                .or_else(|_| trimmed.parse::<i64>().map(FieldValue::Int))
+                .ok(),
+            FieldType::Float => trimmed.parse::<f64>().ok().map(FieldValue::Float),
+            FieldType::Bool => match trimmed.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => Some(FieldValue::Bool(true)),
+                "false" | "no" | "off" | "0" => Some(FieldValue::Bool(false)),
+                _ => None,
+            },
+            FieldType::Ip => trimmed.parse().ok().map(FieldValue::Ip),
+            FieldType::Timestamp => DateTime::parse_from_rfc3339(trimmed)
+                .ok()
+                .map(|t| FieldValue::Timestamp(t.with_timezone(&Utc))),
+        }
+    }
+}
+
+/// A condition on an event field that must hold for a rule to run.
+///
+/// `equals` and the ordering operators compare by type, so `uid = 0` matches
+/// both a numeric `0` and the string `"0"`. `contains` works on the text form.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchCondition {
+    pub field: String,
+    pub equals: Option<FieldValue>,
+    pub contains: Option<String>,
+    pub gt: Option<FieldValue>,
+    pub gte: Option<FieldValue>,
+    pub lt: Option<FieldValue>,
+    pub lte: Option<FieldValue>,
+}
+
+impl MatchCondition {
+    fn matches(&self, event: &AuditEvent) -> bool {
+        let Some(value) = event.fields.get(&self.field) else {
+            return false;
+        };
+        let check = |bound: &Option<FieldValue>, ok: fn(Ordering) -> bool| {
+            bound.as_ref().is_none_or(|b| value.compare(b).is_some_and(ok))
+        };
+        check(&self.equals, Ordering::is_eq)
+            && check(&self.gt, Ordering::is_gt)
+            && check(&self.gte, Ordering::is_ge)
+            && check(&self.lt, Ordering::is_lt)
+            && check(&self.lte, Ordering::is_le)
+            && self.contains.as_ref().is_none_or(|c| value.to_string().contains(c.as_str()))
+    }
+}
+
+/// One extraction rule as written in the configuration file.
+///
+/// Exactly one of `grok` or `regex` must be set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtractRuleConfig {
+    pub name: String,
+    /// Grok expression, e.g. `%{IP:src} %{WORD:user} %{INT:port:int}`.
+    pub grok: Option<String>,
+    /// Rust regex with named capture groups.
+    pub regex: Option<String>,
+    /// Field the pattern is matched against.
+    #[serde(default = "default_field")]
+    pub field: String,
+    /// Only apply to events from the source with this name.
+    pub source: Option<String>,
+    /// Only apply to events matching this condition.
+    pub when: Option<MatchCondition>,
+    /// Type coercions for captured fields, in addition to any `%{PATTERN:name:type}` suffixes.
+    #[serde(default)]
+    pub types: HashMap<String, FieldType>,
+}
+
+fn default_field() -> String {
+    "message".to_string()
+}
+
+/// A rule with its pattern compiled.
+#[derive(Debug, Clone)]
+struct CompiledRule {
+    name: String,
+    regex: Regex,
+    field: String,
+    source: Option<String>,
+    when: Option<MatchCondition>,
+    types: HashMap<String, FieldType>,
+}
+
+/// The configured extraction rules, compiled once at startup.
+#[derive(Debug, Clone, Default)]
+pub struct ExtractionRules {
+    rules: Vec<CompiledRule>,
+}
+
+impl ExtractionRules {
+    /// Compiles every rule, failing on the first invalid one with its name in the error.
+    ///
+    /// `patterns` adds to (or overrides) the built-in grok pattern library.
+    pub fn compile(configs: &[ExtractRuleConfig], patterns: &HashMap<String, String>) -> Result<Self> {
+        let mut library: HashMap<String, String> = BUILTIN_PATTERNS
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        library.extend(patterns.iter().map(|(k, v)| (k.clone(), v.clone())));
+
+        let rules = configs
+            .iter()
+            .map(|config| {
+                compile_rule(config, &library).with_context(|| format!("Invalid extraction rule '{}'", config.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Runs every applicable rule against `event`, merging captures into its fields.
+    ///
+    /// Returns the names of the rules that matched.
+    pub fn apply(&self, source: &str, event: &mut AuditEvent) -> Vec<String> {
+        let mut matched = Vec::new();
+
+        for rule in &self.rules {
+            if rule.source.as_deref().is_some_and(|s| s != source) {
+                continue;
+            }
+            if rule.when.as_ref().is_some_and(|w| !w.matches(event)) {
+                continue;
+            }
+            let Some(text) = event.fields.get(&rule.field) else {
+                continue;
+            };
+            let text = text.to_string();
+            let Some(caps) = rule.regex.captures(&text) else {
+                continue;
+            };
+
+            let mut extracted = Vec::new();
+            for name in rule.regex.capture_names().flatten() {
+                if let Some(m) = caps.name(name) {
+                    let value = m.as_str();
+                    // Values that fail coercion are kept as captured.
+                    let value = rule
+                        .types
+                        .get(name)
+                        .and_then(|t| t.coerce(value))
+                        .unwrap_or_else(|| value.into());
+                    extracted.push((name.to_string(), value));
+                }
+            }
+            event.fields.extend(extracted);
+            matched.push(rule.name.clone());
+        }
+        matched
+    }
+}
+
+fn compile_rule(config: &ExtractRuleConfig, library: &HashMap<String, String>) -> Result<CompiledRule> {
+    let mut types = HashMap::new();
+    let pattern = match (&config.grok, &config.regex) {
+        (Some(grok), None) => expand_grok(grok, library, 0, &mut types)?,
+        (None, Some(regex)) => regex.clone(),
+        _ => bail!("exactly one of 'grok' or 'regex' must be set"),
+    };
+    types.extend(config.types.iter().map(|(k, v)| (k.clone(), *v)));
+
+    let regex = Regex::new(&pattern).with_context(|| format!("pattern does not compile: {}", pattern))?;
+    if regex.capture_names().flatten().next().is_none() {
+        bail!("pattern has no named captures");
+    }
+
+    Ok(CompiledRule {
+        name: config.name.clone(),
+        regex,
+        field: config.field.clone(),
+        source: config.source.clone(),
+        when: config.when.clone(),
+        types,
+    })
+}
+
+/// Expands `%{PATTERN}`, `%{PATTERN:field}` and `%{PATTERN:field:type}` into a regex.
+fn expand_grok(
+    pattern: &str,
+    library: &HashMap<String, String>,
+    depth: usize,
+    types: &mut HashMap<String, FieldType>,
+) -> Result<String> {
+    if depth > MAX_DEPTH {
+        bail!("grok patterns nest too deeply (cycle?)");
+    }
+
+    let mut out = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("%{") {
+        out.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow!("unterminated %{{ in '{}'", pattern))?
+            + start;
+        let mut parts = rest[start + 2..end].splitn(3, ':');
+        let name = parts.next().unwrap_or_default();
+        let field = parts.next();
+        let field_type = parts.next().map(FieldType::from_name).transpose()?;
+
+        let definition = library
+            .get(name)
+            .ok_or_else(|| anyhow!("unknown grok pattern '{}'", name))?;
+        let expanded = expand_grok(definition, library, depth + 1, types)?;
+
+        match field {
+            Some(field) => {
+                out.push_str(&format!("(?P<{}>{})", field, expanded));
+                if let Some(t) = field_type {
+                    types.insert(field.to_string(), t);
+                }
+            }
+            None => out.push_str(&format!("(?:{})", expanded)),
+        }
+        if out.len() > MAX_EXPANDED_LEN {
+            bail!("grok pattern expands to more than {} bytes", MAX_EXPANDED_LEN);
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    if out.len() > MAX_EXPANDED_LEN {
+        bail!("grok pattern expands to more than {} bytes", MAX_EXPANDED_LEN);
+    }
+    Ok(out)
+}
+
+/// Body of `POST /api/parse/test`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParseTestRequest {
+    /// The sample line to parse.
+    pub line: String,
+    /// Format to parse the line as; guessed when omitted.
+    #[serde(default)]
+    pub format: SourceFormat,
+    /// Source name used to select rules; defaults to the format name.
+    pub source: Option<String>,
+    /// Rules to try instead of the configured ones. They are compiled, so invalid
+    /// rules are reported without touching the running configuration.
+    pub rules: Option<Vec<ExtractRuleConfig>>,
+    /// Extra grok patterns for `rules`.
+    #[serde(default)]
+    pub patterns: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ParseTestResponse {
+    pub event: AuditEvent,
+    pub matched_rules: Vec<String>,
+}
+
+/// Parses a sample line and applies extraction rules exactly as the collector would.
+pub fn test_parse(
+    request: &ParseTestRequest,
+    configured: &ExtractionRules,
+    registry: &ParserRegistry,
+) -> Result<ParseTestResponse> {
+    let compiled;
+    let rules = match &request.rules {
+        Some(rules) => {
+            compiled = ExtractionRules::compile(rules, &request.patterns)?;
+            &compiled
+        }
+        None => configured,
+    };
+
+    let parser = registry.parser_for(&request.format)?;
+    let mut event = parser.parse(request.line.as_bytes())?;
+    let source = request.source.as_deref().unwrap_or(request.format.name());
+    let matched_rules = rules.apply(source, &mut event);
+    Ok(ParseTestResponse { event, matched_rules })
+}
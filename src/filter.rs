@@ -0,0 +1,334 @@
+//! Boolean filter expressions over event fields, e.g.
+//!
+//! ```text
+//! event.module == "auditd" and (event.outcome == "failure" or not user.name)
+//! record_type >= 1100 and record_type < 1200
+//! message contains "sudo" or process.name matches "^ssh"
+//! ```
+//!
+//! A bare field is true when the event has it. Comparisons use
+//! `FieldValue::compare`, so `uid == 0` matches both a numeric 0 and the string
+//! `"0"`; when the field is missing every comparison is false, `!=` included.
+//! Besides event fields, `record_type` and `source` (the source type) can be used.
+
+use crate::model::{AuditEvent, FieldValue};
+use crate::normalize;
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Longest filter text accepted, in bytes.
+pub const MAX_LEN: usize = 4096;
+
+/// Deepest nesting of parentheses and `not` accepted.
+pub const MAX_DEPTH: usize = 64;
+
+/// A compiled filter expression.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    text: String,
+    root: Expr,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Exists(String),
+    Compare(String, Op, FieldValue),
+    Contains(String, String),
+    Matches(String, Regex),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Field(String),
+    Str(String),
+    Number(FieldValue),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Contains,
+    Matches,
+    Open,
+    Close,
+}
+
+impl Filter {
+    /// Compiles `text`; filters can come from clients, so their length and
+    /// nesting are limited to keep parsing and evaluation off the end of the stack.
+    pub fn parse(text: &str) -> Result<Self> {
+        if text.len() > MAX_LEN {
+            bail!("Filter is {} bytes long; at most {} are allowed", text.len(), MAX_LEN);
+        }
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, pos: 0, depth: 0 };
+        let root = parser.or()?;
+        if let Some((offset, token)) = parser.tokens.get(parser.pos) {
+            bail!("Unexpected {:?} at offset {}", token, offset);
+        }
+        Ok(Self { text: text.to_string(), root })
+    }
+
+    pub fn matches(&self, event: &AuditEvent) -> bool {
+        self.root.eval(event)
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// The value of `field` on `event`, including the `record_type` and `source` pseudo-fields.
+fn lookup<'a>(event: &'a AuditEvent, field: &str) -> Option<Cow<'a, FieldValue>> {
+    if let Some(value) = event.fields.get(field) {
+        return Some(Cow::Borrowed(value));
+    }
+    match field {
+        "record_type" => Some(Cow::Owned(FieldValue::UInt(event.record_type as u64))),
+        "source" => Some(Cow::Owned(FieldValue::Str(normalize::source_type(event)))),
+        _ => None,
+    }
+}
+
+impl Expr {
+    fn eval(&self, event: &AuditEvent) -> bool {
+        match self {
+            Expr::And(a, b) => a.eval(event) && b.eval(event),
+            Expr::Or(a, b) => a.eval(event) || b.eval(event),
+            Expr::Not(e) => !e.eval(event),
+            Expr::Exists(field) => lookup(event, field).is_some(),
+            Expr::Compare(field, op, expected) => lookup(event, field).is_some_and(|value| {
+                let ordering = value.compare(expected);
+                match op {
+                    Op::Eq => ordering == Some(Ordering::Equal),
+                    Op::Ne => ordering != Some(Ordering::Equal),
+                    Op::Lt => ordering == Some(Ordering::Less),
+                    Op::Le => ordering.is_some_and(Ordering::is_le),
+                    Op::Gt => ordering == Some(Ordering::Greater),
+                    Op::Ge => ordering.is_some_and(Ordering::is_ge),
+                }
+            }),
+            Expr::Contains(field, needle) => lookup(event, field).is_some_and(|v| v.to_string().contains(needle.as_str())),
+            Expr::Matches(field, regex) => lookup(event, field).is_some_and(|v| regex.is_match(&v.to_string())),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(offset, c)) = chars.peek() {
+        let token = match c {
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            '(' | ')' => {
+                chars.next();
+                if c == '(' { Token::Open } else { Token::Close }
+            }
+            '=' | '!' | '<' | '>' | '&' | '|' => {
+                chars.next();
+                let next = chars.peek().map(|&(_, c)| c);
+                let (token, pair) = match (c, next) {
+                    ('=', Some('=')) => (Token::Op(Op::Eq), true),
+                    ('!', Some('=')) => (Token::Op(Op::Ne), true),
+                    ('<', Some('=')) => (Token::Op(Op::Le), true),
+                    ('>', Some('=')) => (Token::Op(Op::Ge), true),
+                    ('&', Some('&')) => (Token::And, true),
+                    ('|', Some('|')) => (Token::Or, true),
+                    ('=', _) => (Token::Op(Op::Eq), false),
+                    ('!', _) => (Token::Not, false),
+                    ('<', _) => (Token::Op(Op::Lt), false),
+                    ('>', _) => (Token::Op(Op::Gt), false),
+                    _ => bail!("Unexpected '{}' at offset {}", c, offset),
+                };
+                if pair {
+                    chars.next();
+                }
+                token
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => {
+                            let (_, escaped) = chars.next().context("Unterminated string")?;
+                            value.push(escaped);
+                        }
+                        Some((_, end)) if end == c => break,
+                        Some((_, other)) => value.push(other),
+                        None => bail!("Unterminated string starting at offset {}", offset),
+                    }
+                }
+                Token::Str(value)
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_ascii_digit() || c == '.' || (c == '-' && number.is_empty())) {
+                        break;
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                let value = if let Ok(n) = number.parse::<u64>() {
+                    FieldValue::UInt(n)
+                } else if let Ok(n) = number.parse::<i64>() {
+                    FieldValue::Int(n)
+                } else {
+                    FieldValue::Float(number.parse().with_context(|| format!("Invalid number '{}'", number))?)
+                };
+                Token::Number(value)
+            }
+            c if c.is_alphabetic() || c == '_' || c == '@' => {
+                let mut word = String::new();
+                while let Some(&(_, c)) = chars.peek() {
+                    if !(c.is_alphanumeric() || matches!(c, '_' | '.' | '@' | '-')) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                match word.as_str() {
+                    "and" => Token::And,
+                    "or" => Token::Or,
+                    "not" => Token::Not,
+                    "contains" => Token::Contains,
+                    "matches" => Token::Matches,
+                    _ => Token::Field(word),
+                }
+            }
+            other => bail!("Unexpected '{}' at offset {}", other, offset),
+        };
+        tokens.push((offset, token));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent, with `not` binding tighter than `and`, and `and` than `or`.
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    /// Open parentheses and `not`s around the current position.
+    depth: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let found = self.tokens.get(self.pos).is_some_and(|(_, t)| t == token);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut left = self.and()?;
+        while self.eat(&Token::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut left = self.not()?;
+        while self.eat(&Token::And) {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    /// Goes one level deeper, failing past `MAX_DEPTH`.
+    fn descend(&mut self) -> Result<()> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("Filter nests deeper than {} levels", MAX_DEPTH);
+        }
+        Ok(())
+    }
+
+    fn not(&mut self) -> Result<Expr> {
+        if self.eat(&Token::Not) {
+            self.descend()?;
+            let expr = Expr::Not(Box::new(self.not()?));
+            self.depth -= 1;
+            return Ok(expr);
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Some(Token::Open) => {
+                self.descend()?;
+                let expr = self.or()?;
+                if !self.eat(&Token::Close) {
+                    bail!("Missing ')'");
+                }
+                self.depth -= 1;
+                Ok(expr)
+            }
+            Some(Token::Field(field)) => {
+                if self.eat(&Token::Contains) {
+                    return Ok(Expr::Contains(field, self.string()?));
+                }
+                if self.eat(&Token::Matches) {
+                    let pattern = self.string()?;
+                    let regex = Regex::new(&pattern).with_context(|| format!("Invalid regex for {}", field))?;
+                    return Ok(Expr::Matches(field, regex));
+                }
+                match self.tokens.get(self.pos) {
+                    Some((_, Token::Op(op))) => {
+                        let op = *op;
+                        self.pos += 1;
+                        Ok(Expr::Compare(field, op, self.value()?))
+                    }
+                    _ => Ok(Expr::Exists(field)),
+                }
+            }
+            Some(token) => bail!("Expected a field, found {:?}", token),
+            None => bail!("Unexpected end of expression"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(s),
+            other => bail!("Expected a string, found {:?}", other),
+        }
+    }
+
+    fn value(&mut self) -> Result<FieldValue> {
+        match self.next() {
+            Some(Token::Str(s)) => Ok(FieldValue::Str(s)),
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Field(word)) if word == "true" || word == "false" => Ok(FieldValue::Bool(word == "true")),
+            other => bail!("Expected a value, found {:?}", other),
+        }
+    }
+}
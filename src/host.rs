@@ -0,0 +1,160 @@
+use crate::model::{AuditEvent, FieldValue};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, UdpSocket};
+use std::process::Command;
+
+/// Settings for the host metadata stamped on events (`[host]`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HostConfig {
+    /// Overrides the detected hostname.
+    pub hostname: Option<String>,
+    /// Free-form tags added to every stamped event, e.g. `["prod", "eu-west"]`.
+    pub tags: Vec<String>,
+}
+
+/// Identity of the machine and agent, gathered once at startup.
+///
+/// Detection is best effort: anything that cannot be read is left empty.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct HostMetadata {
+    pub hostname: String,
+    pub fqdn: Option<String>,
+    pub os_name: String,
+    pub os_version: Option<String>,
+    pub kernel: Option<String>,
+    pub machine_id: Option<String>,
+    pub boot_id: Option<String>,
+    pub ips: Vec<IpAddr>,
+    pub agent_version: String,
+    pub tags: Vec<String>,
+}
+
+impl HostMetadata {
+    /// Detects the metadata of the machine we are running on.
+    pub fn collect(config: &HostConfig) -> Self {
+        let hostname = config.hostname.clone().unwrap_or_else(detect_hostname);
+        let fqdn = command_output("hostname", &["-f"])
+            .filter(|f| f.contains('.'))
+            .or_else(|| hostname.contains('.').then(|| hostname.clone()));
+        let (os_name, os_version) = detect_os();
+
+        Self {
+            hostname,
+            fqdn,
+            os_name,
+            os_version,
+            kernel: read_trimmed("/proc/sys/kernel/osrelease").or_else(|| command_output("uname", &["-r"])),
+            machine_id: read_trimmed("/etc/machine-id").or_else(|| read_trimmed("/var/lib/dbus/machine-id")),
+            boot_id: read_trimmed("/proc/sys/kernel/random/boot_id"),
+            ips: primary_ips(),
+            agent_version: env!("CARGO_PKG_VERSION").to_string(),
+            tags: config.tags.clone(),
+        }
+    }
+
+    /// Adds the metadata to `event` as ECS fields.
+    ///
+    /// Events received from a network peer were produced by another machine, so
+    /// this host is recorded as the `observer` instead of the `host`.
+    pub fn stamp(&self, event: &mut AuditEvent) {
+        let prefix = if is_relayed(event) { "observer" } else { "host" };
+        let mut put = |key: &str, value: FieldValue| {
+            event.fields.insert(format!("{}.{}", prefix, key), value);
+        };
+
+        put("hostname", self.hostname.as_str().into());
+        if let Some(fqdn) = &self.fqdn {
+            put("name", fqdn.as_str().into());
+        } else {
+            put("name", self.hostname.as_str().into());
+        }
+        put("os.name", self.os_name.as_str().into());
+        if let Some(version) = &self.os_version {
+            put("os.version", version.as_str().into());
+        }
+        if let Some(kernel) = &self.kernel {
+            put("os.kernel", kernel.as_str().into());
+        }
+        if let Some(id) = &self.machine_id {
+            put("id", id.as_str().into());
+        }
+        if let Some(id) = &self.boot_id {
+            put("boot.id", id.as_str().into());
+        }
+        if !self.ips.is_empty() {
+            put("ip", FieldValue::List(self.ips.iter().map(|ip| FieldValue::Ip(*ip)).collect()));
+        }
+
+        event.fields.insert("agent.name".to_string(), env!("CARGO_PKG_NAME").into());
+        event.fields.insert("agent.version".to_string(), self.agent_version.as_str().into());
+        if !self.tags.is_empty() {
+            let tags = self.tags.iter().map(|t| t.as_str().into()).collect();
+            event.fields.insert("tags".to_string(), FieldValue::List(tags));
+        }
+    }
+}
+
+fn is_relayed(event: &AuditEvent) -> bool {
+    match event.fields.get("raw") {
+        Some(FieldValue::Map(raw)) => raw.contains_key("peer"),
+        _ => event.fields.contains_key("peer"),
+    }
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+fn detect_hostname() -> String {
+    read_trimmed("/proc/sys/kernel/hostname")
+        .or_else(|| command_output("hostname", &[]))
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// OS name and version from `/etc/os-release`, `sw_vers` or the compile target.
+fn detect_os() -> (String, Option<String>) {
+    if let Some(release) = read_trimmed("/etc/os-release") {
+        let value = |key: &str| {
+            release
+                .lines()
+                .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+                .map(|v| v.trim_matches('"').to_string())
+        };
+        if let Some(name) = value("NAME") {
+            return (name, value("VERSION_ID"));
+        }
+    }
+    if let Some(name) = command_output("sw_vers", &["-productName"]) {
+        return (name, command_output("sw_vers", &["-productVersion"]));
+    }
+    (std::env::consts::OS.to_string(), None)
+}
+
+/// The addresses the default routes leave from. Connecting a UDP socket sends
+/// nothing; it only makes the kernel pick a source address.
+fn primary_ips() -> Vec<IpAddr> {
+    [("0.0.0.0:0", "192.0.2.1:9"), ("[::]:0", "[2001:db8::1]:9")]
+        .into_iter()
+        .filter_map(|(bind, target)| {
+            let socket = UdpSocket::bind(bind).ok()?;
+            socket.connect(target).ok()?;
+            Some(socket.local_addr().ok()?.ip())
+        })
+        .filter(|ip| !ip.is_unspecified() && !ip.is_loopback())
+        .collect()
+}
@@ -1,3 +1,15 @@
 pub mod model;
 pub mod source;
 pub mod collector;
+pub mod parser;
+pub mod config;
+pub mod syslog;
+pub mod auth;
+pub mod authlog;
+pub mod extract;
+pub mod filter;
+pub mod host;
+pub mod normalize;
+pub mod output;
+pub mod store;
+pub mod ws;
@@ -1,5 +1,18 @@
-use audit_collector::collector::Collector;
-use audit_collector::source::AuditSource;
+use audit_collector::auth;
+use audit_collector::collector::{Collector, CollectorStats, CollectorStatsSnapshot, DeadLetter};
+use audit_collector::config::AppConfig;
+use audit_collector::extract::{self, ExtractionRules, ParseTestRequest, ParseTestResponse};
+use audit_collector::normalize::Normalizer;
+use audit_collector::host::HostMetadata;
+use audit_collector::output::{
+    ElasticsearchSink, EventStream, Fanout, FileSink, KafkaSink, LokiSink, OtlpSink, OutputFormat, OutputOptions, Sink,
+    SinkStatsSnapshot, SplunkSink, SseClients, SseSink, SyslogSink, WebhookSink,
+};
+use audit_collector::output::sse::{Delivery, SseClientSnapshot, StreamEvent};
+use audit_collector::store::RecentEvents;
+use audit_collector::ws;
+use audit_collector::parser::ParserRegistry;
+use audit_collector::source::{AuditSource, FileSource, SyslogSource};
 #[cfg(target_os = "macos")]
 use audit_collector::source::MacLogSource;
 #[cfg(target_os = "linux")]
@@ -7,28 +20,50 @@ use audit_collector::source::LinuxAuditSource;
 #[cfg(target_os = "windows")]
 use audit_collector::source::WindowsEventSource;
 use audit_collector::model::{FilterConfig, AuditEvent};
-use crossbeam_channel::unbounded;
-use std::sync::{Arc, RwLock};
+use crossbeam_channel::{bounded, unbounded};
+use std::sync::{Arc, Mutex, RwLock};
 use std::thread;
 use std::time::Duration;
 use axum::{
     routing::{get, post},
     Router,
-    extract::State,
-    response::sse::{Event, Sse},
+    extract::{ws::WebSocketUpgrade, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{
+        sse::{Event, Sse},
+        Response,
+    },
     Json,
 };
 use tokio::sync::broadcast;
-use futures::stream::Stream;
-use tower_http::cors::CorsLayer;
+use futures::stream::{Stream, StreamExt};
 use tower_http::services::ServeDir;
 
 
+/// How many recent events GET /api/events/{id} can look up.
+const RECENT_EVENTS: usize = 10_000;
+
 struct AppState {
     // Current filter configuration
     filter: Arc<RwLock<FilterConfig>>,
-    // Broadcast channel to send events to SSE clients
-    tx_events: broadcast::Sender<AuditEvent>,
+    // Numbered events for SSE clients, with recent ones kept for resuming
+    events: Arc<EventStream>,
+    // SSE clients connected to GET /api/events and how far behind they are
+    sse_clients: Arc<SseClients>,
+    // Broadcast channel for records that failed to parse
+    tx_dead_letters: broadcast::Sender<DeadLetter>,
+    // Parse counters shared by every collector
+    stats: Arc<CollectorStats>,
+    // User-defined extraction rules, compiled once at startup
+    extraction: Arc<ExtractionRules>,
+    // Maps events into the common schema; `None` when disabled in the config
+    normalizer: Option<Arc<Normalizer>>,
+    // Identity of this machine, stamped on outputs that ask for it
+    host: Arc<HostMetadata>,
+    // Latest events by ID, for GET /api/events/{id}
+    recent: Arc<Mutex<RecentEvents>>,
+    // Routes events to the SSE stream and the configured output sinks
+    fanout: Arc<Fanout>,
     // Handle to the current collector thread (so we can restart it) - simplified: 
     // In this basic version we'll use a shared flag or channel to signal restart 
     // but since AuditSource interface is blocking/native thread based, 
@@ -38,26 +73,76 @@ struct AppState {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    println!("Starting Audit Collector Server on http://localhost:9357");
+    let app_config = AppConfig::from_args()?;
+    let address = auth::listen_address(&app_config.auth);
+    println!("Starting Audit Collector Server on http://{}", address);
+    let extraction = ExtractionRules::compile(&app_config.extract, &app_config.grok_patterns)?;
 
-    let (tx_events, _) = broadcast::channel(100);
+    let host = Arc::new(HostMetadata::collect(&app_config.host));
+    let events = Arc::new(EventStream::new(app_config.output.sse.capacity, app_config.output.sse.replay));
+    let mut fanout = Fanout::default();
+    let sse = SseSink::new(app_config.output.sse.clone(), events.clone());
+    fanout.add_sink("sse", app_config.output.sse.filter.as_deref(), sse)?;
+    let normalized = app_config.normalize.enabled;
+    let outputs = app_config.output;
+    add_sinks(&mut fanout, "file", outputs.file, |c| c.filter.clone(), |c| FileSink::new(c, host.clone()))?;
+    add_sinks(&mut fanout, "syslog", outputs.syslog, |c| c.filter.clone(), |c| SyslogSink::new(c, host.clone()))?;
+    add_sinks(&mut fanout, "webhook", outputs.webhook, |c| c.filter.clone(), |c| WebhookSink::new(c, host.clone()))?;
+    add_sinks(&mut fanout, "elasticsearch", outputs.elasticsearch, |c| c.filter.clone(), |c| {
+        ElasticsearchSink::new(c, host.clone()).map(|sink| sink.with_normalized(normalized))
+    })?;
+    add_sinks(&mut fanout, "splunk", outputs.splunk, |c| c.filter.clone(), |c| SplunkSink::new(c, host.clone()))?;
+    add_sinks(&mut fanout, "otlp", outputs.otlp, |c| c.filter.clone(), |c| OtlpSink::new(c, host.clone()))?;
+    add_sinks(&mut fanout, "loki", outputs.loki, |c| c.filter.clone(), |c| LokiSink::new(c, host.clone()))?;
+    add_sinks(&mut fanout, "kafka", outputs.kafka, |c| c.filter.clone(), |c| KafkaSink::new(c, host.clone()))?;
+
+    let (tx_dead_letters, _) = broadcast::channel(100);
     let state = Arc::new(AppState {
         filter: Arc::new(RwLock::new(FilterConfig::default())),
-        tx_events: tx_events.clone(),
+        events,
+        sse_clients: Arc::new(SseClients::default()),
+        tx_dead_letters,
+        stats: Arc::new(CollectorStats::default()),
+        extraction: Arc::new(extraction),
+        normalizer: app_config
+            .normalize
+            .enabled
+            .then(|| Arc::new(Normalizer::new(&app_config.normalize))),
+        host,
+        recent: Arc::new(Mutex::new(RecentEvents::new(RECENT_EVENTS))),
+        fanout: Arc::new(fanout),
         source_arc: Arc::new(RwLock::new(None)),
     });
 
     // Start initial collector
     start_collector(state.clone());
 
+    // The syslog receiver runs independently of the filter-driven platform source
+    if let Some(syslog_config) = app_config.syslog {
+        let source = SyslogSource::new(syslog_config)?;
+        spawn_pipeline(Arc::new(source), &state);
+    }
+    for file_config in app_config.file {
+        let source = FileSource::new(file_config)?;
+        spawn_pipeline(Arc::new(source), &state);
+    }
+
     let app = Router::new()
         .route("/api/config", post(update_config).get(get_config))
         .route("/api/events", get(sse_handler))
+        .route("/api/events/clients", get(get_sse_clients))
+        .route("/api/events/{id}", get(get_event))
+        .route("/api/ws", get(ws_handler))
+        .route("/api/stats", get(get_stats))
+        .route("/api/outputs", get(get_outputs))
+        .route("/api/host", get(get_host))
+        .route("/api/dead-letters", get(dead_letter_handler))
+        .route("/api/parse/test", post(parse_test))
         .fallback_service(ServeDir::new("ui/dist")) // Serve frontend
-        .layer(CorsLayer::permissive())
         .with_state(state);
+    let app = auth::protect(app, &app_config.auth)?;
 
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:9357").await?;
+    let listener = tokio::net::TcpListener::bind(address).await?;
     axum::serve(listener, app).await?;
 
     Ok(())
@@ -106,33 +191,65 @@ fn start_collector(state: Arc<AppState>) {
     // Store source to stop later
     *state.source_arc.write().unwrap() = Some(source.clone());
 
+    spawn_pipeline(source, &state);
+}
+
+/// Adds one `kind` sink per config entry, each behind that entry's filter.
+fn add_sinks<C, S: Sink>(
+    fanout: &mut Fanout,
+    kind: &str,
+    configs: Vec<C>,
+    filter: impl Fn(&C) -> Option<String>,
+    build: impl Fn(C) -> anyhow::Result<S>,
+) -> anyhow::Result<()> {
+    for config in configs {
+        let filter = filter(&config);
+        fanout.add_sink(kind, filter.as_deref(), build(config)?)?;
+    }
+    Ok(())
+}
+
+/// Runs a collector for `source` and hands its events to the fanout, which queues
+/// them for the SSE stream and every configured output. Records that fail to parse
+/// go to the dead-letter broadcast instead.
+fn spawn_pipeline(source: Arc<dyn AuditSource>, state: &AppState) {
     let (tx, rx) = unbounded();
-    let collector = Collector::new(source.clone(), tx);
-    let tx_broadcast = state.tx_events.clone();
+    let (tx_dead, rx_dead) = bounded::<DeadLetter>(1000);
+    let mut collector = Collector::new(source, tx)
+        .with_dead_letter(tx_dead)
+        .with_stats(state.stats.clone())
+        .with_extraction(state.extraction.clone());
+    if let Some(normalizer) = &state.normalizer {
+        collector = collector.with_normalizer(normalizer.clone());
+    }
+    let recent = state.recent.clone();
+    let fanout = state.fanout.clone();
+    let tx_dead_broadcast = state.tx_dead_letters.clone();
+
+    thread::spawn(move || {
+        while let Ok(dead_letter) = rx_dead.recv() {
+            let _ = tx_dead_broadcast.send(dead_letter);
+        }
+    });
 
     // Spawn collector thread
     thread::spawn(move || {
-        // Collector run blocks
-        // We need a way to forward from 'rx' (crossbeam) to 'tx_broadcast' (tokio broadcast)
-        // Since collector.run() sends to 'tx', we can read from 'rx' here or 
-        // actually Collector::run expects a Sender. 
-        // We can just run the Collector in *this* thread? No, Collector::run has a loop.
-        // We need to consume 'rx' and broadcast.
-        
-        // Let's spawn the collector Logic in another sub-thread or just use this one 
-        // to Bridge crossbeam -> tokio broadcast.
+        // Collector::run blocks and sends to 'tx', so it gets its own thread while
+        // this one drains 'rx' (crossbeam) into the recent-events store and the fanout.
         
         // Collector needs to run.
         let col_thread = thread::spawn(move || {
-            if let Err(_e) = collector.run() {
-               // eprintln!("Collector stopped: {:?}", e);
+            if let Err(e) = collector.run() {
+                eprintln!("Collector stopped: {:#}", e);
             }
         });
 
-        // Bridge loop
+        // Fanout loop
         while let Ok(event) = rx.recv() {
-            // Send to frontend
-            let _ = tx_broadcast.send(event);
+            recent.lock().unwrap().push(event.clone());
+            // Each sink, the SSE stream included, has its own queue and filter;
+            // a full queue drops the event for that sink rather than stalling collection.
+            fanout.send(&event);
         }
         
         let _ = col_thread.join();
@@ -156,14 +273,115 @@ async fn get_config(State(state): State<Arc<AppState>>) -> Json<FilterConfig> {
     Json(config)
 }
 
+#[derive(serde::Deserialize)]
+struct EventsQuery {
+    /// `native` (default) or `ocsf`.
+    #[serde(default)]
+    format: OutputFormat,
+    /// Stamp host metadata; off by default to keep the UI stream lean.
+    #[serde(default)]
+    host: bool,
+}
+
 async fn sse_handler(
     State(state): State<Arc<AppState>>,
+    Query(query): Query<EventsQuery>,
+    headers: HeaderMap,
 ) -> Sse<impl Stream<Item = Result<Event, axum::BoxError>>> {
-    let mut rx = state.tx_events.subscribe();
-    
+    // Sent by EventSource when it reconnects; what it missed is replayed first.
+    let last_event_id = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok());
+    let client = state.sse_clients.connect();
+    let subscription = state.events.subscribe(last_event_id);
+    let options = OutputOptions { format: query.format, host_metadata: query.host };
+    let host = state.host.clone();
+
+    // A client that falls behind is told how many events it missed and carries on.
+    let stream = client.deliveries(subscription).map(move |delivery| {
+        Ok(match delivery {
+            Delivery::Event(StreamEvent { id, event }) => {
+                Event::default().id(id.to_string()).json_data(options.render(&event, &host)).unwrap()
+            }
+            Delivery::Lagged(skipped) => {
+                Event::default().event("lagged").json_data(serde_json::json!({ "skipped": skipped })).unwrap()
+            }
+        })
+    });
+
+    Sse::new(stream).keep_alive(axum::response::sse::KeepAlive::default())
+}
+
+/// Upgrades to the WebSocket event API, see `audit_collector::ws`.
+async fn ws_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EventsQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    let options = OutputOptions { format: query.format, host_metadata: query.host };
+    let (events, host) = (state.events.clone(), state.host.clone());
+    upgrade.on_upgrade(move |socket| ws::serve(socket, events, options, host))
+}
+
+async fn get_sse_clients(State(state): State<Arc<AppState>>) -> Json<Vec<SseClientSnapshot>> {
+    Json(state.sse_clients.snapshot())
+}
+
+async fn get_stats(State(state): State<Arc<AppState>>) -> Json<CollectorStatsSnapshot> {
+    Json(state.stats.snapshot())
+}
+
+async fn get_outputs(State(state): State<Arc<AppState>>) -> Json<Vec<SinkStatsSnapshot>> {
+    Json(state.fanout.snapshot())
+}
+
+async fn get_event(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<AuditEvent>, (StatusCode, Json<serde_json::Value>)> {
+    state.recent.lock().unwrap().get(&id).cloned().map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            Json(serde_json::json!({ "error": format!("No recent event with id {}", id) })),
+        )
+    })
+}
+
+async fn get_host(State(state): State<Arc<AppState>>) -> Json<HostMetadata> {
+    Json((*state.host).clone())
+}
+
+/// Parses a sample line with the given format and extraction rules (or the configured ones).
+async fn parse_test(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ParseTestRequest>,
+) -> Result<Json<ParseTestResponse>, (StatusCode, Json<serde_json::Value>)> {
+    extract::test_parse(&request, &state.extraction, &ParserRegistry::default())
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                Json(serde_json::json!({ "error": format!("{:#}", e) })),
+            )
+        })
+}
+
+async fn dead_letter_handler(
+    State(state): State<Arc<AppState>>,
+) -> Sse<impl Stream<Item = Result<Event, axum::BoxError>>> {
+    let mut rx = state.tx_dead_letters.subscribe();
+
     let stream = async_stream::stream! {
-        while let Ok(event) = rx.recv().await {
-            yield Ok(Event::default().json_data(event).unwrap());
+        loop {
+            match rx.recv().await {
+                Ok(dead_letter) => yield Ok(Event::default().json_data(dead_letter).unwrap()),
+                // Too slow to keep up: say how many were skipped and carry on.
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    yield Ok(Event::default().event("lagged").json_data(serde_json::json!({ "skipped": skipped })).unwrap());
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
         }
     };
 
@@ -1,6 +1,15 @@
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use chrono::{DateTime, Utc};
+use serde::de::{self, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use chrono::{DateTime, SecondsFormat, Utc};
+use sha2::{Digest, Sha256};
+
+/// Numbers receptions of records without a time of their own, see `AuditEvent::compute_id`.
+static RECEPTIONS: AtomicU64 = AtomicU64::new(0);
 
 /// Configuration for filtering events
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
@@ -20,6 +29,11 @@ pub struct FilterConfig {
 /// processing pipeline (Filter -> Enrichment -> Output).
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct AuditEvent {
+    /// Stable, globally unique ID assigned by the collector (see `AuditEvent::compute_id`).
+    /// Empty until the event has been through a collector.
+    #[serde(default)]
+    pub id: String,
+
     /// Timestamp when the event occurred (from kernel or reception time).
     pub timestamp: DateTime<Utc>,
     
@@ -27,22 +41,389 @@ pub struct AuditEvent {
     pub record_type: u16,
     
     /// The unique sequence number (serial) of the audit event.
-    pub sequence: u32,
+    pub sequence: u64,
+
+    /// The record's identifier on its source platform, when it has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub native_id: Option<NativeId>,
+
+    /// Set by parsers when the record carried no time of its own and `timestamp`
+    /// is the time it was read.
+    #[serde(skip)]
+    pub synthetic_timestamp: bool,
 
     /// Key-value pairs parsed from the raw audit message.
-    pub fields: HashMap<String, String>,
+    pub fields: HashMap<String, FieldValue>,
 }
 
 impl AuditEvent {
     /// Creates a new empty AuditEvent.
-    pub fn new(record_type: u16, sequence: u32) -> Self {
+    pub fn new(record_type: u16, sequence: u64) -> Self {
         Self {
+            id: String::new(),
             timestamp: Utc::now(),
             record_type,
             sequence,
+            native_id: None,
+            synthetic_timestamp: false,
             fields: HashMap::new(),
         }
     }
+
+    /// Derives the event ID from the source, the sending host (for network sources),
+    /// the native serial, the timestamp and the raw record: 128 bits of SHA-256, hex
+    /// encoded.
+    ///
+    /// Re-reading the same record from the same source yields the same ID, so it can
+    /// be used for deduplication. Records that share a serial (such as the SYSCALL and
+    /// PATH records of one auditd event) still get distinct IDs through the raw bytes,
+    /// and identical lines from two hosts through the peer address. Only its IP counts,
+    /// so a sender that reconnects from another port keeps its IDs.
+    ///
+    /// A `synthetic_timestamp` says nothing about the record, so identical lines would
+    /// collide; each call then mixes in a reception counter as well, and the ID is
+    /// unique but not reproducible.
+    pub fn compute_id(&self, source: &str, peer: Option<IpAddr>, raw: &[u8]) -> String {
+        let peer = peer.map(|ip| ip.to_string()).unwrap_or_default();
+        let mut timestamp = self.timestamp.timestamp_nanos_opt().unwrap_or_default().to_be_bytes().to_vec();
+        if self.synthetic_timestamp {
+            timestamp.extend(RECEPTIONS.fetch_add(1, AtomicOrdering::Relaxed).to_be_bytes());
+        }
+        let mut hasher = Sha256::new();
+        for part in [
+            source.as_bytes(),
+            peer.as_bytes(),
+            &self.record_type.to_be_bytes(),
+            &self.sequence.to_be_bytes(),
+            &timestamp,
+        ] {
+            // Length-prefix each part so boundaries cannot shift between them.
+            hasher.update((part.len() as u64).to_be_bytes());
+            hasher.update(part);
+        }
+        hasher.update(raw);
+        hasher.finalize()[..16].iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// The event as JSON with field values written as `Typed`, instead of the
+    /// all-string shape its `Serialize` produces.
+    pub fn to_typed_json(&self) -> serde_json::Value {
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        let fields = self
+            .fields
+            .iter()
+            .map(|(k, v)| (k.clone(), serde_json::to_value(Typed(v)).unwrap_or_default()))
+            .collect();
+        value["fields"] = serde_json::Value::Object(fields);
+        value
+    }
+}
+
+/// The identifier a record carried on its source platform, kept verbatim.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "value", rename_all = "snake_case")]
+pub enum NativeId {
+    /// Linux audit serial from `msg=audit(TIME:SERIAL)`.
+    AuditSerial(u64),
+    /// Windows `EventRecordID`.
+    WindowsRecordId(u64),
+    /// macOS unified log `traceID`.
+    MacTraceId(u64),
+    /// systemd journal `__CURSOR`.
+    JournalCursor(String),
+}
+
+/// A typed value in `AuditEvent::fields`.
+///
+/// Serializes scalars as JSON strings (`"sshd"`, `"1000"`, `"true"`, ...), the shape
+/// fields had before they were typed; wrap a value in `Typed` for JSON numbers and
+/// booleans. Strings deserialize back as `Str`, non-negative integers as `UInt`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    Str(String),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    Timestamp(DateTime<Utc>),
+    Ip(IpAddr),
+    List(Vec<FieldValue>),
+    Map(BTreeMap<String, FieldValue>),
+}
+
+impl FieldValue {
+    /// Parses an unsigned integer, keeping the text as `Str` if it is not one.
+    pub fn uint(s: &str) -> Self {
+        s.parse().map(FieldValue::UInt).unwrap_or_else(|_| s.into())
+    }
+
+    /// Parses a signed integer, keeping the text as `Str` if it is not one.
+    pub fn int(s: &str) -> Self {
+        s.parse().map(FieldValue::Int).unwrap_or_else(|_| s.into())
+    }
+
+    /// Parses an IP address, keeping the text as `Str` if it is not one.
+    pub fn ip(s: &str) -> Self {
+        s.parse().map(FieldValue::Ip).unwrap_or_else(|_| s.into())
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            FieldValue::Str(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            FieldValue::UInt(v) => Some(v),
+            FieldValue::Int(v) => u64::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            FieldValue::Int(v) => Some(v),
+            FieldValue::UInt(v) => i64::try_from(v).ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            FieldValue::Int(v) => Some(v as f64),
+            FieldValue::UInt(v) => Some(v as f64),
+            FieldValue::Float(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            FieldValue::Bool(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, FieldValue::Int(_) | FieldValue::UInt(_) | FieldValue::Float(_))
+    }
+
+    /// Orders two values by meaning rather than representation.
+    ///
+    /// Numbers compare numerically across `Int`, `UInt` and `Float`. A `Str` compared
+    /// with a typed value is first parsed as that type, so `"500"` equals `UInt(500)`
+    /// and `"10.0.0.1"` equals the matching `Ip`. Returns `None` for incomparable values.
+    pub fn compare(&self, other: &FieldValue) -> Option<Ordering> {
+        use FieldValue::*;
+        match (self, other) {
+            (Str(a), Str(b)) => Some(a.cmp(b)),
+            (Bool(a), Bool(b)) => Some(a.cmp(b)),
+            (Timestamp(a), Timestamp(b)) => Some(a.cmp(b)),
+            (Ip(a), Ip(b)) => Some(a.cmp(b)),
+            (Int(_) | UInt(_), Int(_) | UInt(_)) => {
+                Some(self.as_i128()?.cmp(&other.as_i128()?))
+            }
+            (a, b) if a.is_number() && b.is_number() => a.as_f64()?.partial_cmp(&b.as_f64()?),
+            (Str(s), typed) => Self::parse_like(s, typed)?.compare(typed),
+            (typed, Str(s)) => typed.compare(&Self::parse_like(s, typed)?),
+            _ => None,
+        }
+    }
+
+    fn as_i128(&self) -> Option<i128> {
+        match *self {
+            FieldValue::Int(v) => Some(v as i128),
+            FieldValue::UInt(v) => Some(v as i128),
+            _ => None,
+        }
+    }
+
+    /// Parses `s` as the same kind of value as `like`.
+    fn parse_like(s: &str, like: &FieldValue) -> Option<FieldValue> {
+        let s = s.trim();
+        match like {
+            FieldValue::Int(_) | FieldValue::UInt(_) | FieldValue::Float(_) => s
+                .parse::<i64>()
+                .map(FieldValue::Int)
+                .or_else(|_| s.parse::<u64>().map(FieldValue::UInt))
+                .or_else(|_| s.parse::<f64>().map(FieldValue::Float))
+                .ok(),
+            FieldValue::Bool(_) => s.parse().ok().map(FieldValue::Bool),
+            FieldValue::Timestamp(_) => DateTime::parse_from_rfc3339(s)
+                .ok()
+                .map(|t| FieldValue::Timestamp(t.with_timezone(&Utc))),
+            FieldValue::Ip(_) => s.parse().ok().map(FieldValue::Ip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Str(s) => f.write_str(s),
+            FieldValue::Int(v) => write!(f, "{}", v),
+            FieldValue::UInt(v) => write!(f, "{}", v),
+            FieldValue::Float(v) => write!(f, "{}", v),
+            FieldValue::Bool(v) => write!(f, "{}", v),
+            FieldValue::Timestamp(t) => f.write_str(&t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+            FieldValue::Ip(ip) => write!(f, "{}", ip),
+            FieldValue::List(_) | FieldValue::Map(_) => {
+                f.write_str(&serde_json::to_string(&Typed(self)).map_err(|_| fmt::Error)?)
+            }
+        }
+    }
+}
+
+/// Compares the textual form, so `UInt(22) == "22"`.
+impl PartialEq<str> for FieldValue {
+    fn eq(&self, other: &str) -> bool {
+        match self {
+            FieldValue::Str(s) => s == other,
+            _ => self.to_string().as_str() == other,
+        }
+    }
+}
+
+impl PartialEq<&str> for FieldValue {
+    fn eq(&self, other: &&str) -> bool {
+        self == *other
+    }
+}
+
+impl From<String> for FieldValue {
+    fn from(v: String) -> Self {
+        FieldValue::Str(v)
+    }
+}
+
+impl From<&str> for FieldValue {
+    fn from(v: &str) -> Self {
+        FieldValue::Str(v.to_string())
+    }
+}
+
+impl From<i64> for FieldValue {
+    fn from(v: i64) -> Self {
+        FieldValue::Int(v)
+    }
+}
+
+impl From<u64> for FieldValue {
+    fn from(v: u64) -> Self {
+        FieldValue::UInt(v)
+    }
+}
+
+impl From<f64> for FieldValue {
+    fn from(v: f64) -> Self {
+        FieldValue::Float(v)
+    }
+}
+
+impl From<bool> for FieldValue {
+    fn from(v: bool) -> Self {
+        FieldValue::Bool(v)
+    }
+}
+
+impl From<DateTime<Utc>> for FieldValue {
+    fn from(v: DateTime<Utc>) -> Self {
+        FieldValue::Timestamp(v)
+    }
+}
+
+impl From<IpAddr> for FieldValue {
+    fn from(v: IpAddr) -> Self {
+        FieldValue::Ip(v)
+    }
+}
+
+impl Serialize for FieldValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            FieldValue::Str(s) => serializer.serialize_str(s),
+            FieldValue::List(items) => items.serialize(serializer),
+            FieldValue::Map(map) => map.serialize(serializer),
+            _ => serializer.collect_str(self),
+        }
+    }
+}
+
+/// Serializes a `FieldValue` with integers, floats and booleans as JSON numbers and
+/// booleans. Timestamps and IPs are still strings.
+pub struct Typed<'a>(pub &'a FieldValue);
+
+impl Serialize for Typed<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            FieldValue::Int(v) => serializer.serialize_i64(*v),
+            FieldValue::UInt(v) => serializer.serialize_u64(*v),
+            FieldValue::Float(v) => serializer.serialize_f64(*v),
+            FieldValue::Bool(v) => serializer.serialize_bool(*v),
+            FieldValue::List(items) => serializer.collect_seq(items.iter().map(Typed)),
+            FieldValue::Map(map) => serializer.collect_map(map.iter().map(|(k, v)| (k, Typed(v)))),
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for FieldValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct FieldValueVisitor;
+
+        impl<'de> Visitor<'de> for FieldValueVisitor {
+            type Value = FieldValue;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a string, number, boolean, array or object")
+            }
+
+            fn visit_bool<E: de::Error>(self, v: bool) -> Result<FieldValue, E> {
+                Ok(FieldValue::Bool(v))
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<FieldValue, E> {
+                Ok(u64::try_from(v).map(FieldValue::UInt).unwrap_or(FieldValue::Int(v)))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<FieldValue, E> {
+                Ok(FieldValue::UInt(v))
+            }
+
+            fn visit_f64<E: de::Error>(self, v: f64) -> Result<FieldValue, E> {
+                Ok(FieldValue::Float(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<FieldValue, E> {
+                Ok(FieldValue::Str(v.to_string()))
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<FieldValue, E> {
+                Ok(FieldValue::Str(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<FieldValue, A::Error> {
+                let mut items = Vec::new();
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(FieldValue::List(items))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<FieldValue, A::Error> {
+                let mut entries = BTreeMap::new();
+                while let Some((k, v)) = map.next_entry()? {
+                    entries.insert(k, v);
+                }
+                Ok(FieldValue::Map(entries))
+            }
+        }
+
+        deserializer.deserialize_any(FieldValueVisitor)
+    }
 }
 
 /// Helper struct to parse macOS JSON log output
@@ -61,4 +442,6 @@ pub struct MacLogEntry {
     pub event_message: Option<String>,
     #[serde(rename = "messageType")]
     pub message_type: Option<String>,
+    #[serde(rename = "traceID")]
+    pub trace_id: Option<u64>,
 }
@@ -0,0 +1,419 @@
+use crate::model::{AuditEvent, FieldValue, NativeId};
+use crate::parser::{SourceFormat, GENERIC_RECORD_TYPE};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Field mapping table: each ECS field and the source fields it is read from.
+/// The first source field that is present (and not an "unset" marker) wins.
+type Table = &'static [(&'static str, &'static [&'static str])];
+
+const AUDITD_FIELDS: Table = &[
+    ("process.executable", &["exe"]),
+    ("process.name", &["comm"]),
+    ("process.pid", &["pid"]),
+    ("process.parent.pid", &["ppid"]),
+    ("process.title", &["proctitle"]),
+    ("user.name", &["acct", "UID"]),
+    ("user.id", &["uid"]),
+    ("user.audit.id", &["auid"]),
+    ("source.ip", &["addr"]),
+    ("host.name", &["node"]),
+    ("file.path", &["name"]),
+];
+
+const MAC_JSON_FIELDS: Table = &[
+    ("message", &["message"]),
+    ("process.executable", &["process"]),
+    ("process.pid", &["pid"]),
+    ("process.thread.id", &["thread_id"]),
+];
+
+const WINDOWS_FIELDS: Table = &[
+    ("process.executable", &["NewProcessName", "ProcessName", "Application"]),
+    ("process.pid", &["NewProcessId", "ProcessId", "pid"]),
+    ("process.parent.executable", &["ParentProcessName"]),
+    ("process.command_line", &["CommandLine"]),
+    ("user.name", &["TargetUserName", "SubjectUserName"]),
+    ("user.domain", &["TargetDomainName", "SubjectDomainName"]),
+    ("user.id", &["TargetUserSid", "user_sid"]),
+    ("source.ip", &["IpAddress"]),
+    ("source.port", &["IpPort"]),
+    ("host.name", &["computer"]),
+    ("file.path", &["ObjectName"]),
+];
+
+/// Used for syslog, journald and every line-oriented format.
+const SYSLOG_FIELDS: Table = &[
+    ("message", &["message"]),
+    ("process.executable", &["exe"]),
+    ("process.name", &["app_name", "comm"]),
+    ("process.pid", &["procid", "pid"]),
+    ("process.command_line", &["command", "cmdline"]),
+    ("user.name", &["user"]),
+    ("user.id", &["uid"]),
+    ("user.target.name", &["target_user"]),
+    ("source.ip", &["source_ip"]),
+    ("source.port", &["source_port"]),
+    ("host.name", &["hostname"]),
+];
+
+/// Type of a field in the common schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SchemaType {
+    /// Exact-match strings: names, paths, ids.
+    Keyword,
+    /// Free text meant for full-text search.
+    Text,
+    UInt,
+    Ip,
+}
+
+/// Every field the normalizer and `HostMetadata::stamp` produce, with its type.
+///
+/// Values are converted to these types, and outputs derive their index mappings
+/// from this table. `observer.*` mirrors `host.*`.
+pub const SCHEMA: &[(&str, SchemaType)] = &[
+    ("message", SchemaType::Text),
+    ("event.module", SchemaType::Keyword),
+    ("event.category", SchemaType::Keyword),
+    ("event.action", SchemaType::Keyword),
+    ("event.outcome", SchemaType::Keyword),
+    ("process.executable", SchemaType::Keyword),
+    ("process.name", SchemaType::Keyword),
+    ("process.pid", SchemaType::UInt),
+    ("process.title", SchemaType::Keyword),
+    ("process.command_line", SchemaType::Keyword),
+    ("process.thread.id", SchemaType::UInt),
+    ("process.parent.pid", SchemaType::UInt),
+    ("process.parent.executable", SchemaType::Keyword),
+    ("user.name", SchemaType::Keyword),
+    ("user.id", SchemaType::Keyword),
+    ("user.domain", SchemaType::Keyword),
+    ("user.audit.id", SchemaType::Keyword),
+    ("user.target.name", SchemaType::Keyword),
+    ("source.ip", SchemaType::Ip),
+    ("source.port", SchemaType::UInt),
+    ("file.path", SchemaType::Keyword),
+    ("host.name", SchemaType::Keyword),
+    ("host.hostname", SchemaType::Keyword),
+    ("host.id", SchemaType::Keyword),
+    ("host.ip", SchemaType::Ip),
+    ("host.boot.id", SchemaType::Keyword),
+    ("host.os.name", SchemaType::Keyword),
+    ("host.os.version", SchemaType::Keyword),
+    ("host.os.kernel", SchemaType::Keyword),
+    ("agent.name", SchemaType::Keyword),
+    ("agent.version", SchemaType::Keyword),
+    ("tags", SchemaType::Keyword),
+];
+
+/// The type of `field`: from `SCHEMA`, or guessed from its name for custom mappings.
+pub fn schema_type(field: &str) -> SchemaType {
+    let host_field = field.strip_prefix("observer.").map(|rest| format!("host.{}", rest));
+    let name = host_field.as_deref().unwrap_or(field);
+    if let Some((_, kind)) = SCHEMA.iter().find(|(f, _)| *f == name) {
+        return *kind;
+    }
+    if field.ends_with(".pid") || field.ends_with(".port") {
+        SchemaType::UInt
+    } else if field.ends_with(".ip") {
+        SchemaType::Ip
+    } else {
+        SchemaType::Keyword
+    }
+}
+
+/// Fields that are already in the common schema, e.g. from `HostMetadata::stamp`,
+/// and stay at the top level instead of moving under `raw`.
+const PASSTHROUGH: &[&str] = &["host.", "observer.", "agent.", "tags"];
+
+/// Values auditd and Windows use for "not set".
+const UNSET: &[&str] = &["", "?", "-", "(null)", "(none)"];
+
+/// User-supplied additions to the built-in mapping tables (`[normalize]`).
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NormalizeConfig {
+    /// Rewrites event fields into the common schema. Off by default, since it
+    /// moves the native fields existing consumers read under `raw`.
+    pub enabled: bool,
+    /// Per source type (`auditd`, `syslog`, a custom parser name, ...), ECS field to
+    /// source fields. These are tried before the built-in sources for that field.
+    pub mappings: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+/// Maps each source's native fields into one ECS-style schema.
+///
+/// After `normalize`, an event's fields hold dotted ECS names (`process.pid`,
+/// `user.name`, `event.action`, ...) and the original fields under `raw`.
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    mappings: HashMap<String, HashMap<String, Vec<String>>>,
+}
+
+impl Normalizer {
+    pub fn new(config: &NormalizeConfig) -> Self {
+        Self {
+            mappings: config.mappings.clone(),
+        }
+    }
+
+    /// Rewrites `event` into the common schema. `format` selects the mapping table.
+    pub fn normalize(&self, format: &SourceFormat, event: &mut AuditEvent) {
+        let source_type = match format {
+            SourceFormat::Auto => detect(event),
+            other => other.name(),
+        };
+        let (mut fields, raw): (HashMap<_, _>, HashMap<_, _>) = std::mem::take(&mut event.fields)
+            .into_iter()
+            .partition(|(key, _)| PASSTHROUGH.iter().any(|p| key.starts_with(p)));
+
+        let custom = self.mappings.get(source_type);
+        for (target, sources) in custom.into_iter().flatten() {
+            if fields.contains_key(target) {
+                continue;
+            }
+            if let Some(value) = lookup(&raw, sources.iter().map(String::as_str)) {
+                fields.insert(target.clone(), convert(target, value));
+            }
+        }
+        for (target, sources) in builtin_table(source_type) {
+            if fields.contains_key(*target) {
+                continue;
+            }
+            if let Some(value) = lookup(&raw, sources.iter().copied()) {
+                fields.insert(target.to_string(), convert(target, value));
+            }
+        }
+
+        let (category, action, outcome) = match source_type {
+            "auditd" => classify_auditd(event.record_type, &raw),
+            "windows_xml" => classify_windows(event.record_type),
+            "mac_json" => (None, None, None),
+            _ => classify_authlog(&raw),
+        };
+        fields.insert("event.module".to_string(), source_type.into());
+        if let Some(category) = category {
+            fields.insert("event.category".to_string(), category.into());
+        }
+        if let Some(action) = action {
+            fields.insert("event.action".to_string(), action.into());
+        }
+        if let Some(outcome) = outcome {
+            fields.insert("event.outcome".to_string(), outcome.into());
+        }
+
+        fields.insert("raw".to_string(), FieldValue::Map(raw.into_iter().collect::<BTreeMap<_, _>>()));
+        event.fields = fields;
+    }
+}
+
+fn builtin_table(source_type: &str) -> Table {
+    match source_type {
+        "auditd" => AUDITD_FIELDS,
+        "mac_json" => MAC_JSON_FIELDS,
+        "windows_xml" => WINDOWS_FIELDS,
+        _ => SYSLOG_FIELDS,
+    }
+}
+
+/// The source type of `event`: `event.module` once normalized, guessed otherwise.
+pub fn source_type(event: &AuditEvent) -> String {
+    match event.fields.get("event.module") {
+        Some(module) => module.to_string(),
+        None => detect(event).to_string(),
+    }
+}
+
+/// Guesses the source type for events parsed with `SourceFormat::Auto`, or not
+/// normalized at all: from the kind of native id, else from the fields each
+/// parser always sets.
+fn detect(event: &AuditEvent) -> &'static str {
+    match event.native_id {
+        Some(NativeId::AuditSerial(_)) => return "auditd",
+        Some(NativeId::WindowsRecordId(_)) => return "windows_xml",
+        Some(NativeId::MacTraceId(_)) => return "mac_json",
+        Some(NativeId::JournalCursor(_)) => return "journald",
+        None => {}
+    }
+    // Windows events use their EventID as record type and keep it as a field.
+    let windows_id = event.fields.get("event_id").and_then(FieldValue::as_u64);
+    if windows_id.is_some() && windows_id == Some(u64::from(event.record_type)) {
+        "windows_xml"
+    } else if event.record_type != GENERIC_RECORD_TYPE {
+        "auditd"
+    } else if event.fields.contains_key("process") {
+        "mac_json"
+    } else {
+        "syslog"
+    }
+}
+
+fn lookup<'a, 'b>(
+    raw: &'a HashMap<String, FieldValue>,
+    mut sources: impl Iterator<Item = &'b str>,
+) -> Option<&'a FieldValue> {
+    sources.find_map(|source| raw.get(source).filter(|v| !is_unset(v)))
+}
+
+fn is_unset(value: &FieldValue) -> bool {
+    value
+        .as_str()
+        .is_some_and(|s| UNSET.contains(&s.trim_matches(|c| c == '"' || c == '\'')))
+}
+
+/// Strips auditd quoting and converts to the field's `schema_type`.
+fn convert(target: &str, value: &FieldValue) -> FieldValue {
+    let Some(text) = value.as_str() else {
+        return value.clone();
+    };
+    let text = text.trim_matches(|c| c == '"' || c == '\'');
+    match schema_type(target) {
+        SchemaType::UInt => {
+            // Windows writes process ids in hex.
+            if let Some(hex) = text.strip_prefix("0x") {
+                if let Ok(v) = u64::from_str_radix(hex, 16) {
+                    return FieldValue::UInt(v);
+                }
+            }
+            FieldValue::uint(text)
+        }
+        SchemaType::Ip => FieldValue::ip(text),
+        SchemaType::Keyword | SchemaType::Text => text.into(),
+    }
+}
+
+type Classification = (Option<&'static str>, Option<String>, Option<&'static str>);
+
+/// x86_64 syscall numbers for the calls we name; `ausearch -i` output already has names.
+const SYSCALLS: &[(u64, &str, &str)] = &[
+    (2, "open", "file"),
+    (257, "openat", "file"),
+    (59, "execve", "process"),
+    (322, "execveat", "process"),
+    (87, "unlink", "file"),
+    (263, "unlinkat", "file"),
+    (82, "rename", "file"),
+    (90, "chmod", "file"),
+    (92, "chown", "file"),
+    (42, "connect", "network"),
+    (49, "bind", "network"),
+];
+
+/// Names for records that carry a numeric `type=1300` instead of `type=SYSCALL`.
+const AUDIT_TYPES: &[(u16, &str)] = &[
+    (1100, "USER_AUTH"),
+    (1105, "USER_START"),
+    (1106, "USER_END"),
+    (1112, "USER_LOGIN"),
+    (1114, "ADD_USER"),
+    (1115, "DEL_USER"),
+    (1116, "ADD_GROUP"),
+    (1117, "DEL_GROUP"),
+    (1123, "USER_CMD"),
+    (1300, "SYSCALL"),
+    (1302, "PATH"),
+    (1309, "EXECVE"),
+];
+
+/// The auditd record type name: the `type=` value unless it is numeric, else from `AUDIT_TYPES`.
+fn audit_type_name(record_type: u16, type_field: Option<&str>) -> &str {
+    match type_field {
+        Some(name) if !name.chars().all(|c| c.is_ascii_digit()) => name,
+        _ => AUDIT_TYPES
+            .iter()
+            .find(|(id, _)| *id == record_type)
+            .map(|(_, name)| *name)
+            .unwrap_or("unknown"),
+    }
+}
+
+/// A readable name for the record type of `event`, e.g. `USER_LOGIN` for auditd
+/// 1112. Other sources have no names, so their number is used.
+pub fn record_type_name(event: &AuditEvent) -> String {
+    if source_type(event) != "auditd" {
+        return event.record_type.to_string();
+    }
+    let type_field = match event.fields.get("raw") {
+        Some(FieldValue::Map(raw)) => raw.get("type"),
+        _ => event.fields.get("type"),
+    };
+    audit_type_name(event.record_type, type_field.and_then(FieldValue::as_str)).to_string()
+}
+
+fn classify_auditd(record_type: u16, raw: &HashMap<String, FieldValue>) -> Classification {
+    let type_name = audit_type_name(record_type, raw.get("type").and_then(FieldValue::as_str));
+
+    let outcome = match raw.get("success").or_else(|| raw.get("res")) {
+        Some(FieldValue::Bool(true)) => Some("success"),
+        Some(FieldValue::Bool(false)) => Some("failure"),
+        Some(v) => match v.to_string().trim_matches('\'') {
+            "success" | "yes" => Some("success"),
+            "failed" | "no" => Some("failure"),
+            _ => None,
+        },
+        None => None,
+    };
+
+    let category = match type_name {
+        "SYSCALL" => {
+            let syscall = raw.get("syscall");
+            let known = SYSCALLS.iter().find(|(nr, name, _)| {
+                syscall.is_some_and(|s| s.as_u64() == Some(*nr) || *s == **name)
+            });
+            return match known {
+                Some((_, name, category)) => (Some(category), Some(name.to_string()), outcome),
+                None => (None, Some("syscall".to_string()), outcome),
+            };
+        }
+        "EXECVE" => Some("process"),
+        "PATH" => Some("file"),
+        "USER_AUTH" | "USER_LOGIN" => Some("authentication"),
+        "USER_START" | "USER_END" => Some("session"),
+        "ADD_USER" | "DEL_USER" | "ADD_GROUP" | "DEL_GROUP" => Some("iam"),
+        "USER_CMD" => Some("process"),
+        _ => None,
+    };
+    (category, Some(type_name.to_ascii_lowercase()), outcome)
+}
+
+fn classify_windows(event_id: u16) -> Classification {
+    let (category, action, outcome) = match event_id {
+        4624 => ("authentication", "logon", Some("success")),
+        4625 => ("authentication", "logon", Some("failure")),
+        4634 | 4647 => ("authentication", "logoff", Some("success")),
+        4648 => ("authentication", "explicit_credential_logon", None),
+        4672 => ("iam", "special_privileges_assigned", Some("success")),
+        4688 => ("process", "process_created", Some("success")),
+        4689 => ("process", "process_terminated", Some("success")),
+        4663 => ("file", "object_access", Some("success")),
+        4720 => ("iam", "user_created", Some("success")),
+        4722 => ("iam", "user_enabled", Some("success")),
+        4725 => ("iam", "user_disabled", Some("success")),
+        4726 => ("iam", "user_deleted", Some("success")),
+        4738 => ("iam", "user_modified", Some("success")),
+        _ => return (None, Some(event_id.to_string()), None),
+    };
+    (Some(category), Some(action.to_string()), outcome)
+}
+
+/// Uses the `action`/`outcome` set by `authlog::extract` for syslog-style sources.
+fn classify_authlog(raw: &HashMap<String, FieldValue>) -> Classification {
+    let Some(action) = raw.get("action").map(FieldValue::to_string) else {
+        return (None, None, None);
+    };
+    let category = match action.as_str() {
+        a if a.starts_with("ssh_") => "authentication",
+        "su" | "auth_failure" | "password_changed" => "authentication",
+        "session_open" | "session_close" => "session",
+        "sudo" => "process",
+        a if a.starts_with("user_") || a.starts_with("group_") => "iam",
+        _ => return (None, Some(action), None),
+    };
+    let outcome = match raw.get("outcome").and_then(FieldValue::as_str) {
+        Some("success") => Some("success"),
+        Some("failure") => Some("failure"),
+        _ => None,
+    };
+    (Some(category), Some(action), outcome)
+}
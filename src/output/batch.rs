@@ -0,0 +1,309 @@
+use super::fanout::SinkStats;
+use super::spool::Spool;
+use crate::model::AuditEvent;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use serde::Deserialize;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// When a batching sink sends what it has collected (`[output.<sink>.batch]`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchConfig {
+    /// Send as soon as this many events are waiting.
+    pub max_events: usize,
+    /// Send whatever is waiting once the oldest event is this old.
+    pub flush_interval_ms: u64,
+    /// Events held while a batch is being sent or retried; newer ones are dropped.
+    pub queue_size: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self { max_events: 500, flush_interval_ms: 1000, queue_size: 10_000 }
+    }
+}
+
+/// Retry policy for failed sends (`[output.<sink>.retry]`).
+///
+/// Delays grow exponentially from `initial_backoff_ms` to `max_backoff_ms`, and
+/// each one is drawn uniformly from zero up to that bound ("full jitter") so that
+/// many collectors do not retry in lockstep.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    /// Attempts per batch, including the first, before it is dead-lettered. A
+    /// sink with a spool keeps retrying transient failures regardless.
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self { max_attempts: 5, initial_backoff_ms: 200, max_backoff_ms: 30_000 }
+    }
+}
+
+/// Why a send failed, and whether trying again could help.
+#[derive(Debug)]
+pub enum SendError {
+    /// Network errors, timeouts, 429 and 5xx responses.
+    Retryable(anyhow::Error),
+    /// The receiver rejected the request itself; retrying would fail the same way.
+    Permanent(anyhow::Error),
+    /// The receiver is overloaded and asked to wait this long before retrying.
+    Throttled(Duration, anyhow::Error),
+}
+
+impl SendError {
+    /// Classifies an unsuccessful HTTP response.
+    pub fn from_status(status: u16, body: &str) -> Self {
+        let error = anyhow::anyhow!("HTTP {}: {}", status, body.chars().take(200).collect::<String>());
+        if status == 408 || status == 429 || status >= 500 {
+            SendError::Retryable(error)
+        } else {
+            SendError::Permanent(error)
+        }
+    }
+
+    pub fn into_inner(self) -> anyhow::Error {
+        match self {
+            SendError::Retryable(e) | SendError::Permanent(e) | SendError::Throttled(_, e) => e,
+        }
+    }
+}
+
+impl From<reqwest::Error> for SendError {
+    fn from(error: reqwest::Error) -> Self {
+        SendError::Retryable(error.into())
+    }
+}
+
+impl RetryConfig {
+    /// Keeps retrying retryable failures however long it takes, for sinks whose
+    /// batches wait in a spool rather than being dead-lettered.
+    pub fn until_delivered(&mut self) {
+        self.max_attempts = u32::MAX;
+    }
+
+    /// The jittered delay before retry number `retry` (starting at 1).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let cap = self
+            .initial_backoff_ms
+            .saturating_mul(1u64 << retry.saturating_sub(1).min(32))
+            .min(self.max_backoff_ms);
+        Duration::from_millis(fastrand::u64(0..=cap))
+    }
+
+    /// Calls `send` until it succeeds, fails permanently or runs out of attempts.
+    ///
+    /// A throttled send waits as long as the receiver asked, up to `max_backoff_ms`.
+    pub fn run<T>(&self, mut send: impl FnMut() -> Result<T, SendError>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            match send() {
+                Ok(value) => return Ok(value),
+                Err(SendError::Retryable(_)) if attempt < self.max_attempts => {
+                    thread::sleep(self.delay(attempt));
+                    attempt += 1;
+                }
+                Err(SendError::Throttled(after, _)) if attempt < self.max_attempts => {
+                    thread::sleep(after.min(Duration::from_millis(self.max_backoff_ms)));
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(e.into_inner()).with_context(|| format!("Giving up after {} attempt(s)", attempt));
+                }
+            }
+        }
+    }
+}
+
+/// Runs `flush` on its own thread with batches of events from the returned channel.
+///
+/// The channel holds up to `queue_size` events; a batch is flushed once it has
+/// `max_events` events or its oldest event has waited `flush_interval_ms`.
+/// `flush` returns how many of the events it failed to deliver, and the rest
+/// are counted as delivered in `stats`.
+///
+/// With a `spool`, events go from the channel to disk and batches are read back
+/// from there, so they outlast an outage or a restart; a batch is acknowledged
+/// once `flush` returns. The sink should then keep retrying an unreachable
+/// destination instead of dead-lettering, see `RetryConfig::until_delivered`.
+pub fn spawn_batches(
+    config: BatchConfig,
+    spool: Option<Spool>,
+    stats: Arc<SinkStats>,
+    mut flush: impl FnMut(Vec<AuditEvent>) -> usize + Send + 'static,
+) -> Sender<AuditEvent> {
+    let (tx, rx) = bounded::<AuditEvent>(config.queue_size);
+    let interval = Duration::from_millis(config.flush_interval_ms);
+    let max_events = config.max_events.max(1);
+
+    let flush_stats = stats.clone();
+    let mut flush = move |events: Vec<AuditEvent>| {
+        let count = events.len();
+        let failed = flush(events).min(count);
+        flush_stats.delivered(count - failed);
+        flush_stats.failed(failed);
+    };
+
+    if let Some(spool) = spool {
+        spawn_spooled(rx, spool, interval, max_events, stats, flush);
+        return tx;
+    }
+
+    thread::spawn(move || {
+        let mut batch = Vec::with_capacity(max_events);
+        let mut deadline = None;
+        loop {
+            let received = match deadline {
+                Some(deadline) => rx.recv_deadline(deadline),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            match received {
+                Ok(event) => {
+                    deadline.get_or_insert_with(|| Instant::now() + interval);
+                    batch.push(event);
+                    if batch.len() < max_events {
+                        continue;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    if !batch.is_empty() {
+                        flush(batch);
+                    }
+                    return;
+                }
+            }
+            flush(std::mem::replace(&mut batch, Vec::with_capacity(max_events)));
+            deadline = None;
+        }
+    });
+    tx
+}
+
+/// The spool and whether the channel feeding it has closed.
+type SpoolState = (Mutex<(Spool, bool)>, Condvar);
+
+/// Moves events from `rx` into `spool` on one thread and flushes batches read
+/// back from it on another.
+fn spawn_spooled(
+    rx: Receiver<AuditEvent>,
+    spool: Spool,
+    interval: Duration,
+    max_events: usize,
+    stats: Arc<SinkStats>,
+    mut flush: impl FnMut(Vec<AuditEvent>) + Send + 'static,
+) {
+    stats.spooled(spool.len(), spool.bytes());
+    let state: Arc<SpoolState> = Arc::new((Mutex::new((spool, false)), Condvar::new()));
+
+    let writer_state = state.clone();
+    let writer_stats = stats.clone();
+    thread::spawn(move || {
+        let (lock, ready) = &*writer_state;
+        for event in rx {
+            let mut guard = lock.lock().unwrap();
+            let spool = &mut guard.0;
+            match spool.append(&event) {
+                Ok(true) => {}
+                Ok(false) => {
+                    writer_stats.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => {
+                    eprintln!("Spool {}: {:#}", spool.dir().display(), e);
+                    writer_stats.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            writer_stats.spooled(spool.len(), spool.bytes());
+            ready.notify_one();
+        }
+        lock.lock().unwrap().1 = true;
+        ready.notify_one();
+    });
+
+    thread::spawn(move || {
+        let (lock, ready) = &*state;
+        loop {
+            let mut guard = lock.lock().unwrap();
+            let mut deadline = None;
+            loop {
+                let (spool, closed) = &*guard;
+                let unread = spool.unread();
+                if unread >= max_events as u64 || *closed || deadline.is_some_and(|d| Instant::now() >= d) {
+                    break;
+                }
+                if unread > 0 {
+                    deadline.get_or_insert_with(|| Instant::now() + interval);
+                }
+                guard = match deadline {
+                    Some(d) => ready.wait_timeout(guard, d.saturating_duration_since(Instant::now())).unwrap().0,
+                    None => ready.wait(guard).unwrap(),
+                };
+            }
+            let (spool, closed) = &mut *guard;
+            if *closed && spool.unread() == 0 {
+                return;
+            }
+            let events = match spool.read(max_events) {
+                Ok(events) => events,
+                Err(e) => {
+                    eprintln!("Spool {}: {:#}", spool.dir().display(), e);
+                    match spool.skip_damaged().and_then(|lost| spool.ack().map(|()| lost)) {
+                        Ok(lost) => {
+                            if lost > 0 {
+                                eprintln!("Spool {}: skipped {} damaged event(s)", spool.dir().display(), lost);
+                            }
+                            stats.failed(lost as usize);
+                            stats.spooled(spool.len(), spool.bytes());
+                        }
+                        Err(e) => {
+                            // Likely an I/O error; try again after a while.
+                            eprintln!("Spool {}: {:#}", spool.dir().display(), e);
+                            drop(guard);
+                            thread::sleep(interval);
+                        }
+                    }
+                    continue;
+                }
+            };
+            drop(guard);
+
+            flush(events);
+
+            let spool = &mut lock.lock().unwrap().0;
+            if let Err(e) = spool.ack() {
+                eprintln!("Spool {}: {:#}", spool.dir().display(), e);
+            }
+            stats.spooled(spool.len(), spool.bytes());
+        }
+    });
+}
+
+/// Writes events a sink gave up on to `dir` as NDJSON, one file per batch, so
+/// they can be inspected or replayed.
+pub fn dead_letter(dir: &Path, sink: &str, events: &[AuditEvent]) -> Result<()> {
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let first = events.first().map(|e| e.id.as_str()).unwrap_or_default();
+    let name = format!("{}-{}-{}.ndjson", sink, Utc::now().format("%Y%m%dT%H%M%S%.3fZ"), first);
+    let partial = dir.join(format!("{}.partial", name));
+
+    let mut file = fs::File::create(&partial)?;
+    for event in events {
+        serde_json::to_writer(&mut file, event)?;
+        file.write_all(b"\n")?;
+    }
+    file.sync_all()?;
+    fs::rename(&partial, dir.join(&name))?;
+    Ok(())
+}
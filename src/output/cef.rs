@@ -0,0 +1,204 @@
+//! ArcSight Common Event Format and IBM QRadar LEEF 2.0 renderings of events.
+//!
+//! Both are a pipe-separated header (vendor, product, version, event class)
+//! followed by `key=value` pairs. Normalized fields map onto each format's
+//! predefined keys; fields without a counterpart are left out. Events that
+//! were not normalized on the way in are normalized for rendering.
+
+use super::{origin_host, severity};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::normalize::{self, Normalizer};
+use crate::parser::SourceFormat;
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// Header fields and event classes for CEF and LEEF (`cef = { ... }`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CefConfig {
+    pub vendor: String,
+    pub product: String,
+    pub version: String,
+    /// Event classes keyed by record type or Windows EventID, either as a number
+    /// (`"4625"`) or an auditd type name (`"USER_LOGIN"`).
+    pub classes: BTreeMap<String, EventClass>,
+}
+
+impl Default for CefConfig {
+    fn default() -> Self {
+        Self {
+            vendor: env!("CARGO_PKG_NAME").to_string(),
+            product: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            classes: BTreeMap::new(),
+        }
+    }
+}
+
+impl CefConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (key, class) in &self.classes {
+            if class.severity.is_some_and(|s| s > 10) {
+                bail!("Severity of event class '{}' must be 0-10", key);
+            }
+        }
+        Ok(())
+    }
+
+    /// The class ID, name and 0-10 severity of `event`.
+    ///
+    /// Unmapped events use the record type name as ID, `event.action` as name,
+    /// and a severity derived from the syslog severity.
+    fn class(&self, event: &AuditEvent) -> (String, String, u8) {
+        let type_name = normalize::record_type_name(event);
+        let class = self
+            .classes
+            .get(&event.record_type.to_string())
+            .or_else(|| self.classes.get(&type_name));
+        let id = class.and_then(|c| c.id.clone()).unwrap_or_else(|| type_name.clone());
+        let name = class
+            .and_then(|c| c.name.clone())
+            .or_else(|| event.fields.get("event.action").map(|a| a.to_string()))
+            .unwrap_or(type_name);
+        let severity = class.and_then(|c| c.severity).unwrap_or(SEVERITIES[severity(event) as usize]);
+        (id, name, severity)
+    }
+}
+
+/// How one record type is reported.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EventClass {
+    /// CEF Device Event Class ID / LEEF EventID.
+    pub id: Option<String>,
+    /// CEF Name; LEEF has no name in the header.
+    pub name: Option<String>,
+    /// 0 (lowest) to 10.
+    pub severity: Option<u8>,
+}
+
+/// CEF severity for each syslog severity, emerg to debug.
+const SEVERITIES: [u8; 8] = [10, 9, 8, 7, 5, 3, 1, 0];
+
+/// Normalized fields and their CEF extension keys.
+const CEF_KEYS: &[(&str, &str)] = &[
+    ("event.action", "act"),
+    ("event.category", "cat"),
+    ("event.outcome", "outcome"),
+    ("user.name", "suser"),
+    ("user.id", "suid"),
+    ("user.target.name", "duser"),
+    ("source.ip", "src"),
+    ("source.port", "spt"),
+    ("process.name", "sproc"),
+    ("process.pid", "spid"),
+    ("file.path", "filePath"),
+    ("message", "msg"),
+];
+
+/// Normalized fields and their LEEF attribute keys; the predefined ones where
+/// LEEF has them, custom ones otherwise.
+const LEEF_KEYS: &[(&str, &str)] = &[
+    ("event.action", "action"),
+    ("event.category", "cat"),
+    ("event.outcome", "outcome"),
+    ("user.name", "usrName"),
+    ("user.id", "accountId"),
+    ("user.target.name", "targetUser"),
+    ("source.ip", "src"),
+    ("source.port", "srcPort"),
+    ("process.name", "processName"),
+    ("process.pid", "pid"),
+    ("file.path", "filePath"),
+    ("message", "msg"),
+];
+
+/// Renders `event` as a CEF line, e.g.
+/// `CEF:0|audit_collector|audit_collector|0.1.0|USER_LOGIN|user-login|5|rt=... suser=alice`.
+pub fn to_cef(event: &AuditEvent, config: &CefConfig, host: &HostMetadata) -> String {
+    let event = &*normalized(event);
+    let (id, name, severity) = config.class(event);
+    let header = [&config.vendor, &config.product, &config.version, &id, &name]
+        .map(|s| escape_header(s))
+        .join("|");
+
+    let mut extension = vec![
+        ("rt", event.timestamp.timestamp_millis().to_string()),
+        ("dvchost", origin_host(event, host)),
+        ("externalId", event.id.clone()),
+    ];
+    extension.extend(mapped(event, CEF_KEYS));
+    let extension: Vec<String> = extension
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, escape_cef_value(&value)))
+        .collect();
+    format!("CEF:0|{}|{}|{}", header, severity, extension.join(" "))
+}
+
+/// Renders `event` as a tab-delimited LEEF 2.0 line, e.g.
+/// `LEEF:2.0|audit_collector|audit_collector|0.1.0|USER_LOGIN|x09|devTime=...`.
+pub fn to_leef(event: &AuditEvent, config: &CefConfig, host: &HostMetadata) -> String {
+    let event = &*normalized(event);
+    let (id, _, severity) = config.class(event);
+    let header = [&config.vendor, &config.product, &config.version, &id]
+        .map(|s| escape_header(s))
+        .join("|");
+
+    let mut attributes = vec![
+        ("devTime", event.timestamp.timestamp_millis().to_string()),
+        ("sev", severity.max(1).to_string()),
+        ("identHostName", origin_host(event, host)),
+        ("externalId", event.id.clone()),
+    ];
+    attributes.extend(mapped(event, LEEF_KEYS));
+    let attributes: Vec<String> = attributes
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, escape_leef_value(&value)))
+        .collect();
+    format!("LEEF:2.0|{}|x09|{}", header, attributes.join("\t"))
+}
+
+/// `event` in the common schema; normalized events carry `event.module`.
+fn normalized(event: &AuditEvent) -> Cow<'_, AuditEvent> {
+    if event.fields.contains_key("event.module") {
+        return Cow::Borrowed(event);
+    }
+    let mut normalized = event.clone();
+    Normalizer::default().normalize(&SourceFormat::Auto, &mut normalized);
+    Cow::Owned(normalized)
+}
+
+fn mapped(event: &AuditEvent, keys: &[(&str, &'static str)]) -> Vec<(&'static str, String)> {
+    keys.iter()
+        .filter_map(|(field, key)| event.fields.get(*field).map(|v| (*key, v.to_string())))
+        .collect()
+}
+
+/// Header fields escape `\` and `|`; line breaks would end the record.
+fn escape_header(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace(['\r', '\n'], " ")
+}
+
+/// CEF extension values escape `\` and `=`, and encode line breaks.
+fn escape_cef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+/// LEEF values escape `\` and the tab delimiter, and encode line breaks.
+fn escape_leef_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,376 @@
+use super::batch::{self, BatchConfig, RetryConfig, SendError};
+use super::fanout::{Sink, SinkStats};
+use super::spool::{Spool, SpoolConfig};
+use super::{OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::normalize::{SchemaType, SCHEMA};
+use anyhow::{bail, Context, Result};
+use chrono::format::{Item, StrftimeItems};
+use crossbeam_channel::Sender;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+/// An Elasticsearch or OpenSearch cluster (`[[output.elasticsearch]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ElasticsearchSinkConfig {
+    /// Base URL of the cluster, e.g. `https://es.example.com:9200`.
+    pub url: String,
+    /// Index name; `strftime` specifiers are filled from the event time (UTC).
+    #[serde(default = "default_index")]
+    pub index: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sent as `Authorization: ApiKey <api_key>`.
+    pub api_key: Option<String>,
+    /// Installs an index template for the native format before the first write.
+    #[serde(default = "default_install_template")]
+    pub install_template: bool,
+    #[serde(default = "default_template_name")]
+    pub template_name: String,
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Events that exhaust their retries or are rejected are written here.
+    pub dead_letter_dir: Option<PathBuf>,
+    /// Events wait on disk while the destination is unreachable, see `spool::Spool`.
+    pub spool: Option<SpoolConfig>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub host_metadata: bool,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_index() -> String {
+    "audit-%Y.%m.%d".to_string()
+}
+
+fn default_install_template() -> bool {
+    true
+}
+
+fn default_template_name() -> String {
+    "audit".to_string()
+}
+
+fn default_timeout() -> u64 {
+    30_000
+}
+
+#[derive(Deserialize)]
+struct BulkResponse {
+    errors: bool,
+    /// One single-key object per action (`{"create": {...}}`), in request order.
+    items: Vec<HashMap<String, BulkItem>>,
+}
+
+#[derive(Deserialize)]
+struct BulkItem {
+    status: u16,
+    #[serde(default)]
+    error: Option<Value>,
+}
+
+/// The per-item result of one `_bulk` request.
+#[derive(Debug, Default)]
+pub struct BulkOutcome {
+    /// Items that failed transiently (429, 5xx) and should be sent again.
+    pub retry: Vec<AuditEvent>,
+    /// Items the cluster refused, e.g. mapping errors, with the reason.
+    pub rejected: Vec<(AuditEvent, String)>,
+}
+
+/// Writes events to Elasticsearch/OpenSearch with the `_bulk` API.
+///
+/// Each event is a `create` action with the event ID as `_id`, so redelivering
+/// a batch cannot duplicate documents: items that already exist come back as
+/// 409 and count as delivered. Only the items that failed are retried.
+pub struct ElasticsearchSink {
+    config: ElasticsearchSinkConfig,
+    host: Arc<HostMetadata>,
+    base: String,
+    stats: Arc<SinkStats>,
+    spool: Option<Spool>,
+    normalized: bool,
+}
+
+impl ElasticsearchSink {
+    pub fn new(mut config: ElasticsearchSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        reqwest::Url::parse(&config.url).with_context(|| format!("Invalid Elasticsearch URL {}", config.url))?;
+        if StrftimeItems::new(&config.index).any(|item| item == Item::Error) {
+            bail!("Invalid date format in index name '{}'", config.index);
+        }
+        if config.password.is_some() && config.username.is_none() {
+            bail!("Elasticsearch output {} has a password but no username", config.url);
+        }
+        let base = config.url.trim_end_matches('/').to_string();
+        let spool = config.spool.clone().map(Spool::open).transpose()?;
+        if spool.is_some() {
+            config.retry.until_delivered();
+        }
+        Ok(Self { config, host, base, stats: Arc::new(SinkStats::default()), spool, normalized: false })
+    }
+
+    /// Whether events arrive normalized, which selects the template installed.
+    pub fn with_normalized(mut self, normalized: bool) -> Self {
+        self.normalized = normalized;
+        self
+    }
+
+    /// The index an event is written to.
+    pub fn index_for(&self, event: &AuditEvent) -> String {
+        event.timestamp.format(&self.config.index).to_string()
+    }
+
+    /// The NDJSON `_bulk` request body for `events`.
+    pub fn bulk_body(&self, events: &[AuditEvent]) -> Result<Vec<u8>> {
+        let options = OutputOptions { format: self.config.format, host_metadata: self.config.host_metadata };
+        let mut body = Vec::new();
+        for event in events {
+            let mut action = json!({ "_index": self.index_for(event) });
+            if !event.id.is_empty() {
+                action["_id"] = event.id.clone().into();
+            }
+            serde_json::to_writer(&mut body, &json!({ "create": action }))?;
+            body.push(b'\n');
+            serde_json::to_writer(&mut body, &options.render(event, &self.host))?;
+            body.push(b'\n');
+        }
+        Ok(body)
+    }
+
+    /// Sends `events`, retrying failed items, and returns those that could not be indexed.
+    pub fn deliver(&self, client: &Client, events: Vec<AuditEvent>) -> Vec<AuditEvent> {
+        let retry = &self.config.retry;
+        let mut pending = events;
+        let mut dead = Vec::new();
+        for attempt in 1..=retry.max_attempts.max(1) {
+            match self.bulk(client, &pending) {
+                Ok(outcome) => {
+                    for (event, reason) in outcome.rejected {
+                        eprintln!("Elasticsearch output {}: event {} rejected: {}", self.base, event.id, reason);
+                        dead.push(event);
+                    }
+                    pending = outcome.retry;
+                }
+                Err(SendError::Permanent(e)) => {
+                    eprintln!("Elasticsearch output {}: {:#}", self.base, e);
+                    dead.append(&mut pending);
+                }
+                Err(SendError::Retryable(e) | SendError::Throttled(_, e)) => eprintln!("Elasticsearch output {}: {:#}", self.base, e),
+            }
+            if pending.is_empty() {
+                return dead;
+            }
+            if attempt < retry.max_attempts {
+                thread::sleep(retry.delay(attempt));
+            }
+        }
+        dead.append(&mut pending);
+        dead
+    }
+
+    /// One `_bulk` request, split into the items to retry and those that were refused.
+    fn bulk(&self, client: &Client, events: &[AuditEvent]) -> Result<BulkOutcome, SendError> {
+        let body = self.bulk_body(events).map_err(SendError::Permanent)?;
+        let response = self
+            .request(client.post(format!("{}/_bulk", self.base)))
+            .header(CONTENT_TYPE, "application/x-ndjson")
+            .body(body)
+            .send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(SendError::from_status(status.as_u16(), &response.text().unwrap_or_default()));
+        }
+        let response: BulkResponse = response
+            .json()
+            .map_err(|e| SendError::Retryable(anyhow::Error::new(e).context("Invalid _bulk response")))?;
+
+        let mut outcome = BulkOutcome::default();
+        if !response.errors {
+            return Ok(outcome);
+        }
+        if response.items.len() != events.len() {
+            return Err(SendError::Retryable(anyhow::anyhow!(
+                "_bulk returned {} items for {} events",
+                response.items.len(),
+                events.len()
+            )));
+        }
+        for (event, item) in events.iter().zip(response.items) {
+            let Some(item) = item.into_values().next() else {
+                continue;
+            };
+            match item.status {
+                200..=299 | 409 => {}
+                429 | 500.. => outcome.retry.push(event.clone()),
+                _ => {
+                    let reason = item.error.map(|e| e.to_string()).unwrap_or_else(|| item.status.to_string());
+                    outcome.rejected.push((event.clone(), reason));
+                }
+            }
+        }
+        Ok(outcome)
+    }
+
+    fn install_template(&self, client: &Client) -> Result<()> {
+        let template = index_template(&index_pattern(&self.config.index), self.normalized);
+        let url = format!("{}/_index_template/{}", self.base, self.config.template_name);
+        self.config.retry.run(|| {
+            let response = self.request(client.put(&url)).json(&template).send()?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            Err(SendError::from_status(status.as_u16(), &response.text().unwrap_or_default()))
+        })
+        .context("Failed to install index template")
+    }
+
+    fn request(&self, builder: RequestBuilder) -> RequestBuilder {
+        if let Some(key) = &self.config.api_key {
+            builder.header("Authorization", format!("ApiKey {}", key))
+        } else if let Some(user) = &self.config.username {
+            builder.basic_auth(user, self.config.password.as_ref())
+        } else {
+            builder
+        }
+    }
+}
+
+impl Sink for ElasticsearchSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        let mut client = None;
+        let mut template_pending = self.config.install_template && self.config.format != OutputFormat::Ocsf;
+        batch::spawn_batches(self.config.batch.clone(), self.spool.take(), self.stats.clone(), move |events| {
+            let client = client.get_or_insert_with(|| {
+                Client::builder()
+                    .timeout(Duration::from_millis(self.config.timeout_ms))
+                    .build()
+                    .expect("HTTP client with static settings")
+            });
+            if template_pending {
+                match self.install_template(client) {
+                    Ok(()) => template_pending = false,
+                    Err(e) => eprintln!("Elasticsearch output {}: {:#}", self.base, e),
+                }
+            }
+
+            let dead = self.deliver(client, events);
+            if dead.is_empty() {
+                return 0;
+            }
+            eprintln!("Elasticsearch output {}: {} event(s) not indexed", self.base, dead.len());
+            if let Some(dir) = &self.config.dead_letter_dir {
+                if let Err(e) = batch::dead_letter(dir, "elasticsearch", &dead) {
+                    eprintln!("Elasticsearch output {}: {:#}", self.base, e);
+                }
+            }
+            dead.len()
+        })
+    }
+}
+
+/// The wildcard pattern matching every index `index` expands to, e.g. `audit-*`.
+pub fn index_pattern(index: &str) -> String {
+    match index.find('%') {
+        Some(i) => format!("{}*", &index[..i]),
+        None => index.to_string(),
+    }
+}
+
+/// A composable index template for native-format events.
+///
+/// The top level follows `AuditEvent`. With `normalized`, `fields` gets the
+/// common schema from `normalize::SCHEMA` and `raw` is stored but not indexed
+/// because its fields differ per source. Without it, only the host metadata is
+/// typed: native fields such as mac_json's `process` or an auth log's `user` are
+/// plain strings and would be rejected by the schema's object mappings. Other
+/// string fields map as keywords.
+pub fn index_template(pattern: &str, normalized: bool) -> Value {
+    let mut fields = Map::new();
+    for (name, kind) in SCHEMA {
+        let host_metadata = name.starts_with("host.") || name.starts_with("agent.") || *name == "tags";
+        if normalized || host_metadata {
+            insert_path(&mut fields, name, mapping(*kind));
+        }
+        if let Some(rest) = name.strip_prefix("host.") {
+            insert_path(&mut fields, &format!("observer.{}", rest), mapping(*kind));
+        }
+    }
+    if normalized {
+        fields.insert("raw".to_string(), json!({ "type": "object", "enabled": false }));
+    }
+
+    json!({
+        "index_patterns": [pattern],
+        "priority": 100,
+        "template": {
+            "mappings": {
+                "dynamic_templates": [{
+                    "strings_as_keywords": {
+                        "match_mapping_type": "string",
+                        "mapping": { "type": "keyword", "ignore_above": 1024 }
+                    }
+                }],
+                "properties": {
+                    "id": { "type": "keyword" },
+                    "timestamp": { "type": "date" },
+                    "record_type": { "type": "integer" },
+                    "sequence": { "type": "unsigned_long" },
+                    "native_id": {
+                        "properties": {
+                            "kind": { "type": "keyword" },
+                            "value": { "type": "keyword" }
+                        }
+                    },
+                    "fields": { "properties": fields }
+                }
+            }
+        }
+    })
+}
+
+fn mapping(kind: SchemaType) -> Value {
+    match kind {
+        SchemaType::Keyword => json!({ "type": "keyword", "ignore_above": 1024 }),
+        SchemaType::Text => json!({ "type": "text" }),
+        SchemaType::UInt => json!({ "type": "long" }),
+        SchemaType::Ip => json!({ "type": "ip" }),
+    }
+}
+
+/// Inserts `mapping` at a dotted path, creating intermediate objects.
+fn insert_path(properties: &mut Map<String, Value>, path: &str, mapping: Value) {
+    match path.split_once('.') {
+        None => {
+            properties.insert(path.to_string(), mapping);
+        }
+        Some((head, rest)) => {
+            let object = properties
+                .entry(head.to_string())
+                .or_insert_with(|| json!({ "properties": {} }));
+            if let Some(Value::Object(children)) = object.get_mut("properties") {
+                insert_path(children, rest, mapping);
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use crate::filter::Filter;
+use crate::model::AuditEvent;
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Delivery counters of one sink. The sink reports deliveries and failures;
+/// `Fanout` counts the events it could not queue.
+#[derive(Debug, Default)]
+pub struct SinkStats {
+    /// Events the sink handed to its destination.
+    pub delivered: AtomicU64,
+    /// Events the sink gave up on, dead-lettered or not.
+    pub failed: AtomicU64,
+    /// Events not queued because the sink was full or had stopped, or because
+    /// its spool was full.
+    pub dropped: AtomicU64,
+    /// Events waiting in the sink's spool, and the bytes its segments take up.
+    pub spooled: AtomicU64,
+    pub spooled_bytes: AtomicU64,
+}
+
+impl SinkStats {
+    pub fn delivered(&self, count: usize) {
+        self.delivered.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self, count: usize) {
+        self.failed.fetch_add(count as u64, Ordering::Relaxed);
+    }
+
+    pub fn spooled(&self, events: u64, bytes: u64) {
+        self.spooled.store(events, Ordering::Relaxed);
+        self.spooled_bytes.store(bytes, Ordering::Relaxed);
+    }
+}
+
+/// An output that runs on its own thread and is fed by the `Fanout`.
+pub trait Sink {
+    /// Reports deliveries into the given counters instead of private ones, so
+    /// the fanout can show them next to the events it dropped for this sink.
+    fn with_stats(self, stats: Arc<SinkStats>) -> Self;
+
+    /// Runs the sink on its own thread and returns the channel that feeds it.
+    fn spawn(self) -> Sender<AuditEvent>;
+}
+
+/// A point-in-time view of one sink, suitable for the API.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SinkStatsSnapshot {
+    /// Output kind and position in the config, e.g. `syslog[1]`.
+    pub name: String,
+    pub filter: Option<String>,
+    /// Events waiting in the sink's queue.
+    pub queued: usize,
+    pub delivered: u64,
+    pub failed: u64,
+    pub dropped: u64,
+    /// Events waiting on disk for an unreachable destination, see `spool::Spool`.
+    pub spooled: u64,
+    pub spooled_bytes: u64,
+}
+
+struct Route {
+    name: String,
+    filter: Option<Filter>,
+    sender: Sender<AuditEvent>,
+    stats: Arc<SinkStats>,
+}
+
+/// Hands every event to each sink whose filter it matches.
+///
+/// Each sink reads from its own bounded queue, and events are only ever offered
+/// to it: when the queue is full or the sink has died the event is dropped for
+/// that sink alone, so neither collection nor the other sinks wait on it.
+#[derive(Default)]
+pub struct Fanout {
+    routes: Vec<Route>,
+}
+
+impl Fanout {
+    /// Adds the sink `spawn` starts, numbered after the sinks of the same `kind`.
+    /// `spawn` is given the counters the sink should report into.
+    pub fn add(
+        &mut self,
+        kind: &str,
+        filter: Option<&str>,
+        spawn: impl FnOnce(Arc<SinkStats>) -> Sender<AuditEvent>,
+    ) -> Result<()> {
+        let index = self.routes.iter().filter(|r| r.name.starts_with(&format!("{}[", kind))).count();
+        let name = format!("{}[{}]", kind, index);
+        let filter = filter
+            .map(Filter::parse)
+            .transpose()
+            .with_context(|| format!("Invalid filter for output {}", name))?;
+        let stats = Arc::new(SinkStats::default());
+        let sender = spawn(stats.clone());
+        self.routes.push(Route { name, filter, sender, stats });
+        Ok(())
+    }
+
+    /// Adds `sink`, see `add`.
+    pub fn add_sink(&mut self, kind: &str, filter: Option<&str>, sink: impl Sink) -> Result<()> {
+        self.add(kind, filter, |stats| sink.with_stats(stats).spawn())
+    }
+
+    pub fn send(&self, event: &AuditEvent) {
+        for route in &self.routes {
+            if route.filter.as_ref().is_some_and(|f| !f.matches(event)) {
+                continue;
+            }
+            if route.sender.try_send(event.clone()).is_err() {
+                route.stats.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<SinkStatsSnapshot> {
+        self.routes
+            .iter()
+            .map(|route| SinkStatsSnapshot {
+                name: route.name.clone(),
+                filter: route.filter.as_ref().map(|f| f.to_string()),
+                queued: route.sender.len(),
+                delivered: route.stats.delivered.load(Ordering::Relaxed),
+                failed: route.stats.failed.load(Ordering::Relaxed),
+                dropped: route.stats.dropped.load(Ordering::Relaxed),
+                spooled: route.stats.spooled.load(Ordering::Relaxed),
+                spooled_bytes: route.stats.spooled_bytes.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,460 @@
+use super::cef::{self, CefConfig};
+use super::fanout::{Sink, SinkStats};
+use super::{OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use anyhow::{Context, Result};
+use chrono::Utc;
+use crossbeam_channel::{bounded, RecvTimeoutError, Sender};
+use serde::Deserialize;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the writer thread wakes up to flush and check time-based rotation.
+const TICK: Duration = Duration::from_millis(200);
+
+/// Suffix of segments that are still being written (or compressed).
+const PARTIAL: &str = ".partial";
+
+/// A directory of line-oriented segments (`[[output.file]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSinkConfig {
+    /// Directory the segments are written to; created if missing.
+    pub path: PathBuf,
+    /// Segment file name prefix, e.g. `events-000001-20260101T000000Z.jsonl`.
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub encoding: FileEncoding,
+    /// Shape of JSON lines; ignored for CEF and LEEF.
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Header and event classes for CEF and LEEF lines.
+    #[serde(default)]
+    pub cef: CefConfig,
+    #[serde(default)]
+    pub host_metadata: bool,
+    /// Only events matching this filter expression are written, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Events waiting to be written; newer ones are dropped beyond this.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    /// Start a new segment once the current one would exceed this many bytes.
+    pub max_bytes: Option<u64>,
+    /// Start a new segment after this many seconds.
+    pub rotate_secs: Option<u64>,
+    /// Compression applied to closed segments.
+    #[serde(default)]
+    pub compression: Compression,
+    /// Delete the oldest closed segments beyond this count.
+    pub max_files: Option<usize>,
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+    /// Used with `fsync = "interval"`.
+    #[serde(default = "default_fsync_interval")]
+    pub fsync_interval_ms: u64,
+}
+
+fn default_prefix() -> String {
+    "events".to_string()
+}
+
+fn default_fsync_interval() -> u64 {
+    1000
+}
+
+fn default_queue_size() -> usize {
+    10_000
+}
+
+impl FileSinkConfig {
+    pub fn options(&self) -> OutputOptions {
+        OutputOptions { format: self.format, host_metadata: self.host_metadata }
+    }
+}
+
+/// What each line of a segment holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileEncoding {
+    /// JSON Lines, `.jsonl`.
+    #[default]
+    Json,
+    /// Common Event Format records, `.cef`.
+    Cef,
+    /// LEEF 2.0 records, `.leef`.
+    Leef,
+}
+
+impl FileEncoding {
+    fn extension(&self) -> &'static str {
+        match self {
+            FileEncoding::Json => ".jsonl",
+            FileEncoding::Cef => ".cef",
+            FileEncoding::Leef => ".leef",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn extension(&self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+/// When written data is forced to disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Leave it to the OS.
+    Never,
+    /// Once per segment, when it is closed.
+    #[default]
+    Rotate,
+    /// Every `fsync_interval_ms`.
+    Interval,
+    /// After every event.
+    Always,
+}
+
+/// The segment currently being appended to.
+struct Segment {
+    /// Final name without compression extension; the file on disk has `.partial` appended.
+    name: String,
+    writer: BufWriter<File>,
+    bytes: u64,
+    opened: Instant,
+}
+
+/// Writes events as JSON Lines (or CEF/LEEF records) into rotating segment files.
+///
+/// Segments are named `{prefix}-{seq}-{start}.jsonl` (`.cef`, `.leef`) and only
+/// get that name once they are complete (and compressed); until then they carry
+/// a `.partial` suffix, so anything matching `*.jsonl*` without it can be shipped
+/// or read safely.
+/// Partial segments left behind by a crash are finalized on startup.
+pub struct FileSink {
+    config: FileSinkConfig,
+    host: Arc<HostMetadata>,
+    active: Option<Segment>,
+    next_seq: u64,
+    last_sync: Instant,
+    stats: Arc<SinkStats>,
+}
+
+impl FileSink {
+    pub fn new(config: FileSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        config.cef.validate()?;
+        fs::create_dir_all(&config.path)
+            .with_context(|| format!("Failed to create output directory {}", config.path.display()))?;
+        let stats = Arc::new(SinkStats::default());
+        let mut sink = Self { config, host, active: None, next_seq: 1, last_sync: Instant::now(), stats };
+        sink.recover()?;
+        sink.next_seq = sink.segments()?.iter().map(|(seq, _)| seq + 1).max().unwrap_or(1);
+        Ok(sink)
+    }
+
+    /// Appends one event, rotating first if it would not fit in the current segment.
+    pub fn write(&mut self, event: &AuditEvent) -> Result<()> {
+        let options = self.config.options();
+        let mut line = match self.config.encoding {
+            FileEncoding::Json => serde_json::to_vec(&options.render(event, &self.host))?,
+            FileEncoding::Cef => cef::to_cef(&options.stamp(event, &self.host), &self.config.cef, &self.host).into_bytes(),
+            FileEncoding::Leef => cef::to_leef(&options.stamp(event, &self.host), &self.config.cef, &self.host).into_bytes(),
+        };
+        line.push(b'\n');
+
+        if let (Some(max), Some(active)) = (self.config.max_bytes, &self.active) {
+            if active.bytes > 0 && active.bytes + line.len() as u64 > max {
+                self.close()?;
+            }
+        }
+        self.rotate_if_due()?;
+        if self.active.is_none() {
+            self.open()?;
+        }
+
+        let active = self.active.as_mut().expect("segment was just opened");
+        active.writer.write_all(&line)?;
+        active.bytes += line.len() as u64;
+        match self.config.fsync {
+            FsyncPolicy::Always => self.sync()?,
+            FsyncPolicy::Interval if self.sync_due() => self.sync()?,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Flushes buffered lines and applies time-based rotation and fsync while idle.
+    pub fn tick(&mut self) -> Result<()> {
+        self.rotate_if_due()?;
+        if let Some(active) = self.active.as_mut() {
+            active.writer.flush()?;
+        }
+        if self.config.fsync == FsyncPolicy::Interval && self.sync_due() {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Closes the current segment, if any, giving it its final name.
+    pub fn close(&mut self) -> Result<()> {
+        let Some(mut active) = self.active.take() else {
+            return Ok(());
+        };
+        active.writer.flush()?;
+        let file = active.writer.into_inner().map_err(|e| e.into_error())?;
+        if self.config.fsync != FsyncPolicy::Never {
+            file.sync_all()?;
+        }
+        drop(file);
+
+        let partial = self.config.path.join(format!("{}{}", active.name, PARTIAL));
+        self.finalize(&partial, &active.name)?;
+        self.enforce_retention()
+    }
+
+    fn rotate_if_due(&mut self) -> Result<()> {
+        let due = match (self.config.rotate_secs, &self.active) {
+            (Some(secs), Some(active)) => active.opened.elapsed() >= Duration::from_secs(secs),
+            _ => false,
+        };
+        if due {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    fn open(&mut self) -> Result<()> {
+        let name = format!(
+            "{}-{:06}-{}{}",
+            self.config.prefix,
+            self.next_seq,
+            Utc::now().format("%Y%m%dT%H%M%SZ"),
+            self.config.encoding.extension()
+        );
+        let path = self.config.path.join(format!("{}{}", name, PARTIAL));
+        let file = OpenOptions::new()
+            .create_new(true)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to create segment {}", path.display()))?;
+        self.next_seq += 1;
+        self.active = Some(Segment { name, writer: BufWriter::new(file), bytes: 0, opened: Instant::now() });
+        Ok(())
+    }
+
+    fn sync_due(&self) -> bool {
+        self.last_sync.elapsed() >= Duration::from_millis(self.config.fsync_interval_ms)
+    }
+
+    fn sync(&mut self) -> Result<()> {
+        if let Some(active) = self.active.as_mut() {
+            active.writer.flush()?;
+            active.writer.get_ref().sync_data()?;
+        }
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// Compresses `partial` if configured and renames the result to `name`.
+    fn finalize(&self, partial: &Path, name: &str) -> Result<()> {
+        let extension = self.config.compression.extension();
+        let target = self.config.path.join(format!("{}{}", name, extension));
+        if self.config.compression == Compression::None {
+            fs::rename(partial, &target)?;
+        } else {
+            let compressed = self.config.path.join(format!("{}{}{}", name, extension, PARTIAL));
+            self.compress(partial, &compressed)
+                .with_context(|| format!("Failed to compress {}", partial.display()))?;
+            fs::rename(&compressed, &target)?;
+            fs::remove_file(partial)?;
+        }
+        if self.config.fsync != FsyncPolicy::Never {
+            sync_dir(&self.config.path)?;
+        }
+        Ok(())
+    }
+
+    fn compress(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut input = File::open(from)?;
+        let output = File::create(to)?;
+        let output = match self.config.compression {
+            Compression::None => unreachable!("uncompressed segments are only renamed"),
+            Compression::Gzip => {
+                let mut encoder = flate2::write::GzEncoder::new(output, flate2::Compression::default());
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?
+            }
+            Compression::Zstd => {
+                let mut encoder = zstd::Encoder::new(output, 0)?;
+                io::copy(&mut input, &mut encoder)?;
+                encoder.finish()?
+            }
+        };
+        if self.config.fsync != FsyncPolicy::Never {
+            output.sync_all()?;
+        }
+        Ok(())
+    }
+
+    /// Finalizes segments a previous run left open, dropping a torn last line.
+    ///
+    /// Segments are recovered whatever encoding they were written with, so
+    /// restarting with a different `encoding` does not lose the open segment.
+    fn recover(&self) -> Result<()> {
+        let mut leftovers = Vec::new();
+        let mut compressing = Vec::new();
+        for entry in fs::read_dir(&self.config.path)? {
+            let file_name = entry?.file_name().to_string_lossy().into_owned();
+            if !self.owns(&file_name) {
+                continue;
+            }
+            let Some(name) = file_name.strip_suffix(PARTIAL) else {
+                continue;
+            };
+            let source = [Compression::Gzip, Compression::Zstd]
+                .iter()
+                .find_map(|c| name.strip_suffix(c.extension()));
+            match source {
+                Some(source) => compressing.push((file_name.clone(), source.to_string())),
+                None => leftovers.push(name.to_string()),
+            }
+        }
+
+        for (file_name, source) in compressing {
+            // Interrupted compression; only safe to discard while the segment it
+            // was made from is still there.
+            if self.config.path.join(format!("{}{}", source, PARTIAL)).exists() {
+                fs::remove_file(self.config.path.join(&file_name))?;
+            }
+        }
+
+        for name in leftovers {
+            let partial = self.config.path.join(format!("{}{}", name, PARTIAL));
+            let done = [Compression::None, Compression::Gzip, Compression::Zstd]
+                .iter()
+                .any(|c| self.config.path.join(format!("{}{}", name, c.extension())).exists());
+            if done {
+                // Crashed after the compressed copy was renamed into place.
+                fs::remove_file(&partial)?;
+                continue;
+            }
+            truncate_to_last_line(&partial)?;
+            self.finalize(&partial, &name)?;
+        }
+        Ok(())
+    }
+
+    /// Closed segments of this sink as `(seq, path)`, oldest first.
+    fn segments(&self) -> Result<Vec<(u64, PathBuf)>> {
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&self.config.path)? {
+            let entry = entry?;
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if file_name.ends_with(PARTIAL) || !self.owns(&file_name) {
+                continue;
+            }
+            let seq = file_name[self.config.prefix.len() + 1..]
+                .split('-')
+                .next()
+                .and_then(|seq| seq.parse::<u64>().ok());
+            if let Some(seq) = seq {
+                segments.push((seq, entry.path()));
+            }
+        }
+        segments.sort();
+        Ok(segments)
+    }
+
+    /// Whether `file_name` is one of this sink's segments rather than another
+    /// sink's whose prefix merely starts the same way.
+    fn owns(&self, file_name: &str) -> bool {
+        file_name
+            .strip_prefix(&self.config.prefix)
+            .is_some_and(|rest| rest.starts_with('-'))
+    }
+
+    fn enforce_retention(&self) -> Result<()> {
+        let Some(max) = self.config.max_files else {
+            return Ok(());
+        };
+        let segments = self.segments()?;
+        let excess = segments.len().saturating_sub(max);
+        for (_, path) in &segments[..excess] {
+            fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for FileSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        let (tx, rx) = bounded::<AuditEvent>(self.config.queue_size);
+        thread::spawn(move || {
+            loop {
+                let result = match rx.recv_timeout(TICK) {
+                    Ok(event) => {
+                        let result = self.write(&event);
+                        match result {
+                            Ok(()) => self.stats.delivered(1),
+                            Err(_) => self.stats.failed(1),
+                        }
+                        result
+                    }
+                    Err(RecvTimeoutError::Timeout) => self.tick(),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                };
+                if let Err(e) = result {
+                    eprintln!("File output {}: {:#}", self.config.path.display(), e);
+                }
+            }
+            if let Err(e) = self.close() {
+                eprintln!("File output {}: {:#}", self.config.path.display(), e);
+            }
+        });
+        tx
+    }
+}
+
+fn truncate_to_last_line(path: &Path) -> Result<()> {
+    let mut content = Vec::new();
+    File::open(path)?.read_to_end(&mut content)?;
+    let keep = content.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+    if keep < content.len() {
+        OpenOptions::new().write(true).open(path)?.set_len(keep as u64)?;
+    }
+    Ok(())
+}
+
+/// Makes renames in `dir` durable. Directories cannot be opened this way on Windows.
+fn sync_dir(dir: &Path) -> Result<()> {
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
@@ -0,0 +1,985 @@
+//! A Kafka producer speaking the broker protocol directly over TCP.
+//!
+//! Only what producing needs is implemented: Metadata (v1) to find partition
+//! leaders, InitProducerId (v0) for idempotent mode, and Produce (v3, or v7 for
+//! zstd) carrying v2 record batches. Connections are plaintext.
+
+use super::batch::{self, BatchConfig, RetryConfig};
+use super::fanout::{Sink, SinkStats};
+use super::spool::{Spool, SpoolConfig};
+use super::{origin_host, OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::normalize;
+use anyhow::{anyhow, bail, Context, Result};
+use crossbeam_channel::Sender;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const PRODUCE: i16 = 0;
+const METADATA: i16 = 3;
+const INIT_PRODUCER_ID: i16 = 22;
+
+const DUPLICATE_SEQUENCE_NUMBER: i16 = 46;
+
+/// A Kafka cluster (`[[output.kafka]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct KafkaSinkConfig {
+    /// Bootstrap brokers as `host:port`; the rest of the cluster is discovered.
+    pub brokers: Vec<String>,
+    /// Topic for source types without an entry in `topics`.
+    #[serde(default = "default_topic")]
+    pub topic: String,
+    /// Topic per source type, e.g. `{ auditd = "audit-linux", windows_xml = "audit-windows" }`.
+    #[serde(default)]
+    pub topics: BTreeMap<String, String>,
+    #[serde(default)]
+    pub key: KeySource,
+    #[serde(default)]
+    pub acks: Acks,
+    /// Have the brokers discard retried batches they already wrote, so that
+    /// retries never duplicate events. Requires `acks = "all"`.
+    #[serde(default)]
+    pub idempotent: bool,
+    #[serde(default)]
+    pub compression: Compression,
+    #[serde(default = "default_client_id")]
+    pub client_id: String,
+    /// Socket timeout, and how long brokers may wait for replicas when `acks = "all"`.
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Batches that cannot be delivered are written here.
+    pub dead_letter_dir: Option<PathBuf>,
+    /// Events wait on disk while the destination is unreachable, see `spool::Spool`.
+    pub spool: Option<SpoolConfig>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub host_metadata: bool,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_topic() -> String {
+    "audit-events".to_string()
+}
+
+fn default_client_id() -> String {
+    env!("CARGO_PKG_NAME").to_string()
+}
+
+fn default_timeout() -> u64 {
+    30_000
+}
+
+/// What record keys are made of. Records with the same key go to the same
+/// partition, so per-host keys keep each host's events in order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// The host the event is about, see `origin_host`.
+    #[default]
+    Host,
+    /// The event ID, spreading events evenly over partitions.
+    EventId,
+    /// No key; each batch goes to one randomly chosen partition.
+    None,
+}
+
+/// How many replicas must have a batch before the leader acknowledges it.
+/// Written as `"all"` (or `-1`), `1` or `0` as in the Kafka clients.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(try_from = "AcksSetting")]
+pub enum Acks {
+    /// No response at all; batches lost in flight go unnoticed.
+    None,
+    Leader,
+    #[default]
+    All,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AcksSetting {
+    Number(i64),
+    Name(String),
+}
+
+impl TryFrom<AcksSetting> for Acks {
+    type Error = String;
+
+    fn try_from(setting: AcksSetting) -> Result<Self, Self::Error> {
+        match setting {
+            AcksSetting::Number(0) => Ok(Acks::None),
+            AcksSetting::Number(1) => Ok(Acks::Leader),
+            AcksSetting::Number(-1) => Ok(Acks::All),
+            AcksSetting::Name(name) if name == "all" => Ok(Acks::All),
+            _ => Err("acks must be \"all\", -1, 1 or 0".to_string()),
+        }
+    }
+}
+
+impl Acks {
+    fn code(self) -> i16 {
+        match self {
+            Acks::None => 0,
+            Acks::Leader => 1,
+            Acks::All => -1,
+        }
+    }
+}
+
+/// Record batch compression codecs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    #[default]
+    None,
+    Gzip,
+    Snappy,
+    Lz4,
+    /// Needs Kafka 2.1 or later.
+    Zstd,
+}
+
+impl Compression {
+    /// The codec bits of a record batch's attributes.
+    fn id(self) -> i16 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => 1,
+            Compression::Snappy => 2,
+            Compression::Lz4 => 3,
+            Compression::Zstd => 4,
+        }
+    }
+
+    fn from_id(id: i16) -> Result<Self> {
+        Ok(match id {
+            0 => Compression::None,
+            1 => Compression::Gzip,
+            2 => Compression::Snappy,
+            3 => Compression::Lz4,
+            4 => Compression::Zstd,
+            other => bail!("Unknown compression codec {}", other),
+        })
+    }
+
+    fn compress(self, data: Vec<u8>) -> Result<Vec<u8>> {
+        Ok(match self {
+            Compression::None => data,
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&data)?;
+                encoder.finish()?
+            }
+            Compression::Snappy => snap::raw::Encoder::new().compress_vec(&data)?,
+            Compression::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(&data)?;
+                encoder.finish()?
+            }
+            Compression::Zstd => zstd::encode_all(data.as_slice(), 0)?,
+        })
+    }
+
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        match self {
+            Compression::None => out.extend_from_slice(data),
+            Compression::Gzip => {
+                GzDecoder::new(data).read_to_end(&mut out)?;
+            }
+            Compression::Snappy => out = snap::raw::Decoder::new().decompress_vec(data)?,
+            Compression::Lz4 => {
+                lz4_flex::frame::FrameDecoder::new(data).read_to_end(&mut out)?;
+            }
+            Compression::Zstd => out = zstd::decode_all(data)?,
+        }
+        Ok(out)
+    }
+}
+
+/// The partition the Java client's default partitioner picks for `key`.
+pub fn partition_for(key: &[u8], partitions: usize) -> usize {
+    (murmur2(key) & 0x7fff_ffff) as usize % partitions
+}
+
+/// MurmurHash2 as the Kafka clients implement it.
+pub fn murmur2(data: &[u8]) -> i32 {
+    const M: u32 = 0x5bd1_e995;
+    let mut h = 0x9747_b28c ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes(chunk.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M) ^ k;
+    }
+    let tail = chunks.remainder();
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u32) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h as i32
+}
+
+const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82f6_3b78 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// CRC-32C (Castagnoli), the checksum of v2 record batches.
+fn crc32c(data: &[u8]) -> u32 {
+    !data
+        .iter()
+        .fold(!0u32, |crc, byte| CRC32C_TABLE[((crc ^ *byte as u32) & 0xff) as usize] ^ (crc >> 8))
+}
+
+/// Encodes Kafka protocol primitives, big-endian.
+#[derive(Debug, Default)]
+pub struct Writer(pub Vec<u8>);
+
+impl Writer {
+    pub fn i8(&mut self, value: i8) {
+        self.0.push(value as u8);
+    }
+
+    pub fn i16(&mut self, value: i16) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn i64(&mut self, value: i64) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn string(&mut self, value: &str) {
+        self.i16(value.len() as i16);
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    pub fn nullable_string(&mut self, value: Option<&str>) {
+        match value {
+            Some(value) => self.string(value),
+            None => self.i16(-1),
+        }
+    }
+
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.i32(value.len() as i32);
+        self.0.extend_from_slice(value);
+    }
+
+    /// A zigzag-encoded variable-length integer, as used inside record batches.
+    pub fn varint(&mut self, value: i64) {
+        let mut value = ((value << 1) ^ (value >> 63)) as u64;
+        while value >= 0x80 {
+            self.0.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.0.push(value as u8);
+    }
+
+    pub fn varbytes(&mut self, value: Option<&[u8]>) {
+        match value {
+            Some(value) => {
+                self.varint(value.len() as i64);
+                self.0.extend_from_slice(value);
+            }
+            None => self.varint(-1),
+        }
+    }
+}
+
+/// Decodes Kafka protocol primitives.
+#[derive(Debug)]
+pub struct Reader<'a>(pub &'a [u8]);
+
+impl<'a> Reader<'a> {
+    pub fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!("Truncated message: wanted {} bytes, {} left", len, self.0.len());
+        }
+        let (head, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(head)
+    }
+
+    pub fn i8(&mut self) -> Result<i8> {
+        Ok(self.take(1)?[0] as i8)
+    }
+
+    pub fn i16(&mut self) -> Result<i16> {
+        Ok(i16::from_be_bytes(self.take(2)?.try_into()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64> {
+        Ok(i64::from_be_bytes(self.take(8)?.try_into()?))
+    }
+
+    pub fn nullable_string(&mut self) -> Result<Option<String>> {
+        match self.i16()? {
+            -1 => Ok(None),
+            len => Ok(Some(String::from_utf8(self.take(len.max(0) as usize)?.to_vec())?)),
+        }
+    }
+
+    pub fn string(&mut self) -> Result<String> {
+        self.nullable_string()?.context("Unexpected null string")
+    }
+
+    pub fn bytes(&mut self) -> Result<Option<&'a [u8]>> {
+        match self.i32()? {
+            -1 => Ok(None),
+            len => Ok(Some(self.take(len.max(0) as usize)?)),
+        }
+    }
+
+    pub fn varint(&mut self) -> Result<i64> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.take(1)?[0];
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok((value >> 1) as i64 ^ -((value & 1) as i64));
+            }
+        }
+        bail!("Varint longer than 10 bytes")
+    }
+
+    pub fn varbytes(&mut self) -> Result<Option<&'a [u8]>> {
+        match self.varint()? {
+            -1 => Ok(None),
+            len => Ok(Some(self.take(len.max(0) as usize)?)),
+        }
+    }
+}
+
+/// One message in a record batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Milliseconds since the epoch.
+    pub timestamp: i64,
+    pub key: Option<Vec<u8>>,
+    pub value: Vec<u8>,
+}
+
+/// A v2 ("magic 2") record batch for one partition.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordBatch {
+    /// -1 unless the producer is idempotent; likewise the epoch and sequence.
+    pub producer_id: i64,
+    pub producer_epoch: i16,
+    pub base_sequence: i32,
+    pub compression: Compression,
+    pub records: Vec<Record>,
+}
+
+impl RecordBatch {
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let base_timestamp = self.records.first().map(|r| r.timestamp).unwrap_or_default();
+        let max_timestamp = self.records.iter().map(|r| r.timestamp).max().unwrap_or_default();
+
+        let mut records = Writer::default();
+        for (offset_delta, record) in self.records.iter().enumerate() {
+            let mut body = Writer::default();
+            body.i8(0);
+            body.varint(record.timestamp - base_timestamp);
+            body.varint(offset_delta as i64);
+            body.varbytes(record.key.as_deref());
+            body.varbytes(Some(&record.value));
+            body.varint(0);
+            records.varint(body.0.len() as i64);
+            records.0.extend_from_slice(&body.0);
+        }
+
+        let mut checked = Writer::default();
+        checked.i16(self.compression.id());
+        checked.i32(self.records.len() as i32 - 1);
+        checked.i64(base_timestamp);
+        checked.i64(max_timestamp);
+        checked.i64(self.producer_id);
+        checked.i16(self.producer_epoch);
+        checked.i32(self.base_sequence);
+        checked.i32(self.records.len() as i32);
+        checked.0.extend(self.compression.compress(records.0)?);
+
+        let mut batch = Writer::default();
+        batch.i64(0);
+        batch.i32(4 + 1 + 4 + checked.0.len() as i32);
+        batch.i32(-1);
+        batch.i8(2);
+        batch.0.extend_from_slice(&crc32c(&checked.0).to_be_bytes());
+        batch.0.extend(checked.0);
+        Ok(batch.0)
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader(data);
+        reader.i64()?;
+        let length = reader.i32()?;
+        let mut batch = Reader(reader.take(length.max(0) as usize)?);
+        batch.i32()?;
+        let magic = batch.i8()?;
+        if magic != 2 {
+            bail!("Unsupported record batch version {}", magic);
+        }
+        let crc = batch.i32()? as u32;
+        if crc32c(batch.0) != crc {
+            bail!("Record batch checksum mismatch");
+        }
+        let compression = Compression::from_id(batch.i16()? & 0x07)?;
+        batch.i32()?;
+        let base_timestamp = batch.i64()?;
+        batch.i64()?;
+        let producer_id = batch.i64()?;
+        let producer_epoch = batch.i16()?;
+        let base_sequence = batch.i32()?;
+        let count = batch.i32()?;
+
+        let body = compression.decompress(batch.0)?;
+        let mut body = Reader(&body);
+        let mut records = Vec::new();
+        for _ in 0..count {
+            let len = body.varint()?;
+            let mut record = Reader(body.take(len.max(0) as usize)?);
+            record.i8()?;
+            let timestamp = base_timestamp + record.varint()?;
+            record.varint()?;
+            let key = record.varbytes()?.map(|k| k.to_vec());
+            let value = record.varbytes()?.unwrap_or_default().to_vec();
+            for _ in 0..record.varint()? {
+                record.varbytes()?;
+                record.varbytes()?;
+            }
+            records.push(Record { timestamp, key, value });
+        }
+        Ok(Self { producer_id, producer_epoch, base_sequence, compression, records })
+    }
+}
+
+/// Errors worth retrying, and whether they mean our metadata is stale.
+fn retriable(code: i16) -> Option<bool> {
+    match code {
+        3 | 5 | 6 | 13 | 56 => Some(true),
+        7 | 19 | 20 | 89 => Some(false),
+        _ => None,
+    }
+}
+
+fn error_name(code: i16) -> String {
+    let name = match code {
+        -1 => "UNKNOWN_SERVER_ERROR",
+        2 => "CORRUPT_MESSAGE",
+        3 => "UNKNOWN_TOPIC_OR_PARTITION",
+        5 => "LEADER_NOT_AVAILABLE",
+        6 => "NOT_LEADER_OR_FOLLOWER",
+        7 => "REQUEST_TIMED_OUT",
+        10 => "MESSAGE_TOO_LARGE",
+        13 => "NETWORK_EXCEPTION",
+        17 => "INVALID_TOPIC_EXCEPTION",
+        18 => "RECORD_LIST_TOO_LARGE",
+        19 => "NOT_ENOUGH_REPLICAS",
+        20 => "NOT_ENOUGH_REPLICAS_AFTER_APPEND",
+        29 => "TOPIC_AUTHORIZATION_FAILED",
+        45 => "OUT_OF_ORDER_SEQUENCE_NUMBER",
+        47 => "INVALID_PRODUCER_EPOCH",
+        56 => "KAFKA_STORAGE_ERROR",
+        59 => "UNKNOWN_PRODUCER_ID",
+        76 => "UNSUPPORTED_COMPRESSION_TYPE",
+        87 => "INVALID_RECORD",
+        89 => "THROTTLING_QUOTA_EXCEEDED",
+        _ => return format!("error code {}", code),
+    };
+    name.to_string()
+}
+
+/// A connection to one broker, sending one request at a time.
+struct Connection {
+    stream: TcpStream,
+    correlation_id: i32,
+}
+
+impl Connection {
+    fn open(address: &str, timeout: Duration) -> Result<Self> {
+        let addr = address
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {}", address))?
+            .next()
+            .with_context(|| format!("No address for {}", address))?;
+        let stream = TcpStream::connect_timeout(&addr, timeout).with_context(|| format!("Failed to connect to {}", address))?;
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))?;
+        stream.set_nodelay(true)?;
+        Ok(Self { stream, correlation_id: 0 })
+    }
+
+    /// Sends a request and returns the response body, if one is expected.
+    fn request(&mut self, api_key: i16, version: i16, client_id: &str, body: &[u8], expect_response: bool) -> Result<Option<Vec<u8>>> {
+        self.correlation_id = self.correlation_id.wrapping_add(1);
+        let mut message = Writer::default();
+        message.i32(0);
+        message.i16(api_key);
+        message.i16(version);
+        message.i32(self.correlation_id);
+        message.nullable_string(Some(client_id));
+        message.0.extend_from_slice(body);
+        let size = (message.0.len() - 4) as i32;
+        message.0[..4].copy_from_slice(&size.to_be_bytes());
+        self.stream.write_all(&message.0)?;
+        if !expect_response {
+            return Ok(None);
+        }
+
+        let mut size = [0; 4];
+        self.stream.read_exact(&mut size)?;
+        let mut response = vec![0; i32::from_be_bytes(size).max(0) as usize];
+        self.stream.read_exact(&mut response)?;
+        let correlation_id = Reader(&response).i32()?;
+        if correlation_id != self.correlation_id {
+            bail!("Response {} does not match request {}", correlation_id, self.correlation_id);
+        }
+        Ok(Some(response.split_off(4)))
+    }
+}
+
+/// Events bound for one partition, encoded once so that retries resend the
+/// same batch (and, when idempotent, the same sequence numbers).
+struct PartitionBatch {
+    topic: String,
+    partition: i32,
+    events: Vec<AuditEvent>,
+    records: Vec<u8>,
+}
+
+/// Produces events to Kafka, one JSON record per event.
+///
+/// Each flush is split into one record batch per partition. Batches that fail
+/// with retriable errors are resent, after refreshing the partition leaders
+/// when those have moved; batches the brokers refuse are dead-lettered.
+pub struct KafkaSink {
+    config: KafkaSinkConfig,
+    host: Arc<HostMetadata>,
+    name: String,
+    /// Address of each broker by node ID.
+    brokers: HashMap<i32, String>,
+    /// Leader of each partition by topic; -1 where there is none.
+    leaders: HashMap<String, Vec<i32>>,
+    connections: HashMap<i32, Connection>,
+    producer: Option<(i64, i16)>,
+    sequences: HashMap<(String, i32), i32>,
+    stats: Arc<SinkStats>,
+    spool: Option<Spool>,
+}
+
+impl KafkaSink {
+    pub fn new(mut config: KafkaSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        if config.brokers.is_empty() {
+            bail!("At least one Kafka broker is required");
+        }
+        if config.idempotent && config.acks != Acks::All {
+            bail!("idempotent = true requires acks = \"all\"");
+        }
+        let name = config.brokers.join(",");
+        let spool = config.spool.clone().map(Spool::open).transpose()?;
+        if spool.is_some() {
+            config.retry.until_delivered();
+        }
+        Ok(Self {
+            config,
+            host,
+            name,
+            brokers: HashMap::new(),
+            leaders: HashMap::new(),
+            connections: HashMap::new(),
+            producer: None,
+            sequences: HashMap::new(),
+            stats: Arc::new(SinkStats::default()),
+            spool,
+        })
+    }
+
+    /// The topic an event is routed to by its source type.
+    pub fn topic_for(&self, event: &AuditEvent) -> &str {
+        self.config
+            .topics
+            .get(&normalize::source_type(event))
+            .unwrap_or(&self.config.topic)
+    }
+
+    pub fn key_for(&self, event: &AuditEvent) -> Option<Vec<u8>> {
+        match self.config.key {
+            KeySource::Host => Some(origin_host(event, &self.host).into_bytes()),
+            KeySource::EventId => Some(event.id.clone().into_bytes()),
+            KeySource::None => None,
+        }
+    }
+
+    /// Produces a flush of events, retrying per partition, and returns those
+    /// that could not be delivered.
+    pub fn deliver(&mut self, events: Vec<AuditEvent>) -> Vec<AuditEvent> {
+        let retry = self.config.retry.clone();
+        let mut unassigned = events;
+        let mut pending = Vec::new();
+        let mut dead = Vec::new();
+        for attempt in 1..=retry.max_attempts.max(1) {
+            if !unassigned.is_empty() {
+                match self.assign(&unassigned) {
+                    Ok(batches) => {
+                        pending.extend(batches);
+                        unassigned.clear();
+                    }
+                    Err(e) => eprintln!("Kafka output {}: {:#}", self.name, e),
+                }
+            }
+            if !pending.is_empty() {
+                pending = self.produce(pending, &mut dead);
+            }
+            if unassigned.is_empty() && pending.is_empty() {
+                return dead;
+            }
+            if attempt < retry.max_attempts {
+                thread::sleep(retry.delay(attempt));
+            }
+        }
+        if self.config.idempotent && !pending.is_empty() {
+            self.reset_producer();
+        }
+        dead.extend(unassigned);
+        dead.extend(pending.into_iter().flat_map(|b| b.events));
+        dead
+    }
+
+    /// Picks each event's partition and encodes one record batch per partition.
+    fn assign(&mut self, events: &[AuditEvent]) -> Result<Vec<PartitionBatch>> {
+        let topics: BTreeSet<String> = events.iter().map(|e| self.topic_for(e).to_string()).collect();
+        if topics.iter().any(|t| !self.leaders.contains_key(t)) {
+            self.refresh_metadata(&topics)?;
+        }
+        if self.config.idempotent && self.producer.is_none() {
+            self.init_producer_id()?;
+        }
+
+        let options = OutputOptions { format: self.config.format, host_metadata: self.config.host_metadata };
+        let mut unkeyed = HashMap::new();
+        let mut groups: BTreeMap<(String, i32), Vec<(Record, AuditEvent)>> = BTreeMap::new();
+        for event in events {
+            let topic = self.topic_for(event).to_string();
+            let partitions = self.leaders[&topic].len();
+            let key = self.key_for(event);
+            let partition = match &key {
+                Some(key) => partition_for(key, partitions),
+                None => *unkeyed.entry(topic.clone()).or_insert_with(|| fastrand::usize(..partitions)),
+            };
+            let value = serde_json::to_vec(&options.render(event, &self.host))?;
+            let record = Record { timestamp: event.timestamp.timestamp_millis(), key, value };
+            groups.entry((topic, partition as i32)).or_default().push((record, event.clone()));
+        }
+
+        let (producer_id, producer_epoch) = self.producer.unwrap_or((-1, -1));
+        let mut batches = Vec::new();
+        for ((topic, partition), entries) in groups {
+            let (records, events): (Vec<_>, Vec<_>) = entries.into_iter().unzip();
+            let base_sequence = match self.producer {
+                Some(_) => {
+                    let next = self.sequences.entry((topic.clone(), partition)).or_insert(0);
+                    let base = *next;
+                    *next = next.wrapping_add(records.len() as i32) & i32::MAX;
+                    base
+                }
+                None => -1,
+            };
+            let batch = RecordBatch { producer_id, producer_epoch, base_sequence, compression: self.config.compression, records };
+            batches.push(PartitionBatch { topic, partition, events, records: batch.encode()? });
+        }
+        Ok(batches)
+    }
+
+    /// Sends each batch to its partition leader and returns those to retry.
+    fn produce(&mut self, batches: Vec<PartitionBatch>, dead: &mut Vec<AuditEvent>) -> Vec<PartitionBatch> {
+        let topics: BTreeSet<String> = batches.iter().map(|b| b.topic.clone()).collect();
+        if topics.iter().any(|t| !self.leaders.contains_key(t)) {
+            if let Err(e) = self.refresh_metadata(&topics) {
+                eprintln!("Kafka output {}: {:#}", self.name, e);
+                return batches;
+            }
+        }
+
+        let mut retry = Vec::new();
+        let mut stale = BTreeSet::new();
+        let mut by_leader: BTreeMap<i32, Vec<PartitionBatch>> = BTreeMap::new();
+        for batch in batches {
+            match self.leaders.get(&batch.topic).and_then(|p| p.get(batch.partition as usize)) {
+                Some(leader) if *leader >= 0 => by_leader.entry(*leader).or_default().push(batch),
+                // No leader yet (-1), or the topic's metadata is missing: look it up again.
+                _ => {
+                    stale.insert(batch.topic.clone());
+                    retry.push(batch);
+                }
+            }
+        }
+        // Only now, so the topic's partitions that do have a leader are still sent.
+        for topic in stale {
+            self.leaders.remove(&topic);
+        }
+
+        for (leader, batches) in by_leader {
+            let errors = match self.produce_to(leader, &batches) {
+                Ok(errors) => errors,
+                Err(e) => {
+                    eprintln!("Kafka output {}: broker {}: {:#}", self.name, leader, e);
+                    self.connections.remove(&leader);
+                    self.leaders.clear();
+                    retry.extend(batches);
+                    continue;
+                }
+            };
+            for batch in batches {
+                let code = errors.get(&(batch.topic.clone(), batch.partition)).copied().unwrap_or(0);
+                if code == 0 || code == DUPLICATE_SEQUENCE_NUMBER {
+                    continue;
+                }
+                let error = error_name(code);
+                match retriable(code) {
+                    Some(stale) => {
+                        eprintln!("Kafka output {}: {}-{}: {}", self.name, batch.topic, batch.partition, error);
+                        if stale {
+                            self.leaders.remove(&batch.topic);
+                        }
+                        retry.push(batch);
+                    }
+                    None => {
+                        eprintln!(
+                            "Kafka output {}: {}-{} refused {} event(s): {}",
+                            self.name,
+                            batch.topic,
+                            batch.partition,
+                            batch.events.len(),
+                            error
+                        );
+                        if self.config.idempotent {
+                            self.reset_producer();
+                        }
+                        dead.extend(batch.events);
+                    }
+                }
+            }
+        }
+        retry
+    }
+
+    /// One Produce request to `leader`, returning the error code per partition.
+    /// With `acks = 0` there is no response and nothing to report.
+    fn produce_to(&mut self, leader: i32, batches: &[PartitionBatch]) -> Result<HashMap<(String, i32), i16>> {
+        let version = if self.config.compression == Compression::Zstd { 7 } else { 3 };
+        let mut by_topic: BTreeMap<&str, Vec<&PartitionBatch>> = BTreeMap::new();
+        for batch in batches {
+            by_topic.entry(&batch.topic).or_default().push(batch);
+        }
+        let mut body = Writer::default();
+        body.nullable_string(None);
+        body.i16(self.config.acks.code());
+        body.i32(self.config.timeout_ms.min(i32::MAX as u64) as i32);
+        body.i32(by_topic.len() as i32);
+        for (topic, batches) in by_topic {
+            body.string(topic);
+            body.i32(batches.len() as i32);
+            for batch in batches {
+                body.i32(batch.partition);
+                body.bytes(&batch.records);
+            }
+        }
+
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let connection = match self.connections.entry(leader) {
+            std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+            std::collections::hash_map::Entry::Vacant(entry) => {
+                let address = self.brokers.get(&leader).with_context(|| format!("Unknown broker {}", leader))?;
+                entry.insert(Connection::open(address, timeout)?)
+            }
+        };
+        let expect_response = self.config.acks != Acks::None;
+        let Some(response) = connection.request(PRODUCE, version, &self.config.client_id, &body.0, expect_response)? else {
+            return Ok(HashMap::new());
+        };
+
+        let mut reader = Reader(&response);
+        let mut errors = HashMap::new();
+        for _ in 0..reader.i32()? {
+            let topic = reader.string()?;
+            for _ in 0..reader.i32()? {
+                let partition = reader.i32()?;
+                let code = reader.i16()?;
+                reader.i64()?;
+                reader.i64()?;
+                if version >= 5 {
+                    reader.i64()?;
+                }
+                errors.insert((topic.clone(), partition), code);
+            }
+        }
+        let throttle_ms = reader.i32()?;
+        if throttle_ms > 0 {
+            thread::sleep(Duration::from_millis(throttle_ms as u64).min(Duration::from_millis(self.config.retry.max_backoff_ms)));
+        }
+        Ok(errors)
+    }
+
+    /// Runs `request` against the known brokers, then the bootstrap ones, until one answers.
+    fn any_broker<T>(&self, mut request: impl FnMut(&mut Connection) -> Result<T>) -> Result<T> {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        let mut last_error = anyhow!("No brokers to ask");
+        let known: BTreeSet<&String> = self.brokers.values().collect();
+        for address in known.into_iter().chain(&self.config.brokers) {
+            match Connection::open(address, timeout).and_then(|mut c| request(&mut c)) {
+                Ok(value) => return Ok(value),
+                Err(e) => last_error = e.context(format!("Broker {}", address)),
+            }
+        }
+        Err(last_error)
+    }
+
+    /// Looks up the brokers and the partition leaders of `topics`.
+    fn refresh_metadata(&mut self, topics: &BTreeSet<String>) -> Result<()> {
+        let mut body = Writer::default();
+        body.i32(topics.len() as i32);
+        for topic in topics {
+            body.string(topic);
+        }
+        let client_id = self.config.client_id.clone();
+        let response = self.any_broker(|c| c.request(METADATA, 1, &client_id, &body.0, true))?.unwrap_or_default();
+
+        let mut reader = Reader(&response);
+        let mut brokers = HashMap::new();
+        for _ in 0..reader.i32()? {
+            let node_id = reader.i32()?;
+            let host = reader.string()?;
+            let port = reader.i32()?;
+            reader.nullable_string()?;
+            brokers.insert(node_id, format!("{}:{}", host, port));
+        }
+        reader.i32()?;
+        let mut leaders = HashMap::new();
+        let mut errors = Vec::new();
+        for _ in 0..reader.i32()? {
+            let code = reader.i16()?;
+            let topic = reader.string()?;
+            reader.i8()?;
+            let mut partitions = Vec::new();
+            for _ in 0..reader.i32()? {
+                reader.i16()?;
+                let partition = reader.i32()?.max(0) as usize;
+                let leader = reader.i32()?;
+                for _ in 0..2 {
+                    for _ in 0..reader.i32()? {
+                        reader.i32()?;
+                    }
+                }
+                if partitions.len() <= partition {
+                    partitions.resize(partition + 1, -1);
+                }
+                partitions[partition] = leader;
+            }
+            if code != 0 || partitions.is_empty() {
+                errors.push(format!("{}: {}", topic, error_name(code)));
+            } else {
+                leaders.insert(topic, partitions);
+            }
+        }
+
+        // Connections to brokers that moved or left are reopened on next use.
+        self.connections.retain(|id, _| brokers.get(id) == self.brokers.get(id));
+        self.brokers = brokers;
+        self.leaders.extend(leaders);
+        if !errors.is_empty() {
+            bail!("No partition leaders for {}", errors.join(", "));
+        }
+        Ok(())
+    }
+
+    /// Gets a producer ID and epoch for idempotent produce requests.
+    fn init_producer_id(&mut self) -> Result<()> {
+        let mut body = Writer::default();
+        body.nullable_string(None);
+        body.i32(60_000);
+        let client_id = self.config.client_id.clone();
+        let response = self.any_broker(|c| c.request(INIT_PRODUCER_ID, 0, &client_id, &body.0, true))?.unwrap_or_default();
+        let mut reader = Reader(&response);
+        reader.i32()?;
+        let code = reader.i16()?;
+        if code != 0 {
+            bail!("InitProducerId failed: {}", error_name(code));
+        }
+        self.producer = Some((reader.i64()?, reader.i16()?));
+        self.sequences.clear();
+        Ok(())
+    }
+
+    /// Abandons the producer ID after giving up on a batch, since the brokers
+    /// would otherwise wait forever for the sequence numbers it skipped.
+    fn reset_producer(&mut self) {
+        self.producer = None;
+        self.sequences.clear();
+    }
+}
+
+impl Sink for KafkaSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        batch::spawn_batches(self.config.batch.clone(), self.spool.take(), self.stats.clone(), move |events| {
+            let dead = self.deliver(events);
+            if dead.is_empty() {
+                return 0;
+            }
+            eprintln!("Kafka output {}: giving up on {} event(s)", self.name, dead.len());
+            if let Some(dir) = &self.config.dead_letter_dir {
+                if let Err(e) = batch::dead_letter(dir, "kafka", &dead) {
+                    eprintln!("Kafka output {}: {:#}", self.name, e);
+                }
+            }
+            dead.len()
+        })
+    }
+}
@@ -0,0 +1,409 @@
+use super::batch::{self, BatchConfig, RetryConfig, SendError};
+use super::fanout::{Sink, SinkStats};
+use super::spool::{Spool, SpoolConfig};
+use super::{origin_host, OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::normalize::{self, schema_type, SchemaType};
+use anyhow::{bail, Context, Result};
+use crossbeam_channel::Sender;
+use prost::Message;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::CONTENT_TYPE;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// A Grafana Loki instance (`[[output.loki]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LokiSinkConfig {
+    /// Base URL of Loki, e.g. `http://loki:3100`; pushes go to `/loki/api/v1/push`.
+    pub url: String,
+    /// Stream labels as templates, e.g. `{ source = "{source}", user = "{user.name}" }`.
+    /// Besides event fields, `{source}`, `{host}`, `{record_type}` and
+    /// `{record_type_name}` are available. See `LabelTemplate`.
+    #[serde(default = "default_labels")]
+    pub labels: BTreeMap<String, String>,
+    /// Distinct values a label may take; later values become `overflow`.
+    #[serde(default = "default_max_label_values")]
+    pub max_label_values: usize,
+    #[serde(default)]
+    pub encoding: LokiEncoding,
+    /// Sent as `X-Scope-OrgID` for multi-tenant Loki.
+    pub tenant_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Batches that exhaust their retries or are rejected are written here.
+    pub dead_letter_dir: Option<PathBuf>,
+    /// Events wait on disk while the destination is unreachable, see `spool::Spool`.
+    pub spool: Option<SpoolConfig>,
+    /// Shape of each log line.
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub host_metadata: bool,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_labels() -> BTreeMap<String, String> {
+    [("source", "{source}"), ("host", "{host}"), ("record_type", "{record_type_name}")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+fn default_max_label_values() -> usize {
+    100
+}
+
+fn default_timeout() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LokiEncoding {
+    /// Snappy-compressed `logproto.PushRequest`, Loki's native format.
+    #[default]
+    Protobuf,
+    Json,
+}
+
+/// Fields that are unique, or nearly so, per event. As labels each value would
+/// create a new stream, which Loki handles badly, so templates may not use them.
+/// Numeric ids and ports (`SchemaType::UInt`) and free text are refused too.
+const HIGH_CARDINALITY: &[&str] = &[
+    "id", "sequence", "timestamp", "native_id", "raw", "pid", "ppid", "tid", "ses",
+    "process.command_line", "process.title",
+];
+
+/// Placeholders that are not event fields.
+const BUILTINS: &[&str] = &["source", "host", "record_type", "record_type_name"];
+
+/// Text with `{field}` placeholders, rendered per event to a label value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelTemplate {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Field(String),
+}
+
+impl LabelTemplate {
+    /// Parses `template`, refusing placeholders for high-cardinality fields.
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_string()));
+            }
+            let Some(end) = rest[start..].find('}') else {
+                bail!("Unclosed '{{' in label template '{}'", template);
+            };
+            let field = rest[start + 1..start + end].trim();
+            if field.is_empty() {
+                bail!("Empty placeholder in label template '{}'", template);
+            }
+            if is_high_cardinality(field) {
+                bail!("'{}' has too many distinct values to be a Loki label", field);
+            }
+            parts.push(Part::Field(field.to_string()));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_string()));
+        }
+        Ok(Self { parts })
+    }
+
+    /// The label value for `event`; empty if any placeholder has no value.
+    pub fn render(&self, event: &AuditEvent, host: &HostMetadata) -> String {
+        let mut value = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(text) => value.push_str(text),
+                Part::Field(field) => {
+                    let resolved = match field.as_str() {
+                        "source" => normalize::source_type(event),
+                        "host" => origin_host(event, host),
+                        "record_type" => event.record_type.to_string(),
+                        "record_type_name" => normalize::record_type_name(event),
+                        _ => match event.fields.get(field) {
+                            Some(v) => v.to_string(),
+                            None => return String::new(),
+                        },
+                    };
+                    value.push_str(&resolved);
+                }
+            }
+        }
+        value
+    }
+}
+
+fn is_high_cardinality(field: &str) -> bool {
+    if BUILTINS.contains(&field) {
+        return false;
+    }
+    let last = field.rsplit('.').next().unwrap_or(field);
+    HIGH_CARDINALITY.contains(&field)
+        || (HIGH_CARDINALITY.contains(&last) && last != "id")
+        || field.starts_with("raw.")
+        || matches!(schema_type(field), SchemaType::UInt | SchemaType::Text)
+}
+
+/// `logproto.PushRequest`, the body of a protobuf push.
+#[derive(Clone, PartialEq, Message)]
+pub struct PushRequest {
+    #[prost(message, repeated, tag = "1")]
+    pub streams: Vec<StreamAdapter>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StreamAdapter {
+    /// Labels in Prometheus syntax, e.g. `{host="web-1", source="auditd"}`.
+    #[prost(string, tag = "1")]
+    pub labels: String,
+    #[prost(message, repeated, tag = "2")]
+    pub entries: Vec<EntryAdapter>,
+    #[prost(uint64, tag = "3")]
+    pub hash: u64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct EntryAdapter {
+    #[prost(message, optional, tag = "1")]
+    pub timestamp: Option<Timestamp>,
+    #[prost(string, tag = "2")]
+    pub line: String,
+}
+
+/// `google.protobuf.Timestamp`.
+#[derive(Clone, PartialEq, Message)]
+pub struct Timestamp {
+    #[prost(int64, tag = "1")]
+    pub seconds: i64,
+    #[prost(int32, tag = "2")]
+    pub nanos: i32,
+}
+
+/// One stream of a push: its labels and its entries, oldest first.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream {
+    pub labels: BTreeMap<String, String>,
+    /// Nanoseconds since the epoch and the log line.
+    pub entries: Vec<(i64, String)>,
+}
+
+/// Pushes events to Loki, grouped into streams by their rendered labels.
+///
+/// Loki rejects entries older than the newest one already in a stream, so the
+/// entries of each stream are sorted by timestamp before a batch is pushed.
+pub struct LokiSink {
+    config: LokiSinkConfig,
+    host: Arc<HostMetadata>,
+    url: String,
+    templates: Vec<(String, LabelTemplate)>,
+    /// Values seen per label, for `max_label_values`.
+    seen: HashMap<String, HashSet<String>>,
+    stats: Arc<SinkStats>,
+    spool: Option<Spool>,
+}
+
+impl LokiSink {
+    pub fn new(mut config: LokiSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        reqwest::Url::parse(&config.url).with_context(|| format!("Invalid Loki URL {}", config.url))?;
+        if config.password.is_some() && config.username.is_none() {
+            bail!("Loki output {} has a password but no username", config.url);
+        }
+        let mut templates = Vec::new();
+        for (name, template) in &config.labels {
+            if !is_label_name(name) {
+                bail!("Invalid Loki label name '{}'", name);
+            }
+            let template = LabelTemplate::parse(template).with_context(|| format!("Invalid template for label {}", name))?;
+            templates.push((name.clone(), template));
+        }
+        let url = format!("{}/loki/api/v1/push", config.url.trim_end_matches('/'));
+        let spool = config.spool.clone().map(Spool::open).transpose()?;
+        if spool.is_some() {
+            config.retry.until_delivered();
+        }
+        Ok(Self { config, host, url, templates, seen: HashMap::new(), stats: Arc::new(SinkStats::default()), spool })
+    }
+
+    /// The labels of `event`'s stream, capping the distinct values of each label.
+    pub fn labels(&mut self, event: &AuditEvent) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::new();
+        for (name, template) in &self.templates {
+            let value = template.render(event, &self.host);
+            if value.is_empty() {
+                continue;
+            }
+            let seen = self.seen.entry(name.clone()).or_default();
+            if seen.contains(&value) || seen.len() < self.config.max_label_values {
+                seen.insert(value.clone());
+                labels.insert(name.clone(), value);
+            } else {
+                if seen.insert("overflow".to_string()) {
+                    eprintln!(
+                        "Loki output {}: label {} exceeded {} values; further values are reported as 'overflow'",
+                        self.config.url, name, self.config.max_label_values
+                    );
+                }
+                labels.insert(name.clone(), "overflow".to_string());
+            }
+        }
+        labels
+    }
+
+    /// Groups `events` into streams with their entries sorted by time.
+    pub fn streams(&mut self, events: &[AuditEvent]) -> Vec<Stream> {
+        let options = OutputOptions { format: self.config.format, host_metadata: self.config.host_metadata };
+        let mut streams: BTreeMap<BTreeMap<String, String>, Vec<(i64, String)>> = BTreeMap::new();
+        for event in events {
+            let labels = self.labels(event);
+            let nanos = event.timestamp.timestamp_nanos_opt().unwrap_or_default();
+            let line = options.render(event, &self.host).to_string();
+            streams.entry(labels).or_default().push((nanos, line));
+        }
+        streams
+            .into_iter()
+            .map(|(labels, mut entries)| {
+                entries.sort_by_key(|(nanos, _)| *nanos);
+                Stream { labels, entries }
+            })
+            .collect()
+    }
+
+    /// The push request body and its content type.
+    pub fn encode(&self, streams: &[Stream]) -> Result<(Vec<u8>, &'static str)> {
+        match self.config.encoding {
+            LokiEncoding::Json => {
+                let streams: Vec<_> = streams
+                    .iter()
+                    .map(|s| {
+                        let values: Vec<_> = s.entries.iter().map(|(nanos, line)| json!([nanos.to_string(), line])).collect();
+                        json!({ "stream": s.labels, "values": values })
+                    })
+                    .collect();
+                Ok((serde_json::to_vec(&json!({ "streams": streams }))?, "application/json"))
+            }
+            LokiEncoding::Protobuf => {
+                let request = PushRequest {
+                    streams: streams
+                        .iter()
+                        .map(|s| StreamAdapter {
+                            labels: label_string(&s.labels),
+                            entries: s
+                                .entries
+                                .iter()
+                                .map(|(nanos, line)| EntryAdapter {
+                                    timestamp: Some(Timestamp {
+                                        seconds: nanos.div_euclid(1_000_000_000),
+                                        nanos: nanos.rem_euclid(1_000_000_000) as i32,
+                                    }),
+                                    line: line.clone(),
+                                })
+                                .collect(),
+                            hash: 0,
+                        })
+                        .collect(),
+                };
+                let body = snap::raw::Encoder::new().compress_vec(&request.encode_to_vec())?;
+                Ok((body, "application/x-protobuf"))
+            }
+        }
+    }
+
+    fn push(&self, client: &Client, streams: &[Stream]) -> Result<()> {
+        let (body, content_type) = self.encode(streams)?;
+        self.config.retry.run(|| {
+            let response = self
+                .request(client.post(&self.url))
+                .header(CONTENT_TYPE, content_type)
+                .body(body.clone())
+                .send()?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            Err(SendError::from_status(status.as_u16(), &response.text().unwrap_or_default()))
+        })
+    }
+
+    fn request(&self, mut builder: RequestBuilder) -> RequestBuilder {
+        if let Some(tenant) = &self.config.tenant_id {
+            builder = builder.header("X-Scope-OrgID", tenant);
+        }
+        if let Some(user) = &self.config.username {
+            builder = builder.basic_auth(user, self.config.password.as_ref());
+        }
+        builder
+    }
+}
+
+impl Sink for LokiSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        let mut client = None;
+        batch::spawn_batches(self.config.batch.clone(), self.spool.take(), self.stats.clone(), move |events| {
+            let client = client.get_or_insert_with(|| {
+                Client::builder()
+                    .timeout(Duration::from_millis(self.config.timeout_ms))
+                    .build()
+                    .expect("HTTP client with static settings")
+            });
+            let streams = self.streams(&events);
+            let Err(e) = self.push(client, &streams) else {
+                return 0;
+            };
+            eprintln!("Loki output {}: {:#}", self.config.url, e);
+            if let Some(dir) = &self.config.dead_letter_dir {
+                if let Err(e) = batch::dead_letter(dir, "loki", &events) {
+                    eprintln!("Loki output {}: {:#}", self.config.url, e);
+                }
+            }
+            events.len()
+        })
+    }
+}
+
+/// Labels in Prometheus syntax, e.g. `{host="web-1", source="auditd"}`.
+pub fn label_string(labels: &BTreeMap<String, String>) -> String {
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(name, value)| {
+            let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+            format!("{}=\"{}\"", name, escaped)
+        })
+        .collect();
+    format!("{{{}}}", pairs.join(", "))
+}
+
+/// Whether `name` is a valid Prometheus/Loki label name.
+fn is_label_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with("__")
+}
@@ -0,0 +1,138 @@
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::normalize;
+use crate::syslog::severity_code;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+
+pub mod batch;
+pub mod cef;
+pub mod elasticsearch;
+pub mod fanout;
+pub mod file;
+pub mod kafka;
+pub mod loki;
+pub mod ocsf;
+pub mod otlp;
+pub mod splunk;
+pub mod spool;
+pub mod sse;
+pub mod syslog;
+pub mod webhook;
+
+pub use elasticsearch::{ElasticsearchSink, ElasticsearchSinkConfig};
+pub use fanout::{Fanout, Sink, SinkStats, SinkStatsSnapshot};
+pub use file::{FileSink, FileSinkConfig};
+pub use kafka::{KafkaSink, KafkaSinkConfig};
+pub use loki::{LokiSink, LokiSinkConfig};
+pub use otlp::{OtlpSink, OtlpSinkConfig};
+pub use splunk::{SplunkSink, SplunkSinkConfig};
+pub use sse::{EventStream, SseClients, SseSink, SseSinkConfig};
+pub use syslog::{SyslogSink, SyslogSinkConfig};
+pub use webhook::{WebhookSink, WebhookSinkConfig};
+
+/// The shape an output sink writes events in. Each sink picks its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// `AuditEvent` as-is, with every field value a JSON string.
+    #[default]
+    Native,
+    /// `AuditEvent` with numeric and boolean field values as JSON numbers and
+    /// booleans, see `AuditEvent::to_typed_json`.
+    Typed,
+    /// Open Cybersecurity Schema Framework classes, see `ocsf::to_ocsf`.
+    Ocsf,
+}
+
+impl OutputFormat {
+    /// Converts an event into the JSON document this format describes.
+    pub fn render(&self, event: &AuditEvent) -> serde_json::Value {
+        match self {
+            OutputFormat::Native => serde_json::to_value(event).unwrap_or_default(),
+            OutputFormat::Typed => event.to_typed_json(),
+            OutputFormat::Ocsf => ocsf::to_ocsf(event),
+        }
+    }
+}
+
+/// Sinks that collected events are routed to (`[output]`). Each one can have a
+/// `filter` expression and only receives the events matching it.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputsConfig {
+    /// The live stream served by GET /api/events (`[output.sse]`).
+    pub sse: SseSinkConfig,
+    /// JSON Lines files (`[[output.file]]`).
+    pub file: Vec<FileSinkConfig>,
+    /// RFC 5424 syslog receivers (`[[output.syslog]]`).
+    pub syslog: Vec<SyslogSinkConfig>,
+    /// HTTP endpoints receiving batches (`[[output.webhook]]`).
+    pub webhook: Vec<WebhookSinkConfig>,
+    /// Elasticsearch/OpenSearch clusters (`[[output.elasticsearch]]`).
+    pub elasticsearch: Vec<ElasticsearchSinkConfig>,
+    /// Splunk HTTP Event Collectors (`[[output.splunk]]`).
+    pub splunk: Vec<SplunkSinkConfig>,
+    /// OpenTelemetry logs receivers (`[[output.otlp]]`).
+    pub otlp: Vec<OtlpSinkConfig>,
+    /// Grafana Loki instances (`[[output.loki]]`).
+    pub loki: Vec<LokiSinkConfig>,
+    /// Kafka clusters (`[[output.kafka]]`).
+    pub kafka: Vec<KafkaSinkConfig>,
+}
+
+/// The machine an event is about: its normalized host name, the reporting host
+/// of a syslog or journald record, or this host.
+///
+/// Other sources use `hostname` for something else, e.g. the remote peer of an
+/// auditd login, so it is only trusted where it names the sender.
+pub fn origin_host(event: &AuditEvent, host: &HostMetadata) -> String {
+    let reported = match normalize::source_type(event).as_str() {
+        "syslog" | "journald" => event.fields.get("hostname"),
+        _ => None,
+    };
+    event
+        .fields
+        .get("host.name")
+        .or(reported)
+        .map(|v| v.to_string())
+        .or_else(|| host.fqdn.clone())
+        .unwrap_or_else(|| host.hostname.clone())
+}
+
+/// The syslog severity of an event: its `severity` field, else warning for
+/// failed outcomes and informational otherwise.
+pub fn severity(event: &AuditEvent) -> u8 {
+    match event.fields.get("severity").and_then(|s| severity_code(&s.to_string())) {
+        Some(code) => code,
+        None if event.fields.get("event.outcome").is_some_and(|o| *o == "failure") => 4,
+        None => 6,
+    }
+}
+
+/// Settings every output sink shares.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutputOptions {
+    pub format: OutputFormat,
+    /// Stamps host and agent metadata onto events before formatting.
+    pub host_metadata: bool,
+}
+
+impl OutputOptions {
+    /// `event` with `host` stamped on it if enabled.
+    pub fn stamp<'a>(&self, event: &'a AuditEvent, host: &HostMetadata) -> Cow<'a, AuditEvent> {
+        if self.host_metadata {
+            let mut event = event.clone();
+            host.stamp(&mut event);
+            Cow::Owned(event)
+        } else {
+            Cow::Borrowed(event)
+        }
+    }
+
+    /// Stamps `host` if enabled, then renders with `format`.
+    pub fn render(&self, event: &AuditEvent, host: &HostMetadata) -> serde_json::Value {
+        self.format.render(&self.stamp(event, host))
+    }
+}
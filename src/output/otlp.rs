@@ -0,0 +1,368 @@
+use super::batch::{self, BatchConfig, RetryConfig, SendError};
+use super::fanout::{Sink, SinkStats};
+use super::spool::{Spool, SpoolConfig};
+use super::severity;
+use crate::host::HostMetadata;
+use crate::model::{AuditEvent, FieldValue};
+use crate::syslog::severity_name;
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use crossbeam_channel::Sender;
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_client::LogsServiceClient;
+use opentelemetry_proto::tonic::collector::logs::v1::{ExportLogsServiceRequest, ExportLogsServiceResponse};
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use opentelemetry_proto::tonic::common::v1::{AnyValue, ArrayValue, InstrumentationScope, KeyValue, KeyValueList};
+use opentelemetry_proto::tonic::logs::v1::{LogRecord, ResourceLogs, ScopeLogs};
+use opentelemetry_proto::tonic::resource::v1::Resource;
+use prost::Message;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tonic::metadata::{MetadataKey, MetadataMap, MetadataValue};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::Code;
+
+/// An OpenTelemetry collector or other OTLP logs receiver (`[[output.otlp]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtlpSinkConfig {
+    /// Base URL of the receiver, e.g. `http://otel-collector:4318`; HTTP requests go to `/v1/logs`.
+    pub endpoint: String,
+    #[serde(default)]
+    pub protocol: OtlpProtocol,
+    /// Sent with every request (as gRPC metadata for `grpc`), e.g. for authentication.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Batches that exhaust their retries or are rejected are written here.
+    pub dead_letter_dir: Option<PathBuf>,
+    /// Events wait on disk while the destination is unreachable, see `spool::Spool`.
+    pub spool: Option<SpoolConfig>,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_timeout() -> u64 {
+    10_000
+}
+
+/// OTLP transports, named as in `OTEL_EXPORTER_OTLP_PROTOCOL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+pub enum OtlpProtocol {
+    #[default]
+    #[serde(rename = "http/protobuf")]
+    HttpProtobuf,
+    #[serde(rename = "http/json")]
+    HttpJson,
+    #[serde(rename = "grpc")]
+    Grpc,
+}
+
+enum Transport {
+    Http(Client),
+    /// The sink thread is synchronous, so it drives the gRPC client on its own runtime.
+    Grpc(Box<tokio::runtime::Runtime>, LogsServiceClient<Channel>),
+}
+
+/// Exports events as OTLP log records.
+///
+/// Each event becomes a `LogRecord` with its time, severity, `message` as the body
+/// and the remaining fields as attributes; host metadata describes the resource.
+/// Failed exports are retried as the OTLP specification allows: HTTP 429, 502,
+/// 503 and 504, and the transient gRPC status codes, honoring any delay the
+/// receiver asks for.
+pub struct OtlpSink {
+    config: OtlpSinkConfig,
+    resource: Resource,
+    url: String,
+    headers: HeaderMap,
+    metadata: MetadataMap,
+    stats: Arc<SinkStats>,
+    spool: Option<Spool>,
+}
+
+impl OtlpSink {
+    pub fn new(mut config: OtlpSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        reqwest::Url::parse(&config.endpoint).with_context(|| format!("Invalid OTLP endpoint {}", config.endpoint))?;
+        let url = format!("{}/v1/logs", config.endpoint.trim_end_matches('/'));
+        let mut headers = HeaderMap::new();
+        let mut metadata = MetadataMap::new();
+        for (name, value) in &config.headers {
+            let header = HeaderName::try_from(name.as_str()).with_context(|| format!("Invalid header name '{}'", name))?;
+            headers.insert(
+                header,
+                HeaderValue::try_from(value.as_str()).with_context(|| format!("Invalid value for header {}", name))?,
+            );
+            let key = MetadataKey::from_bytes(name.to_ascii_lowercase().as_bytes())
+                .with_context(|| format!("Invalid metadata key '{}'", name))?;
+            metadata.insert(
+                key,
+                MetadataValue::try_from(value.as_str()).with_context(|| format!("Invalid value for header {}", name))?,
+            );
+        }
+        let content_type = match config.protocol {
+            OtlpProtocol::HttpJson => "application/json",
+            _ => "application/x-protobuf",
+        };
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        let spool = config.spool.clone().map(Spool::open).transpose()?;
+        if spool.is_some() {
+            config.retry.until_delivered();
+        }
+        Ok(Self { resource: resource(&host), config, url, headers, metadata, stats: Arc::new(SinkStats::default()), spool })
+    }
+
+    /// The export request for a batch of events.
+    pub fn request(&self, events: &[AuditEvent]) -> ExportLogsServiceRequest {
+        ExportLogsServiceRequest {
+            resource_logs: vec![ResourceLogs {
+                resource: Some(self.resource.clone()),
+                scope_logs: vec![ScopeLogs {
+                    scope: Some(InstrumentationScope {
+                        name: env!("CARGO_PKG_NAME").to_string(),
+                        version: env!("CARGO_PKG_VERSION").to_string(),
+                        ..Default::default()
+                    }),
+                    log_records: events.iter().map(log_record).collect(),
+                    schema_url: String::new(),
+                }],
+                schema_url: String::new(),
+            }],
+        }
+    }
+
+    fn connect(&self) -> Result<Transport> {
+        let timeout = Duration::from_millis(self.config.timeout_ms);
+        if self.config.protocol != OtlpProtocol::Grpc {
+            return Ok(Transport::Http(Client::builder().timeout(timeout).build()?));
+        }
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        let mut endpoint = Endpoint::from_shared(self.config.endpoint.clone())?.timeout(timeout);
+        if self.config.endpoint.starts_with("https:") {
+            endpoint = endpoint.tls_config(ClientTlsConfig::new().with_webpki_roots())?;
+        }
+        let channel = {
+            let _guard = runtime.enter();
+            endpoint.connect_lazy()
+        };
+        Ok(Transport::Grpc(Box::new(runtime), LogsServiceClient::new(channel)))
+    }
+
+    fn export(&self, transport: &mut Transport, events: &[AuditEvent]) -> Result<()> {
+        let request = self.request(events);
+        let response = match transport {
+            Transport::Http(client) => {
+                let body = match self.config.protocol {
+                    OtlpProtocol::HttpJson => serde_json::to_vec(&request)?,
+                    _ => request.encode_to_vec(),
+                };
+                self.config.retry.run(|| self.post(client, &body))?
+            }
+            Transport::Grpc(runtime, client) => self.config.retry.run(|| {
+                let mut call = tonic::Request::new(request.clone());
+                *call.metadata_mut() = self.metadata.clone();
+                match runtime.block_on(client.export(call)) {
+                    Ok(response) => Ok(response.into_inner()),
+                    Err(status) => Err(grpc_error(&status)),
+                }
+            })?,
+        };
+        if let Some(partial) = response.partial_success.filter(|p| p.rejected_log_records > 0) {
+            // Rejected records would be rejected again, so they are not retried.
+            eprintln!(
+                "OTLP output {}: receiver rejected {} log record(s): {}",
+                self.config.endpoint, partial.rejected_log_records, partial.error_message
+            );
+        }
+        Ok(())
+    }
+
+    fn post(&self, client: &Client, body: &[u8]) -> Result<ExportLogsServiceResponse, SendError> {
+        let response = client.post(&self.url).headers(self.headers.clone()).body(body.to_vec()).send()?;
+        let status = response.status();
+        if status.is_success() {
+            let bytes = response.bytes()?;
+            return Ok(match self.config.protocol {
+                OtlpProtocol::HttpJson => serde_json::from_slice(&bytes).unwrap_or_default(),
+                _ => ExportLogsServiceResponse::decode(bytes).unwrap_or_default(),
+            });
+        }
+        let retry_after = response
+            .headers()
+            .get(RETRY_AFTER)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().parse().ok())
+            .map(Duration::from_secs);
+        let error = anyhow!("HTTP {}: {}", status.as_u16(), response.text().unwrap_or_default().chars().take(200).collect::<String>());
+        Err(match (status.as_u16(), retry_after) {
+            (429 | 502 | 503 | 504, Some(after)) => SendError::Throttled(after, error),
+            (429 | 502 | 503 | 504, None) => SendError::Retryable(error),
+            _ => SendError::Permanent(error),
+        })
+    }
+}
+
+impl Sink for OtlpSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        let mut transport = None;
+        batch::spawn_batches(self.config.batch.clone(), self.spool.take(), self.stats.clone(), move |events| {
+            let result = match &mut transport {
+                Some(transport) => self.export(transport, &events),
+                None => self.connect().and_then(|t| self.export(transport.insert(t), &events)),
+            };
+            let Err(e) = result else {
+                return 0;
+            };
+            eprintln!("OTLP output {}: {:#}", self.config.endpoint, e);
+            if let Some(dir) = &self.config.dead_letter_dir {
+                if let Err(e) = batch::dead_letter(dir, "otlp", &events) {
+                    eprintln!("OTLP output {}: {:#}", self.config.endpoint, e);
+                }
+            }
+            events.len()
+        })
+    }
+}
+
+/// Maps an event to a log record.
+pub fn log_record(event: &AuditEvent) -> LogRecord {
+    let severity = severity(event);
+    let mut attributes = vec![
+        key_value("log.record.uid", Value::StringValue(event.id.clone())),
+        key_value("audit.record_type", Value::IntValue(event.record_type.into())),
+        key_value("audit.sequence", uint_value(event.sequence)),
+    ];
+    let mut keys: Vec<&String> = event.fields.keys().filter(|k| k.as_str() != "message").collect();
+    keys.sort();
+    attributes.extend(keys.into_iter().map(|k| KeyValue { key: k.clone(), value: Some(any_value(&event.fields[k])) }));
+
+    LogRecord {
+        time_unix_nano: event.timestamp.timestamp_nanos_opt().unwrap_or_default() as u64,
+        observed_time_unix_nano: Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64,
+        severity_number: SEVERITY_NUMBERS[severity as usize],
+        severity_text: severity_name(severity).to_string(),
+        body: event.fields.get("message").map(|m| AnyValue { value: Some(Value::StringValue(m.to_string())) }),
+        attributes,
+        event_name: event.fields.get("event.action").map(|a| a.to_string()).unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+/// OTLP `SeverityNumber` for each syslog severity, emerg to debug.
+const SEVERITY_NUMBERS: [i32; 8] = [21, 20, 19, 17, 13, 10, 9, 5];
+
+/// Resource attributes for this host, following the OpenTelemetry semantic conventions.
+fn resource(host: &HostMetadata) -> Resource {
+    let mut attributes = vec![
+        key_value("service.name", Value::StringValue(env!("CARGO_PKG_NAME").to_string())),
+        key_value("service.version", Value::StringValue(host.agent_version.clone())),
+        key_value("host.name", Value::StringValue(host.fqdn.clone().unwrap_or_else(|| host.hostname.clone()))),
+        key_value("os.name", Value::StringValue(host.os_name.clone())),
+    ];
+    let mut optional = |key: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            attributes.push(key_value(key, Value::StringValue(value.clone())));
+        }
+    };
+    optional("host.id", &host.machine_id);
+    optional("os.version", &host.os_version);
+    optional("os.kernel", &host.kernel);
+    if !host.ips.is_empty() {
+        let ips = host.ips.iter().map(|ip| AnyValue { value: Some(Value::StringValue(ip.to_string())) }).collect();
+        attributes.push(key_value("host.ip", Value::ArrayValue(ArrayValue { values: ips })));
+    }
+    if !host.tags.is_empty() {
+        let tags = host.tags.iter().map(|t| AnyValue { value: Some(Value::StringValue(t.clone())) }).collect();
+        attributes.push(key_value("tags", Value::ArrayValue(ArrayValue { values: tags })));
+    }
+    Resource { attributes, ..Default::default() }
+}
+
+fn key_value(key: &str, value: Value) -> KeyValue {
+    KeyValue { key: key.to_string(), value: Some(AnyValue { value: Some(value) }) }
+}
+
+/// OTLP integers are signed, so values beyond `i64::MAX` are sent as strings.
+fn uint_value(value: u64) -> Value {
+    i64::try_from(value).map(Value::IntValue).unwrap_or_else(|_| Value::StringValue(value.to_string()))
+}
+
+fn any_value(value: &FieldValue) -> AnyValue {
+    let value = match value {
+        FieldValue::Str(s) => Value::StringValue(s.clone()),
+        FieldValue::Int(i) => Value::IntValue(*i),
+        FieldValue::UInt(u) => uint_value(*u),
+        FieldValue::Float(f) => Value::DoubleValue(*f),
+        FieldValue::Bool(b) => Value::BoolValue(*b),
+        FieldValue::Timestamp(_) | FieldValue::Ip(_) => Value::StringValue(value.to_string()),
+        FieldValue::List(items) => Value::ArrayValue(ArrayValue { values: items.iter().map(any_value).collect() }),
+        FieldValue::Map(map) => Value::KvlistValue(KeyValueList {
+            values: map.iter().map(|(k, v)| KeyValue { key: k.clone(), value: Some(any_value(v)) }).collect(),
+        }),
+    };
+    AnyValue { value: Some(value) }
+}
+
+/// `google.rpc.Status`, as carried in the `grpc-status-details-bin` trailer.
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<ProtoAny>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoAny {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+/// `google.rpc.RetryInfo`, the server's requested delay before a retry.
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<ProtoDuration>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+struct ProtoDuration {
+    #[prost(int64, tag = "1")]
+    seconds: i64,
+    #[prost(int32, tag = "2")]
+    nanos: i32,
+}
+
+/// Classifies a failed gRPC export as the OTLP specification describes.
+/// `RESOURCE_EXHAUSTED` is only retried when the server says when to retry.
+fn grpc_error(status: &tonic::Status) -> SendError {
+    let error = anyhow!("gRPC {:?}: {}", status.code(), status.message());
+    let retry_delay = RpcStatus::decode(status.details())
+        .ok()
+        .into_iter()
+        .flat_map(|s| s.details)
+        .filter(|d| d.type_url.ends_with("/google.rpc.RetryInfo"))
+        .find_map(|d| RetryInfo::decode(d.value.as_slice()).ok()?.retry_delay)
+        .map(|d| Duration::new(d.seconds.max(0) as u64, d.nanos.max(0) as u32));
+    match (status.code(), retry_delay) {
+        (_, Some(after)) => SendError::Throttled(after, error),
+        (Code::Cancelled | Code::DeadlineExceeded | Code::Aborted | Code::OutOfRange | Code::Unavailable | Code::DataLoss, None) => {
+            SendError::Retryable(error)
+        }
+        _ => SendError::Permanent(error),
+    }
+}
@@ -0,0 +1,249 @@
+use super::batch::{self, BatchConfig, RetryConfig, SendError};
+use super::fanout::{Sink, SinkStats};
+use super::spool::{Spool, SpoolConfig};
+use super::{origin_host, OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::normalize;
+use anyhow::{bail, Context, Result};
+use crossbeam_channel::Sender;
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A Splunk HTTP Event Collector (`[[output.splunk]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SplunkSinkConfig {
+    /// Base URL of the collector, e.g. `https://splunk.example.com:8088`.
+    pub url: String,
+    /// HEC token, sent as `Authorization: Splunk <token>`.
+    pub token: String,
+    pub index: Option<String>,
+    /// Overrides the per-source-type default, see `sourcetype_for`.
+    pub sourcetype: Option<String>,
+    /// Overrides the default `audit_collector:<source type>`.
+    pub source: Option<String>,
+    /// Wait for indexer acknowledgement of every batch and resend unacknowledged ones.
+    #[serde(default)]
+    pub ack: bool,
+    /// Request channel for acknowledgements; a random one is generated if unset.
+    pub channel: Option<String>,
+    #[serde(default = "default_ack_poll_interval")]
+    pub ack_poll_interval_ms: u64,
+    /// Resend a batch that has not been acknowledged within this time.
+    #[serde(default = "default_ack_timeout")]
+    pub ack_timeout_ms: u64,
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Batches that cannot be delivered are written here.
+    pub dead_letter_dir: Option<PathBuf>,
+    /// Events wait on disk while the destination is unreachable, see `spool::Spool`.
+    pub spool: Option<SpoolConfig>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub host_metadata: bool,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_ack_poll_interval() -> u64 {
+    1000
+}
+
+fn default_ack_timeout() -> u64 {
+    60_000
+}
+
+fn default_timeout() -> u64 {
+    30_000
+}
+
+/// Splunk's conventional sourcetype for each of our source types.
+pub fn sourcetype_for(source_type: &str) -> String {
+    match source_type {
+        "auditd" => "linux:audit".to_string(),
+        "windows_xml" => "XmlWinEventLog".to_string(),
+        "mac_json" => "macos:unifiedlog".to_string(),
+        "syslog" => "syslog".to_string(),
+        "journald" => "journald".to_string(),
+        other => format!("{}:{}", env!("CARGO_PKG_NAME"), other),
+    }
+}
+
+#[derive(Deserialize)]
+struct EventResponse {
+    #[serde(rename = "ackId")]
+    ack_id: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct AckResponse {
+    acks: HashMap<String, bool>,
+}
+
+/// Sends batches of events to a Splunk HTTP Event Collector.
+///
+/// With `ack` enabled, a batch only counts as delivered once the indexers have
+/// acknowledged it on the request channel; batches that are not acknowledged in
+/// time are sent again, so delivery is at-least-once.
+pub struct SplunkSink {
+    config: SplunkSinkConfig,
+    host: Arc<HostMetadata>,
+    base: String,
+    channel: String,
+    stats: Arc<SinkStats>,
+    spool: Option<Spool>,
+}
+
+impl SplunkSink {
+    pub fn new(mut config: SplunkSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        reqwest::Url::parse(&config.url).with_context(|| format!("Invalid HEC URL {}", config.url))?;
+        let base = config.url.trim_end_matches('/').to_string();
+        let channel = config.channel.clone().unwrap_or_else(random_uuid);
+        let spool = config.spool.clone().map(Spool::open).transpose()?;
+        if spool.is_some() {
+            config.retry.until_delivered();
+        }
+        Ok(Self { config, host, base, channel, stats: Arc::new(SinkStats::default()), spool })
+    }
+
+    /// The HEC envelope for one event.
+    pub fn envelope(&self, event: &AuditEvent) -> Value {
+        let options = OutputOptions { format: self.config.format, host_metadata: self.config.host_metadata };
+        let source_type = normalize::source_type(event);
+        let mut envelope = json!({
+            "time": event.timestamp.timestamp_millis() as f64 / 1000.0,
+            "host": origin_host(event, &self.host),
+            "source": self
+                .config
+                .source
+                .clone()
+                .unwrap_or_else(|| format!("{}:{}", env!("CARGO_PKG_NAME"), source_type)),
+            "sourcetype": self.config.sourcetype.clone().unwrap_or_else(|| sourcetype_for(&source_type)),
+            "event": options.render(event, &self.host),
+        });
+        if let Some(index) = &self.config.index {
+            envelope["index"] = index.as_str().into();
+        }
+        envelope
+    }
+
+    /// Sends a batch, waiting for its acknowledgement if enabled.
+    pub fn deliver(&self, client: &Client, events: &[AuditEvent]) -> Result<()> {
+        let mut body = Vec::new();
+        for event in events {
+            serde_json::to_writer(&mut body, &self.envelope(event))?;
+            body.push(b'\n');
+        }
+
+        let attempts = self.config.retry.max_attempts.max(1);
+        for attempt in 1..=attempts {
+            let ack_id = self.config.retry.run(|| self.post(client, &body))?;
+            if !self.config.ack {
+                return Ok(());
+            }
+            let Some(ack_id) = ack_id else {
+                bail!("HEC returned no ackId; is indexer acknowledgement enabled for the token?");
+            };
+            if self.wait_for_ack(client, ack_id) {
+                return Ok(());
+            }
+            eprintln!("Splunk output {}: batch {} not acknowledged (attempt {})", self.base, ack_id, attempt);
+        }
+        bail!("Batch not acknowledged after {} attempt(s)", attempts)
+    }
+
+    fn post(&self, client: &Client, body: &[u8]) -> Result<Option<u64>, SendError> {
+        let mut request = client
+            .post(format!("{}/services/collector/event", self.base))
+            .header("Authorization", format!("Splunk {}", self.config.token))
+            .body(body.to_vec());
+        if self.config.ack {
+            request = request.header("X-Splunk-Request-Channel", &self.channel);
+        }
+        let response = request.send()?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(SendError::from_status(status.as_u16(), &response.text().unwrap_or_default()));
+        }
+        let response: EventResponse = response.json().unwrap_or(EventResponse { ack_id: None });
+        Ok(response.ack_id)
+    }
+
+    /// Polls until `ack_id` is acknowledged or `ack_timeout_ms` passes.
+    fn wait_for_ack(&self, client: &Client, ack_id: u64) -> bool {
+        let deadline = Instant::now() + Duration::from_millis(self.config.ack_timeout_ms);
+        let interval = Duration::from_millis(self.config.ack_poll_interval_ms);
+        while Instant::now() < deadline {
+            thread::sleep(interval.min(deadline.saturating_duration_since(Instant::now())));
+            match self.poll_ack(client, ack_id) {
+                Ok(true) => return true,
+                Ok(false) => {}
+                Err(e) => eprintln!("Splunk output {}: ack poll failed: {:#}", self.base, e),
+            }
+        }
+        false
+    }
+
+    fn poll_ack(&self, client: &Client, ack_id: u64) -> Result<bool> {
+        let response = client
+            .post(format!("{}/services/collector/ack", self.base))
+            .query(&[("channel", &self.channel)])
+            .header("Authorization", format!("Splunk {}", self.config.token))
+            .header("X-Splunk-Request-Channel", &self.channel)
+            .json(&json!({ "acks": [ack_id] }))
+            .send()?
+            .error_for_status()?;
+        let response: AckResponse = response.json()?;
+        Ok(response.acks.get(&ack_id.to_string()).copied().unwrap_or(false))
+    }
+}
+
+impl Sink for SplunkSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        let mut client = None;
+        batch::spawn_batches(self.config.batch.clone(), self.spool.take(), self.stats.clone(), move |events| {
+            let client = client.get_or_insert_with(|| {
+                Client::builder()
+                    .timeout(Duration::from_millis(self.config.timeout_ms))
+                    .build()
+                    .expect("HTTP client with static settings")
+            });
+            let Err(e) = self.deliver(client, &events) else {
+                return 0;
+            };
+            eprintln!("Splunk output {}: {:#}", self.base, e);
+            if let Some(dir) = &self.config.dead_letter_dir {
+                if let Err(e) = batch::dead_letter(dir, "splunk", &events) {
+                    eprintln!("Splunk output {}: {:#}", self.base, e);
+                }
+            }
+            events.len()
+        })
+    }
+}
+
+/// A random (version 4) UUID, the form HEC expects for request channels.
+fn random_uuid() -> String {
+    let mut bytes = fastrand::u128(..).to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
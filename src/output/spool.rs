@@ -0,0 +1,392 @@
+use crate::model::AuditEvent;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the file holding the acknowledged position.
+const ACK_FILE: &str = "ack";
+
+/// Segment file extension.
+const SEGMENT: &str = "wal";
+
+/// Record header: payload length and CRC-32, both little-endian.
+const HEADER: u64 = 8;
+
+/// A disk queue in front of a batching sink (`[output.<sink>.spool]`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SpoolConfig {
+    /// Directory of the segment files; each sink needs its own.
+    pub dir: PathBuf,
+    /// Events that would take the spool past this many bytes are dropped.
+    #[serde(default = "default_max_bytes")]
+    pub max_bytes: u64,
+    /// Start a new segment file once the current one would exceed this size.
+    #[serde(default = "default_segment_bytes")]
+    pub segment_bytes: u64,
+}
+
+fn default_max_bytes() -> u64 {
+    1 << 30
+}
+
+fn default_segment_bytes() -> u64 {
+    16 << 20
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Position {
+    segment: u64,
+    offset: u64,
+}
+
+/// A write-ahead queue of events in segment files.
+///
+/// Events are appended as length-prefixed, checksummed JSON records and read
+/// back in order. Reading does not remove anything: only `ack` moves the
+/// acknowledged position (kept in the `ack` file) past what has been read, and
+/// segments wholly behind it are deleted. After a crash, everything after the
+/// acknowledged position is read again, and a record torn by the crash is cut off
+/// the last segment. Other damage is left in place for reading to skip with
+/// `skip_damaged`.
+pub struct Spool {
+    config: SpoolConfig,
+    /// Size of each segment by sequence number.
+    segments: BTreeMap<u64, u64>,
+    writer: Option<File>,
+    reader: Option<(u64, BufReader<File>)>,
+    read: Position,
+    acked: Position,
+    unread: u64,
+    in_flight: u64,
+}
+
+impl Spool {
+    /// Opens the spool in `config.dir`, recovering whatever a previous run left.
+    pub fn open(config: SpoolConfig) -> Result<Self> {
+        let dir = &config.dir;
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create spool directory {}", dir.display()))?;
+        let mut acked = read_ack(dir)?;
+
+        let mut found = BTreeMap::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            match segment_seq(&path) {
+                Some(seq) if seq < acked.segment => fs::remove_file(&path)?,
+                Some(seq) => {
+                    found.insert(seq, path);
+                }
+                None => {}
+            }
+        }
+        let last = found.keys().next_back().copied();
+
+        let mut segments = BTreeMap::new();
+        let mut unread = 0;
+        for (seq, path) in found {
+            let start = if seq == acked.segment { acked.offset } else { 0 };
+            let (count, end) = scan(&path, start)?;
+            let mut len = fs::metadata(&path)?.len();
+            if end < len && Some(seq) == last {
+                eprintln!("Spool {}: cutting off a torn record of {} byte(s) from {}", dir.display(), len - end, path.display());
+                OpenOptions::new().write(true).open(&path)?.set_len(end)?;
+                len = end;
+            }
+            unread += count;
+            if end < len {
+                // Only the last segment is written to, so this is damage, not a
+                // torn write. Counted as one event, for reading to skip.
+                eprintln!("Spool {}: {} damaged byte(s) at {} in {}", dir.display(), len - end, end, path.display());
+                unread += 1;
+            }
+            segments.insert(seq, len);
+        }
+        // The acknowledged segment may have been deleted once it was used up.
+        if !segments.contains_key(&acked.segment) {
+            let next = segments.keys().next().copied().unwrap_or(acked.segment).max(acked.segment);
+            acked = Position { segment: next, offset: 0 };
+        }
+
+        Ok(Self { config, segments, writer: None, reader: None, read: acked, acked, unread, in_flight: 0 })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.config.dir
+    }
+
+    /// Events not yet acknowledged, read or not.
+    pub fn len(&self) -> u64 {
+        self.unread + self.in_flight
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Events appended but not yet read.
+    pub fn unread(&self) -> u64 {
+        self.unread
+    }
+
+    /// Bytes of all segment files.
+    pub fn bytes(&self) -> u64 {
+        self.segments.values().sum()
+    }
+
+    /// Appends `event`, or returns false if the spool is full.
+    pub fn append(&mut self, event: &AuditEvent) -> Result<bool> {
+        // Typed, so numbers are still numbers when the event is read back.
+        let payload = serde_json::to_vec(&event.to_typed_json())?;
+        let size = HEADER + payload.len() as u64;
+        if self.bytes() + size > self.config.max_bytes {
+            return Ok(false);
+        }
+
+        let last = self.segments.last_key_value().map(|(seq, len)| (*seq, *len));
+        let seq = match last {
+            Some((seq, len)) if seq >= self.read.segment && (len == 0 || len + size <= self.config.segment_bytes) => seq,
+            _ => {
+                if let Some(writer) = self.writer.take() {
+                    writer.sync_data()?;
+                }
+                let seq = last.map(|(seq, _)| seq + 1).unwrap_or(0).max(self.acked.segment);
+                File::create(segment_path(&self.config.dir, seq))?;
+                self.segments.insert(seq, 0);
+                seq
+            }
+        };
+        if self.writer.is_none() {
+            let file = OpenOptions::new().append(true).open(segment_path(&self.config.dir, seq))?;
+            self.writer = Some(file);
+        }
+
+        let mut record = Vec::with_capacity(size as usize);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+        record.extend_from_slice(&payload);
+        self.writer.as_mut().expect("writer was just opened").write_all(&record)?;
+        *self.segments.get_mut(&seq).expect("segment was just added") += size;
+        self.unread += 1;
+        Ok(true)
+    }
+
+    /// Reads up to `max` events after those already read, oldest first. A
+    /// damaged record ends the read, and is an error once it comes first, until
+    /// `skip_damaged` moves past it.
+    pub fn read(&mut self, max: usize) -> Result<Vec<AuditEvent>> {
+        let mut events = Vec::new();
+        while events.len() < max && self.unread > 0 {
+            match self.next_event() {
+                Ok(event) => events.push(event),
+                Err(e) if events.is_empty() => return Err(e),
+                Err(_) => break,
+            }
+        }
+        Ok(events)
+    }
+
+    /// The event at the read position, moving past it only if it is intact.
+    fn next_event(&mut self) -> Result<AuditEvent> {
+        loop {
+            let len = self.segments.get(&self.read.segment).copied().unwrap_or(0);
+            if self.read.offset < len {
+                break;
+            }
+            let Some(next) = self.segments.range(self.read.segment + 1..).next().map(|(seq, _)| *seq) else {
+                bail!("Spool {} lost track of {} event(s)", self.config.dir.display(), self.unread);
+            };
+            self.read = Position { segment: next, offset: 0 };
+            self.reader = None;
+        }
+
+        if self.reader.as_ref().is_none_or(|(seq, _)| *seq != self.read.segment) {
+            let mut file = File::open(segment_path(&self.config.dir, self.read.segment))?;
+            file.seek(SeekFrom::Start(self.read.offset))?;
+            self.reader = Some((self.read.segment, BufReader::new(file)));
+        }
+        let remaining = self.segments[&self.read.segment] - self.read.offset;
+        let (_, reader) = self.reader.as_mut().expect("reader was just opened");
+        let record = next_record(reader, remaining).map_err(anyhow::Error::from).and_then(|record| match record {
+            Record::Intact(payload) => {
+                let event: AuditEvent = serde_json::from_slice(&payload)?;
+                Ok((event, HEADER + payload.len() as u64))
+            }
+            Record::Damaged(_) => bail!("Record fails its checksum"),
+            Record::Torn | Record::End => bail!("Record is incomplete"),
+        });
+        match record {
+            Ok((event, size)) => {
+                self.read.offset += size;
+                self.unread -= 1;
+                self.in_flight += 1;
+                Ok(event)
+            }
+            Err(e) => {
+                // Reopened at the read position on the next attempt.
+                self.reader = None;
+                Err(e.context(format!("Damaged record in spool segment {} at {}", self.read.segment, self.read.offset)))
+            }
+        }
+    }
+
+    /// Moves the read position past a damaged record and returns how many events
+    /// were lost: the record alone if its length is still plausible, otherwise
+    /// the rest of its segment, as the damage hides where the next record starts.
+    /// Nothing is skipped if the record reads fine after all.
+    pub fn skip_damaged(&mut self) -> Result<u64> {
+        let Position { segment, offset } = self.read;
+        self.reader = None;
+        let mut file = File::open(segment_path(&self.config.dir, segment))?;
+        file.seek(SeekFrom::Start(offset))?;
+        let remaining = self.segments.get(&segment).copied().unwrap_or(0).saturating_sub(offset);
+        let size = match next_record(&mut BufReader::new(file), remaining)? {
+            Record::Intact(payload) if serde_json::from_slice::<AuditEvent>(&payload).is_ok() => return Ok(0),
+            Record::Intact(payload) => Some(HEADER + payload.len() as u64),
+            Record::Damaged(size) => Some(size),
+            Record::Torn | Record::End => None,
+        };
+        if let Some(size) = size {
+            self.read.offset += size;
+            self.unread = self.unread.saturating_sub(1);
+            return Ok(1);
+        }
+
+        let mut later = 0;
+        for seq in self.segments.range(segment + 1..).map(|(seq, _)| *seq).collect::<Vec<_>>() {
+            later += scan(&segment_path(&self.config.dir, seq), 0)?.0;
+        }
+        let lost = self.unread.saturating_sub(later);
+        // Reading resumes in the next segment, which `append` starts if this one
+        // is still being written.
+        self.read = Position { segment: segment + 1, offset: 0 };
+        self.unread -= lost;
+        Ok(lost)
+    }
+
+    /// Acknowledges everything read so far, deleting the segments it used up.
+    pub fn ack(&mut self) -> Result<()> {
+        if self.acked == self.read {
+            return Ok(());
+        }
+        self.acked = self.read;
+        self.in_flight = 0;
+
+        // A segment read to the end is done with, even the one being written once
+        // the reader has caught up.
+        if self.segments.get(&self.acked.segment) == Some(&self.acked.offset) {
+            let next = self.segments.range(self.acked.segment + 1..).next().map(|(seq, _)| *seq);
+            if next.is_none() {
+                self.writer = None;
+            }
+            self.reader = None;
+            self.acked = Position { segment: next.unwrap_or(self.acked.segment + 1), offset: 0 };
+            self.read = self.acked;
+        }
+        write_ack(&self.config.dir, self.acked)?;
+
+        let done: Vec<u64> = self.segments.range(..self.acked.segment).map(|(seq, _)| *seq).collect();
+        for seq in done {
+            self.segments.remove(&seq);
+            fs::remove_file(segment_path(&self.config.dir, seq))?;
+        }
+        Ok(())
+    }
+}
+
+fn segment_path(dir: &Path, seq: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", seq, SEGMENT))
+}
+
+fn segment_seq(path: &Path) -> Option<u64> {
+    if path.extension()? != SEGMENT {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse().ok()
+}
+
+fn read_ack(dir: &Path) -> Result<Position> {
+    let text = match fs::read_to_string(dir.join(ACK_FILE)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Position::default()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut parts = text.split_whitespace().map(str::parse::<u64>);
+    match (parts.next(), parts.next()) {
+        (Some(Ok(segment)), Some(Ok(offset))) => Ok(Position { segment, offset }),
+        _ => bail!("Invalid spool ack file in {}", dir.display()),
+    }
+}
+
+/// Replaces the ack file atomically, so a crash leaves the old or the new position.
+fn write_ack(dir: &Path, position: Position) -> Result<()> {
+    let partial = dir.join(format!("{}.partial", ACK_FILE));
+    let mut file = File::create(&partial)?;
+    writeln!(file, "{} {}", position.segment, position.offset)?;
+    file.sync_all()?;
+    fs::rename(&partial, dir.join(ACK_FILE))?;
+    Ok(())
+}
+
+enum Record {
+    Intact(Vec<u8>),
+    /// Fails its checksum; the size is that of the whole record, header included.
+    Damaged(u64),
+    /// Runs past the end of the segment, as the last write before a crash may.
+    Torn,
+    End,
+}
+
+/// Reads the next record. `remaining` is what is left of the segment; a header
+/// claiming more is taken for a torn record, and is not allocated for.
+fn next_record(reader: &mut impl Read, remaining: u64) -> io::Result<Record> {
+    if remaining == 0 {
+        return Ok(Record::End);
+    }
+    let mut header = [0; HEADER as usize];
+    match reader.read_exact(&mut header) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Record::Torn),
+        Err(e) => return Err(e),
+    }
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as u64;
+    let crc = u32::from_le_bytes(header[4..].try_into().unwrap());
+    if len > remaining.saturating_sub(HEADER) {
+        return Ok(Record::Torn);
+    }
+    let mut payload = vec![0; len as usize];
+    match reader.read_exact(&mut payload) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(Record::Torn),
+        Err(e) => return Err(e),
+    }
+    if crc32fast::hash(&payload) != crc {
+        return Ok(Record::Damaged(HEADER + len));
+    }
+    Ok(Record::Intact(payload))
+}
+
+/// Counts the records of a segment from `offset`, damaged ones included,
+/// returning the count and where the last one ends, before any torn record.
+fn scan(path: &Path, offset: u64) -> Result<(u64, u64)> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    if offset > len {
+        bail!("Spool ack position is past the end of {}", path.display());
+    }
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(file);
+    let (mut count, mut end) = (0, offset);
+    loop {
+        let size = match next_record(&mut reader, len - end)? {
+            Record::Intact(payload) => HEADER + payload.len() as u64,
+            Record::Damaged(size) => size,
+            Record::Torn | Record::End => break,
+        };
+        count += 1;
+        end += size;
+    }
+    Ok((count, end))
+}
@@ -0,0 +1,270 @@
+use super::fanout::{Sink, SinkStats};
+use crate::model::AuditEvent;
+use chrono::{DateTime, Utc};
+use crossbeam_channel::{bounded, Sender};
+use futures::stream::Stream;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// The live stream served by GET /api/events (`[output.sse]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SseSinkConfig {
+    /// Only events matching this filter expression are streamed, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Events waiting to be broadcast; newer ones are dropped beyond this.
+    pub queue_size: usize,
+    /// Events each client can fall behind by before it skips ahead and is sent
+    /// a `lagged` event with the number it missed.
+    pub capacity: usize,
+    /// Recent events kept for clients that reconnect with `Last-Event-ID`.
+    pub replay: usize,
+}
+
+impl Default for SseSinkConfig {
+    fn default() -> Self {
+        Self { filter: None, queue_size: 1000, capacity: 100, replay: 1000 }
+    }
+}
+
+/// An event with its id on the stream, sent as the SSE `id:` field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StreamEvent {
+    pub id: u64,
+    pub event: AuditEvent,
+}
+
+/// What a new client is sent: what it missed, then everything live.
+pub struct Subscription {
+    /// Events the client asked for that are no longer kept.
+    pub missed: u64,
+    pub replay: Vec<StreamEvent>,
+    pub live: broadcast::Receiver<StreamEvent>,
+}
+
+struct Recent {
+    /// Id of the first event published since startup.
+    first_id: u64,
+    next_id: u64,
+    events: VecDeque<StreamEvent>,
+}
+
+/// The broadcast SSE clients subscribe to, keeping the latest events so that a
+/// client reconnecting with `Last-Event-ID` can be sent what it missed.
+///
+/// Ids count up from the time the process started in microseconds, so ids from
+/// before a restart are always lower than those after it.
+pub struct EventStream {
+    sender: broadcast::Sender<StreamEvent>,
+    recent: Mutex<Recent>,
+    replay: usize,
+}
+
+impl EventStream {
+    /// A stream clients can fall `capacity` events behind on, keeping `replay` events.
+    pub fn new(capacity: usize, replay: usize) -> Self {
+        let first_id = Utc::now().timestamp_micros().max(0) as u64;
+        Self {
+            sender: broadcast::channel(capacity.max(1)).0,
+            recent: Mutex::new(Recent { first_id, next_id: first_id, events: VecDeque::with_capacity(replay) }),
+            replay,
+        }
+    }
+
+    /// Numbers and broadcasts `event`; returns whether any client was there to receive it.
+    pub fn publish(&self, event: AuditEvent) -> bool {
+        // Held while sending, so that `subscribe` sees each event either kept or live.
+        let mut recent = self.recent.lock().unwrap();
+        let event = StreamEvent { id: recent.next_id, event };
+        recent.next_id += 1;
+        if self.replay > 0 {
+            if recent.events.len() == self.replay {
+                recent.events.pop_front();
+            }
+            recent.events.push_back(event.clone());
+        }
+        self.sender.send(event).is_ok()
+    }
+
+    /// The kept events, oldest first.
+    pub fn recent(&self) -> Vec<StreamEvent> {
+        self.recent.lock().unwrap().events.iter().cloned().collect()
+    }
+
+    /// Subscribes to live events, after those following `last_event_id` when given.
+    ///
+    /// An id from before this process started, or one not yet issued, is taken to
+    /// be from an earlier run and gets every kept event replayed.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription {
+        let recent = self.recent.lock().unwrap();
+        let live = self.sender.subscribe();
+        let Some(last) = last_event_id else {
+            return Subscription { missed: 0, replay: Vec::new(), live };
+        };
+        let oldest = recent.events.front().map(|e| e.id).unwrap_or(recent.next_id);
+        let known = last >= recent.first_id && last < recent.next_id;
+        let missed = if known { oldest.saturating_sub(last + 1) } else { 0 };
+        let replay = recent.events.iter().filter(|e| !known || e.id > last).cloned().collect();
+        Subscription { missed, replay, live }
+    }
+}
+
+/// Moves events from its queue onto the `EventStream` SSE clients subscribe to.
+///
+/// An event counts as delivered when at least one client was connected to receive it.
+pub struct SseSink {
+    config: SseSinkConfig,
+    stream: Arc<EventStream>,
+    stats: Arc<SinkStats>,
+}
+
+impl SseSink {
+    pub fn new(config: SseSinkConfig, stream: Arc<EventStream>) -> Self {
+        Self { config, stream, stats: Arc::new(SinkStats::default()) }
+    }
+}
+
+impl Sink for SseSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(self) -> Sender<AuditEvent> {
+        let (tx, rx) = bounded::<AuditEvent>(self.config.queue_size);
+        thread::spawn(move || {
+            for event in rx {
+                if self.stream.publish(event) {
+                    self.stats.delivered(1);
+                }
+            }
+        });
+        tx
+    }
+}
+
+/// What an SSE client is sent next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Delivery {
+    Event(StreamEvent),
+    /// The client fell behind and this many events were skipped.
+    Lagged(u64),
+}
+
+#[derive(Debug)]
+struct ClientStats {
+    connected_at: DateTime<Utc>,
+    sent: AtomicU64,
+    pending: AtomicU64,
+    lagged: AtomicU64,
+    skipped: AtomicU64,
+}
+
+/// A point-in-time view of one connected SSE client.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct SseClientSnapshot {
+    pub id: u64,
+    pub connected_at: DateTime<Utc>,
+    /// Events sent to the client.
+    pub sent: u64,
+    /// Events broadcast but not yet sent to the client.
+    pub pending: u64,
+    /// Times the client fell more than `capacity` events behind.
+    pub lagged: u64,
+    /// Events the client missed because of that.
+    pub skipped: u64,
+}
+
+/// The SSE clients currently connected, for GET /api/events/clients.
+#[derive(Debug, Default)]
+pub struct SseClients {
+    next_id: AtomicU64,
+    clients: Mutex<BTreeMap<u64, Arc<ClientStats>>>,
+}
+
+impl SseClients {
+    /// Starts tracking a new client until the returned handle is dropped.
+    pub fn connect(self: &Arc<Self>) -> SseClient {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let stats = Arc::new(ClientStats {
+            connected_at: Utc::now(),
+            sent: AtomicU64::new(0),
+            pending: AtomicU64::new(0),
+            lagged: AtomicU64::new(0),
+            skipped: AtomicU64::new(0),
+        });
+        self.clients.lock().unwrap().insert(id, stats.clone());
+        SseClient { id, stats, clients: self.clone() }
+    }
+
+    pub fn snapshot(&self) -> Vec<SseClientSnapshot> {
+        self.clients
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, stats)| SseClientSnapshot {
+                id: *id,
+                connected_at: stats.connected_at,
+                sent: stats.sent.load(Ordering::Relaxed),
+                pending: stats.pending.load(Ordering::Relaxed),
+                lagged: stats.lagged.load(Ordering::Relaxed),
+                skipped: stats.skipped.load(Ordering::Relaxed),
+            })
+            .collect()
+    }
+}
+
+/// One connected client, see `SseClients::connect`.
+pub struct SseClient {
+    id: u64,
+    stats: Arc<ClientStats>,
+    clients: Arc<SseClients>,
+}
+
+impl SseClient {
+    /// What to send the client from `subscription`: a `Lagged` for any events it
+    /// missed that were no longer kept, the kept ones, then live events until the
+    /// broadcast closes.
+    ///
+    /// A client that falls more than the channel capacity behind skips to the
+    /// oldest event still held, and is told how many it missed.
+    pub fn deliveries(self, subscription: Subscription) -> impl Stream<Item = Delivery> {
+        let Subscription { missed, replay, live: mut rx } = subscription;
+        async_stream::stream! {
+            if missed > 0 {
+                self.stats.lagged.fetch_add(1, Ordering::Relaxed);
+                self.stats.skipped.fetch_add(missed, Ordering::Relaxed);
+                yield Delivery::Lagged(missed);
+            }
+            for event in replay {
+                self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                yield Delivery::Event(event);
+            }
+            loop {
+                match rx.recv().await {
+                    Ok(event) => {
+                        self.stats.sent.fetch_add(1, Ordering::Relaxed);
+                        self.stats.pending.store(rx.len() as u64, Ordering::Relaxed);
+                        yield Delivery::Event(event);
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        self.stats.lagged.fetch_add(1, Ordering::Relaxed);
+                        self.stats.skipped.fetch_add(skipped, Ordering::Relaxed);
+                        yield Delivery::Lagged(skipped);
+                    }
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SseClient {
+    fn drop(&mut self) {
+        self.clients.clients.lock().unwrap().remove(&self.id);
+    }
+}
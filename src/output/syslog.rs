@@ -0,0 +1,380 @@
+use super::cef::{self, CefConfig};
+use super::fanout::{Sink, SinkStats};
+use super::{origin_host, severity, OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use crate::syslog::{self, SdElement, SyslogMessage};
+use anyhow::{anyhow, bail, Context, Result};
+use crossbeam_channel::{bounded, Sender};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName};
+use rustls::{ClientConfig, ClientConnection, RootCertStore, StreamOwned};
+use serde::Deserialize;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Largest UDP payload over IPv4; longer messages cannot be sent as one datagram.
+const MAX_DATAGRAM: usize = 65_507;
+
+/// A remote syslog receiver (`[[output.syslog]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SyslogSinkConfig {
+    /// `host:port` of the receiver.
+    pub address: String,
+    #[serde(default)]
+    pub transport: SyslogTransport,
+    /// PEM file with the CA certificates trusted for `tls`.
+    pub ca: Option<PathBuf>,
+    /// Name checked against the server certificate; defaults to the host in `address`.
+    pub server_name: Option<String>,
+    #[serde(default)]
+    pub body: SyslogBody,
+    #[serde(default = "default_facility")]
+    pub facility: String,
+    #[serde(default = "default_app_name")]
+    pub app_name: String,
+    /// SD-ID of the element carrying the event fields.
+    #[serde(default = "default_sd_id")]
+    pub sd_id: String,
+    /// Shape of the JSON body; ignored for structured data.
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Header and event classes for the CEF and LEEF bodies.
+    #[serde(default)]
+    pub cef: CefConfig,
+    #[serde(default)]
+    pub host_metadata: bool,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Events held while the receiver is unreachable; newer ones are dropped beyond this.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    #[serde(default = "default_reconnect_min")]
+    pub reconnect_min_ms: u64,
+    #[serde(default = "default_reconnect_max")]
+    pub reconnect_max_ms: u64,
+}
+
+fn default_facility() -> String {
+    "authpriv".to_string()
+}
+
+fn default_app_name() -> String {
+    env!("CARGO_PKG_NAME").to_string()
+}
+
+fn default_sd_id() -> String {
+    "audit@32473".to_string()
+}
+
+fn default_queue_size() -> usize {
+    10_000
+}
+
+fn default_reconnect_min() -> u64 {
+    500
+}
+
+fn default_reconnect_max() -> u64 {
+    30_000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogTransport {
+    Udp,
+    /// Octet-counting framing (RFC 6587).
+    #[default]
+    Tcp,
+    /// Octet-counting framing over TLS (RFC 5425).
+    Tls,
+}
+
+/// What goes into the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogBody {
+    /// Event fields as one SD-ELEMENT, the `message` field as MSG.
+    #[default]
+    StructuredData,
+    /// The whole event rendered as JSON in MSG.
+    Json,
+    /// A Common Event Format record in MSG.
+    Cef,
+    /// A LEEF 2.0 record in MSG.
+    Leef,
+}
+
+enum Connection {
+    Udp(UdpSocket),
+    Tcp(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Connection {
+    fn send(&mut self, message: &[u8]) -> io::Result<()> {
+        let mut frame = format!("{} ", message.len()).into_bytes();
+        frame.extend_from_slice(message);
+        match self {
+            Connection::Udp(socket) => socket.send(message).map(|_| ()),
+            Connection::Tcp(stream) => stream.write_all(&frame).and_then(|_| stream.flush()),
+            Connection::Tls(stream) => stream.write_all(&frame).and_then(|_| stream.flush()),
+        }
+    }
+
+    /// Whether a stream peer has gone away. Receivers never send anything, so
+    /// readable data on a plain TCP stream (EOF included) means it is done. Over
+    /// TLS the server does send records, such as TLS 1.3 session tickets, so those
+    /// go through rustls and only EOF, close_notify or an error count.
+    fn is_closed(&mut self) -> bool {
+        match self {
+            Connection::Udp(_) => false,
+            Connection::Tcp(stream) => {
+                if stream.set_nonblocking(true).is_err() {
+                    return true;
+                }
+                let peeked = stream.peek(&mut [0u8; 1]);
+                let _ = stream.set_nonblocking(false);
+                !matches!(peeked, Err(e) if e.kind() == ErrorKind::WouldBlock)
+            }
+            Connection::Tls(stream) => {
+                if stream.sock.set_nonblocking(true).is_err() {
+                    return true;
+                }
+                let closed = tls_closed(stream);
+                let _ = stream.sock.set_nonblocking(false);
+                closed
+            }
+        }
+    }
+}
+
+/// Takes in whatever the server sent, without blocking, and tells whether the
+/// session has ended.
+fn tls_closed(stream: &mut StreamOwned<ClientConnection, TcpStream>) -> bool {
+    loop {
+        match stream.conn.read_tls(&mut stream.sock) {
+            Ok(0) => return true,
+            Ok(_) => match stream.conn.process_new_packets() {
+                Ok(state) if state.peer_has_closed() => return true,
+                Ok(state) => {
+                    // Nothing is expected, but unread data would stop further reads.
+                    if state.plaintext_bytes_to_read() > 0 {
+                        let mut discard = vec![0; state.plaintext_bytes_to_read()];
+                        let _ = stream.conn.reader().read(&mut discard);
+                    }
+                }
+                Err(_) => return true,
+            },
+            Err(e) if e.kind() == ErrorKind::WouldBlock => return false,
+            Err(_) => return true,
+        }
+    }
+}
+
+/// Forwards events to a syslog receiver as RFC 5424.
+///
+/// Events are queued in memory while the receiver is unreachable and sent in
+/// order once a connection is re-established; reconnects and resends back off
+/// exponentially. Over UDP, messages that cannot be sent are dropped and
+/// counted as failed instead of retried.
+pub struct SyslogSink {
+    config: SyslogSinkConfig,
+    host: Arc<HostMetadata>,
+    facility: u8,
+    tls: Option<Arc<ClientConfig>>,
+    stats: Arc<SinkStats>,
+}
+
+impl SyslogSink {
+    pub fn new(config: SyslogSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        let facility = syslog::facility_code(&config.facility)
+            .ok_or_else(|| anyhow!("Unknown syslog facility '{}'", config.facility))?;
+        let tls = match (config.transport, &config.ca) {
+            (SyslogTransport::Tls, Some(ca)) => Some(load_client_config(ca)?),
+            (SyslogTransport::Tls, None) => bail!("Syslog output {} uses TLS but has no `ca`", config.address),
+            _ => None,
+        };
+        config.cef.validate()?;
+        Ok(Self { config, host, facility, tls, stats: Arc::new(SinkStats::default()) })
+    }
+
+    /// Builds the syslog message for `event`.
+    pub fn message(&self, event: &AuditEvent) -> SyslogMessage {
+        let options = OutputOptions { format: self.config.format, host_metadata: self.config.host_metadata };
+        let stamped = options.stamp(event, &self.host);
+        let event = stamped.as_ref();
+        let field = |keys: &[&str]| keys.iter().find_map(|k| event.fields.get(*k)).map(|v| v.to_string());
+
+        let (structured_data, message) = match self.config.body {
+            SyslogBody::StructuredData => {
+                let mut params = vec![
+                    ("id".to_string(), event.id.clone()),
+                    ("record_type".to_string(), event.record_type.to_string()),
+                    ("sequence".to_string(), event.sequence.to_string()),
+                ];
+                let mut keys: Vec<&String> = event
+                    .fields
+                    .keys()
+                    .filter(|k| !matches!(k.as_str(), "message" | "raw"))
+                    .collect();
+                keys.sort();
+                params.extend(keys.into_iter().map(|k| (k.clone(), event.fields[k].to_string())));
+                let element = SdElement { id: self.config.sd_id.clone(), params };
+                (vec![element], field(&["message"]).unwrap_or_default())
+            }
+            SyslogBody::Json => (Vec::new(), self.config.format.render(event).to_string()),
+            SyslogBody::Cef => (Vec::new(), cef::to_cef(event, &self.config.cef, &self.host)),
+            SyslogBody::Leef => (Vec::new(), cef::to_leef(event, &self.config.cef, &self.host)),
+        };
+
+        SyslogMessage {
+            facility: self.facility,
+            severity: severity(event),
+            version: Some(1),
+            timestamp: Some(event.timestamp),
+            hostname: Some(origin_host(event, &self.host)),
+            app_name: Some(self.config.app_name.clone()),
+            procid: field(&["process.pid", "pid"]),
+            msgid: field(&["event.action"]).or_else(|| Some(event.record_type.to_string())),
+            structured_data,
+            message,
+        }
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let addr = self
+            .config
+            .address
+            .to_socket_addrs()
+            .with_context(|| format!("Failed to resolve {}", self.config.address))?
+            .next()
+            .ok_or_else(|| anyhow!("No address for {}", self.config.address))?;
+
+        if self.config.transport == SyslogTransport::Udp {
+            let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+            let socket = UdpSocket::bind(bind)?;
+            socket.connect(addr)?;
+            return Ok(Connection::Udp(socket));
+        }
+
+        let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)
+            .with_context(|| format!("Failed to connect to {}", addr))?;
+        stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        let Some(tls) = &self.tls else {
+            return Ok(Connection::Tcp(stream));
+        };
+
+        let name = match &self.config.server_name {
+            Some(name) => name.clone(),
+            None => host_part(&self.config.address).to_string(),
+        };
+        let name = ServerName::try_from(name).context("Invalid TLS server name")?;
+        let conn = ClientConnection::new(tls.clone(), name)?;
+        let mut stream = StreamOwned::new(conn, stream);
+        // Handshake now so certificate problems show up as connect errors.
+        while stream.conn.is_handshaking() {
+            stream.conn.complete_io(&mut stream.sock)?;
+        }
+        Ok(Connection::Tls(Box::new(stream)))
+    }
+}
+
+impl Sink for SyslogSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(self) -> Sender<AuditEvent> {
+        let (tx, rx) = bounded::<AuditEvent>(self.config.queue_size);
+        thread::spawn(move || {
+            let min = Duration::from_millis(self.config.reconnect_min_ms);
+            let max = Duration::from_millis(self.config.reconnect_max_ms);
+            let mut backoff = min;
+            let mut connection: Option<Connection> = None;
+
+            for event in rx {
+                let message = self.message(&event).to_rfc5424();
+                let udp = self.config.transport == SyslogTransport::Udp;
+                if udp && message.len() > MAX_DATAGRAM {
+                    eprintln!(
+                        "Syslog output {}: dropping {} byte message, too long for a datagram",
+                        self.config.address,
+                        message.len()
+                    );
+                    self.stats.failed(1);
+                    continue;
+                }
+                loop {
+                    if connection.as_mut().is_some_and(|c| c.is_closed()) {
+                        connection = None;
+                    }
+                    let conn = match connection.as_mut() {
+                        Some(conn) => conn,
+                        None => match self.connect() {
+                            Ok(conn) => connection.insert(conn),
+                            Err(e) => {
+                                eprintln!("Syslog output {}: {:#}", self.config.address, e);
+                                thread::sleep(backoff);
+                                backoff = (backoff * 2).min(max);
+                                continue;
+                            }
+                        },
+                    };
+                    match conn.send(message.as_bytes()) {
+                        Ok(()) => {
+                            self.stats.delivered(1);
+                            backoff = min;
+                            break;
+                        }
+                        Err(e) => {
+                            eprintln!("Syslog output {}: {}", self.config.address, e);
+                            connection = None;
+                            thread::sleep(backoff);
+                            backoff = (backoff * 2).min(max);
+                            // A datagram that was refused once will be refused
+                            // again; UDP does not promise delivery anyway.
+                            if udp {
+                                self.stats.failed(1);
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        });
+        tx
+    }
+}
+
+/// `host` from `host:port` or `[v6]:port`.
+fn host_part(address: &str) -> &str {
+    let host = address.rsplit_once(':').map_or(address, |(host, _)| host);
+    host.trim_start_matches('[').trim_end_matches(']')
+}
+
+fn load_client_config(ca: &std::path::Path) -> Result<Arc<ClientConfig>> {
+    let mut roots = RootCertStore::empty();
+    for cert in CertificateDer::pem_file_iter(ca)
+        .with_context(|| format!("Failed to read CA file {}", ca.display()))?
+    {
+        roots
+            .add(cert.with_context(|| format!("Invalid certificate in {}", ca.display()))?)
+            .context("Unusable CA certificate")?;
+    }
+
+    let config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
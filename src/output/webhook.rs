@@ -0,0 +1,162 @@
+use super::batch::{self, BatchConfig, RetryConfig, SendError};
+use super::fanout::{Sink, SinkStats};
+use super::spool::{Spool, SpoolConfig};
+use super::{OutputFormat, OutputOptions};
+use crate::host::HostMetadata;
+use crate::model::AuditEvent;
+use anyhow::{Context, Result};
+use crossbeam_channel::Sender;
+use flate2::write::GzEncoder;
+use reqwest::blocking::Client;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_ENCODING, CONTENT_TYPE};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// An HTTP endpoint that receives batches of events (`[[output.webhook]]`).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookSinkConfig {
+    pub url: String,
+    #[serde(default)]
+    pub encoding: BatchEncoding,
+    /// Compress request bodies and send `Content-Encoding: gzip`.
+    #[serde(default)]
+    pub gzip: bool,
+    /// Extra request headers, e.g. `Authorization`.
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "default_timeout")]
+    pub timeout_ms: u64,
+    /// Only events matching this filter expression are sent, see `filter::Filter`.
+    pub filter: Option<String>,
+    /// Batches that exhaust their retries are written here; dropped if unset.
+    pub dead_letter_dir: Option<PathBuf>,
+    /// Events wait on disk while the destination is unreachable, see `spool::Spool`.
+    pub spool: Option<SpoolConfig>,
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub host_metadata: bool,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub retry: RetryConfig,
+}
+
+fn default_timeout() -> u64 {
+    10_000
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchEncoding {
+    /// One JSON array per request.
+    #[default]
+    JsonArray,
+    /// One JSON document per line.
+    Ndjson,
+}
+
+/// POSTs batches of events to an HTTP endpoint.
+pub struct WebhookSink {
+    config: WebhookSinkConfig,
+    host: Arc<HostMetadata>,
+    headers: HeaderMap,
+    stats: Arc<SinkStats>,
+    spool: Option<Spool>,
+}
+
+impl WebhookSink {
+    pub fn new(mut config: WebhookSinkConfig, host: Arc<HostMetadata>) -> Result<Self> {
+        reqwest::Url::parse(&config.url).with_context(|| format!("Invalid webhook URL {}", config.url))?;
+        let mut headers = HeaderMap::new();
+        for (name, value) in &config.headers {
+            let name = HeaderName::try_from(name.as_str()).with_context(|| format!("Invalid header name '{}'", name))?;
+            let value = HeaderValue::try_from(value.as_str()).with_context(|| format!("Invalid value for header {}", name))?;
+            headers.insert(name, value);
+        }
+        let content_type = match config.encoding {
+            BatchEncoding::JsonArray => "application/json",
+            BatchEncoding::Ndjson => "application/x-ndjson",
+        };
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        if config.gzip {
+            headers.insert(CONTENT_ENCODING, HeaderValue::from_static("gzip"));
+        }
+        let spool = config.spool.clone().map(Spool::open).transpose()?;
+        if spool.is_some() {
+            config.retry.until_delivered();
+        }
+        Ok(Self { config, host, headers, stats: Arc::new(SinkStats::default()), spool })
+    }
+
+    /// The request body for a batch, compressed if configured.
+    pub fn encode(&self, events: &[AuditEvent]) -> Result<Vec<u8>> {
+        let options = OutputOptions { format: self.config.format, host_metadata: self.config.host_metadata };
+        let rendered = events.iter().map(|e| options.render(e, &self.host));
+        let body = match self.config.encoding {
+            BatchEncoding::JsonArray => serde_json::to_vec(&rendered.collect::<Vec<_>>())?,
+            BatchEncoding::Ndjson => {
+                let mut body = Vec::new();
+                for value in rendered {
+                    serde_json::to_writer(&mut body, &value)?;
+                    body.push(b'\n');
+                }
+                body
+            }
+        };
+        if !self.config.gzip {
+            return Ok(body);
+        }
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&body)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn deliver(&self, client: &Client, events: &[AuditEvent]) -> Result<()> {
+        let body = self.encode(events)?;
+        self.config.retry.run(|| {
+            let response = client.post(&self.config.url).body(body.clone()).send()?;
+            let status = response.status();
+            if status.is_success() {
+                return Ok(());
+            }
+            Err(SendError::from_status(status.as_u16(), &response.text().unwrap_or_default()))
+        })
+    }
+}
+
+impl Sink for WebhookSink {
+    fn with_stats(mut self, stats: Arc<SinkStats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn spawn(mut self) -> Sender<AuditEvent> {
+        // The blocking client runs its own runtime, so it is built on the sink thread.
+        let mut client = None;
+        batch::spawn_batches(self.config.batch.clone(), self.spool.take(), self.stats.clone(), move |events| {
+            let client = client.get_or_insert_with(|| {
+                Client::builder()
+                    .timeout(Duration::from_millis(self.config.timeout_ms))
+                    .default_headers(self.headers.clone())
+                    .build()
+                    .expect("HTTP client with static settings")
+            });
+            let Err(e) = self.deliver(client, &events) else {
+                return 0;
+            };
+            eprintln!("Webhook output {}: {:#}", self.config.url, e);
+            if let Some(dir) = &self.config.dead_letter_dir {
+                if let Err(e) = batch::dead_letter(dir, "webhook", &events) {
+                    eprintln!("Webhook output {}: {:#}", self.config.url, e);
+                }
+            }
+            events.len()
+        })
+    }
+}
@@ -0,0 +1,105 @@
+use super::EventParser;
+use crate::model::{AuditEvent, FieldValue, NativeId};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Numeric auditd fields. Everything else stays a string; in particular the
+/// syscall arguments `a0`..`a3` are hex and must not be read as decimal.
+const UINT_FIELDS: &[&str] = &[
+    "pid", "ppid", "uid", "auid", "euid", "suid", "fsuid", "gid", "egid", "sgid", "fsgid",
+    "ouid", "ogid", "ses", "syscall", "items", "item", "inode", "argc",
+];
+
+/// Converts a raw auditd value to its typed form.
+fn typed(key: &str, value: &str) -> FieldValue {
+    match key {
+        "exit" => FieldValue::int(value),
+        "success" => match value {
+            "yes" => FieldValue::Bool(true),
+            "no" => FieldValue::Bool(false),
+            _ => value.into(),
+        },
+        k if UINT_FIELDS.contains(&k) => FieldValue::uint(value),
+        _ => value.into(),
+    }
+}
+
+/// Parses `audit(SECONDS.MILLIS:SERIAL):` into the record time and serial.
+fn parse_header(value: &str) -> Result<(DateTime<Utc>, u64)> {
+    let inner = value
+        .strip_prefix("audit(")
+        .and_then(|v| v.split_once(')'))
+        .map(|(inner, _)| inner)
+        .with_context(|| format!("Malformed audit header '{}'", value))?;
+    let (time, serial) = inner
+        .split_once(':')
+        .with_context(|| format!("Missing serial in audit header '{}'", value))?;
+    let serial = serial
+        .parse::<u64>()
+        .with_context(|| format!("Invalid audit serial '{}'", serial))?;
+
+    let (secs, millis) = time.split_once('.').unwrap_or((time, "0"));
+    let timestamp = secs
+        .parse::<i64>()
+        .ok()
+        .zip(millis.parse::<u32>().ok().and_then(|m| m.checked_mul(1_000_000)))
+        .and_then(|(secs, nanos)| DateTime::from_timestamp(secs, nanos))
+        .with_context(|| format!("Invalid audit timestamp '{}'", time))?;
+    Ok((timestamp, serial))
+}
+
+/// Parses Linux audit records: `type=1300 msg=audit(1674390000.123:100): key=value ...`
+pub struct AuditdParser;
+
+impl AuditdParser {
+    /// Returns true if the line looks like an auditd record.
+    pub fn matches(s: &str) -> bool {
+        s.contains("type=") && s.contains("msg=audit")
+    }
+}
+
+impl EventParser for AuditdParser {
+    fn name(&self) -> &str {
+        "auditd"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let s = String::from_utf8_lossy(raw);
+        if !Self::matches(&s) {
+            bail!("Not an auditd record");
+        }
+
+        let mut type_id = 0;
+        let mut header = None;
+        let mut fields = HashMap::new();
+
+        for part in s.split_whitespace() {
+            if let Some((k, v)) = part.split_once('=') {
+                fields.insert(k.to_string(), typed(k, v));
+                if k == "type" {
+                    let digits: String = v.chars().filter(|c| c.is_ascii_digit()).collect();
+                    type_id = digits.parse().unwrap_or(0);
+                }
+                // USER_* records carry a second, quoted `msg='...'` with the payload.
+                if k == "msg" && header.is_none() && v.starts_with("audit(") {
+                    header = Some(parse_header(v)?);
+                }
+            }
+        }
+
+        let Some((timestamp, serial)) = header else {
+            bail!("Missing msg=audit(...) header");
+        };
+
+        Ok(AuditEvent {
+            id: String::new(),
+            timestamp,
+            record_type: type_id,
+            sequence: serial,
+            native_id: Some(NativeId::AuditSerial(serial)),
+            synthetic_timestamp: false,
+            fields,
+        })
+    }
+}
@@ -0,0 +1,34 @@
+use super::{AuditdParser, EventParser, LineParser, MacJsonParser, SyslogParser};
+use crate::model::AuditEvent;
+use anyhow::Result;
+
+/// Guesses the format of each record. Used by sources that do not declare one.
+///
+/// JSON objects are treated as macOS log entries, `<PRI>` and auth.log lines as
+/// syslog, `type=... msg=audit(...)` as auditd, and anything else as a plain line.
+pub struct AutoParser;
+
+impl EventParser for AutoParser {
+    fn name(&self) -> &str {
+        "auto"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let s = String::from_utf8_lossy(raw);
+        let trimmed = s.trim();
+
+        if trimmed.starts_with('{') {
+            return MacJsonParser.parse(raw);
+        }
+        // Syslog wins for <PRI> lines so forwarded auditd records keep their header
+        if trimmed.starts_with('<') || !AuditdParser::matches(&s) {
+            if let Ok(event) = SyslogParser.parse(raw) {
+                return Ok(event);
+            }
+        }
+        if AuditdParser::matches(&s) {
+            return AuditdParser.parse(raw);
+        }
+        LineParser.parse(raw)
+    }
+}
@@ -0,0 +1,92 @@
+use super::{EventParser, GENERIC_RECORD_TYPE};
+use crate::authlog;
+use crate::model::{AuditEvent, FieldValue, NativeId};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Parses one entry from `journalctl -o json`.
+///
+/// Well-known journal fields are renamed to the names the other parsers use
+/// (`message`, `pid`, `comm`, `exe`, `hostname`, `app_name`); everything else is kept
+/// under its journal name.
+pub struct JournaldParser;
+
+const RENAMES: [(&str, &str); 9] = [
+    ("MESSAGE", "message"),
+    ("_PID", "pid"),
+    ("_UID", "uid"),
+    ("_GID", "gid"),
+    ("_COMM", "comm"),
+    ("_EXE", "exe"),
+    ("_CMDLINE", "cmdline"),
+    ("_HOSTNAME", "hostname"),
+    ("SYSLOG_IDENTIFIER", "app_name"),
+];
+
+impl EventParser for JournaldParser {
+    fn name(&self) -> &str {
+        "journald"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let value: Value = serde_json::from_slice(raw).context("Invalid journald JSON")?;
+        let Value::Object(entry) = value else {
+            bail!("Journald entry is not a JSON object");
+        };
+
+        let mut fields = HashMap::new();
+        for (key, value) in entry {
+            let value = match value {
+                Value::String(s) => s,
+                // Binary-safe fields are exported as arrays of bytes.
+                Value::Array(bytes) => {
+                    let bytes: Vec<u8> = bytes.iter().filter_map(|b| b.as_u64()).map(|b| b as u8).collect();
+                    String::from_utf8_lossy(&bytes).into_owned()
+                }
+                Value::Null => continue,
+                other => other.to_string(),
+            };
+            let key = RENAMES
+                .iter()
+                .find(|(from, _)| *from == key)
+                .map(|(_, to)| to.to_string())
+                .unwrap_or(key);
+            // The journal exports everything as text; type the ids we rename.
+            let value = match key.as_str() {
+                "pid" | "uid" | "gid" => FieldValue::uint(&value),
+                _ => value.into(),
+            };
+            fields.insert(key, value);
+        }
+
+        if let (Some(app), Some(message)) = (fields.get("app_name"), fields.get("message")) {
+            let (app, message) = (app.to_string(), message.to_string());
+            authlog::extract(&app, &message, &mut fields);
+        }
+
+        let timestamp = fields
+            .get("__REALTIME_TIMESTAMP")
+            .and_then(|t| t.to_string().parse::<i64>().ok())
+            .and_then(DateTime::<Utc>::from_timestamp_micros);
+        let sequence = match fields.get("__SEQNUM") {
+            Some(seqnum) => {
+                let seqnum = seqnum.to_string();
+                seqnum.parse::<u64>().with_context(|| format!("Invalid __SEQNUM '{}'", seqnum))?
+            }
+            None => 0,
+        };
+        let native_id = fields.get("__CURSOR").map(|c| NativeId::JournalCursor(c.to_string()));
+
+        Ok(AuditEvent {
+            id: String::new(),
+            timestamp: timestamp.unwrap_or_else(Utc::now),
+            record_type: GENERIC_RECORD_TYPE,
+            sequence,
+            native_id,
+            synthetic_timestamp: timestamp.is_none(),
+            fields,
+        })
+    }
+}
@@ -0,0 +1,33 @@
+use super::{EventParser, GENERIC_RECORD_TYPE};
+use crate::model::AuditEvent;
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Fallback for free-text logs: the whole line becomes the `message` field.
+pub struct LineParser;
+
+impl EventParser for LineParser {
+    fn name(&self) -> &str {
+        "line"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let s = String::from_utf8_lossy(raw);
+        if s.trim().is_empty() {
+            bail!("Empty line");
+        }
+
+        let mut fields = HashMap::new();
+        fields.insert("message".to_string(), s.as_ref().into());
+
+        Ok(AuditEvent {
+            id: String::new(),
+            timestamp: chrono::Utc::now(),
+            record_type: GENERIC_RECORD_TYPE,
+            sequence: 0,
+            native_id: None,
+            synthetic_timestamp: true,
+            fields,
+        })
+    }
+}
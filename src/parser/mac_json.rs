@@ -0,0 +1,61 @@
+use super::{EventParser, GENERIC_RECORD_TYPE};
+use crate::model::{AuditEvent, MacLogEntry, NativeId};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+
+/// Parses one object from `log stream --style json`.
+pub struct MacJsonParser;
+
+impl EventParser for MacJsonParser {
+    fn name(&self) -> &str {
+        "mac_json"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let entry: MacLogEntry =
+            serde_json::from_slice(raw).context("Invalid macOS log JSON")?;
+        let mut fields = HashMap::new();
+
+        if let Some(msg) = entry.event_message {
+            fields.insert("message".to_string(), msg.into());
+        }
+        if let Some(proc) = entry.process_image_path.clone() {
+            fields.insert("process".to_string(), proc.into());
+        }
+        if let Some(pid) = entry.process_id {
+            fields.insert("pid".to_string(), pid.into());
+        }
+        if let Some(tid) = entry.thread_id {
+            fields.insert("thread_id".to_string(), tid.into());
+        }
+        if let Some(sub) = entry.subsystem {
+            fields.insert("subsystem".to_string(), sub.into());
+        }
+        if let Some(cat) = entry.category {
+            fields.insert("category".to_string(), cat.into());
+        }
+        if let Some(lib) = entry.process_image_path.as_ref() {
+            // Sometimes image path is the library if it's loaded dylib vs executable?
+            // Actually processImagePath is usually the main executable.
+            fields.insert("library".to_string(), lib.as_str().into());
+        }
+
+        // `log` prints local time with an offset, e.g. `2024-01-02 03:04:05.123456-0800`.
+        let timestamp = entry
+            .timestamp
+            .as_deref()
+            .and_then(|t| DateTime::parse_from_str(t, "%Y-%m-%d %H:%M:%S%.f%z").ok())
+            .map(|t| t.with_timezone(&Utc));
+
+        Ok(AuditEvent {
+            id: String::new(),
+            timestamp: timestamp.unwrap_or_else(Utc::now),
+            record_type: GENERIC_RECORD_TYPE,
+            sequence: entry.trace_id.unwrap_or(0),
+            native_id: entry.trace_id.map(NativeId::MacTraceId),
+            synthetic_timestamp: timestamp.is_none(),
+            fields,
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use super::{EventParser, GENERIC_RECORD_TYPE};
+use crate::model::AuditEvent;
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Parses lines with a user-supplied regex; each named capture group becomes a field.
+///
+/// The full line is kept as `message`. Lines that do not match are parse failures.
+pub struct RegexParser {
+    regex: Regex,
+}
+
+impl RegexParser {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern).with_context(|| format!("Invalid regex '{}'", pattern))?;
+        if regex.capture_names().flatten().next().is_none() {
+            return Err(anyhow!("Regex '{}' has no named capture groups", pattern));
+        }
+        Ok(Self { regex })
+    }
+}
+
+impl EventParser for RegexParser {
+    fn name(&self) -> &str {
+        "regex"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let s = String::from_utf8_lossy(raw);
+        let caps = self
+            .regex
+            .captures(&s)
+            .ok_or_else(|| anyhow!("Line does not match pattern"))?;
+
+        let mut fields = HashMap::new();
+        fields.insert("message".to_string(), s.as_ref().into());
+        for name in self.regex.capture_names().flatten() {
+            if let Some(m) = caps.name(name) {
+                fields.insert(name.to_string(), m.as_str().into());
+            }
+        }
+
+        Ok(AuditEvent {
+            id: String::new(),
+            timestamp: chrono::Utc::now(),
+            record_type: GENERIC_RECORD_TYPE,
+            sequence: 0,
+            native_id: None,
+            synthetic_timestamp: true,
+            fields,
+        })
+    }
+}
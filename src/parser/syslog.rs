@@ -0,0 +1,62 @@
+use super::{EventParser, GENERIC_RECORD_TYPE};
+use crate::model::{AuditEvent, FieldValue};
+use crate::{authlog, syslog};
+use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+/// Parses RFC 3164 / RFC 5424 syslog messages, as well as the PRI-less lines written
+/// to `/var/log/auth.log` and `/var/log/secure`.
+///
+/// Messages from authentication programs (sshd, sudo, su, ...) are further broken
+/// down by `authlog::extract`.
+pub struct SyslogParser;
+
+impl EventParser for SyslogParser {
+    fn name(&self) -> &str {
+        "syslog"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let s = String::from_utf8_lossy(raw);
+        let mut fields = HashMap::new();
+
+        // Network syslog: starts with a <PRI> header
+        if let Some(msg) = syslog::parse(&s) {
+            msg.insert_fields(&mut fields);
+            if let Some(app) = &msg.app_name {
+                authlog::extract(app, &msg.message, &mut fields);
+            }
+            return Ok(AuditEvent {
+                id: String::new(),
+                timestamp: msg.timestamp.unwrap_or_else(chrono::Utc::now),
+                record_type: GENERIC_RECORD_TYPE,
+                sequence: 0,
+                native_id: None,
+                synthetic_timestamp: msg.timestamp.is_none(),
+                fields,
+            });
+        }
+
+        // Traditional auth.log / secure line: `Mmm dd hh:mm:ss host prog[pid]: msg`
+        if let Some(line) = authlog::parse_line(&s) {
+            fields.insert("hostname".to_string(), line.hostname.into());
+            fields.insert("app_name".to_string(), line.program.into());
+            if let Some(pid) = line.pid {
+                fields.insert("procid".to_string(), FieldValue::uint(pid));
+            }
+            fields.insert("message".to_string(), line.message.into());
+            authlog::extract(line.program, line.message, &mut fields);
+            return Ok(AuditEvent {
+                id: String::new(),
+                timestamp: line.timestamp,
+                record_type: GENERIC_RECORD_TYPE,
+                sequence: 0,
+                native_id: None,
+                synthetic_timestamp: false,
+                fields,
+            });
+        }
+
+        bail!("Not a syslog message")
+    }
+}
@@ -0,0 +1,157 @@
+use super::EventParser;
+use crate::model::{AuditEvent, FieldValue, NativeId};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Parses a Windows Event Log record rendered with `EvtRenderEventXml`.
+///
+/// `System` properties become fields alongside every named `EventData/Data`
+/// element, the EventID becomes the record type and EventRecordID the sequence.
+pub struct WindowsXmlParser;
+
+#[derive(Debug, Deserialize)]
+struct Event {
+    #[serde(rename = "System")]
+    system: System,
+    #[serde(rename = "EventData")]
+    event_data: Option<EventData>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct System {
+    provider: Option<Provider>,
+    #[serde(rename = "EventID")]
+    event_id: Text,
+    level: Option<Text>,
+    task: Option<Text>,
+    opcode: Option<Text>,
+    keywords: Option<Text>,
+    time_created: Option<TimeCreated>,
+    #[serde(rename = "EventRecordID")]
+    event_record_id: Option<Text>,
+    execution: Option<Execution>,
+    channel: Option<Text>,
+    computer: Option<Text>,
+    security: Option<Security>,
+}
+
+/// Element content; attributes such as `Qualifiers` on `EventID` are ignored.
+#[derive(Debug, Deserialize)]
+struct Text {
+    #[serde(rename = "$text", default)]
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Provider {
+    #[serde(rename = "@Name")]
+    name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TimeCreated {
+    #[serde(rename = "@SystemTime")]
+    system_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Execution {
+    #[serde(rename = "@ProcessID")]
+    process_id: Option<String>,
+    #[serde(rename = "@ThreadID")]
+    thread_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Security {
+    #[serde(rename = "@UserID")]
+    user_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EventData {
+    #[serde(rename = "Data", default)]
+    data: Vec<Data>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Data {
+    #[serde(rename = "@Name")]
+    name: Option<String>,
+    #[serde(rename = "$text", default)]
+    value: String,
+}
+
+impl EventParser for WindowsXmlParser {
+    fn name(&self) -> &str {
+        "windows_xml"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let xml = String::from_utf8_lossy(raw);
+        let event: Event = quick_xml::de::from_str(&xml).context("Invalid Windows event XML")?;
+        let system = event.system;
+        let mut fields = HashMap::new();
+
+        let event_id: u16 = system
+            .event_id
+            .value
+            .trim()
+            .parse()
+            .context("Invalid EventID")?;
+        fields.insert("event_id".to_string(), u64::from(event_id).into());
+
+        let mut put = |key: &str, value: Option<String>| {
+            if let Some(v) = value.filter(|v| !v.is_empty()) {
+                fields.insert(key.to_string(), v.into());
+            }
+        };
+        put("provider", system.provider.and_then(|p| p.name));
+        put("level", system.level.map(|t| t.value));
+        put("task", system.task.map(|t| t.value));
+        put("opcode", system.opcode.map(|t| t.value));
+        put("keywords", system.keywords.map(|t| t.value));
+        put("channel", system.channel.map(|t| t.value));
+        put("computer", system.computer.map(|t| t.value));
+        put("user_sid", system.security.and_then(|s| s.user_id));
+        if let Some(execution) = system.execution {
+            for (key, value) in [("pid", execution.process_id), ("thread_id", execution.thread_id)] {
+                if let Some(v) = value {
+                    fields.insert(key.to_string(), FieldValue::uint(&v));
+                }
+            }
+        }
+
+        // Unnamed Data elements (classic event sources) are numbered instead.
+        for (i, data) in event.event_data.map(|d| d.data).unwrap_or_default().into_iter().enumerate() {
+            let key = data.name.unwrap_or_else(|| format!("data_{}", i));
+            fields.insert(key, data.value.into());
+        }
+
+        let timestamp = system
+            .time_created
+            .and_then(|t| t.system_time)
+            .and_then(|t| DateTime::parse_from_rfc3339(&t).ok())
+            .map(|t| t.with_timezone(&Utc));
+        let record_id = match system.event_record_id {
+            Some(id) => {
+                let id = id.value.trim();
+                Some(id.parse::<u64>().with_context(|| format!("Invalid EventRecordID '{}'", id))?)
+            }
+            None => None,
+        };
+
+        Ok(AuditEvent {
+            id: String::new(),
+            timestamp: timestamp.unwrap_or_else(Utc::now),
+            record_type: event_id,
+            sequence: record_id.unwrap_or(0),
+            native_id: record_id.map(NativeId::WindowsRecordId),
+            synthetic_timestamp: timestamp.is_none(),
+            fields,
+        })
+    }
+}
@@ -0,0 +1,123 @@
+use super::AuditSource;
+use crate::parser::SourceFormat;
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// A log file to follow, e.g. an application log that is not an audit record.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileSourceConfig {
+    pub path: PathBuf,
+    /// Name used to attach extraction rules; defaults to the path.
+    pub name: Option<String>,
+    /// Format of each line.
+    #[serde(default = "default_format")]
+    pub format: SourceFormat,
+    /// Read the existing contents first instead of starting at the end.
+    #[serde(default)]
+    pub from_beginning: bool,
+}
+
+fn default_format() -> SourceFormat {
+    SourceFormat::Line
+}
+
+/// Source that follows a file line by line, like `tail -F`.
+///
+/// Truncation and replacement (log rotation) are detected by the file shrinking,
+/// after which the file is reopened from the start.
+pub struct FileSource {
+    queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    stop_signal: Arc<Mutex<bool>>,
+    name: String,
+    format: SourceFormat,
+}
+
+impl FileSource {
+    pub fn new(config: FileSourceConfig) -> Result<Self> {
+        let mut file = File::open(&config.path)
+            .with_context(|| format!("Failed to open {}", config.path.display()))?;
+        if !config.from_beginning {
+            file.seek(SeekFrom::End(0))?;
+        }
+
+        let queue = Arc::new(Mutex::new(VecDeque::new()));
+        let stop_signal = Arc::new(Mutex::new(false));
+        let (q_clone, stop_clone) = (queue.clone(), stop_signal.clone());
+        let path = config.path.clone();
+
+        thread::spawn(move || {
+            let mut reader = BufReader::new(file);
+            let mut line = Vec::new();
+            let mut position = reader.stream_position().unwrap_or(0);
+
+            while !*stop_clone.lock().unwrap() {
+                match reader.read_until(b'\n', &mut line) {
+                    // Only hand out complete lines; a partial one stays buffered.
+                    Ok(n) if n > 0 && line.ends_with(b"\n") => {
+                        position += line.len() as u64;
+                        let trimmed = line.trim_ascii();
+                        if !trimmed.is_empty() {
+                            q_clone.lock().unwrap().push_back(trimmed.to_vec());
+                        }
+                        line.clear();
+                    }
+                    Ok(_) => {
+                        thread::sleep(POLL_INTERVAL);
+                        let len = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(position);
+                        if len < position {
+                            if let Ok(file) = File::open(&path) {
+                                reader = BufReader::new(file);
+                                position = 0;
+                                line.clear();
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        eprintln!("Error reading {}: {}", path.display(), e);
+                        thread::sleep(POLL_INTERVAL);
+                    }
+                }
+            }
+        });
+
+        let name = config
+            .name
+            .unwrap_or_else(|| config.path.display().to_string());
+        Ok(Self { queue, stop_signal, name, format: config.format })
+    }
+}
+
+impl AuditSource for FileSource {
+    fn receive(&self) -> Result<Vec<u8>> {
+        loop {
+            let mut q = self.queue.lock().unwrap();
+            if let Some(data) = q.pop_front() {
+                return Ok(data);
+            }
+            drop(q);
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn format(&self) -> SourceFormat {
+        self.format.clone()
+    }
+
+    fn stop(&self) {
+        *self.stop_signal.lock().unwrap() = true;
+    }
+}
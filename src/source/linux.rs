@@ -1,22 +1,46 @@
 use super::AuditSource;
-use anyhow::{Context, Result};
+use crate::parser::SourceFormat;
+use anyhow::Result;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use std::thread;
 use std::process::{Command, Stdio};
 use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+/// Log files tried in order by `LinuxAuditSource::new`: auditd first, then the
+/// Debian and RHEL authentication logs for hosts without auditd.
+const DEFAULT_LOG_PATHS: [&str; 3] = ["/var/log/audit/audit.log", "/var/log/auth.log", "/var/log/secure"];
 
 /// Source that reads from Linux audit log file.
 /// For this implementation, we simply tail /var/log/audit/audit.log
+/// (or auth.log / secure when auditd is not installed).
 /// This requires the application to have read permissions on that file.
 pub struct LinuxAuditSource {
     queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
     child_pid: Arc<Mutex<Option<u32>>>,
+    format: SourceFormat,
 }
 
 impl LinuxAuditSource {
     pub fn new() -> Result<Self> {
+        let path = DEFAULT_LOG_PATHS
+            .iter()
+            .find(|p| Path::new(p).exists())
+            .unwrap_or(&DEFAULT_LOG_PATHS[0]);
+        Self::with_path(path)
+    }
+
+    /// Tails a specific log file.
+    pub fn with_path(path: &str) -> Result<Self> {
+        // auth.log and secure are syslog files; anything else is assumed to be auditd
+        let format = if path.ends_with("audit.log") {
+            SourceFormat::Auditd
+        } else {
+            SourceFormat::Syslog
+        };
+        let path = path.to_string();
         let queue = Arc::new(Mutex::new(VecDeque::new()));
         let q_clone = queue.clone();
         let child_pid = Arc::new(Mutex::new(None));
@@ -24,7 +48,7 @@ impl LinuxAuditSource {
 
         thread::spawn(move || {
             let mut cmd = Command::new("tail");
-            cmd.arg("-f").arg("/var/log/audit/audit.log");
+            cmd.arg("-f").arg(&path);
 
             println!("Starting audit log stream: {:?}", cmd);
 
@@ -40,21 +64,20 @@ impl LinuxAuditSource {
 
             if let Some(stdout) = child.stdout.take() {
                 let reader = BufReader::new(stdout);
-                for line in reader.lines() {
-                    if let Ok(l) = line {
-                        let trimmed = l.trim();
-                        if !trimmed.is_empty() {
-                             q_clone.lock().unwrap().push_back(trimmed.as_bytes().to_vec());
-                        }
+                for l in reader.lines().map_while(std::result::Result::ok) {
+                    let trimmed = l.trim();
+                    if !trimmed.is_empty() {
+                         q_clone.lock().unwrap().push_back(trimmed.as_bytes().to_vec());
                     }
                 }
             }
+            let _ = child.wait();
             // Child exited
             let mut pid_lock = pid_clone.lock().unwrap();
             *pid_lock = None;
         });
 
-        Ok(Self { queue, child_pid })
+        Ok(Self { queue, child_pid, format })
     }
 }
 
@@ -70,6 +93,10 @@ impl AuditSource for LinuxAuditSource {
         }
     }
     
+    fn format(&self) -> SourceFormat {
+        self.format.clone()
+    }
+
     fn stop(&self) {
         let pid_opt = self.child_pid.lock().unwrap();
         if let Some(pid) = *pid_opt {
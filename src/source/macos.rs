@@ -1,5 +1,6 @@
 use super::AuditSource;
 use crate::model::FilterConfig;
+use crate::parser::SourceFormat;
 use anyhow::Result;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
@@ -112,6 +113,10 @@ impl AuditSource for MacLogSource {
         }
     }
     
+    fn format(&self) -> SourceFormat {
+        SourceFormat::MacJson
+    }
+
     fn stop(&self) {
         let pid_opt = self.child_pid.lock().unwrap();
         if let Some(pid) = *pid_opt {
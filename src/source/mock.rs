@@ -1,4 +1,5 @@
 use super::AuditSource;
+use crate::parser::SourceFormat;
 use anyhow::Result;
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
@@ -8,12 +9,18 @@ use std::thread;
 /// A Mock source that replays a predefined sequence of byte vectors.
 pub struct MockAuditSource {
     queue: Arc<Mutex<VecDeque<Vec<u8>>>>,
+    format: SourceFormat,
 }
 
 impl MockAuditSource {
     pub fn new(data: Vec<Vec<u8>>) -> Self {
+        Self::with_format(data, SourceFormat::Auto)
+    }
+    /// Creates a mock that declares a specific format instead of letting the parser guess.
+    pub fn with_format(data: Vec<Vec<u8>>, format: SourceFormat) -> Self {
         Self {
             queue: Arc::new(Mutex::new(VecDeque::from(data))),
+            format,
         }
     }
     pub fn push(&self, packet: Vec<u8>) {
@@ -32,4 +39,8 @@ impl AuditSource for MockAuditSource {
             thread::sleep(Duration::from_millis(100));
         }
     }
+
+    fn format(&self) -> SourceFormat {
+        self.format.clone()
+    }
 }
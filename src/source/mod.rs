@@ -1,14 +1,38 @@
+use crate::parser::SourceFormat;
 use anyhow::Result;
+use std::net::SocketAddr;
 
+/// The remote peer a packet was received from, for network sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Peer {
+    pub addr: SocketAddr,
+    /// Transport the packet arrived on (e.g. "udp", "tcp", "tls").
+    pub transport: &'static str,
+}
 
 pub trait AuditSource: Send + Sync {
     /// Blocks until a new packet of data is available.
     fn receive(&self) -> Result<Vec<u8>>;
+    /// Like `receive`, but also reports which peer sent the packet.
+    /// Local sources have no peer and can rely on the default.
+    fn receive_from(&self) -> Result<(Vec<u8>, Option<Peer>)> {
+        Ok((self.receive()?, None))
+    }
+    /// Name used to attach extraction rules to this source.
+    fn name(&self) -> String {
+        self.format().name().to_string()
+    }
+    /// The format of the packets this source produces, used to pick a parser.
+    fn format(&self) -> SourceFormat {
+        SourceFormat::Auto
+    }
     /// Optional: Signal to stop the source
     fn stop(&self) {}
 }
 
+pub mod file;
 pub mod mock;
+pub mod syslog;
 #[cfg(target_os = "macos")]
 pub mod macos;
 #[cfg(target_os = "linux")]
@@ -17,7 +41,9 @@ pub mod linux;
 pub mod windows;
 
 // Re-export common types if necessary
+pub use file::{FileSource, FileSourceConfig};
 pub use mock::MockAuditSource;
+pub use syslog::{SyslogConfig, SyslogSource};
 #[cfg(target_os = "macos")]
 pub use macos::MacLogSource;
 #[cfg(target_os = "linux")]
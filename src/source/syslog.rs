@@ -0,0 +1,387 @@
+use super::{AuditSource, Peer};
+use crate::parser::SourceFormat;
+use crate::syslog::FrameDecoder;
+use anyhow::{Context, Result};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use serde::Deserialize;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often blocked listener threads wake up to check the stop signal.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Messages received but not yet taken by the collector.
+struct Queue {
+    messages: Mutex<VecDeque<(Vec<u8>, Peer)>>,
+    capacity: usize,
+    dropped: AtomicU64,
+}
+
+impl Queue {
+    /// Queues a message, or drops and counts it once the queue is full.
+    fn push(&self, data: Vec<u8>, peer: Peer) {
+        let mut messages = self.messages.lock().unwrap();
+        if messages.len() < self.capacity {
+            messages.push_back((data, peer));
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Open TCP and TLS connections, shared by both listeners.
+struct Connections {
+    active: AtomicUsize,
+    max: usize,
+    rejected: AtomicU64,
+    idle_timeout: Duration,
+}
+
+impl Connections {
+    /// Takes a connection slot, or counts the connection as rejected when all are in use.
+    fn try_open(&self) -> bool {
+        let opened = self
+            .active
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| (n < self.max).then_some(n + 1))
+            .is_ok();
+        if !opened {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+        }
+        opened
+    }
+
+    fn close(&self) {
+        self.active.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// Listener addresses for the syslog receiver. Each transport is optional.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyslogConfig {
+    /// UDP listen address, e.g. `0.0.0.0:514`.
+    pub udp: Option<SocketAddr>,
+    /// Plain TCP listen address (octet-counting or newline framing).
+    pub tcp: Option<SocketAddr>,
+    /// TLS listen address and server certificate.
+    pub tls: Option<SyslogTlsConfig>,
+    /// Messages waiting for the collector; newer ones are dropped beyond this.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+    /// Open TCP and TLS connections; further ones are closed straight away.
+    #[serde(default = "default_max_connections")]
+    pub max_connections: usize,
+    /// Connections that send nothing for this long are closed.
+    #[serde(default = "default_idle_timeout")]
+    pub idle_timeout_secs: u64,
+}
+
+fn default_queue_size() -> usize {
+    10_000
+}
+
+fn default_max_connections() -> usize {
+    1024
+}
+
+fn default_idle_timeout() -> u64 {
+    300
+}
+
+impl Default for SyslogConfig {
+    fn default() -> Self {
+        Self {
+            udp: None,
+            tcp: None,
+            tls: None,
+            queue_size: default_queue_size(),
+            max_connections: default_max_connections(),
+            idle_timeout_secs: default_idle_timeout(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyslogTlsConfig {
+    pub listen: SocketAddr,
+    /// PEM file with the server certificate chain.
+    pub cert: PathBuf,
+    /// PEM file with the server private key.
+    pub key: PathBuf,
+}
+
+/// Source that receives RFC 3164 / RFC 5424 syslog messages from the network.
+///
+/// Every packet is tagged with the peer it came from so the collector can
+/// tell devices apart.
+pub struct SyslogSource {
+    queue: Arc<Queue>,
+    connections: Arc<Connections>,
+    stop_signal: Arc<Mutex<bool>>,
+    udp_addr: Option<SocketAddr>,
+    tcp_addr: Option<SocketAddr>,
+    tls_addr: Option<SocketAddr>,
+}
+
+impl SyslogSource {
+    pub fn new(config: SyslogConfig) -> Result<Self> {
+        let queue = Arc::new(Queue {
+            messages: Mutex::new(VecDeque::new()),
+            capacity: config.queue_size,
+            dropped: AtomicU64::new(0),
+        });
+        let connections = Arc::new(Connections {
+            active: AtomicUsize::new(0),
+            max: config.max_connections,
+            rejected: AtomicU64::new(0),
+            idle_timeout: Duration::from_secs(config.idle_timeout_secs),
+        });
+        let stop_signal = Arc::new(Mutex::new(false));
+        let mut source = Self {
+            queue: queue.clone(),
+            connections: connections.clone(),
+            stop_signal: stop_signal.clone(),
+            udp_addr: None,
+            tcp_addr: None,
+            tls_addr: None,
+        };
+
+        if let Some(addr) = config.udp {
+            let socket = UdpSocket::bind(addr)
+                .with_context(|| format!("Failed to bind syslog UDP listener on {}", addr))?;
+            socket.set_read_timeout(Some(POLL_INTERVAL))?;
+            source.udp_addr = Some(socket.local_addr()?);
+            let (q, stop) = (queue.clone(), stop_signal.clone());
+            thread::spawn(move || run_udp(socket, q, stop));
+        }
+
+        if let Some(addr) = config.tcp {
+            let listener = bind_tcp(addr)?;
+            source.tcp_addr = Some(listener.local_addr()?);
+            let (q, conns, stop) = (queue.clone(), connections.clone(), stop_signal.clone());
+            thread::spawn(move || accept_loop(listener, None, q, conns, stop));
+        }
+
+        if let Some(tls) = config.tls {
+            let server_config = load_tls_config(&tls.cert, &tls.key)?;
+            let listener = bind_tcp(tls.listen)?;
+            source.tls_addr = Some(listener.local_addr()?);
+            let (q, conns, stop) = (queue.clone(), connections.clone(), stop_signal.clone());
+            thread::spawn(move || accept_loop(listener, Some(server_config), q, conns, stop));
+        }
+
+        println!(
+            "Syslog receiver listening (udp: {:?}, tcp: {:?}, tls: {:?})",
+            source.udp_addr, source.tcp_addr, source.tls_addr
+        );
+        Ok(source)
+    }
+
+    /// The bound UDP address, useful when listening on port 0.
+    pub fn udp_addr(&self) -> Option<SocketAddr> {
+        self.udp_addr
+    }
+
+    /// The bound TCP address, useful when listening on port 0.
+    pub fn tcp_addr(&self) -> Option<SocketAddr> {
+        self.tcp_addr
+    }
+
+    /// The bound TLS address, useful when listening on port 0.
+    pub fn tls_addr(&self) -> Option<SocketAddr> {
+        self.tls_addr
+    }
+
+    /// Messages dropped because the queue was full.
+    pub fn dropped(&self) -> u64 {
+        self.queue.dropped.load(Ordering::Relaxed)
+    }
+
+    /// TCP and TLS connections currently open.
+    pub fn active_connections(&self) -> usize {
+        self.connections.active.load(Ordering::Acquire)
+    }
+
+    /// Connections closed on accept because `max_connections` were already open.
+    pub fn rejected_connections(&self) -> u64 {
+        self.connections.rejected.load(Ordering::Relaxed)
+    }
+}
+
+fn bind_tcp(addr: SocketAddr) -> Result<TcpListener> {
+    let listener = TcpListener::bind(addr)
+        .with_context(|| format!("Failed to bind syslog TCP listener on {}", addr))?;
+    // Non-blocking accept so the loop can notice the stop signal.
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+fn load_tls_config(cert: &Path, key: &Path) -> Result<Arc<ServerConfig>> {
+    let certs = CertificateDer::pem_file_iter(cert)
+        .with_context(|| format!("Failed to read certificate {}", cert.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("Invalid certificate {}", cert.display()))?;
+    let key = PrivateKeyDer::from_pem_file(key)
+        .with_context(|| format!("Failed to read private key {}", key.display()))?;
+
+    let config = ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .context("Invalid TLS certificate or key")?;
+    Ok(Arc::new(config))
+}
+
+fn stopped(stop: &Mutex<bool>) -> bool {
+    *stop.lock().unwrap()
+}
+
+fn run_udp(socket: UdpSocket, queue: Arc<Queue>, stop: Arc<Mutex<bool>>) {
+    let mut buf = vec![0u8; crate::syslog::MAX_FRAME_LEN];
+    while !stopped(&stop) {
+        match socket.recv_from(&mut buf) {
+            Ok((n, addr)) => {
+                // One datagram is one message; trailing newlines are tolerated.
+                let data = buf[..n].trim_ascii_end();
+                if !data.is_empty() {
+                    let peer = Peer { addr, transport: "udp" };
+                    queue.push(data.to_vec(), peer);
+                }
+            }
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(e) => eprintln!("Syslog UDP receive error: {}", e),
+        }
+    }
+}
+
+fn accept_loop(
+    listener: TcpListener,
+    tls: Option<Arc<ServerConfig>>,
+    queue: Arc<Queue>,
+    connections: Arc<Connections>,
+    stop: Arc<Mutex<bool>>,
+) {
+    while !stopped(&stop) {
+        match listener.accept() {
+            Ok((stream, addr)) => {
+                if !connections.try_open() {
+                    eprintln!("Syslog connection from {} refused: {} already open", addr, connections.max);
+                    continue;
+                }
+                if stream.set_nonblocking(false).is_err()
+                    || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err()
+                {
+                    connections.close();
+                    continue;
+                }
+                let (q, conns, stop) = (queue.clone(), connections.clone(), stop.clone());
+                let tls = tls.clone();
+                thread::spawn(move || {
+                    handle_connection(stream, addr, tls, &q, conns.idle_timeout, &stop);
+                    conns.close();
+                });
+            }
+            Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(50)),
+            Err(e) => {
+                eprintln!("Syslog accept error: {}", e);
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+fn handle_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    tls: Option<Arc<ServerConfig>>,
+    queue: &Queue,
+    idle_timeout: Duration,
+    stop: &Mutex<bool>,
+) {
+    match tls {
+        Some(config) => match ServerConnection::new(config) {
+            Ok(conn) => {
+                let peer = Peer { addr, transport: "tls" };
+                read_frames(StreamOwned::new(conn, stream), peer, queue, idle_timeout, stop);
+            }
+            Err(e) => eprintln!("Syslog TLS setup failed for {}: {}", addr, e),
+        },
+        None => {
+            let peer = Peer { addr, transport: "tcp" };
+            read_frames(stream, peer, queue, idle_timeout, stop);
+        }
+    }
+}
+
+/// Reads a stream connection until EOF or `idle_timeout` without data, splitting
+/// it into syslog frames.
+fn read_frames<R: Read>(
+    mut stream: R,
+    peer: Peer,
+    queue: &Queue,
+    idle_timeout: Duration,
+    stop: &Mutex<bool>,
+) {
+    let mut decoder = FrameDecoder::new();
+    let mut buf = [0u8; 8192];
+    let mut last_data = Instant::now();
+
+    while !stopped(stop) && last_data.elapsed() < idle_timeout {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                last_data = Instant::now();
+                decoder.push(&buf[..n]);
+                while let Some(frame) = decoder.next_frame() {
+                    if !frame.is_empty() {
+                        queue.push(frame, peer);
+                    }
+                }
+            }
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted
+                ) => {}
+            Err(_) => break,
+        }
+    }
+
+    if let Some(frame) = decoder.finish() {
+        queue.push(frame, peer);
+    }
+}
+
+impl AuditSource for SyslogSource {
+    fn receive(&self) -> Result<Vec<u8>> {
+        self.receive_from().map(|(data, _)| data)
+    }
+
+    fn receive_from(&self) -> Result<(Vec<u8>, Option<Peer>)> {
+        loop {
+            let mut q = self.queue.messages.lock().unwrap();
+            if let Some((data, peer)) = q.pop_front() {
+                return Ok((data, Some(peer)));
+            }
+            drop(q);
+            thread::sleep(Duration::from_millis(50));
+        }
+    }
+
+    fn format(&self) -> SourceFormat {
+        SourceFormat::Syslog
+    }
+
+    fn stop(&self) {
+        *self.stop_signal.lock().unwrap() = true;
+    }
+}
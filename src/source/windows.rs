@@ -1,4 +1,5 @@
 use super::AuditSource;
+use crate::parser::SourceFormat;
 use anyhow::{Result, anyhow};
 use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
@@ -92,11 +93,11 @@ impl WindowsEventSource {
                                  let h_evt = event_handles[i as usize];
                                  if !h_evt.is_invalid() {
                                      if let Ok(xml) = render_event_xml(h_evt) {
-                                         // Convert simple XML to a "fake" JSON or just pass XML bytes?
-                                         // The collector expects JSON or key=value.
-                                         // Let's wrap it in a JSON structure so the existing parser catches it.
-                                         let json = format!("{{ \"message\": \"Windows Event\", \"details\": {:?} }}", xml);
-                                         q_clone.lock().unwrap().push_back(json.as_bytes().to_vec());
+                                         // Pass the rendered XML through; WindowsXmlParser handles it.
+                                         let xml = xml.trim_end_matches('\0');
+                                         if !xml.is_empty() {
+                                             q_clone.lock().unwrap().push_back(xml.as_bytes().to_vec());
+                                         }
                                      }
                                      EvtClose(h_evt);
                                  }
@@ -148,6 +149,10 @@ impl AuditSource for WindowsEventSource {
         }
     }
     
+    fn format(&self) -> SourceFormat {
+        SourceFormat::WindowsXml
+    }
+
     fn stop(&self) {
         *self.stop_signal.lock().unwrap() = true;
     }
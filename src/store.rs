@@ -0,0 +1,53 @@
+use crate::model::AuditEvent;
+use std::collections::{HashMap, VecDeque};
+
+/// The most recent events, indexed by ID so the API can return a single one.
+///
+/// Holds at most `capacity` events; the oldest is evicted first.
+#[derive(Debug)]
+pub struct RecentEvents {
+    capacity: usize,
+    order: VecDeque<String>,
+    events: HashMap<String, AuditEvent>,
+}
+
+impl RecentEvents {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            order: VecDeque::with_capacity(capacity),
+            events: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Stores `event`. Events without an ID are ignored; a repeated ID replaces
+    /// the stored event without changing its position.
+    pub fn push(&mut self, event: AuditEvent) {
+        if event.id.is_empty() || self.capacity == 0 {
+            return;
+        }
+        if let Some(existing) = self.events.get_mut(&event.id) {
+            *existing = event;
+            return;
+        }
+        if self.order.len() == self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.events.remove(&oldest);
+            }
+        }
+        self.order.push_back(event.id.clone());
+        self.events.insert(event.id.clone(), event);
+    }
+
+    pub fn get(&self, id: &str) -> Option<&AuditEvent> {
+        self.events.get(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
@@ -0,0 +1,470 @@
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use crate::model::FieldValue;
+use std::collections::HashMap;
+
+/// Largest frame accepted from a stream transport. Anything bigger is dropped.
+pub const MAX_FRAME_LEN: usize = 64 * 1024;
+
+const FACILITIES: [&str; 24] = [
+    "kern", "user", "mail", "daemon", "auth", "syslog", "lpr", "news",
+    "uucp", "cron", "authpriv", "ftp", "ntp", "security", "console", "solaris-cron",
+    "local0", "local1", "local2", "local3", "local4", "local5", "local6", "local7",
+];
+
+const SEVERITIES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// A single SD-ELEMENT from RFC 5424 structured data, e.g. `[origin ip="10.0.0.1"]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdElement {
+    pub id: String,
+    pub params: Vec<(String, String)>,
+}
+
+/// A syslog message parsed from either the BSD (RFC 3164) or the IETF (RFC 5424) format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyslogMessage {
+    pub facility: u8,
+    pub severity: u8,
+    /// Protocol version; `Some(1)` for RFC 5424, `None` for RFC 3164.
+    pub version: Option<u8>,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub hostname: Option<String>,
+    pub app_name: Option<String>,
+    pub procid: Option<String>,
+    pub msgid: Option<String>,
+    pub structured_data: Vec<SdElement>,
+    pub message: String,
+}
+
+impl SyslogMessage {
+    /// The combined PRI value (`facility * 8 + severity`).
+    pub fn pri(&self) -> u8 {
+        self.facility * 8 + self.severity
+    }
+
+    /// Writes the parsed header, structured data and message into an event field map.
+    ///
+    /// Structured data parameters are flattened to `sd.<SD-ID>.<PARAM-NAME>` keys.
+    pub fn insert_fields(&self, fields: &mut HashMap<String, FieldValue>) {
+        fields.insert("pri".to_string(), u64::from(self.pri()).into());
+        fields.insert("facility".to_string(), FACILITIES[self.facility as usize].into());
+        fields.insert("severity".to_string(), SEVERITIES[self.severity as usize].into());
+        if let Some(ts) = self.timestamp {
+            fields.insert("syslog_timestamp".to_string(), ts.into());
+        }
+        if let Some(host) = &self.hostname {
+            fields.insert("hostname".to_string(), host.as_str().into());
+        }
+        if let Some(app) = &self.app_name {
+            fields.insert("app_name".to_string(), app.as_str().into());
+        }
+        if let Some(procid) = &self.procid {
+            // PROCID is usually a pid but may be any token
+            fields.insert("procid".to_string(), FieldValue::uint(procid));
+        }
+        if let Some(msgid) = &self.msgid {
+            fields.insert("msgid".to_string(), msgid.as_str().into());
+        }
+        for element in &self.structured_data {
+            for (name, value) in &element.params {
+                fields.insert(format!("sd.{}.{}", element.id, name), value.as_str().into());
+            }
+        }
+        fields.insert("message".to_string(), self.message.as_str().into());
+    }
+}
+
+impl SyslogMessage {
+    /// Formats the message as RFC 5424 (without transport framing).
+    ///
+    /// Header fields are cut to their maximum length and SD names stripped of the
+    /// characters RFC 5424 forbids, so any message produces a valid line.
+    pub fn to_rfc5424(&self) -> String {
+        let timestamp = self
+            .timestamp
+            .map(|t| t.to_rfc3339_opts(SecondsFormat::Micros, true))
+            .unwrap_or_else(|| "-".to_string());
+        let mut line = format!(
+            "<{}>1 {} {} {} {} {} ",
+            self.pri(),
+            timestamp,
+            header_token(self.hostname.as_deref(), 255),
+            header_token(self.app_name.as_deref(), 48),
+            header_token(self.procid.as_deref(), 128),
+            header_token(self.msgid.as_deref(), 32),
+        );
+
+        if self.structured_data.is_empty() {
+            line.push('-');
+        }
+        for element in &self.structured_data {
+            line.push('[');
+            line.push_str(&sd_name(&element.id));
+            for (name, value) in &element.params {
+                line.push(' ');
+                line.push_str(&sd_name(name));
+                line.push_str("=\"");
+                for c in value.chars() {
+                    if matches!(c, '"' | '\\' | ']') {
+                        line.push('\\');
+                    }
+                    line.push(c);
+                }
+                line.push('"');
+            }
+            line.push(']');
+        }
+
+        if !self.message.is_empty() {
+            line.push(' ');
+            line.push_str(&self.message);
+        }
+        line
+    }
+}
+
+/// Looks up a facility by keyword, e.g. `authpriv` → 10.
+pub fn facility_code(name: &str) -> Option<u8> {
+    FACILITIES.iter().position(|f| *f == name).map(|i| i as u8)
+}
+
+/// Looks up a severity by keyword, e.g. `warning` → 4.
+pub fn severity_code(name: &str) -> Option<u8> {
+    SEVERITIES.iter().position(|s| *s == name).map(|i| i as u8)
+}
+
+/// The keyword for a severity code, e.g. 4 → `warning`.
+pub fn severity_name(code: u8) -> &'static str {
+    SEVERITIES[(code & 7) as usize]
+}
+
+/// A header field as PRINTUSASCII, at most `max` characters, or the NILVALUE.
+fn header_token(value: Option<&str>, max: usize) -> String {
+    let token: String = value
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| c.is_ascii_graphic())
+        .take(max)
+        .collect();
+    if token.is_empty() {
+        "-".to_string()
+    } else {
+        token
+    }
+}
+
+/// An SD-ID or PARAM-NAME: printable ASCII except `=`, `]` and `"`, at most 32 characters.
+fn sd_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"') { c } else { '_' })
+        .take(32)
+        .collect();
+    if name.is_empty() {
+        "_".to_string()
+    } else {
+        name
+    }
+}
+
+/// Parses a syslog line, detecting RFC 5424 by the version digit following the PRI.
+///
+/// Returns `None` if the line does not start with a valid `<PRI>` header.
+pub fn parse(line: &str) -> Option<SyslogMessage> {
+    let line = line.trim_end_matches(['\r', '\n', '\0']);
+    let (pri, rest) = parse_pri(line)?;
+
+    if let Some(body) = rest.strip_prefix("1 ") {
+        parse_rfc5424(pri, body)
+    } else {
+        Some(parse_rfc3164(pri, rest))
+    }
+}
+
+fn parse_pri(line: &str) -> Option<(u8, &str)> {
+    let rest = line.strip_prefix('<')?;
+    let end = rest.find('>')?;
+    if end == 0 || end > 3 {
+        return None;
+    }
+    let pri: u8 = rest[..end].parse().ok()?;
+    if pri > 191 {
+        return None;
+    }
+    Some((pri, &rest[end + 1..]))
+}
+
+fn nil(value: &str) -> Option<String> {
+    if value == "-" {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn parse_rfc5424(pri: u8, body: &str) -> Option<SyslogMessage> {
+    let mut parts = body.splitn(6, ' ');
+    let timestamp = parts.next()?;
+    let hostname = parts.next()?;
+    let app_name = parts.next()?;
+    let procid = parts.next()?;
+    let msgid = parts.next()?;
+    let rest = parts.next().unwrap_or("");
+
+    let timestamp = if timestamp == "-" {
+        None
+    } else {
+        Some(DateTime::parse_from_rfc3339(timestamp).ok()?.with_timezone(&Utc))
+    };
+
+    let (structured_data, msg) = parse_structured_data(rest)?;
+    let msg = msg.strip_prefix(' ').unwrap_or(msg);
+    let msg = msg.strip_prefix('\u{feff}').unwrap_or(msg);
+
+    Some(SyslogMessage {
+        facility: pri / 8,
+        severity: pri % 8,
+        version: Some(1),
+        timestamp,
+        hostname: nil(hostname),
+        app_name: nil(app_name),
+        procid: nil(procid),
+        msgid: nil(msgid),
+        structured_data,
+        message: msg.to_string(),
+    })
+}
+
+/// Parses the STRUCTURED-DATA part, returning the elements and the remaining message.
+fn parse_structured_data(s: &str) -> Option<(Vec<SdElement>, &str)> {
+    if let Some(rest) = s.strip_prefix('-') {
+        return Some((Vec::new(), rest));
+    }
+
+    let mut elements = Vec::new();
+    let mut rest = s;
+    while let Some(body) = rest.strip_prefix('[') {
+        let id_end = body.find([' ', ']'])?;
+        let mut element = SdElement {
+            id: body[..id_end].to_string(),
+            params: Vec::new(),
+        };
+        let mut cur = &body[id_end..];
+
+        loop {
+            cur = cur.trim_start_matches(' ');
+            if let Some(after) = cur.strip_prefix(']') {
+                rest = after;
+                break;
+            }
+            let eq = cur.find('=')?;
+            let name = cur[..eq].to_string();
+            let value_part = cur[eq + 1..].strip_prefix('"')?;
+
+            // PARAM-VALUE escapes '"', '\' and ']' with a backslash.
+            let mut value = String::new();
+            let mut chars = value_part.char_indices();
+            let mut close = None;
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '\\' => match chars.next() {
+                        Some((_, e @ ('"' | '\\' | ']'))) => value.push(e),
+                        Some((_, e)) => {
+                            value.push('\\');
+                            value.push(e);
+                        }
+                        None => return None,
+                    },
+                    '"' => {
+                        close = Some(i);
+                        break;
+                    }
+                    _ => value.push(c),
+                }
+            }
+            element.params.push((name, value));
+            cur = &value_part[close? + 1..];
+        }
+        elements.push(element);
+    }
+
+    if elements.is_empty() {
+        return None;
+    }
+    Some((elements, rest))
+}
+
+/// Parses the BSD format: `Mmm dd hh:mm:ss HOSTNAME TAG[PID]: MSG`.
+///
+/// RFC 3164 is loosely specified, so anything that does not fit the header is kept
+/// as the message rather than rejected.
+fn parse_rfc3164(pri: u8, rest: &str) -> SyslogMessage {
+    let mut msg = SyslogMessage {
+        facility: pri / 8,
+        severity: pri % 8,
+        version: None,
+        timestamp: None,
+        hostname: None,
+        app_name: None,
+        procid: None,
+        msgid: None,
+        structured_data: Vec::new(),
+        message: rest.to_string(),
+    };
+
+    let Some((timestamp, after_ts)) = parse_bsd_timestamp(rest) else {
+        return msg;
+    };
+    msg.timestamp = Some(timestamp);
+
+    let after_ts = after_ts.trim_start_matches(' ');
+    let (hostname, after_host) = after_ts.split_once(' ').unwrap_or((after_ts, ""));
+    msg.hostname = Some(hostname.to_string());
+
+    let (tag, content) = split_tag(after_host);
+    if let Some((app_name, procid)) = tag {
+        msg.app_name = Some(app_name.to_string());
+        msg.procid = procid.map(str::to_string);
+    }
+    msg.message = content.to_string();
+    msg
+}
+
+/// Parses a `Mmm dd hh:mm:ss` timestamp in local time, assuming the current year.
+pub fn parse_bsd_timestamp(s: &str) -> Option<(DateTime<Utc>, &str)> {
+    parse_bsd_timestamp_at(s, Local::now())
+}
+
+/// `parse_bsd_timestamp` as of `now`. A time more than a day ahead of `now` is
+/// from last year, e.g. a December record read in January.
+pub fn parse_bsd_timestamp_at(s: &str, now: DateTime<Local>) -> Option<(DateTime<Utc>, &str)> {
+    let candidate = s.get(..15)?.replace("  ", " ");
+    let in_year = |year: i32| {
+        let naive = NaiveDateTime::parse_from_str(&format!("{} {}", year, candidate), "%Y %b %d %H:%M:%S").ok()?;
+        Local.from_local_datetime(&naive).earliest()
+    };
+    let local = match in_year(now.year()) {
+        Some(t) if t <= now + Duration::days(1) => t,
+        _ => in_year(now.year() - 1)?,
+    };
+    Some((local.with_timezone(&Utc), &s[15..]))
+}
+
+/// Splits a `TAG[PID]: content` prefix off a BSD message body.
+pub fn split_tag(s: &str) -> (Option<(&str, Option<&str>)>, &str) {
+    let Some(colon) = s.find(": ") else {
+        return (None, s);
+    };
+    let tag = &s[..colon];
+    if tag.is_empty() || tag.contains(' ') {
+        return (None, s);
+    }
+    let content = &s[colon + 2..];
+    match tag.split_once('[') {
+        Some((name, pid)) => (Some((name, Some(pid.trim_end_matches(']')))), content),
+        None => (Some((tag, None)), content),
+    }
+}
+
+/// Incremental decoder for syslog over a stream transport (RFC 6587).
+///
+/// Each frame is detected independently: a leading digit means octet-counting
+/// (`LEN SP MSG`), anything else is newline-delimited.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    /// Dropping the rest of an oversized newline-delimited frame.
+    discarding: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends bytes read from the transport.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// Returns the next complete frame, if one is buffered.
+    pub fn next_frame(&mut self) -> Option<Vec<u8>> {
+        loop {
+            if self.discarding {
+                match self.buf.iter().position(|b| *b == b'\n') {
+                    Some(end) => {
+                        self.buf.drain(..=end);
+                        self.discarding = false;
+                    }
+                    None => {
+                        self.buf.clear();
+                        return None;
+                    }
+                }
+            }
+
+            // Skip stray separators between frames.
+            let skip = self.buf.iter().take_while(|b| matches!(b, b'\n' | b'\r' | b'\0')).count();
+            self.buf.drain(..skip);
+            let first = *self.buf.first()?;
+
+            if first.is_ascii_digit() {
+                let digits = self.buf.iter().take_while(|b| b.is_ascii_digit()).count();
+                match self.buf.get(digits) {
+                    // Length prefix not complete yet.
+                    None if digits <= 5 => return None,
+                    Some(b' ') => {
+                        let len: usize = std::str::from_utf8(&self.buf[..digits])
+                            .ok()
+                            .and_then(|s| s.parse().ok())
+                            .unwrap_or(usize::MAX);
+                        if len <= MAX_FRAME_LEN {
+                            let start = digits + 1;
+                            if self.buf.len() < start + len {
+                                return None;
+                            }
+                            let frame = self.buf[start..start + len].to_vec();
+                            self.buf.drain(..start + len);
+                            return Some(frame);
+                        }
+                    }
+                    // Not a length prefix; fall back to newline framing.
+                    _ => {}
+                }
+            }
+
+            match self.buf.iter().position(|b| *b == b'\n') {
+                Some(end) => {
+                    let mut frame: Vec<u8> = self.buf.drain(..=end).collect();
+                    frame.pop();
+                    if frame.last() == Some(&b'\r') {
+                        frame.pop();
+                    }
+                    if frame.len() > MAX_FRAME_LEN {
+                        continue;
+                    }
+                    return Some(frame);
+                }
+                None => {
+                    if self.buf.len() > MAX_FRAME_LEN {
+                        self.buf.clear();
+                        self.discarding = true;
+                    }
+                    return None;
+                }
+            }
+        }
+    }
+
+    /// Returns whatever is left once the peer has closed the connection.
+    pub fn finish(&mut self) -> Option<Vec<u8>> {
+        let frame = std::mem::take(&mut self.buf);
+        if std::mem::take(&mut self.discarding) || frame.iter().all(|b| b.is_ascii_whitespace() || *b == 0) {
+            None
+        } else {
+            Some(frame)
+        }
+    }
+}
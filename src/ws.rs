@@ -0,0 +1,364 @@
+//! The WebSocket event API served at GET /api/ws.
+//!
+//! Clients send JSON commands and receive JSON messages, both tagged by `type`.
+//! A socket carries any number of subscriptions, each named by the client:
+//!
+//! ```text
+//! {"type":"subscribe","id":"fails","filter":"event.outcome == 'failure'","sample":0.5,"window":100}
+//! {"type":"history","id":"fails","limit":50}
+//! {"type":"pause","id":"fails"}            // without "id": every subscription
+//! {"type":"resume","id":"fails"}
+//! {"type":"sample","id":"fails","rate":0.1}
+//! {"type":"ack","id":"fails","seq":1718000000000123}
+//! {"type":"unsubscribe","id":"fails"}
+//! ```
+//!
+//! Events arrive as `{"type":"event","id":"fails","seq":...,"event":{...}}`, where
+//! `seq` is the event's id on the stream (the SSE `id:`). Each subscription may
+//! have up to `window` events unacknowledged; past that its events are dropped and
+//! it is sent `backpressure` with `paused: true`, then `paused: false` and the
+//! number dropped once an `ack` makes room. A window of 0 needs no acks. A
+//! socket too slow to keep up with the stream itself is sent `lagged`.
+
+use crate::filter::Filter;
+use crate::host::HostMetadata;
+use crate::output::sse::{EventStream, StreamEvent};
+use crate::output::OutputOptions;
+use axum::extract::ws::{Message, WebSocket};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Subscriptions one socket may hold.
+pub const MAX_SUBSCRIPTIONS: usize = 32;
+
+fn default_rate() -> f64 {
+    1.0
+}
+
+fn default_window() -> u64 {
+    1000
+}
+
+fn default_limit() -> usize {
+    100
+}
+
+/// A command from the client.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Subscribe {
+        id: String,
+        /// Filter expression, see `filter::Filter`; every event if unset.
+        filter: Option<String>,
+        /// Fraction of matching events to send, from 0 to 1.
+        #[serde(default = "default_rate")]
+        sample: f64,
+        /// Unacknowledged events allowed before backpressure; 0 for no limit.
+        #[serde(default = "default_window")]
+        window: u64,
+    },
+    Unsubscribe {
+        id: String,
+    },
+    Pause {
+        id: Option<String>,
+    },
+    Resume {
+        id: Option<String>,
+    },
+    Sample {
+        id: String,
+        rate: f64,
+    },
+    /// Sends up to `limit` of the most recent kept events matching the filter.
+    History {
+        id: String,
+        #[serde(default = "default_limit")]
+        limit: usize,
+    },
+    /// Acknowledges every event of the subscription up to and including `seq`.
+    Ack {
+        id: String,
+        seq: u64,
+    },
+}
+
+/// A message to the client.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Subscribed {
+        id: String,
+    },
+    Unsubscribed {
+        id: String,
+    },
+    Paused {
+        ids: Vec<String>,
+    },
+    Resumed {
+        ids: Vec<String>,
+    },
+    Sampling {
+        id: String,
+        rate: f64,
+    },
+    Event {
+        id: String,
+        seq: u64,
+        /// Sent in answer to `history` rather than live.
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        history: bool,
+        event: serde_json::Value,
+    },
+    HistoryEnd {
+        id: String,
+        count: usize,
+    },
+    /// The subscription's window filled up (`paused`), or an ack made room again
+    /// after `dropped` events were left out.
+    Backpressure {
+        id: String,
+        paused: bool,
+        unacked: u64,
+        dropped: u64,
+    },
+    /// The socket fell behind the stream and this many events were skipped.
+    Lagged {
+        skipped: u64,
+    },
+    Error {
+        id: Option<String>,
+        message: String,
+    },
+}
+
+struct Subscription {
+    filter: Option<Filter>,
+    rate: f64,
+    /// Matching events seen, for sampling.
+    seen: u64,
+    paused: bool,
+    window: u64,
+    /// Sequence numbers sent and not yet acknowledged, oldest first.
+    unacked: VecDeque<u64>,
+    /// Whether the window is full, and the events dropped since it filled.
+    blocked: bool,
+    dropped: u64,
+}
+
+impl Subscription {
+    /// Whether the `seen`-th matching event is in the sample: exactly `rate` of
+    /// them are, spread evenly.
+    fn sampled(&mut self) -> bool {
+        let before = (self.seen as f64 * self.rate).floor();
+        self.seen += 1;
+        (self.seen as f64 * self.rate).floor() > before
+    }
+}
+
+/// The state of one socket's subscriptions, independent of the transport.
+pub struct Session {
+    options: OutputOptions,
+    host: Arc<HostMetadata>,
+    subscriptions: BTreeMap<String, Subscription>,
+}
+
+impl Session {
+    pub fn new(options: OutputOptions, host: Arc<HostMetadata>) -> Self {
+        Self { options, host, subscriptions: BTreeMap::new() }
+    }
+
+    /// Answers a command; `stream` provides events for `history`.
+    pub fn handle(&mut self, text: &str, stream: &EventStream) -> Vec<ServerMessage> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(e) => return vec![error(None, format!("Invalid message: {}", e))],
+        };
+        match message {
+            ClientMessage::Subscribe { id, filter, sample, window } => {
+                if self.subscriptions.contains_key(&id) {
+                    return vec![error(Some(id), "Already subscribed".to_string())];
+                }
+                if self.subscriptions.len() >= MAX_SUBSCRIPTIONS {
+                    return vec![error(Some(id), format!("At most {} subscriptions per socket", MAX_SUBSCRIPTIONS))];
+                }
+                if let Err(message) = check_rate(sample) {
+                    return vec![error(Some(id), message)];
+                }
+                let filter = match filter.as_deref().map(Filter::parse).transpose() {
+                    Ok(filter) => filter,
+                    Err(e) => return vec![error(Some(id), format!("{:#}", e))],
+                };
+                let subscription = Subscription {
+                    filter,
+                    rate: sample,
+                    seen: 0,
+                    paused: false,
+                    window,
+                    unacked: VecDeque::new(),
+                    blocked: false,
+                    dropped: 0,
+                };
+                self.subscriptions.insert(id.clone(), subscription);
+                vec![ServerMessage::Subscribed { id }]
+            }
+            ClientMessage::Unsubscribe { id } => match self.subscriptions.remove(&id) {
+                Some(_) => vec![ServerMessage::Unsubscribed { id }],
+                None => vec![not_subscribed(id)],
+            },
+            ClientMessage::Pause { id } => match self.set_paused(id, true) {
+                Ok(ids) => vec![ServerMessage::Paused { ids }],
+                Err(message) => vec![message],
+            },
+            ClientMessage::Resume { id } => match self.set_paused(id, false) {
+                Ok(ids) => vec![ServerMessage::Resumed { ids }],
+                Err(message) => vec![message],
+            },
+            ClientMessage::Sample { id, rate } => {
+                if let Err(message) = check_rate(rate) {
+                    return vec![error(Some(id), message)];
+                }
+                let Some(subscription) = self.subscriptions.get_mut(&id) else {
+                    return vec![not_subscribed(id)];
+                };
+                subscription.rate = rate;
+                subscription.seen = 0;
+                vec![ServerMessage::Sampling { id, rate }]
+            }
+            ClientMessage::History { id, limit } => {
+                let Some(subscription) = self.subscriptions.get(&id) else {
+                    return vec![not_subscribed(id)];
+                };
+                let matching: Vec<StreamEvent> = stream
+                    .recent()
+                    .into_iter()
+                    .filter(|e| subscription.filter.as_ref().is_none_or(|f| f.matches(&e.event)))
+                    .collect();
+                let skip = matching.len().saturating_sub(limit);
+                let mut replies: Vec<ServerMessage> = matching[skip..]
+                    .iter()
+                    .map(|e| ServerMessage::Event {
+                        id: id.clone(),
+                        seq: e.id,
+                        history: true,
+                        event: self.options.render(&e.event, &self.host),
+                    })
+                    .collect();
+                replies.push(ServerMessage::HistoryEnd { id, count: replies.len() });
+                replies
+            }
+            ClientMessage::Ack { id, seq } => {
+                let Some(subscription) = self.subscriptions.get_mut(&id) else {
+                    return vec![not_subscribed(id)];
+                };
+                while subscription.unacked.front().is_some_and(|s| *s <= seq) {
+                    subscription.unacked.pop_front();
+                }
+                let unacked = subscription.unacked.len() as u64;
+                if !subscription.blocked || unacked >= subscription.window {
+                    return Vec::new();
+                }
+                subscription.blocked = false;
+                let dropped = std::mem::take(&mut subscription.dropped);
+                vec![ServerMessage::Backpressure { id, paused: false, unacked, dropped }]
+            }
+        }
+    }
+
+    /// What each subscription is sent for a live event.
+    pub fn deliver(&mut self, event: &StreamEvent) -> Vec<ServerMessage> {
+        let mut messages = Vec::new();
+        let mut rendered = None;
+        for (id, subscription) in &mut self.subscriptions {
+            if subscription.paused || subscription.filter.as_ref().is_some_and(|f| !f.matches(&event.event)) {
+                continue;
+            }
+            if !subscription.sampled() {
+                continue;
+            }
+            let unacked = subscription.unacked.len() as u64;
+            if subscription.window > 0 && unacked >= subscription.window {
+                subscription.dropped += 1;
+                if !subscription.blocked {
+                    subscription.blocked = true;
+                    messages.push(ServerMessage::Backpressure { id: id.clone(), paused: true, unacked, dropped: 0 });
+                }
+                continue;
+            }
+            if subscription.window > 0 {
+                subscription.unacked.push_back(event.id);
+            }
+            let rendered = rendered.get_or_insert_with(|| self.options.render(&event.event, &self.host));
+            messages.push(ServerMessage::Event { id: id.clone(), seq: event.id, history: false, event: rendered.clone() });
+        }
+        messages
+    }
+
+    /// Pauses or resumes subscription `id`, or all of them; returns which.
+    fn set_paused(&mut self, id: Option<String>, paused: bool) -> Result<Vec<String>, ServerMessage> {
+        match id {
+            Some(id) => match self.subscriptions.get_mut(&id) {
+                Some(subscription) => {
+                    subscription.paused = paused;
+                    Ok(vec![id])
+                }
+                None => Err(not_subscribed(id)),
+            },
+            None => Ok(self
+                .subscriptions
+                .iter_mut()
+                .map(|(id, subscription)| {
+                    subscription.paused = paused;
+                    id.clone()
+                })
+                .collect()),
+        }
+    }
+}
+
+fn error(id: Option<String>, message: String) -> ServerMessage {
+    ServerMessage::Error { id, message }
+}
+
+fn not_subscribed(id: String) -> ServerMessage {
+    error(Some(id), "No such subscription".to_string())
+}
+
+fn check_rate(rate: f64) -> Result<(), String> {
+    if (0.0..=1.0).contains(&rate) {
+        Ok(())
+    } else {
+        Err(format!("Sampling rate must be between 0 and 1, not {}", rate))
+    }
+}
+
+/// Runs the protocol on `socket` until either side closes it.
+pub async fn serve(mut socket: WebSocket, stream: Arc<EventStream>, options: OutputOptions, host: Arc<HostMetadata>) {
+    let mut session = Session::new(options, host);
+    let mut live = stream.subscribe(None).live;
+    loop {
+        let messages = tokio::select! {
+            received = socket.recv() => match received {
+                Some(Ok(Message::Text(text))) => session.handle(&text, &stream),
+                Some(Ok(Message::Binary(_))) => vec![error(None, "Only text messages are accepted".to_string())],
+                Some(Ok(Message::Ping(_) | Message::Pong(_))) => continue,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => return,
+            },
+            event = live.recv() => match event {
+                Ok(event) => session.deliver(&event),
+                Err(RecvError::Lagged(skipped)) => vec![ServerMessage::Lagged { skipped }],
+                Err(RecvError::Closed) => return,
+            },
+        };
+        for message in messages {
+            let text = serde_json::to_string(&message).expect("server messages serialize");
+            if socket.send(Message::Text(text.into())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,270 @@
+use audit_collector::auth::{self, hash_token, required_role, AuthConfig, Role, TokenStore};
+use audit_collector::config::AppConfig;
+use axum::http::Method;
+use axum::routing::{any, get};
+use axum::Router;
+use reqwest::{Client, StatusCode};
+use std::fs;
+use std::net::SocketAddr;
+use std::path::Path;
+
+const VIEWER: &str = "viewer-token";
+const OPERATOR: &str = "operator-token";
+const ADMIN: &str = "admin-token";
+
+/// Every route the server has, with stand-in handlers.
+fn api() -> Router {
+    Router::new()
+        .route("/api/config", get(|| async { "config" }).post(|| async { "updated" }))
+        .route("/api/events", get(|| async { "stream" }))
+        .route("/api/events/clients", get(|| async { "clients" }))
+        .route("/api/events/{id}", get(|| async { "event" }))
+        .route("/api/ws", get(|| async { "socket" }))
+        .route("/api/stats", get(|| async { "stats" }))
+        .route("/api/outputs", get(|| async { "outputs" }))
+        .route("/api/host", get(|| async { "host" }))
+        .route("/api/dead-letters", get(|| async { "dead letters" }))
+        .route("/api/parse/test", any(|| async { "parsed" }))
+        .fallback(|| async { "ui" })
+}
+
+fn write_tokens(path: &Path) {
+    let mut text = String::new();
+    for (name, role, token) in [("ui", "viewer", VIEWER), ("ops", "operator", OPERATOR), ("root", "admin", ADMIN)] {
+        text += &format!("[[tokens]]\nname = \"{}\"\nrole = \"{}\"\nsha256 = \"{}\"\n\n", name, role, hash_token(token));
+    }
+    fs::write(path, text).unwrap();
+}
+
+async fn serve(config: &AuthConfig) -> SocketAddr {
+    let app = auth::protect(api(), config).unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    addr
+}
+
+async fn serve_with_tokens(dir: &Path, origins: &[&str]) -> SocketAddr {
+    let tokens_file = dir.join("tokens.toml");
+    write_tokens(&tokens_file);
+    let config = AuthConfig {
+        tokens_file: Some(tokens_file),
+        cors_origins: origins.iter().map(|o| o.to_string()).collect(),
+        ..AuthConfig::default()
+    };
+    serve(&config).await
+}
+
+async fn call(addr: SocketAddr, method: Method, path: &str, token: Option<&str>) -> StatusCode {
+    let mut request = Client::new().request(method.clone(), format!("http://{}{}", addr, path));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    if method == Method::POST && path == "/api/tokens" {
+        let name = format!("t{}", fastrand::u32(..));
+        request = request.json(&serde_json::json!({ "name": name, "role": "viewer" }));
+    }
+    request.send().await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_every_route_requires_its_role() {
+    let dir = tempfile::tempdir().unwrap();
+    let addr = serve_with_tokens(dir.path(), &[]).await;
+    let routes: &[(Method, &str, Option<Role>)] = &[
+        (Method::GET, "/", None),
+        (Method::GET, "/index.html", None),
+        (Method::GET, "/api/config", Some(Role::Viewer)),
+        (Method::POST, "/api/config", Some(Role::Operator)),
+        (Method::GET, "/api/events", Some(Role::Viewer)),
+        (Method::GET, "/api/events/clients", Some(Role::Viewer)),
+        (Method::GET, "/api/events/0123abcd", Some(Role::Viewer)),
+        (Method::GET, "/api/ws", Some(Role::Viewer)),
+        (Method::GET, "/api/stats", Some(Role::Viewer)),
+        (Method::GET, "/api/outputs", Some(Role::Viewer)),
+        (Method::GET, "/api/host", Some(Role::Viewer)),
+        (Method::GET, "/api/dead-letters", Some(Role::Viewer)),
+        (Method::POST, "/api/parse/test", Some(Role::Operator)),
+        (Method::GET, "/api/unknown", Some(Role::Viewer)),
+        (Method::GET, "/api/tokens", Some(Role::Admin)),
+        (Method::POST, "/api/tokens", Some(Role::Admin)),
+        (Method::DELETE, "/api/tokens/nobody", Some(Role::Admin)),
+    ];
+    let callers = [(None, None), (Some("wrong-token"), None), (Some(VIEWER), Some(Role::Viewer)), (Some(OPERATOR), Some(Role::Operator)), (Some(ADMIN), Some(Role::Admin))];
+
+    for (method, path, needed) in routes {
+        assert_eq!(required_role(method, path), *needed, "{} {}", method, path);
+        for (token, role) in callers {
+            let status = call(addr, method.clone(), path, token).await;
+            let expected = match (needed, role) {
+                (None, _) => "allowed",
+                (Some(_), None) => "unauthorized",
+                (Some(needed), Some(role)) if role < *needed => "forbidden",
+                _ => "allowed",
+            };
+            let actual = match status {
+                StatusCode::UNAUTHORIZED => "unauthorized",
+                StatusCode::FORBIDDEN => "forbidden",
+                _ => "allowed",
+            };
+            assert_eq!(actual, expected, "{} {} as {:?}: {}", method, path, role, status);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_how_tokens_are_presented() {
+    let dir = tempfile::tempdir().unwrap();
+    let addr = serve_with_tokens(dir.path(), &[]).await;
+    let client = Client::new();
+    let get = |path: &str| client.get(format!("http://{}{}", addr, path));
+
+    let response = get("/api/stats").send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+    let status = |r: reqwest::Response| r.status();
+    assert_eq!(status(get("/api/stats").header("Authorization", "bearer viewer-token").send().await.unwrap()), StatusCode::OK);
+    assert_eq!(status(get("/api/stats").header("Authorization", "Basic dmlld2VyLXRva2Vu").send().await.unwrap()), StatusCode::UNAUTHORIZED);
+    assert_eq!(status(get("/api/stats").header("Authorization", "Bearer").send().await.unwrap()), StatusCode::UNAUTHORIZED);
+
+    // Streams take the token as a query parameter, other endpoints do not.
+    for path in ["/api/events", "/api/dead-letters", "/api/ws"] {
+        let url = format!("{}?format=ocsf&access_token={}", path, VIEWER);
+        assert_eq!(status(get(&url).send().await.unwrap()), StatusCode::OK, "{}", path);
+    }
+    assert_eq!(status(get("/api/events?access_token=nope").send().await.unwrap()), StatusCode::UNAUTHORIZED);
+    let url = format!("/api/stats?access_token={}", VIEWER);
+    assert_eq!(status(get(&url).send().await.unwrap()), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_admins_issue_and_revoke_tokens() {
+    let dir = tempfile::tempdir().unwrap();
+    let addr = serve_with_tokens(dir.path(), &[]).await;
+    let client = Client::new();
+    let url = |path: &str| format!("http://{}{}", addr, path);
+
+    let response = client
+        .post(url("/api/tokens"))
+        .bearer_auth(ADMIN)
+        .json(&serde_json::json!({ "name": "dashboard", "role": "operator" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let issued: serde_json::Value = response.json().await.unwrap();
+    let token = issued["token"].as_str().unwrap().to_string();
+    assert_eq!((issued["name"].as_str(), issued["role"].as_str()), (Some("dashboard"), Some("operator")));
+    assert_eq!(token.len(), 64);
+
+    // Only the hash is written down.
+    let file = fs::read_to_string(dir.path().join("tokens.toml")).unwrap();
+    assert!(file.contains(&hash_token(&token)) && !file.contains(&token), "{}", file);
+    assert_eq!(call(addr, Method::POST, "/api/config", Some(&token)).await, StatusCode::OK);
+
+    let listed: Vec<serde_json::Value> = client.get(url("/api/tokens")).bearer_auth(ADMIN).send().await.unwrap().json().await.unwrap();
+    let names: Vec<&str> = listed.iter().filter_map(|t| t["name"].as_str()).collect();
+    assert_eq!(names, vec!["ui", "ops", "root", "dashboard"]);
+
+    let duplicate = client.post(url("/api/tokens")).bearer_auth(ADMIN).json(&serde_json::json!({ "name": "ui", "role": "admin" }));
+    assert_eq!(duplicate.send().await.unwrap().status(), StatusCode::CONFLICT);
+    let invalid = client.post(url("/api/tokens")).bearer_auth(ADMIN).json(&serde_json::json!({ "name": "a b", "role": "viewer" }));
+    assert_eq!(invalid.send().await.unwrap().status(), StatusCode::BAD_REQUEST);
+    // Failing to save is the server's fault, not a conflict.
+    fs::create_dir(dir.path().join("tokens.partial")).unwrap();
+    let unsaved = client.post(url("/api/tokens")).bearer_auth(ADMIN).json(&serde_json::json!({ "name": "ci", "role": "viewer" }));
+    assert_eq!(unsaved.send().await.unwrap().status(), StatusCode::INTERNAL_SERVER_ERROR);
+    fs::remove_dir(dir.path().join("tokens.partial")).unwrap();
+
+    let revoked = client.delete(url("/api/tokens/dashboard")).bearer_auth(ADMIN).send().await.unwrap();
+    assert_eq!(revoked.status(), StatusCode::NO_CONTENT);
+    assert_eq!(call(addr, Method::GET, "/api/stats", Some(&token)).await, StatusCode::UNAUTHORIZED);
+    let last_admin = client.delete(url("/api/tokens/root")).bearer_auth(ADMIN).send().await.unwrap();
+    assert_eq!(last_admin.status(), StatusCode::CONFLICT);
+
+    // Changes survive a restart.
+    let store = TokenStore::load(&dir.path().join("tokens.toml")).unwrap();
+    assert_eq!(store.list().len(), 3);
+    assert_eq!(store.authenticate(ADMIN), Some(("root".to_string(), Role::Admin)));
+}
+
+#[tokio::test]
+async fn test_cors_only_for_configured_origins() {
+    let dir = tempfile::tempdir().unwrap();
+    let addr = serve_with_tokens(dir.path(), &["https://soc.example.com"]).await;
+    let client = Client::new();
+
+    let allowed = client
+        .get(format!("http://{}/api/stats", addr))
+        .bearer_auth(VIEWER)
+        .header("Origin", "https://soc.example.com")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(allowed.headers()["access-control-allow-origin"], "https://soc.example.com");
+    let other = client
+        .get(format!("http://{}/api/stats", addr))
+        .bearer_auth(VIEWER)
+        .header("Origin", "https://evil.example.net")
+        .send()
+        .await
+        .unwrap();
+    assert!(other.headers().get("access-control-allow-origin").is_none());
+
+    // Preflight requests carry no token and are answered before the check.
+    let preflight = client
+        .request(Method::OPTIONS, format!("http://{}/api/config", addr))
+        .header("Origin", "https://soc.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "authorization,content-type")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(preflight.status(), StatusCode::OK);
+    assert_eq!(preflight.headers()["access-control-allow-origin"], "https://soc.example.com");
+}
+
+#[tokio::test]
+async fn test_without_tokens_file_only_loopback_is_served() {
+    let open = AuthConfig::default();
+    assert_eq!(auth::listen_address(&open), "127.0.0.1:9357".parse().unwrap());
+    let allowed = AuthConfig { allow_unauthenticated: true, ..AuthConfig::default() };
+    assert_eq!(auth::listen_address(&allowed), "0.0.0.0:9357".parse().unwrap());
+    let protected = AuthConfig { tokens_file: Some("/etc/audit_collector/tokens.toml".into()), ..AuthConfig::default() };
+    assert_eq!(auth::listen_address(&protected), "0.0.0.0:9357".parse().unwrap());
+
+    // What loopback gets is the API without token checks or token management.
+    let addr = serve(&open).await;
+    assert_eq!(call(addr, Method::POST, "/api/config", None).await, StatusCode::OK);
+    let body = Client::new().get(format!("http://{}/api/tokens", addr)).send().await.unwrap().text().await.unwrap();
+    assert_eq!(body, "ui");
+}
+
+#[test]
+fn test_auth_config() {
+    let config = AppConfig::parse(
+        "[auth]\ntokens_file = \"/etc/audit_collector/tokens.toml\"\ncors_origins = [\"https://soc.example.com\"]\n",
+    )
+    .unwrap();
+    assert_eq!(config.auth.cors_origins, vec!["https://soc.example.com"]);
+    assert!(!config.auth.allow_unauthenticated);
+    assert!(AppConfig::parse("[auth]\nallow_unauthenticated = true\n").unwrap().auth.allow_unauthenticated);
+    assert!(auth::cors_layer(&["*".to_string()]).is_err());
+    assert!(auth::cors_layer(&["bad\norigin".to_string()]).is_err());
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tokens.toml");
+    assert!(TokenStore::load(&path).is_err());
+    fs::write(&path, "[[tokens]]\nname = \"x\"\nrole = \"viewer\"\nsha256 = \"abc\"\n").unwrap();
+    assert!(TokenStore::load(&path).is_err());
+    let hash = hash_token("t");
+    fs::write(&path, format!("[[tokens]]\nname = \"x\"\nrole = \"owner\"\nsha256 = \"{}\"\n", hash)).unwrap();
+    assert!(TokenStore::load(&path).is_err());
+    let entry = format!("[[tokens]]\nname = \"x\"\nrole = \"viewer\"\nsha256 = \"{}\"\n", hash.to_uppercase());
+    fs::write(&path, entry.repeat(2)).unwrap();
+    assert!(TokenStore::load(&path).is_err());
+    fs::write(&path, entry).unwrap();
+    assert_eq!(TokenStore::load(&path).unwrap().authenticate("t"), Some(("x".to_string(), Role::Viewer)));
+    assert_eq!(TokenStore::load(&path).unwrap().authenticate("T"), None);
+}
@@ -0,0 +1,101 @@
+use audit_collector::authlog;
+use audit_collector::collector::Collector;
+use audit_collector::model::FieldValue;
+use audit_collector::source::MockAuditSource;
+use crossbeam_channel::unbounded;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const FIXTURE: &str = include_str!("fixtures/auth.log");
+
+/// Expected fields for each line of `fixtures/auth.log`, in order.
+/// `None` means the message is not an authentication event and must not match.
+fn expectations() -> Vec<Option<Vec<(&'static str, &'static str)>>> {
+    vec![
+        Some(vec![("action", "ssh_login"), ("outcome", "success"), ("user", "alice"), ("source_ip", "10.0.0.5"), ("source_port", "51234"), ("auth_method", "password")]),
+        Some(vec![("action", "ssh_login"), ("outcome", "success"), ("user", "deploy"), ("auth_method", "publickey"), ("key_type", "RSA"), ("key_fingerprint", "SHA256:Yx0rX2mR6FqT4xwz1")]),
+        Some(vec![("action", "ssh_login"), ("outcome", "failure"), ("user", "root"), ("source_ip", "203.0.113.9"), ("source_port", "60111")]),
+        Some(vec![("action", "ssh_invalid_user"), ("outcome", "failure"), ("user", "admin"), ("source_port", "60112")]),
+        Some(vec![("action", "ssh_invalid_user"), ("outcome", "failure"), ("user", "oracle"), ("source_ip", "198.51.100.7")]),
+        Some(vec![("action", "session_open"), ("user", "alice"), ("uid", "1000"), ("pam_service", "sshd")]),
+        Some(vec![("action", "session_close"), ("user", "alice"), ("pam_service", "sshd")]),
+        Some(vec![("action", "sudo"), ("outcome", "success"), ("user", "alice"), ("tty", "pts/0"), ("target_user", "root"), ("command", "/usr/bin/apt update"), ("cwd", "/home/alice")]),
+        Some(vec![("action", "sudo"), ("outcome", "failure"), ("user", "bob"), ("reason", "3 incorrect password attempts"), ("command", "/bin/cat /etc/shadow")]),
+        Some(vec![("action", "session_open"), ("user", "root"), ("by", "alice(uid=1000)"), ("pam_service", "sudo")]),
+        Some(vec![("action", "su"), ("outcome", "success"), ("user", "alice"), ("target_user", "root"), ("tty", "pts/0")]),
+        Some(vec![("action", "su"), ("outcome", "failure"), ("user", "bob"), ("tty", "pts/1")]),
+        Some(vec![("action", "user_created"), ("user", "carol"), ("uid", "1002"), ("shell", "/bin/bash"), ("tty", "/dev/pts/0")]),
+        Some(vec![("action", "group_created"), ("group", "carol"), ("gid", "1002")]),
+        Some(vec![("action", "user_group_added"), ("user", "carol"), ("group", "sudo")]),
+        Some(vec![("action", "user_modified"), ("user", "carol"), ("change", "shell from '/bin/bash' to '/bin/zsh'")]),
+        Some(vec![("action", "password_changed"), ("user", "carol"), ("pam_service", "passwd")]),
+        Some(vec![("action", "ssh_login"), ("user", "dave"), ("source_ip", "2001:db8::1"), ("auth_method", "keyboard-interactive/pam")]),
+        Some(vec![("action", "session_open"), ("user", "root"), ("uid", "0"), ("pam_service", "cron")]),
+        None,
+    ]
+}
+
+#[test]
+fn test_fixture_corpus() {
+    let lines: Vec<&str> = FIXTURE.lines().collect();
+    let expected = expectations();
+    assert_eq!(lines.len(), expected.len(), "fixture and expectations out of sync");
+
+    for (line, expected) in lines.iter().zip(expected) {
+        let parsed = authlog::parse_line(line).unwrap_or_else(|| panic!("header not parsed: {}", line));
+        let mut fields = HashMap::new();
+        let matched = authlog::extract(parsed.program, parsed.message, &mut fields);
+
+        match expected {
+            Some(pairs) => {
+                assert!(matched, "no rule matched: {}", line);
+                for (key, value) in pairs {
+                    assert_eq!(fields.get(key).map(ToString::to_string).as_deref(), Some(value), "{} in: {}", key, line);
+                }
+            }
+            None => {
+                assert!(!matched, "unexpected match: {}", line);
+                assert!(fields.is_empty());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_line_header() {
+    let line = authlog::parse_line("Jan  2 03:04:05 web01 sshd[42]: Connection closed").unwrap();
+    assert_eq!(line.hostname, "web01");
+    assert_eq!(line.program, "sshd");
+    assert_eq!(line.pid, Some("42"));
+    assert_eq!(line.message, "Connection closed");
+
+    let line = authlog::parse_line("2024-05-01T10:15:01+02:00 db01 sudo: hello").unwrap();
+    assert_eq!(line.timestamp.to_rfc3339(), "2024-05-01T08:15:01+00:00");
+    assert_eq!(line.pid, None);
+
+    assert!(authlog::parse_line("just some text").is_none());
+}
+
+#[test]
+fn test_collector_parses_auth_log_lines() {
+    let lines: Vec<Vec<u8>> = FIXTURE.lines().take(2).map(|l| l.as_bytes().to_vec()).collect();
+    let source = Arc::new(MockAuditSource::new(lines));
+    let (tx, rx) = unbounded();
+    let collector = Collector::new(source, tx);
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+
+    let event = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event");
+    assert_eq!(event.record_type, 1);
+    assert_eq!(event.fields.get("app_name"), Some(&FieldValue::from("sshd")));
+    assert_eq!(event.fields.get("procid"), Some(&FieldValue::UInt(1201)));
+    assert_eq!(event.fields.get("hostname"), Some(&FieldValue::from("web01")));
+    assert_eq!(event.fields.get("user"), Some(&FieldValue::from("alice")));
+    assert_eq!(event.fields.get("source_ip"), Some(&FieldValue::ip("10.0.0.5")));
+
+    let event = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event");
+    assert_eq!(event.fields.get("auth_method"), Some(&FieldValue::from("publickey")));
+}
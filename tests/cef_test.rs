@@ -0,0 +1,149 @@
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::cef::{to_cef, to_leef, CefConfig};
+use audit_collector::output::{FileSink, SyslogSink};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use std::fs;
+use std::sync::Arc;
+
+fn cef_config(extra: &str) -> CefConfig {
+    let text = format!("[[output.syslog]]\naddress = \"127.0.0.1:514\"\nbody = \"cef\"\n{}", extra);
+    AppConfig::parse(&text).unwrap().output.syslog.remove(0).cef
+}
+
+fn login_event() -> AuditEvent {
+    let mut event = AuditEvent::new(1112, 7);
+    event.id = "abc".to_string();
+    event.timestamp = "2026-01-02T03:04:05Z".parse().unwrap();
+    event.fields.insert("event.module".to_string(), "auditd".into());
+    event.fields.insert("event.action".to_string(), "user-login".into());
+    event.fields.insert("event.outcome".to_string(), "failure".into());
+    event.fields.insert("user.name".to_string(), "alice".into());
+    event.fields.insert("source.ip".to_string(), FieldValue::ip("10.0.0.5"));
+    event.fields.insert("host.name".to_string(), "web-1".into());
+    event.fields.insert("message".to_string(), "bad login user=alice | a\\b\nnext".into());
+    event
+}
+
+fn windows_event(event_id: u16) -> AuditEvent {
+    let mut event = AuditEvent::new(event_id, 1);
+    event.id = "win".to_string();
+    event.timestamp = "2026-01-02T03:04:05Z".parse().unwrap();
+    event.fields.insert("event.module".to_string(), "windows_xml".into());
+    event.fields.insert("host.name".to_string(), "dc01".into());
+    event
+}
+
+#[test]
+fn test_cef_escapes_header_and_extension() {
+    let config = cef_config("cef = { vendor = 'Acme|Corp\\', product = 'Audit', version = '1.0' }");
+    let line = to_cef(&login_event(), &config, &HostMetadata::default());
+    assert_eq!(
+        line,
+        "CEF:0|Acme\\|Corp\\\\|Audit|1.0|USER_LOGIN|user-login|5|rt=1767323045000 dvchost=web-1 externalId=abc \
+         act=user-login outcome=failure suser=alice src=10.0.0.5 msg=bad login user\\=alice | a\\\\b\\nnext"
+    );
+}
+
+#[test]
+fn test_event_classes_by_number_or_type_name() {
+    let config = cef_config(
+        r#"
+        [output.syslog.cef.classes]
+        4625 = { id = "win-logon-failure", name = "An account failed to log on", severity = 8 }
+        USER_LOGIN = { id = "linux-login" }
+        "#,
+    );
+    let host = HostMetadata::default();
+
+    let line = to_cef(&windows_event(4625), &config, &host);
+    assert!(line.contains("|win-logon-failure|An account failed to log on|8|"), "{}", line);
+    let line = to_cef(&login_event(), &config, &host);
+    assert!(line.contains("|linux-login|user-login|5|"), "{}", line);
+    // Unmapped Windows events use the EventID.
+    let line = to_cef(&windows_event(4688), &config, &host);
+    assert!(line.contains("|4688|4688|1|"), "{}", line);
+
+    let bad = cef_config("cef = { classes = { 4625 = { severity = 11 } } }");
+    assert!(bad.validate().is_err());
+}
+
+#[test]
+fn test_leef_2_is_tab_delimited() {
+    let line = to_leef(&login_event(), &CefConfig::default(), &HostMetadata::default());
+    let version = env!("CARGO_PKG_VERSION");
+    let (header, attributes) = line.split_at(line.find("|x09|").unwrap() + 5);
+    assert_eq!(header, format!("LEEF:2.0|audit_collector|audit_collector|{}|USER_LOGIN|x09|", version));
+    let attributes: Vec<&str> = attributes.split('\t').collect();
+    assert_eq!(
+        attributes,
+        vec![
+            "devTime=1767323045000",
+            "sev=5",
+            "identHostName=web-1",
+            "externalId=abc",
+            "action=user-login",
+            "outcome=failure",
+            "usrName=alice",
+            "src=10.0.0.5",
+            "msg=bad login user=alice | a\\\\b\\nnext",
+        ]
+    );
+}
+
+#[test]
+fn test_events_that_were_not_normalized() {
+    let raw = "type=USER_LOGIN msg=audit(1700000000.5:90): pid=812 uid=0 msg='op=login acct=\"alice\" \
+               exe=\"/usr/sbin/sshd\" hostname=? addr=10.0.0.5 terminal=ssh res=failed'";
+    let mut event = ParserRegistry::default()
+        .parser_for(&SourceFormat::Auditd)
+        .unwrap()
+        .parse(raw.as_bytes())
+        .unwrap();
+    event.id = "abc".to_string();
+    assert!(!event.fields.contains_key("event.module"));
+    let host = HostMetadata { hostname: "collector".to_string(), ..Default::default() };
+
+    let line = to_cef(&event, &CefConfig::default(), &host);
+    assert!(line.contains("|USER_LOGIN|"), "{}", line);
+    assert!(line.contains(" dvchost=collector "), "{}", line);
+    for pair in ["act=user_login", "outcome=failure", "suser=alice", "src=10.0.0.5"] {
+        assert!(line.contains(pair), "{} missing from {}", pair, line);
+    }
+
+    let line = to_leef(&event, &CefConfig::default(), &host);
+    for pair in ["identHostName=collector", "action=user_login", "outcome=failure", "usrName=alice", "src=10.0.0.5"] {
+        assert!(line.split('\t').any(|a| a == pair), "{} missing from {}", pair, line);
+    }
+}
+
+#[test]
+fn test_syslog_and_file_outputs_carry_cef_and_leef() {
+    let host = Arc::new(HostMetadata { hostname: "collector".to_string(), ..Default::default() });
+    let text = "[[output.syslog]]\naddress = \"127.0.0.1:514\"\nbody = \"leef\"";
+    let config = AppConfig::parse(text).unwrap().output.syslog.remove(0);
+    let message = SyslogSink::new(config, host.clone()).unwrap().message(&login_event());
+    assert!(message.message.starts_with("LEEF:2.0|"), "{}", message.message);
+    assert!(message.structured_data.is_empty());
+
+    let dir = tempfile::tempdir().unwrap();
+    let text = format!("[[output.file]]\npath = {:?}\nencoding = \"cef\"", dir.path().to_str().unwrap());
+    let config = AppConfig::parse(&text).unwrap().output.file.remove(0);
+    let mut sink = FileSink::new(config, host).unwrap();
+    sink.write(&login_event()).unwrap();
+    sink.write(&windows_event(4624)).unwrap();
+    sink.close().unwrap();
+
+    let names: Vec<String> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names.len(), 1);
+    assert!(names[0].ends_with(".cef"), "{:?}", names);
+    let content = fs::read_to_string(dir.path().join(&names[0])).unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.iter().all(|l| l.starts_with("CEF:0|")));
+    assert!(lines[1].contains("dvchost=dc01"));
+}
@@ -0,0 +1,80 @@
+//! Helpers shared by the integration tests. Each test crate uses only some of
+//! them, hence the `dead_code` allowance.
+#![allow(dead_code)]
+
+use audit_collector::model::AuditEvent;
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A bare event whose ID is derived from `sequence`.
+pub fn event(sequence: u64) -> AuditEvent {
+    let mut event = AuditEvent::new(1300, sequence);
+    event.id = format!("{:032x}", sequence);
+    event
+}
+
+/// Polls until `done` holds, failing the test after five seconds.
+pub fn wait_for(what: &str, mut done: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        assert!(start.elapsed() < Duration::from_secs(5), "expected {}", what);
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// Serves `app` on a background runtime, for sinks that block.
+pub fn serve_app(app: Router) -> SocketAddr {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            axum::serve(listener, app).await.unwrap();
+        })
+    });
+    addr
+}
+
+/// An HTTP endpoint that records what is posted to it.
+#[derive(Default)]
+pub struct Stub {
+    /// Statuses to answer with, in order; 200 once exhausted.
+    pub statuses: Mutex<VecDeque<u16>>,
+    /// Headers and body of every request.
+    pub requests: Mutex<Vec<(HeaderMap, Vec<u8>)>>,
+    /// Bodies of the requests answered with 200.
+    pub accepted: Mutex<Vec<Vec<u8>>>,
+}
+
+impl Stub {
+    /// Waits for `count` requests and returns all of them.
+    pub fn requests(&self, count: usize) -> Vec<(HeaderMap, Vec<u8>)> {
+        wait_for(&format!("{} requests", count), || self.requests.lock().unwrap().len() >= count);
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+async fn receive(State(stub): State<Arc<Stub>>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    stub.requests.lock().unwrap().push((headers, body.to_vec()));
+    let status = stub.statuses.lock().unwrap().pop_front().unwrap_or(200);
+    if status == 200 {
+        stub.accepted.lock().unwrap().push(body.to_vec());
+    }
+    StatusCode::from_u16(status).unwrap()
+}
+
+/// Serves a stub taking POSTs at `path`.
+pub fn serve(path: &str, statuses: &[u16]) -> (SocketAddr, Arc<Stub>) {
+    let stub = Arc::new(Stub { statuses: Mutex::new(statuses.iter().copied().collect()), ..Default::default() });
+    let app = Router::new().route(path, post(receive)).with_state(stub.clone());
+    (serve_app(app), stub)
+}
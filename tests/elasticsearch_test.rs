@@ -0,0 +1,191 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::AuditEvent;
+use audit_collector::output::elasticsearch::{index_pattern, index_template};
+use audit_collector::output::{ElasticsearchSink, ElasticsearchSinkConfig, Sink};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::HeaderMap;
+use axum::routing::{post, put};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+/// Mimics the parts of Elasticsearch the sink uses: `create` conflicts on
+/// existing IDs, plus IDs scripted to fail once (429) or always (400).
+#[derive(Default)]
+struct Cluster {
+    docs: Mutex<HashMap<String, (String, Value)>>,
+    fail_once: Mutex<HashSet<String>>,
+    reject: HashSet<String>,
+    /// IDs in each `_bulk` request, in order.
+    requests: Mutex<Vec<Vec<String>>>,
+    templates: Mutex<Vec<(String, Value)>>,
+    authorization: Mutex<Option<String>>,
+}
+
+async fn bulk(State(cluster): State<Arc<Cluster>>, headers: HeaderMap, body: Bytes) -> Json<Value> {
+    *cluster.authorization.lock().unwrap() =
+        headers.get("authorization").map(|v| v.to_str().unwrap().to_string());
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    let lines: Vec<Value> = text.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+
+    let mut ids = Vec::new();
+    let mut items = Vec::new();
+    for pair in lines.chunks(2) {
+        let action = &pair[0]["create"];
+        let id = action["_id"].as_str().unwrap().to_string();
+        ids.push(id.clone());
+        let mut docs = cluster.docs.lock().unwrap();
+        let (status, error) = if cluster.reject.contains(&id) {
+            (400, Some(json!({ "type": "mapper_parsing_exception" })))
+        } else if cluster.fail_once.lock().unwrap().remove(&id) {
+            (429, Some(json!({ "type": "es_rejected_execution_exception" })))
+        } else if docs.contains_key(&id) {
+            (409, Some(json!({ "type": "version_conflict_engine_exception" })))
+        } else {
+            docs.insert(id.clone(), (action["_index"].as_str().unwrap().to_string(), pair[1].clone()));
+            (201, None)
+        };
+        let mut item = json!({ "_id": id, "status": status });
+        if let Some(error) = error {
+            item["error"] = error;
+        }
+        items.push(json!({ "create": item }));
+    }
+    cluster.requests.lock().unwrap().push(ids);
+    let errors = items.iter().any(|i| i["create"]["status"] != 201);
+    Json(json!({ "took": 1, "errors": errors, "items": items }))
+}
+
+async fn template(State(cluster): State<Arc<Cluster>>, Path(name): Path<String>, Json(body): Json<Value>) -> Json<Value> {
+    cluster.templates.lock().unwrap().push((name, body));
+    Json(json!({ "acknowledged": true }))
+}
+
+fn serve(cluster: Cluster) -> (SocketAddr, Arc<Cluster>) {
+    let cluster = Arc::new(cluster);
+    let app = Router::new()
+        .route("/_bulk", post(bulk))
+        .route("/_index_template/{name}", put(template))
+        .with_state(cluster.clone());
+    (common::serve_app(app), cluster)
+}
+
+fn config(addr: SocketAddr, extra: &str) -> ElasticsearchSinkConfig {
+    let text = format!("[[output.elasticsearch]]\nurl = \"http://{}/\"\n{}", addr, extra);
+    AppConfig::parse(&text).unwrap().output.elasticsearch.remove(0)
+}
+
+fn event(id: &str) -> AuditEvent {
+    let mut event = AuditEvent::new(1300, 1);
+    event.id = id.to_string();
+    event.timestamp = "2026-01-02T03:04:05Z".parse().unwrap();
+    event
+}
+
+#[test]
+fn test_bulk_body_uses_daily_index_and_event_id() {
+    let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+    let sink = ElasticsearchSink::new(config(addr, ""), Arc::new(HostMetadata::default())).unwrap();
+    let body = String::from_utf8(sink.bulk_body(&[event("a1")]).unwrap()).unwrap();
+    let lines: Vec<Value> = body.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+    assert_eq!(lines[0], json!({ "create": { "_index": "audit-2026.01.02", "_id": "a1" } }));
+    assert_eq!(lines[1]["id"], "a1");
+
+    assert!(ElasticsearchSink::new(config(addr, "index = \"audit-%Q\""), Arc::new(HostMetadata::default())).is_err());
+}
+
+#[test]
+fn test_only_failed_items_are_retried() {
+    let (addr, cluster) = serve(Cluster {
+        fail_once: Mutex::new(["b".to_string()].into()),
+        reject: ["c".to_string()].into(),
+        ..Default::default()
+    });
+    let extra = "api_key = \"k3y\"\nretry = { initial_backoff_ms = 1, max_backoff_ms = 5 }";
+    let sink = ElasticsearchSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap();
+    let client = reqwest::blocking::Client::new();
+
+    let dead = sink.deliver(&client, vec![event("a"), event("b"), event("c")]);
+    assert_eq!(dead.iter().map(|e| e.id.as_str()).collect::<Vec<_>>(), vec!["c"]);
+    assert_eq!(*cluster.requests.lock().unwrap(), vec![vec!["a", "b", "c"], vec!["b"]]);
+    assert_eq!(cluster.authorization.lock().unwrap().as_deref(), Some("ApiKey k3y"));
+
+    // Redelivering is idempotent: existing IDs conflict and count as delivered.
+    assert!(sink.deliver(&client, vec![event("a"), event("b")]).is_empty());
+    let docs = cluster.docs.lock().unwrap();
+    assert_eq!(docs.len(), 2);
+    assert_eq!(docs["a"].0, "audit-2026.01.02");
+}
+
+#[test]
+fn test_spawned_sink_installs_template_and_dead_letters_rejects() {
+    let dir = tempfile::tempdir().unwrap();
+    let (addr, cluster) = serve(Cluster { reject: ["bad".to_string()].into(), ..Default::default() });
+    let extra = format!(
+        "username = \"elastic\"\npassword = \"changeme\"\ndead_letter_dir = {:?}\nbatch = {{ max_events = 2 }}",
+        dir.path().to_str().unwrap()
+    );
+    let tx = ElasticsearchSink::new(config(addr, &extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(event("good")).unwrap();
+    tx.send(event("bad")).unwrap();
+
+    common::wait_for("a dead letter", || std::fs::read_dir(dir.path()).map(|d| d.count()).unwrap_or(0) > 0);
+    let templates = cluster.templates.lock().unwrap();
+    assert_eq!(templates.len(), 1);
+    assert_eq!(templates[0].0, "audit");
+    // Normalization is off unless the sink is told otherwise.
+    assert!(templates[0].1["template"]["mappings"]["properties"]["fields"]["properties"]["process"].is_null());
+    assert!(cluster.authorization.lock().unwrap().as_deref().unwrap().starts_with("Basic "));
+    assert!(cluster.docs.lock().unwrap().contains_key("good"));
+}
+
+#[test]
+fn test_index_template_follows_schema() {
+    assert_eq!(index_pattern("audit-%Y.%m.%d"), "audit-*");
+    assert_eq!(index_pattern("audit"), "audit");
+
+    let template = index_template("audit-*", true);
+    assert_eq!(template["index_patterns"], json!(["audit-*"]));
+    let properties = &template["template"]["mappings"]["properties"];
+    assert_eq!(properties["timestamp"]["type"], "date");
+    assert_eq!(properties["sequence"]["type"], "unsigned_long");
+
+    let fields = &properties["fields"]["properties"];
+    assert_eq!(fields["process"]["properties"]["pid"]["type"], "long");
+    assert_eq!(fields["process"]["properties"]["parent"]["properties"]["pid"]["type"], "long");
+    assert_eq!(fields["source"]["properties"]["ip"]["type"], "ip");
+    assert_eq!(fields["host"]["properties"]["os"]["properties"]["name"]["type"], "keyword");
+    assert_eq!(fields["observer"]["properties"]["ip"]["type"], "ip");
+    assert_eq!(fields["message"]["type"], "text");
+    assert_eq!(fields["raw"]["enabled"], false);
+}
+
+#[test]
+fn test_template_without_normalization_accepts_native_fields() {
+    let registry = ParserRegistry::default();
+    let mac = registry
+        .parser_for(&SourceFormat::MacJson)
+        .unwrap()
+        .parse(br#"{"processImagePath":"/usr/libexec/sshd","processID":42,"eventMessage":"hi"}"#)
+        .unwrap();
+    let document = serde_json::to_value(&mac).unwrap();
+    assert!(document["fields"]["process"].is_string());
+
+    // Every native string field must be free of an object mapping.
+    let template = index_template("audit-*", false);
+    let fields = &template["template"]["mappings"]["properties"]["fields"]["properties"];
+    for (name, value) in document["fields"].as_object().unwrap() {
+        assert!(fields[name]["properties"].is_null(), "{} = {} is mapped as an object", name, value);
+    }
+    assert!(fields["raw"].is_null());
+    assert_eq!(fields["host"]["properties"]["name"]["type"], "keyword");
+    assert_eq!(fields["observer"]["properties"]["ip"]["type"], "ip");
+    assert_eq!(fields["agent"]["properties"]["version"]["type"], "keyword");
+}
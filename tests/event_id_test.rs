@@ -0,0 +1,148 @@
+use audit_collector::collector::Collector;
+use audit_collector::model::{AuditEvent, NativeId};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use audit_collector::source::MockAuditSource;
+use audit_collector::store::RecentEvents;
+use crossbeam_channel::unbounded;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const RECORDS: [&str; 3] = [
+    r#"type=SYSCALL msg=audit(1700000000.123:77): syscall=59 pid=10 exe="/usr/bin/cat""#,
+    r#"type=PATH msg=audit(1700000000.123:77): item=0 name="/etc/passwd""#,
+    r#"{"timestamp":"2024-01-02 03:04:05.123456-0800","processImagePath":"/usr/sbin/cfprefsd","eventMessage":"hi"}"#,
+];
+
+fn collect() -> Vec<AuditEvent> {
+    let data = RECORDS.iter().map(|r| r.as_bytes().to_vec()).collect();
+    let (tx, rx) = unbounded();
+    let collector = Collector::new(Arc::new(MockAuditSource::new(data)), tx);
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+    (0..RECORDS.len())
+        .map(|_| rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event"))
+        .collect()
+}
+
+#[test]
+fn test_ids_are_stable_and_unique() {
+    let first = collect();
+    let second = collect();
+
+    for (a, b) in first.iter().zip(&second) {
+        assert_eq!(a.id.len(), 32);
+        assert!(a.id.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_eq!(a.id, b.id, "re-reading a record must give the same id");
+    }
+    // SYSCALL and PATH share serial 77 but are different records.
+    assert_ne!(first[0].id, first[1].id);
+    assert_ne!(first[1].id, first[2].id);
+
+    let mut event = first[0].clone();
+    assert_ne!(event.compute_id("other-source", None, RECORDS[0].as_bytes()), event.id);
+    event.sequence += 1;
+    assert_ne!(event.compute_id("auto", None, RECORDS[0].as_bytes()), first[0].id);
+
+    // The same line sent by two hosts.
+    let web1 = event.compute_id("syslog", Some("10.0.0.1".parse().unwrap()), RECORDS[0].as_bytes());
+    let web2 = event.compute_id("syslog", Some("10.0.0.2".parse().unwrap()), RECORDS[0].as_bytes());
+    assert_ne!(web1, web2);
+    assert_ne!(web1, event.compute_id("syslog", None, RECORDS[0].as_bytes()));
+    assert_eq!(web1, event.compute_id("syslog", Some("10.0.0.1".parse().unwrap()), RECORDS[0].as_bytes()));
+}
+
+#[test]
+fn test_repeated_lines_without_time_get_distinct_ids() {
+    let registry = ParserRegistry::default();
+    let cases = [
+        (SourceFormat::Line, "free text without a time"),
+        (SourceFormat::Syslog, "<34>su: 'su root' failed for lonvick on /dev/pts/8"),
+    ];
+    for (format, raw) in cases {
+        let parser = registry.parser_for(&format).unwrap();
+        let first = parser.parse(raw.as_bytes()).unwrap();
+        assert!(first.synthetic_timestamp);
+
+        // Even received within the same instant, two copies are two events.
+        let second = first.clone();
+        let id = first.compute_id(format.name(), None, raw.as_bytes());
+        assert_ne!(id, second.compute_id(format.name(), None, raw.as_bytes()), "{}", raw);
+        assert_eq!(id.len(), 32);
+    }
+
+    // A time taken from the record still counts.
+    let auditd = registry.parser_for(&SourceFormat::Auditd).unwrap().parse(RECORDS[0].as_bytes()).unwrap();
+    assert!(!auditd.synthetic_timestamp);
+    let mut later = auditd.clone();
+    later.timestamp += chrono::Duration::seconds(1);
+    let id = auditd.compute_id("auditd", None, RECORDS[0].as_bytes());
+    assert_eq!(id, auditd.compute_id("auditd", None, RECORDS[0].as_bytes()));
+    assert_ne!(id, later.compute_id("auditd", None, RECORDS[0].as_bytes()));
+}
+
+#[test]
+fn test_native_timestamps_are_used() {
+    let registry = ParserRegistry::default();
+    let auditd = registry.parser_for(&SourceFormat::Auditd).unwrap().parse(RECORDS[0].as_bytes()).unwrap();
+    assert_eq!(auditd.timestamp.to_rfc3339(), "2023-11-14T22:13:20.123+00:00");
+    assert_eq!(auditd.sequence, 77);
+
+    let mac = registry.parser_for(&SourceFormat::MacJson).unwrap().parse(RECORDS[2].as_bytes()).unwrap();
+    assert_eq!(mac.timestamp.to_rfc3339(), "2024-01-02T11:04:05.123456+00:00");
+}
+
+#[test]
+fn test_recent_events_lookup_and_eviction() {
+    let mut recent = RecentEvents::new(2);
+    for (i, id) in ["a", "b", "c"].iter().enumerate() {
+        let mut event = AuditEvent::new(1, i as u64);
+        event.id = id.to_string();
+        recent.push(event);
+    }
+    assert_eq!(recent.len(), 2);
+    assert!(recent.get("a").is_none());
+    assert_eq!(recent.get("c").unwrap().sequence, 2);
+
+    // Same id again replaces in place; no id is not stored.
+    let mut again = AuditEvent::new(5, 9);
+    again.id = "b".to_string();
+    recent.push(again);
+    recent.push(AuditEvent::new(1, 0));
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent.get("b").unwrap().sequence, 9);
+}
+
+#[test]
+fn test_native_ids_are_64_bit_and_strict() {
+    let registry = ParserRegistry::default();
+    let parse = |format: SourceFormat, raw: &str| registry.parser_for(&format).unwrap().parse(raw.as_bytes());
+
+    let big = parse(SourceFormat::Auditd, "type=SYSCALL msg=audit(1700000000.123:18446744073709551615): pid=1").unwrap();
+    assert_eq!(big.sequence, u64::MAX);
+    assert_eq!(big.native_id, Some(NativeId::AuditSerial(u64::MAX)));
+    for bad in ["18446744073709551616", "-1", "abc", ""] {
+        let raw = format!("type=SYSCALL msg=audit(1700000000.123:{}): pid=1", bad);
+        let err = parse(SourceFormat::Auditd, &raw).unwrap_err();
+        assert!(err.to_string().contains("Invalid audit serial"), "{}", err);
+    }
+    assert!(parse(SourceFormat::Auditd, "type=SYSCALL pid=1").is_err());
+
+    let windows = r#"<Event><System><EventID>4688</EventID><EventRecordID>5000000000</EventRecordID></System></Event>"#;
+    let event = parse(SourceFormat::WindowsXml, windows).unwrap();
+    assert_eq!(event.sequence, 5_000_000_000);
+    assert_eq!(event.native_id, Some(NativeId::WindowsRecordId(5_000_000_000)));
+    assert!(parse(SourceFormat::WindowsXml, &windows.replace("5000000000", "5e9")).is_err());
+
+    let mac = parse(SourceFormat::MacJson, r#"{"traceID":9223372036854775809,"eventMessage":"hi"}"#).unwrap();
+    assert_eq!(mac.native_id, Some(NativeId::MacTraceId(9_223_372_036_854_775_809)));
+
+    let journal = parse(SourceFormat::Journald, r#"{"__CURSOR":"s=abc;i=7","__SEQNUM":"7","MESSAGE":"hi"}"#).unwrap();
+    assert_eq!(journal.sequence, 7);
+    assert_eq!(journal.native_id, Some(NativeId::JournalCursor("s=abc;i=7".to_string())));
+
+    let json = serde_json::to_value(&journal).unwrap();
+    assert_eq!(json["native_id"], serde_json::json!({"kind": "journal_cursor", "value": "s=abc;i=7"}));
+    assert!(serde_json::to_value(AuditEvent::new(1, 0)).unwrap().get("native_id").is_none());
+}
@@ -0,0 +1,239 @@
+use audit_collector::collector::Collector;
+use audit_collector::config::AppConfig;
+use audit_collector::extract::{self, ExtractRuleConfig, ExtractionRules, ParseTestRequest};
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use audit_collector::source::{FileSource, FileSourceConfig};
+use crossbeam_channel::unbounded;
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn rule(name: &str, grok: &str) -> ExtractRuleConfig {
+    ExtractRuleConfig {
+        name: name.to_string(),
+        grok: Some(grok.to_string()),
+        regex: None,
+        field: "message".to_string(),
+        source: None,
+        when: None,
+        types: HashMap::new(),
+    }
+}
+
+fn event(fields: &[(&str, &str)]) -> AuditEvent {
+    let mut event = AuditEvent::new(1, 0);
+    for (k, v) in fields {
+        event.fields.insert(k.to_string(), (*v).into());
+    }
+    event
+}
+
+fn field<'a>(event: &'a AuditEvent, key: &str) -> Option<&'a FieldValue> {
+    event.fields.get(key)
+}
+
+#[test]
+fn test_grok_extraction_with_types() {
+    let rules = ExtractionRules::compile(
+        &[rule("login", "%{IP:src} %{WORD:user} port=%{INT:port:int} ok=%{WORD:ok}")],
+        &HashMap::new(),
+    )
+    .unwrap();
+    let mut rules_config = rule("typed", "ok=%{WORD:ok}");
+    rules_config.types.insert("ok".to_string(), extract::FieldType::Bool);
+    let typed = ExtractionRules::compile(&[rules_config], &HashMap::new()).unwrap();
+
+    let mut e = event(&[("message", "10.0.0.5 alice port=0022 ok=YES")]);
+    assert_eq!(rules.apply("any", &mut e), vec!["login".to_string()]);
+    assert_eq!(field(&e, "src").unwrap(), "10.0.0.5");
+    assert_eq!(field(&e, "user").unwrap(), "alice");
+    assert_eq!(field(&e, "port"), Some(&FieldValue::UInt(22)));
+    assert_eq!(field(&e, "ok").unwrap(), "YES");
+
+    typed.apply("any", &mut e);
+    assert_eq!(field(&e, "ok"), Some(&FieldValue::Bool(true)));
+}
+
+#[test]
+fn test_failed_coercion_keeps_captured_value() {
+    let mut config = ExtractRuleConfig {
+        regex: Some(r"size=(?P<size>\S+)".to_string()),
+        grok: None,
+        ..rule("size", "")
+    };
+    config.types.insert("size".to_string(), extract::FieldType::Int);
+    let rules = ExtractionRules::compile(&[config], &HashMap::new()).unwrap();
+
+    let mut e = event(&[("message", "size=big")]);
+    rules.apply("any", &mut e);
+    assert_eq!(field(&e, "size").unwrap(), "big");
+}
+
+#[test]
+fn test_rules_attach_to_source_and_condition() {
+    let mut by_source = rule("by_source", "user=%{USER:user}");
+    by_source.source = Some("app".to_string());
+    let mut by_condition = rule("by_condition", "code=%{INT:code}");
+    by_condition.when = Some(extract::MatchCondition {
+        field: "app_name".to_string(),
+        equals: Some("nginx".into()),
+        ..Default::default()
+    });
+    let rules = ExtractionRules::compile(&[by_source, by_condition], &HashMap::new()).unwrap();
+
+    let mut e = event(&[("message", "user=bob code=500"), ("app_name", "sshd")]);
+    assert!(rules.apply("syslog", &mut e).is_empty());
+    assert_eq!(field(&e, "user"), None);
+
+    let mut e = event(&[("message", "user=bob code=500"), ("app_name", "nginx")]);
+    assert_eq!(rules.apply("app", &mut e), vec!["by_source".to_string(), "by_condition".to_string()]);
+    assert_eq!(field(&e, "user").unwrap(), "bob");
+    assert_eq!(field(&e, "code").unwrap(), "500");
+}
+
+#[test]
+fn test_conditions_compare_typed_values() {
+    let condition = |json: serde_json::Value| -> extract::MatchCondition { serde_json::from_value(json).unwrap() };
+    let mut gated = rule("gated", "%{WORD:word}");
+    gated.when = Some(condition(serde_json::json!({ "field": "uid", "gte": 1000, "lt": 60000 })));
+    let mut root = rule("root", "%{WORD:root_word}");
+    root.when = Some(condition(serde_json::json!({ "field": "uid", "equals": "0" })));
+    let rules = ExtractionRules::compile(&[gated, root], &HashMap::new()).unwrap();
+
+    let mut e = event(&[("message", "hello")]);
+    e.fields.insert("uid".to_string(), FieldValue::UInt(1000));
+    assert_eq!(rules.apply("any", &mut e), vec!["gated".to_string()]);
+
+    e.fields.insert("uid".to_string(), FieldValue::UInt(0));
+    assert_eq!(rules.apply("any", &mut e), vec!["root".to_string()]);
+
+    // String values are compared numerically against numeric bounds.
+    e.fields.insert("uid".to_string(), "1500".into());
+    assert_eq!(rules.apply("any", &mut e), vec!["gated".to_string()]);
+}
+
+#[test]
+fn test_custom_patterns_and_other_fields() {
+    let patterns = HashMap::from([("TICKET".to_string(), "[A-Z]{3}-%{INT}".to_string())]);
+    let mut config = rule("ticket", "%{TICKET:ticket}");
+    config.field = "subject".to_string();
+    let rules = ExtractionRules::compile(&[config], &patterns).unwrap();
+
+    let mut e = event(&[("subject", "see OPS-1234 now")]);
+    rules.apply("any", &mut e);
+    assert_eq!(field(&e, "ticket").unwrap(), "OPS-1234");
+}
+
+#[test]
+fn test_invalid_rules_are_rejected() {
+    let compile = |config: ExtractRuleConfig, patterns: &[(&str, &str)]| {
+        let patterns = patterns.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        ExtractionRules::compile(&[config], &patterns).map(|_| ()).unwrap_err().to_string()
+    };
+
+    assert!(compile(rule("bad", "%{NOPE:x}"), &[]).contains("Invalid extraction rule 'bad'"));
+    let both = ExtractRuleConfig { regex: Some("(?P<x>.)".to_string()), ..rule("both", "%{WORD:x}") };
+    assert!(ExtractionRules::compile(&[both], &HashMap::new()).is_err());
+    assert!(ExtractionRules::compile(&[rule("nocap", "%{WORD}")], &HashMap::new()).is_err());
+    assert!(ExtractionRules::compile(&[rule("type", "%{WORD:x:date}")], &HashMap::new()).is_err());
+    assert!(ExtractionRules::compile(&[rule("open", "%{WORD:x")], &HashMap::new()).is_err());
+    compile(rule("cycle", "%{A:x}"), &[("A", "%{B}"), ("B", "%{A}")]);
+
+    // Each level references the next twice: 2^15 copies if expanded in full.
+    let names: Vec<String> = (0..16).map(|i| format!("P{}", i)).collect();
+    let mut doubling: HashMap<String, String> =
+        names.windows(2).map(|w| (w[0].clone(), format!("%{{{}}}%{{{}}}", w[1], w[1]))).collect();
+    doubling.insert("P15".to_string(), "[a-z]+".to_string());
+    let err = ExtractionRules::compile(&[rule("bomb", "%{P0:x}")], &doubling).map(|_| ()).unwrap_err();
+    assert!(format!("{:#}", err).contains("expands to more than"), "{:#}", err);
+}
+
+#[test]
+fn test_parse_test_request() {
+    let request: ParseTestRequest = serde_json::from_value(serde_json::json!({
+        "line": "<13>1 - host nginx - - - 10.1.1.1 GET /index.html 200",
+        "format": { "type": "syslog" },
+        "rules": [{
+            "name": "access",
+            "grok": "%{IP:client} %{WORD:method} %{URIPATH:path} %{INT:status:int}",
+            "when": { "field": "app_name", "equals": "nginx" }
+        }]
+    }))
+    .unwrap();
+    let response = extract::test_parse(&request, &ExtractionRules::default(), &ParserRegistry::default()).unwrap();
+    assert_eq!(response.matched_rules, vec!["access".to_string()]);
+    assert_eq!(field(&response.event, "client").unwrap(), "10.1.1.1");
+    assert_eq!(field(&response.event, "path").unwrap(), "/index.html");
+    assert_eq!(field(&response.event, "status"), Some(&FieldValue::UInt(200)));
+
+    let invalid: ParseTestRequest = serde_json::from_value(serde_json::json!({
+        "line": "x",
+        "rules": [{ "name": "broken", "regex": "(?P<x>" }]
+    }))
+    .unwrap();
+    let err = extract::test_parse(&invalid, &ExtractionRules::default(), &ParserRegistry::default()).unwrap_err();
+    assert!(format!("{:#}", err).contains("broken"));
+}
+
+#[test]
+fn test_config_file_rules() {
+    let config = AppConfig::parse(
+        r#"
+        [grok_patterns]
+        REQID = "req-[0-9]+"
+
+        [[file]]
+        path = "/var/log/app.log"
+        name = "app"
+
+        [[extract]]
+        name = "request"
+        grok = "%{REQID:request_id} took %{NUMBER:ms:float}ms"
+        source = "app"
+        "#,
+    )
+    .unwrap();
+    assert_eq!(config.file[0].format, SourceFormat::Line);
+    let rules = ExtractionRules::compile(&config.extract, &config.grok_patterns).unwrap();
+
+    let mut e = event(&[("message", "req-77 took 12.50ms")]);
+    rules.apply("app", &mut e);
+    assert_eq!(field(&e, "request_id").unwrap(), "req-77");
+    assert_eq!(field(&e, "ms"), Some(&FieldValue::Float(12.5)));
+}
+
+#[test]
+fn test_file_source_with_extraction() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "old line before start").unwrap();
+
+    let source = FileSource::new(FileSourceConfig {
+        path: file.path().to_path_buf(),
+        name: Some("app".to_string()),
+        format: SourceFormat::Line,
+        from_beginning: false,
+    })
+    .unwrap();
+    let mut config = rule("user", "user=%{USER:user}");
+    config.source = Some("app".to_string());
+    let rules = Arc::new(ExtractionRules::compile(&[config], &HashMap::new()).unwrap());
+
+    let (tx, rx) = unbounded();
+    let collector = Collector::new(Arc::new(source), tx).with_extraction(rules);
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+
+    write!(file, "login user=carol").unwrap();
+    file.flush().unwrap();
+    thread::sleep(Duration::from_millis(300));
+    writeln!(file, " ok").unwrap();
+    file.flush().unwrap();
+
+    let e = rx.recv_timeout(Duration::from_secs(3)).expect("Failed to receive event");
+    assert_eq!(field(&e, "message").unwrap(), "login user=carol ok");
+    assert_eq!(field(&e, "user").unwrap(), "carol");
+}
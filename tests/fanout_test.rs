@@ -0,0 +1,136 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::filter::{self, Filter};
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::batch::{self, BatchConfig};
+use audit_collector::output::{Fanout, SinkStats};
+use crossbeam_channel::{bounded, unbounded};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+fn event(sequence: u64, fields: &[(&str, FieldValue)]) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.record_type = 1112;
+    event.fields.insert("event.module".to_string(), "auditd".into());
+    for (key, value) in fields {
+        event.fields.insert(key.to_string(), value.clone());
+    }
+    event
+}
+
+#[test]
+fn test_filter_expressions() {
+    let login = event(
+        1,
+        &[
+            ("event.outcome", "failure".into()),
+            ("user.name", "admin-bob".into()),
+            ("process.pid", FieldValue::UInt(4242)),
+            ("uid", "0".into()),
+            ("message", "sudo: bad \"password\"".into()),
+        ],
+    );
+    let matches = |text: &str| Filter::parse(text).unwrap().matches(&login);
+
+    assert!(matches("event.outcome == \"failure\""));
+    assert!(matches("event.outcome == 'failure' and process.pid > 4000 and process.pid <= 4242"));
+    assert!(matches("uid == 0 && record_type >= 1100 && record_type < 1200"));
+    assert!(matches("source == 'auditd' and not event.category"));
+    assert!(matches("user.name matches '^admin-' or missing"));
+    assert!(matches("message contains \"bad \\\"password\\\"\""));
+    assert!(matches("(missing or process.pid != 1) and !(uid > 0)"));
+    assert!(!matches("missing != 'x'"));
+    assert!(!matches("event.outcome == 'success' or process.pid < 100"));
+    // `and` binds tighter than `or`.
+    assert!(matches("uid == 1 and missing or uid == 0"));
+    assert!(!matches("uid == 1 and (missing or uid == 0)"));
+
+    for bad in ["", "user.name ==", "(uid == 0", "uid == 0)", "uid = = 0", "name matches '('", "'x' == name", "a contains 1"] {
+        assert!(Filter::parse(bad).is_err(), "{:?} should not parse", bad);
+    }
+}
+
+#[test]
+fn test_filter_nesting_and_length_are_limited() {
+    let parens = format!("{}uid{}", "(".repeat(2_000), ")".repeat(2_000));
+    assert!(Filter::parse(&parens).unwrap_err().to_string().contains("deeper"));
+    let nots = format!("{}uid", "not ".repeat(1_000));
+    assert!(Filter::parse(&nots).unwrap_err().to_string().contains("deeper"));
+
+    let nested = |depth: usize| format!("{}uid{}", "(not ".repeat(depth / 2), ")".repeat(depth / 2));
+    assert!(Filter::parse(&nested(filter::MAX_DEPTH)).is_ok());
+    assert!(Filter::parse(&nested(filter::MAX_DEPTH + 2)).is_err());
+
+    // Deep enough to overflow a tokio worker's stack, were it parsed.
+    let long = format!("{}uid{}", "(".repeat(10_000), ")".repeat(10_000));
+    assert!(Filter::parse(&long).unwrap_err().to_string().contains("bytes long"));
+}
+
+#[test]
+fn test_slow_and_dead_sinks_do_not_hold_up_others() {
+    let mut fanout = Fanout::default();
+    let (slow_tx, slow_rx) = bounded(2);
+    let (fast_tx, fast_rx) = unbounded();
+    let (dead_tx, dead_rx) = bounded(10);
+    drop(dead_rx);
+    fanout.add("webhook", None, |_| slow_tx).unwrap();
+    fanout.add("file", Some("process.pid > 100"), |_| fast_tx).unwrap();
+    fanout.add("file", None, |_| dead_tx).unwrap();
+
+    for sequence in 0..10 {
+        fanout.send(&event(sequence, &[("process.pid", FieldValue::UInt(sequence * 50))]));
+    }
+
+    let received: Vec<u64> = fast_rx.try_iter().map(|e| e.sequence).collect();
+    assert_eq!(received, vec![3, 4, 5, 6, 7, 8, 9]);
+    assert_eq!(slow_rx.len(), 2);
+
+    let snapshot = fanout.snapshot();
+    let names: Vec<&str> = snapshot.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["webhook[0]", "file[0]", "file[1]"]);
+    assert_eq!((snapshot[0].queued, snapshot[0].dropped), (2, 8));
+    assert_eq!((snapshot[1].dropped, snapshot[1].filter.as_deref()), (0, Some("process.pid > 100")));
+    assert_eq!(snapshot[2].dropped, 10);
+
+    let error = fanout.add("syslog", Some("pid >"), |_| unbounded().0).unwrap_err();
+    assert!(format!("{:#}", error).contains("syslog[0]"), "{:#}", error);
+}
+
+#[test]
+fn test_batching_sinks_report_delivered_and_failed() {
+    let stats = Arc::new(SinkStats::default());
+    let config = BatchConfig { max_events: 3, ..Default::default() };
+    // Every other batch fails one event.
+    let mut flushes = 0;
+    let tx = batch::spawn_batches(config, None, stats.clone(), move |_events| {
+        flushes += 1;
+        if flushes % 2 == 0 { 1 } else { 0 }
+    });
+    for sequence in 0..6 {
+        tx.send(event(sequence, &[])).unwrap();
+    }
+
+    common::wait_for("every batch to be flushed", || stats.delivered.load(Ordering::Relaxed) + stats.failed.load(Ordering::Relaxed) >= 6);
+    assert_eq!(stats.delivered.load(Ordering::Relaxed), 5);
+    assert_eq!(stats.failed.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_outputs_take_filters() {
+    let text = r#"
+        [output.sse]
+        filter = "event.outcome == 'failure'"
+        queue_size = 50
+
+        [[output.file]]
+        path = "/tmp/audit"
+        filter = "source == 'windows_xml'"
+        queue_size = 100
+    "#;
+    let config = AppConfig::parse(text).unwrap();
+    assert_eq!(config.output.sse.filter.as_deref(), Some("event.outcome == 'failure'"));
+    assert_eq!(config.output.sse.queue_size, 50);
+    assert_eq!(config.output.file[0].filter.as_deref(), Some("source == 'windows_xml'"));
+    assert_eq!(AppConfig::default().output.sse.queue_size, 1000);
+}
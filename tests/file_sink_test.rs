@@ -0,0 +1,150 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::AuditEvent;
+use audit_collector::output::file::{Compression, FsyncPolicy};
+use audit_collector::output::{FileSink, FileSinkConfig, Sink};
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn config(dir: &Path, extra: &str) -> FileSinkConfig {
+    let text = format!("[[output.file]]\npath = {:?}\n{}", dir.to_str().unwrap(), extra);
+    AppConfig::parse(&text).unwrap().output.file.remove(0)
+}
+
+fn event(sequence: u64) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.fields.insert("exe".to_string(), "/usr/bin/cat".into());
+    event
+}
+
+/// Closed segment names in `dir`, sorted.
+fn segments(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    names
+}
+
+/// Sequence numbers of the events in a (possibly compressed) segment.
+fn read_sequences(path: &Path) -> Vec<u64> {
+    let file = fs::File::open(path).unwrap();
+    let mut reader: Box<dyn Read> = match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => Box::new(flate2::read::GzDecoder::new(file)),
+        Some("zst") => Box::new(zstd::Decoder::new(file).unwrap()),
+        _ => Box::new(file),
+    };
+    let mut text = String::new();
+    reader.read_to_string(&mut text).unwrap();
+    text.lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["sequence"].as_u64().unwrap())
+        .collect()
+}
+
+#[test]
+fn test_config_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config(dir.path(), "");
+    assert_eq!(config.prefix, "events");
+    assert_eq!(config.compression, Compression::None);
+    assert_eq!(config.fsync, FsyncPolicy::Rotate);
+    assert!(config.max_bytes.is_none() && config.max_files.is_none());
+
+    let text = "[[output.file]]\npath = \"/tmp\"\nrotate = 1";
+    assert!(AppConfig::parse(text).is_err(), "unknown keys are rejected");
+}
+
+#[test]
+fn test_size_rotation_compression_and_retention() {
+    for (compression, extension) in [("gzip", ".jsonl.gz"), ("zstd", ".jsonl.zst"), ("none", ".jsonl")] {
+        let dir = tempfile::tempdir().unwrap();
+        let extra = format!("max_bytes = 400\nmax_files = 3\ncompression = {:?}\nfsync = \"always\"", compression);
+        let mut sink = FileSink::new(config(dir.path(), &extra), Arc::new(HostMetadata::default())).unwrap();
+        for i in 0..20 {
+            sink.write(&event(i)).unwrap();
+        }
+
+        // The active segment is not visible under a final name until closed.
+        let open = segments(dir.path());
+        assert!(open.last().unwrap().ends_with(".jsonl.partial"), "{:?}", open);
+        sink.close().unwrap();
+
+        let names = segments(dir.path());
+        assert_eq!(names.len(), 3, "{:?}", names);
+        assert!(names.iter().all(|n| n.starts_with("events-") && n.ends_with(extension)), "{:?}", names);
+
+        // Retention drops the oldest segments; the remaining ones are contiguous.
+        let sequences: Vec<u64> = names.iter().flat_map(|n| read_sequences(&dir.path().join(n))).collect();
+        assert_eq!(sequences.last(), Some(&19));
+        assert!(sequences.windows(2).all(|w| w[1] == w[0] + 1), "{:?}", sequences);
+        assert!(sequences[0] > 0);
+    }
+}
+
+#[test]
+fn test_time_rotation_while_idle() {
+    let dir = tempfile::tempdir().unwrap();
+    let sink = FileSink::new(config(dir.path(), "rotate_secs = 1"), Arc::new(HostMetadata::default())).unwrap();
+    let tx = sink.spawn();
+    tx.send(event(1)).unwrap();
+
+    thread::sleep(Duration::from_millis(1500));
+    let names = segments(dir.path());
+    assert_eq!(names.len(), 1);
+    assert!(names[0].ends_with(".jsonl"), "{:?}", names);
+    assert_eq!(read_sequences(&dir.path().join(&names[0])), vec![1]);
+}
+
+#[test]
+fn test_crash_recovery_finalizes_partial_segments() {
+    let dir = tempfile::tempdir().unwrap();
+    let line = serde_json::to_string(&event(7)).unwrap();
+    fs::write(dir.path().join("events-000004-20260101T000000Z.jsonl.partial"), format!("{}\n{{\"torn", line)).unwrap();
+    fs::write(dir.path().join("events-000004-20260101T000000Z.jsonl.gz.partial"), b"half").unwrap();
+    fs::write(dir.path().join("unrelated.txt"), b"keep").unwrap();
+
+    let mut sink = FileSink::new(config(dir.path(), "compression = \"gzip\""), Arc::new(HostMetadata::default())).unwrap();
+    assert_eq!(
+        segments(dir.path()),
+        vec!["events-000004-20260101T000000Z.jsonl.gz".to_string(), "unrelated.txt".to_string()]
+    );
+    assert_eq!(read_sequences(&dir.path().join("events-000004-20260101T000000Z.jsonl.gz")), vec![7]);
+
+    // New segments continue after the highest existing sequence.
+    sink.write(&event(8)).unwrap();
+    sink.close().unwrap();
+    assert!(segments(dir.path()).iter().any(|n| n.starts_with("events-000005-")));
+}
+
+#[test]
+fn test_recovery_after_encoding_change_keeps_open_segment() {
+    let dir = tempfile::tempdir().unwrap();
+    let line = serde_json::to_string(&event(3)).unwrap();
+    fs::write(dir.path().join("events-000002-20260101T000000Z.jsonl.partial"), format!("{}\n", line)).unwrap();
+    // Another sink in the same directory whose prefix starts the same way.
+    fs::write(dir.path().join("events2-000009-20260101T000000Z.jsonl.partial"), b"other\n").unwrap();
+    fs::write(dir.path().join("events2-000010-20260101T000000Z.jsonl.gz.partial"), b"half").unwrap();
+
+    let mut sink = FileSink::new(config(dir.path(), "encoding = \"cef\""), Arc::new(HostMetadata::default())).unwrap();
+    assert_eq!(
+        segments(dir.path()),
+        vec![
+            "events-000002-20260101T000000Z.jsonl".to_string(),
+            "events2-000009-20260101T000000Z.jsonl.partial".to_string(),
+            "events2-000010-20260101T000000Z.jsonl.gz.partial".to_string(),
+        ]
+    );
+    assert_eq!(read_sequences(&dir.path().join("events-000002-20260101T000000Z.jsonl")), vec![3]);
+
+    // Sequence numbers ignore the other sink's files.
+    sink.write(&event(4)).unwrap();
+    sink.close().unwrap();
+    assert!(segments(dir.path()).iter().any(|n| n.starts_with("events-000003-") && n.ends_with(".cef")));
+}
@@ -0,0 +1,110 @@
+use audit_collector::config::AppConfig;
+use audit_collector::host::{HostConfig, HostMetadata};
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::{origin_host, OutputFormat, OutputOptions};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+
+fn parse(format: SourceFormat, raw: &str) -> AuditEvent {
+    ParserRegistry::default().parser_for(&format).unwrap().parse(raw.as_bytes()).unwrap()
+}
+
+fn metadata() -> HostMetadata {
+    HostMetadata {
+        hostname: "web01".to_string(),
+        fqdn: Some("web01.example.com".to_string()),
+        os_name: "Ubuntu".to_string(),
+        os_version: Some("24.04".to_string()),
+        kernel: Some("6.8.0-31-generic".to_string()),
+        machine_id: Some("4c4c4544004d".to_string()),
+        boot_id: None,
+        ips: vec!["10.0.0.7".parse().unwrap()],
+        agent_version: "0.1.0".to_string(),
+        tags: vec!["prod".to_string()],
+    }
+}
+
+#[test]
+fn test_collect_uses_config() {
+    let config = AppConfig::parse(
+        r#"
+        [host]
+        hostname = "collector-1"
+        tags = ["prod", "eu-west"]
+        "#,
+    )
+    .unwrap();
+    let host = HostMetadata::collect(&config.host);
+    assert_eq!(host.hostname, "collector-1");
+    assert_eq!(host.tags, vec!["prod".to_string(), "eu-west".to_string()]);
+    assert_eq!(host.agent_version, env!("CARGO_PKG_VERSION"));
+    assert!(!host.os_name.is_empty());
+
+    let detected = HostMetadata::collect(&HostConfig::default());
+    assert!(!detected.hostname.is_empty());
+    assert!(detected.ips.iter().all(|ip| !ip.is_loopback()));
+}
+
+#[test]
+fn test_stamp_local_event() {
+    let mut event = AuditEvent::new(1, 0);
+    metadata().stamp(&mut event);
+
+    assert_eq!(event.fields["host.hostname"], "web01");
+    assert_eq!(event.fields["host.name"], "web01.example.com");
+    assert_eq!(event.fields["host.os.name"], "Ubuntu");
+    assert_eq!(event.fields["host.os.kernel"], "6.8.0-31-generic");
+    assert_eq!(event.fields["host.id"], "4c4c4544004d");
+    assert!(!event.fields.contains_key("host.boot.id"));
+    assert_eq!(event.fields["host.ip"], FieldValue::List(vec![FieldValue::ip("10.0.0.7")]));
+    assert_eq!(event.fields["agent.version"], "0.1.0");
+    assert_eq!(event.fields["tags"], FieldValue::List(vec!["prod".into()]));
+}
+
+#[test]
+fn test_relayed_events_record_observer() {
+    let mut event = AuditEvent::new(1, 0);
+    event.fields.insert("hostname".to_string(), "router".into());
+    event.fields.insert("peer".to_string(), "192.0.2.9:514".into());
+    metadata().stamp(&mut event);
+
+    assert_eq!(event.fields["observer.hostname"], "web01");
+    assert!(!event.fields.contains_key("host.hostname"));
+    assert_eq!(event.fields["hostname"], "router");
+}
+
+#[test]
+fn test_stamping_is_per_output() {
+    let event = AuditEvent::new(1, 0);
+    let host = metadata();
+
+    let lean = OutputOptions::default().render(&event, &host);
+    assert!(lean["fields"].get("host.hostname").is_none());
+
+    let stamped = OutputOptions { format: OutputFormat::Native, host_metadata: true }.render(&event, &host);
+    assert_eq!(stamped["fields"]["host.hostname"], "web01");
+
+    let ocsf = OutputOptions { format: OutputFormat::Ocsf, host_metadata: true }.render(&event, &host);
+    assert_eq!(ocsf["device"]["hostname"], "web01.example.com");
+    assert_eq!(ocsf["device"]["ip"], "10.0.0.7");
+    assert_eq!(ocsf["device"]["os"]["name"], "Ubuntu");
+}
+
+#[test]
+fn test_origin_host_ignores_remote_peers() {
+    let host = metadata();
+
+    // auditd's hostname is the peer of a login, not the machine that logged it.
+    let login = parse(
+        SourceFormat::Auditd,
+        "type=USER_LOGIN msg=audit(1700000000.5:90): pid=812 uid=0 msg='op=login acct=\"alice\" hostname=attacker.example addr=10.0.0.5 res=failed'",
+    );
+    assert_eq!(login.fields["hostname"], "attacker.example");
+    assert_eq!(origin_host(&login, &host), "web01.example.com");
+
+    let journal = parse(SourceFormat::Journald, r#"{"MESSAGE":"hi","_HOSTNAME":"db02","__CURSOR":"s=1"}"#);
+    assert_eq!(origin_host(&journal, &host), "db02");
+
+    let mut normalized = login.clone();
+    normalized.fields.insert("host.name".to_string(), "node7".into());
+    assert_eq!(origin_host(&normalized, &host), "node7");
+}
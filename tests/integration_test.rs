@@ -1,6 +1,6 @@
 use audit_collector::collector::Collector;
+use audit_collector::model::FieldValue;
 use audit_collector::source::MockAuditSource;
-use audit_collector::model::AuditEvent;
 use crossbeam_channel::unbounded;
 use std::sync::Arc;
 use std::thread;
@@ -33,14 +33,14 @@ fn test_collector_pipeline() {
     let event1 = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event 1");
     assert_eq!(event1.record_type, 1300);
     assert_eq!(event1.sequence, 100);
-    assert_eq!(event1.fields.get("comm"), Some(&"\"cat\"".to_string()));
-    assert_eq!(event1.fields.get("uid"), Some(&"1000".to_string()));
+    assert_eq!(event1.fields.get("comm"), Some(&FieldValue::from("\"cat\"")));
+    assert_eq!(event1.fields.get("uid"), Some(&FieldValue::UInt(1000)));
 
     // Expect Event 2
     let event2 = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event 2");
     assert_eq!(event2.record_type, 1101);
     assert_eq!(event2.sequence, 101);
-    assert_eq!(event2.fields.get("pid"), Some(&"123".to_string()));
+    assert_eq!(event2.fields.get("pid"), Some(&FieldValue::UInt(123)));
 
     println!("Test passed!");
 }
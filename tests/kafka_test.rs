@@ -0,0 +1,328 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::AuditEvent;
+use audit_collector::output::kafka::{murmur2, partition_for, Compression, Reader, Record, RecordBatch, Writer};
+use audit_collector::output::{KafkaSink, KafkaSinkConfig, Sink};
+use std::collections::{HashSet, VecDeque};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const PARTITIONS: i32 = 3;
+const PRODUCER_ID: i64 = 1000;
+
+/// How the broker answers the next Produce request.
+enum Script {
+    /// Fail every partition with this error code without writing anything.
+    Error(i16),
+    /// Write the batches, then drop the connection instead of answering.
+    Disconnect,
+}
+
+struct Produced {
+    version: i16,
+    acks: i16,
+    topic: String,
+    partition: i32,
+    batch: RecordBatch,
+}
+
+/// A single-node broker that answers Metadata, InitProducerId and Produce, and
+/// like a real one discards idempotent batches it has already written.
+#[derive(Default)]
+struct Broker {
+    script: Mutex<VecDeque<Script>>,
+    produced: Mutex<Vec<Produced>>,
+    attempts: Mutex<usize>,
+    sequences: Mutex<HashSet<(String, i32, i32)>>,
+    /// Partitions reported with leader -1, as during a leader election.
+    leaderless: Mutex<HashSet<i32>>,
+}
+
+fn serve(script: Vec<Script>) -> (SocketAddr, Arc<Broker>) {
+    let broker = Arc::new(Broker { script: Mutex::new(script.into()), ..Default::default() });
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let state = broker.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let state = state.clone();
+            thread::spawn(move || {
+                let _ = handle(stream.unwrap(), addr, &state);
+            });
+        }
+    });
+    (addr, broker)
+}
+
+fn handle(mut stream: TcpStream, addr: SocketAddr, broker: &Broker) -> anyhow::Result<()> {
+    loop {
+        let mut size = [0; 4];
+        stream.read_exact(&mut size)?;
+        let mut request = vec![0; i32::from_be_bytes(size) as usize];
+        stream.read_exact(&mut request)?;
+        let mut reader = Reader(&request);
+        let api_key = reader.i16()?;
+        let version = reader.i16()?;
+        let correlation_id = reader.i32()?;
+        reader.nullable_string()?;
+
+        let mut response = Writer::default();
+        response.i32(correlation_id);
+        match api_key {
+            3 => metadata(&mut reader, &mut response, addr, broker)?,
+            22 => {
+                response.i32(0);
+                response.i16(0);
+                response.i64(PRODUCER_ID);
+                response.i16(0);
+            }
+            0 => {
+                if !produce(&mut reader, &mut response, version, broker)? {
+                    continue;
+                }
+            }
+            other => panic!("unexpected API key {}", other),
+        }
+        let mut message = Writer::default();
+        message.bytes(&response.0);
+        stream.write_all(&message.0)?;
+    }
+}
+
+fn metadata(reader: &mut Reader, response: &mut Writer, addr: SocketAddr, broker: &Broker) -> anyhow::Result<()> {
+    let leaderless = broker.leaderless.lock().unwrap();
+    let topics: Vec<String> = (0..reader.i32()?).map(|_| reader.string()).collect::<Result<_, _>>()?;
+    response.i32(1);
+    response.i32(1);
+    response.string(&addr.ip().to_string());
+    response.i32(addr.port() as i32);
+    response.nullable_string(None);
+    response.i32(1);
+    response.i32(topics.len() as i32);
+    for topic in topics {
+        response.i16(0);
+        response.string(&topic);
+        response.i8(0);
+        response.i32(PARTITIONS);
+        for partition in 0..PARTITIONS {
+            response.i16(0);
+            response.i32(partition);
+            response.i32(if leaderless.contains(&partition) { -1 } else { 1 });
+            response.i32(1);
+            response.i32(1);
+            response.i32(1);
+            response.i32(1);
+        }
+    }
+    Ok(())
+}
+
+/// Handles a Produce request; false if the broker should not answer.
+fn produce(reader: &mut Reader, response: &mut Writer, version: i16, broker: &Broker) -> anyhow::Result<bool> {
+    *broker.attempts.lock().unwrap() += 1;
+    let script = broker.script.lock().unwrap().pop_front();
+    reader.nullable_string()?;
+    let acks = reader.i16()?;
+    reader.i32()?;
+    let mut results = Vec::new();
+    for _ in 0..reader.i32()? {
+        let topic = reader.string()?;
+        for _ in 0..reader.i32()? {
+            let partition = reader.i32()?;
+            let batch = RecordBatch::decode(reader.bytes()?.unwrap())?;
+            let code = match script {
+                Some(Script::Error(code)) => code,
+                _ => 0,
+            };
+            let duplicate = batch.producer_id >= 0
+                && !broker.sequences.lock().unwrap().insert((topic.clone(), partition, batch.base_sequence));
+            if code == 0 && !duplicate {
+                broker.produced.lock().unwrap().push(Produced { version, acks, topic: topic.clone(), partition, batch });
+            }
+            results.push((topic.clone(), partition, code));
+        }
+    }
+    if matches!(script, Some(Script::Disconnect)) {
+        anyhow::bail!("scripted disconnect");
+    }
+    if acks == 0 {
+        return Ok(false);
+    }
+
+    response.i32(results.len() as i32);
+    for (topic, partition, code) in results {
+        response.string(&topic);
+        response.i32(1);
+        response.i32(partition);
+        response.i16(code);
+        response.i64(0);
+        response.i64(-1);
+        if version >= 5 {
+            response.i64(0);
+        }
+    }
+    response.i32(0);
+    Ok(true)
+}
+
+fn config(addr: SocketAddr, extra: &str) -> KafkaSinkConfig {
+    let text = format!("[[output.kafka]]\nbrokers = [\"{}\"]\n{}", addr, extra);
+    AppConfig::parse(&text).unwrap().output.kafka.remove(0)
+}
+
+fn event(sequence: u64, module: &str, host: &str) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.record_type = 1112;
+    event.timestamp = "2026-01-02T03:04:05Z".parse().unwrap();
+    event.fields.insert("event.module".to_string(), module.into());
+    event.fields.insert("host.name".to_string(), host.into());
+    event
+}
+
+fn wait_for(broker: &Broker, records: usize) {
+    let produced = || broker.produced.lock().unwrap().iter().map(|p| p.batch.records.len()).sum::<usize>();
+    common::wait_for(&format!("{} records", records), || produced() >= records);
+}
+
+#[test]
+fn test_partitioner_matches_java_client() {
+    assert_eq!(murmur2(b"21"), -973932308);
+    assert_eq!(murmur2(b"foobar"), -790332482);
+    assert_eq!(murmur2(b"a-little-bit-long-string"), -985981536);
+    assert_eq!(murmur2(b"a-little-bit-longer-string"), -1486304829);
+    assert_eq!(murmur2(b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8"), -58897971);
+    assert_eq!(partition_for(b"foobar", 3), (-790332482i32 & 0x7fffffff) as usize % 3);
+}
+
+#[test]
+fn test_record_batches_round_trip_with_every_codec() {
+    let records: Vec<Record> = (0..20)
+        .map(|i| Record {
+            timestamp: 1_767_323_045_000 + i,
+            key: (i % 2 == 0).then(|| format!("key-{}", i).into_bytes()),
+            value: format!("{{\"sequence\":{}}}", i).into_bytes(),
+        })
+        .collect();
+    for compression in [Compression::None, Compression::Gzip, Compression::Snappy, Compression::Lz4, Compression::Zstd] {
+        let batch = RecordBatch { producer_id: 7, producer_epoch: 1, base_sequence: 40, compression, records: records.clone() };
+        let mut encoded = batch.encode().unwrap();
+        assert_eq!(RecordBatch::decode(&encoded).unwrap(), batch, "{:?}", compression);
+
+        let last = encoded.len() - 1;
+        encoded[last] ^= 0xff;
+        assert!(RecordBatch::decode(&encoded).is_err(), "{:?} corruption went unnoticed", compression);
+    }
+}
+
+#[test]
+fn test_routes_by_source_type_and_keys_by_host() {
+    let (addr, broker) = serve(Vec::new());
+    let extra = "topics = { windows_xml = \"audit-windows\" }\nacks = 1\ncompression = \"zstd\"\nbatch = { max_events = 4 }";
+    let tx = KafkaSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(event(1, "auditd", "web-1")).unwrap();
+    tx.send(event(2, "auditd", "web-2")).unwrap();
+    tx.send(event(3, "windows_xml", "dc01")).unwrap();
+    tx.send(event(4, "auditd", "web-1")).unwrap();
+
+    wait_for(&broker, 4);
+    let produced = broker.produced.lock().unwrap();
+    for p in produced.iter() {
+        assert_eq!((p.version, p.acks), (7, 1));
+        assert_eq!(p.batch.compression, Compression::Zstd);
+        assert_eq!(p.batch.producer_id, -1);
+        for record in &p.batch.records {
+            let key = record.key.as_deref().unwrap();
+            assert_eq!(p.partition as usize, partition_for(key, PARTITIONS as usize));
+            let value: serde_json::Value = serde_json::from_slice(&record.value).unwrap();
+            assert_eq!(value["fields"]["host.name"].as_str().unwrap().as_bytes(), key);
+            let topic = if key == b"dc01" { "audit-windows" } else { "audit-events" };
+            assert_eq!(p.topic, topic);
+        }
+    }
+    // Both web-1 events share a partition, in order.
+    let web1: Vec<u64> = produced
+        .iter()
+        .flat_map(|p| &p.batch.records)
+        .filter(|r| r.key.as_deref() == Some(b"web-1".as_slice()))
+        .map(|r| serde_json::from_slice::<serde_json::Value>(&r.value).unwrap()["sequence"].as_u64().unwrap())
+        .collect();
+    assert_eq!(web1, vec![1, 4]);
+}
+
+#[test]
+fn test_idempotent_retries_are_not_duplicated() {
+    let (addr, broker) = serve(vec![Script::Disconnect]);
+    let extra = "key = \"none\"\nidempotent = true\ncompression = \"gzip\"\nbatch = { max_events = 3 }\nretry = { initial_backoff_ms = 1 }";
+    let tx = KafkaSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    for sequence in 1..=6 {
+        tx.send(event(sequence, "auditd", "web-1")).unwrap();
+    }
+
+    wait_for(&broker, 6);
+    thread::sleep(Duration::from_millis(50));
+    assert_eq!(*broker.attempts.lock().unwrap(), 3);
+    let produced = broker.produced.lock().unwrap();
+    assert_eq!(produced.len(), 2);
+    for p in produced.iter() {
+        assert_eq!((p.batch.producer_id, p.batch.producer_epoch), (PRODUCER_ID, 0));
+        assert_eq!(p.acks, -1);
+        assert!(p.batch.records.iter().all(|r| r.key.is_none()));
+    }
+    // The resent batch kept its sequence number; the next one follows it.
+    assert_eq!(produced[0].batch.base_sequence, 0);
+    if produced[1].partition == produced[0].partition {
+        assert_eq!(produced[1].batch.base_sequence, 3);
+    }
+}
+
+#[test]
+fn test_leaderless_partitions_wait_while_the_others_are_sent() {
+    let (addr, broker) = serve(Vec::new());
+    broker.leaderless.lock().unwrap().insert(0);
+    let host_on = |partition: usize| {
+        let n = (0..).find(|n| partition_for(format!("web-{}", n).as_bytes(), PARTITIONS as usize) == partition).unwrap();
+        format!("web-{}", n)
+    };
+    let extra = "batch = { max_events = 2 }\nretry = { max_attempts = 1000, initial_backoff_ms = 1, max_backoff_ms = 10 }";
+    let tx = KafkaSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    // One flush with a batch for the leaderless partition 0 and one for partition 1.
+    tx.send(event(1, "auditd", &host_on(0))).unwrap();
+    tx.send(event(2, "auditd", &host_on(1))).unwrap();
+
+    wait_for(&broker, 1);
+    assert_eq!(broker.produced.lock().unwrap()[0].partition, 1);
+    broker.leaderless.lock().unwrap().clear();
+    wait_for(&broker, 2);
+    assert_eq!(broker.produced.lock().unwrap()[1].partition, 0);
+}
+
+#[test]
+fn test_retriable_errors_are_retried_and_refusals_dead_lettered() {
+    let dir = tempfile::tempdir().unwrap();
+    let (addr, broker) = serve(vec![Script::Error(6)]);
+    let extra = format!(
+        "key = \"event_id\"\ndead_letter_dir = {:?}\nbatch = {{ max_events = 1 }}\nretry = {{ initial_backoff_ms = 1 }}",
+        dir.path().to_str().unwrap()
+    );
+    let tx = KafkaSink::new(config(addr, &extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    // NOT_LEADER_OR_FOLLOWER, then written.
+    tx.send(event(1, "auditd", "web-1")).unwrap();
+    wait_for(&broker, 1);
+    // MESSAGE_TOO_LARGE is final.
+    broker.script.lock().unwrap().push_back(Script::Error(10));
+    tx.send(event(2, "auditd", "web-1")).unwrap();
+
+    common::wait_for("a dead letter", || std::fs::read_dir(dir.path()).map(|d| d.count()).unwrap_or(0) > 0);
+    assert_eq!(*broker.attempts.lock().unwrap(), 3);
+    assert_eq!(broker.produced.lock().unwrap()[0].batch.records[0].key.as_deref(), Some(format!("{:032x}", 1).as_bytes()));
+
+    let bad = format!("[[output.kafka]]\nbrokers = [\"{}\"]\nacks = 1\nidempotent = true", addr);
+    let config = AppConfig::parse(&bad).unwrap().output.kafka.remove(0);
+    assert!(KafkaSink::new(config, Arc::new(HostMetadata::default())).is_err());
+    assert!(AppConfig::parse("[[output.kafka]]\nbrokers = []\nacks = 2").is_err());
+}
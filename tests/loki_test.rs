@@ -0,0 +1,107 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::loki::{label_string, LabelTemplate, PushRequest};
+use audit_collector::output::{LokiSink, LokiSinkConfig, Sink};
+use prost::Message;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+fn config(addr: SocketAddr, extra: &str) -> LokiSinkConfig {
+    let text = format!("[[output.loki]]\nurl = \"http://{}\"\n{}", addr, extra);
+    AppConfig::parse(&text).unwrap().output.loki.remove(0)
+}
+
+fn event(sequence: u64, second: u32, user: &str) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.record_type = 1112;
+    event.timestamp = format!("2026-01-02T03:04:{:02}Z", second).parse().unwrap();
+    event.fields.insert("event.module".to_string(), "auditd".into());
+    event.fields.insert("host.name".to_string(), "web-1".into());
+    event.fields.insert("user.name".to_string(), user.into());
+    event.fields.insert("process.pid".to_string(), FieldValue::UInt(4242));
+    event
+}
+
+#[test]
+fn test_label_templates_refuse_high_cardinality_fields() {
+    for template in ["{pid}", "{process.pid}", "{process.parent.pid}", "{source.port}", "{message}", "{id}", "{raw.ses}"] {
+        assert!(LabelTemplate::parse(template).is_err(), "{} should be refused", template);
+    }
+    for template in ["{source}", "{record_type_name}", "{user.name}", "{host.id}", "audit-{event.module}"] {
+        assert!(LabelTemplate::parse(template).is_ok(), "{} should be accepted", template);
+    }
+    assert!(LabelTemplate::parse("{user.name").is_err());
+
+    let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+    let host = Arc::new(HostMetadata::default());
+    assert!(LokiSink::new(config(addr, "labels = { \"bad-name\" = \"{source}\" }"), host.clone()).is_err());
+    assert!(LokiSink::new(config(addr, "labels = { pid = \"{process.pid}\" }"), host).is_err());
+}
+
+#[test]
+fn test_streams_are_grouped_sorted_and_capped() {
+    let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+    let extra = "labels = { source = \"{source}\", kind = \"{record_type_name}\", user = \"{user.name}\" }\nmax_label_values = 2";
+    let mut sink = LokiSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap();
+
+    let events = [event(1, 30, "alice"), event(2, 10, "bob"), event(3, 20, "alice"), event(4, 5, "carol"), event(5, 1, "dave")];
+    let streams = sink.streams(&events);
+    let users: Vec<&str> = streams.iter().map(|s| s.labels["user"].as_str()).collect();
+    assert_eq!(users, vec!["alice", "bob", "overflow"]);
+    assert_eq!(streams[0].labels["source"], "auditd");
+    assert_eq!(streams[0].labels["kind"], "USER_LOGIN");
+
+    // Entries within a stream are oldest first, regardless of arrival order.
+    let seconds = |i: usize| streams[i].entries.iter().map(|(nanos, _)| nanos / 1_000_000_000 % 60).collect::<Vec<_>>();
+    assert_eq!(seconds(0), vec![20, 30]);
+    assert_eq!(seconds(2), vec![1, 5]);
+
+    let labels: BTreeMap<String, String> = [("a".to_string(), "say \"hi\"".to_string())].into();
+    assert_eq!(label_string(&labels), r#"{a="say \"hi\""}"#);
+}
+
+#[test]
+fn test_protobuf_push_is_snappy_compressed() {
+    let (addr, stub) = common::serve("/loki/api/v1/push", &[]);
+    let extra = "tenant_id = \"team-a\"\nbatch = { max_events = 3 }";
+    let tx = LokiSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(event(1, 9, "alice")).unwrap();
+    tx.send(event(2, 3, "alice")).unwrap();
+    tx.send(event(3, 6, "alice")).unwrap();
+
+    let pushes = stub.requests(1);
+    let (headers, body) = &pushes[0];
+    assert_eq!(headers["content-type"], "application/x-protobuf");
+    assert_eq!(headers["x-scope-orgid"], "team-a");
+    let body = snap::raw::Decoder::new().decompress_vec(body).unwrap();
+    let request = PushRequest::decode(body.as_slice()).unwrap();
+    assert_eq!(request.streams.len(), 1);
+    let stream = &request.streams[0];
+    assert_eq!(stream.labels, r#"{host="web-1", record_type="USER_LOGIN", source="auditd"}"#);
+    let seconds: Vec<i64> = stream.entries.iter().map(|e| e.timestamp.as_ref().unwrap().seconds % 60).collect();
+    assert_eq!(seconds, vec![3, 6, 9]);
+    let line: serde_json::Value = serde_json::from_str(&stream.entries[0].line).unwrap();
+    assert_eq!(line["sequence"], 2);
+}
+
+#[test]
+fn test_json_push_retries_server_errors() {
+    let (addr, stub) = common::serve("/loki/api/v1/push", &[500]);
+    let extra = "encoding = \"json\"\nbatch = { max_events = 2 }\nretry = { initial_backoff_ms = 1 }";
+    let tx = LokiSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(event(1, 1, "alice")).unwrap();
+    tx.send(event(2, 2, "bob")).unwrap();
+
+    let pushes = stub.requests(2);
+    assert_eq!(pushes[0].1, pushes[1].1);
+    assert_eq!(pushes[1].0["content-type"], "application/json");
+    let body: serde_json::Value = serde_json::from_slice(&pushes[1].1).unwrap();
+    let streams = body["streams"].as_array().unwrap();
+    assert_eq!(streams.len(), 1);
+    assert_eq!(streams[0]["stream"]["host"], "web-1");
+    assert_eq!(streams[0]["values"][0][0], "1767323041000000000");
+}
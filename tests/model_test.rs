@@ -0,0 +1,107 @@
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::OutputFormat;
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use std::cmp::Ordering;
+use std::net::IpAddr;
+
+#[test]
+fn test_field_values_serialize_as_strings() {
+    let mut event = AuditEvent::new(1300, 7);
+    event.fields.insert("comm".to_string(), "cat".into());
+    event.fields.insert("uid".to_string(), FieldValue::UInt(1000));
+    event.fields.insert("exit".to_string(), FieldValue::Int(-13));
+    event.fields.insert("success".to_string(), FieldValue::Bool(false));
+    event.fields.insert("source_ip".to_string(), FieldValue::ip("10.0.0.5"));
+    event.fields.insert("args".to_string(), FieldValue::List(vec!["-l".into(), FieldValue::UInt(2)]));
+
+    let json = serde_json::to_value(&event).unwrap();
+    assert_eq!(
+        json["fields"],
+        serde_json::json!({
+            "comm": "cat",
+            "uid": "1000",
+            "exit": "-13",
+            "success": "false",
+            "source_ip": "10.0.0.5",
+            "args": ["-l", "2"]
+        })
+    );
+
+    // The typed form opts in to JSON numbers and booleans, and round-trips them.
+    let typed = event.to_typed_json();
+    assert_eq!(
+        typed["fields"],
+        serde_json::json!({
+            "comm": "cat",
+            "uid": 1000,
+            "exit": -13,
+            "success": false,
+            "source_ip": "10.0.0.5",
+            "args": ["-l", 2]
+        })
+    );
+    assert_eq!(typed["sequence"], json["sequence"]);
+    assert_eq!(OutputFormat::Typed.render(&event), typed);
+    assert_eq!(OutputFormat::Native.render(&event), json);
+
+    // IPs and timestamps come back as strings; everything else round-trips exactly.
+    let back: AuditEvent = serde_json::from_value(typed).unwrap();
+    assert_eq!(back.fields["uid"], FieldValue::UInt(1000));
+    assert_eq!(back.fields["exit"], FieldValue::Int(-13));
+    assert_eq!(back.fields["args"], event.fields["args"]);
+    assert_eq!(back.fields["source_ip"], FieldValue::from("10.0.0.5"));
+}
+
+#[test]
+fn test_parsed_event_keeps_string_json_shape() {
+    let raw = r#"type=SYSCALL msg=audit(1700000000.123:77): arch=c000003e syscall=59 success=yes exit=0 pid=10 uid=0 comm="cat" exe="/usr/bin/cat""#;
+    let parser = ParserRegistry::default().parser_for(&SourceFormat::Auditd).unwrap();
+    let event = parser.parse(raw.as_bytes()).unwrap();
+    assert_eq!(event.fields["uid"], FieldValue::UInt(0));
+
+    let json = serde_json::to_value(&event).unwrap();
+    for (key, value) in [("uid", "0"), ("pid", "10"), ("exit", "0"), ("syscall", "59"), ("comm", "\"cat\"")] {
+        assert_eq!(json["fields"][key], serde_json::json!(value), "{}", key);
+    }
+    assert!(json["fields"].as_object().unwrap().values().all(|v| v.is_string()), "{}", json["fields"]);
+}
+
+#[test]
+fn test_string_only_events_still_deserialize() {
+    let event: AuditEvent = serde_json::from_str(
+        r#"{"timestamp":"2024-01-01T00:00:00Z","record_type":1,"sequence":0,"fields":{"pid":"42","message":"hi"}}"#,
+    )
+    .unwrap();
+    assert_eq!(event.fields["pid"], FieldValue::from("42"));
+    assert_eq!(event.fields["pid"].compare(&FieldValue::UInt(42)), Some(Ordering::Equal));
+}
+
+#[test]
+fn test_compare_across_types() {
+    let cmp = |a: FieldValue, b: FieldValue| a.compare(&b);
+    assert_eq!(cmp(FieldValue::UInt(5), FieldValue::Int(-1)), Some(Ordering::Greater));
+    assert_eq!(cmp(FieldValue::Float(2.5), FieldValue::UInt(2)), Some(Ordering::Greater));
+    assert_eq!(cmp("500".into(), FieldValue::UInt(500)), Some(Ordering::Equal));
+    assert_eq!(cmp(FieldValue::UInt(9), "10".into()), Some(Ordering::Less));
+    assert_eq!(cmp("abc".into(), FieldValue::UInt(1)), None);
+    assert_eq!(cmp(FieldValue::Bool(true), FieldValue::UInt(1)), None);
+
+    let ip: IpAddr = "10.0.0.1".parse().unwrap();
+    assert_eq!(cmp(FieldValue::Ip(ip), "10.0.0.1".into()), Some(Ordering::Equal));
+    assert_eq!(FieldValue::UInt(22), "22");
+}
+
+#[test]
+fn test_auditd_fields_are_typed() {
+    let registry = ParserRegistry::default();
+    let parser = registry.parser_for(&SourceFormat::Auditd).unwrap();
+    let raw = br#"type=SYSCALL msg=audit(1700000000.123:77): arch=c000003e syscall=59 success=yes exit=-2 a0=7ffd pid=4321 uid=0 comm="bash""#;
+    let event = parser.parse(raw).unwrap();
+
+    assert_eq!(event.fields["syscall"], FieldValue::UInt(59));
+    assert_eq!(event.fields["success"], FieldValue::Bool(true));
+    assert_eq!(event.fields["exit"], FieldValue::Int(-2));
+    assert_eq!(event.fields["pid"], FieldValue::UInt(4321));
+    // Syscall arguments are hex and stay textual.
+    assert_eq!(event.fields["a0"], FieldValue::from("7ffd"));
+}
@@ -0,0 +1,150 @@
+use audit_collector::collector::Collector;
+use audit_collector::config::AppConfig;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::normalize::{NormalizeConfig, Normalizer};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use audit_collector::source::MockAuditSource;
+use crossbeam_channel::unbounded;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn normalized(format: SourceFormat, raw: &str) -> AuditEvent {
+    let mut event = ParserRegistry::default().parser_for(&format).unwrap().parse(raw.as_bytes()).unwrap();
+    Normalizer::default().normalize(&format, &mut event);
+    event
+}
+
+fn field<'a>(event: &'a AuditEvent, key: &str) -> Option<&'a FieldValue> {
+    event.fields.get(key)
+}
+
+fn raw<'a>(event: &'a AuditEvent, key: &str) -> Option<&'a FieldValue> {
+    match event.fields.get("raw") {
+        Some(FieldValue::Map(raw)) => raw.get(key),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_auditd_execve() {
+    let event = normalized(
+        SourceFormat::Auditd,
+        r#"type=SYSCALL msg=audit(1700000000.123:77): arch=c000003e syscall=59 success=yes exit=0 pid=4321 ppid=1 uid=1000 comm="cat" exe="/usr/bin/cat" node=web01"#,
+    );
+    assert_eq!(field(&event, "event.module").unwrap(), "auditd");
+    assert_eq!(field(&event, "event.category").unwrap(), "process");
+    assert_eq!(field(&event, "event.action").unwrap(), "execve");
+    assert_eq!(field(&event, "event.outcome").unwrap(), "success");
+    assert_eq!(field(&event, "process.executable").unwrap(), "/usr/bin/cat");
+    assert_eq!(field(&event, "process.name").unwrap(), "cat");
+    assert_eq!(field(&event, "process.pid"), Some(&FieldValue::UInt(4321)));
+    assert_eq!(field(&event, "user.id"), Some(&FieldValue::UInt(1000)));
+    assert_eq!(field(&event, "host.name").unwrap(), "web01");
+    assert_eq!(raw(&event, "exe").unwrap(), "\"/usr/bin/cat\"");
+    assert_eq!(field(&event, "exe"), None);
+}
+
+#[test]
+fn test_auditd_user_login_with_unset_values() {
+    let event = normalized(
+        SourceFormat::Auditd,
+        "type=USER_LOGIN msg=audit(1700000000.5:90): pid=812 uid=0 msg='op=login acct=\"alice\" exe=\"/usr/sbin/sshd\" hostname=? addr=10.0.0.5 terminal=ssh res=failed'",
+    );
+    assert_eq!(field(&event, "event.category").unwrap(), "authentication");
+    assert_eq!(field(&event, "event.action").unwrap(), "user_login");
+    assert_eq!(field(&event, "event.outcome").unwrap(), "failure");
+    assert_eq!(field(&event, "user.name").unwrap(), "alice");
+    assert_eq!(field(&event, "source.ip"), Some(&FieldValue::ip("10.0.0.5")));
+    assert_eq!(field(&event, "host.name"), None);
+}
+
+#[test]
+fn test_windows_process_creation() {
+    let xml = r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event">
+  <System>
+    <Provider Name="Microsoft-Windows-Security-Auditing"/>
+    <EventID>4688</EventID>
+    <Computer>WS01.corp.example.com</Computer>
+  </System>
+  <EventData>
+    <Data Name="SubjectUserName">alice</Data>
+    <Data Name="NewProcessId">0x1a4</Data>
+    <Data Name="NewProcessName">C:\Windows\System32\cmd.exe</Data>
+    <Data Name="IpAddress">-</Data>
+  </EventData>
+</Event>"#;
+    let event = normalized(SourceFormat::WindowsXml, xml);
+    assert_eq!(field(&event, "event.category").unwrap(), "process");
+    assert_eq!(field(&event, "event.action").unwrap(), "process_created");
+    assert_eq!(field(&event, "process.executable").unwrap(), r"C:\Windows\System32\cmd.exe");
+    assert_eq!(field(&event, "process.pid"), Some(&FieldValue::UInt(0x1a4)));
+    assert_eq!(field(&event, "user.name").unwrap(), "alice");
+    assert_eq!(field(&event, "host.name").unwrap(), "WS01.corp.example.com");
+    assert_eq!(field(&event, "source.ip"), None);
+}
+
+#[test]
+fn test_auth_log_ssh_login() {
+    let event = normalized(
+        SourceFormat::Syslog,
+        "Jan  2 03:04:05 web01 sshd[1201]: Failed password for root from 203.0.113.9 port 60111 ssh2",
+    );
+    assert_eq!(field(&event, "event.category").unwrap(), "authentication");
+    assert_eq!(field(&event, "event.action").unwrap(), "ssh_login");
+    assert_eq!(field(&event, "event.outcome").unwrap(), "failure");
+    assert_eq!(field(&event, "process.name").unwrap(), "sshd");
+    assert_eq!(field(&event, "process.pid"), Some(&FieldValue::UInt(1201)));
+    assert_eq!(field(&event, "user.name").unwrap(), "root");
+    assert_eq!(field(&event, "source.ip"), Some(&FieldValue::ip("203.0.113.9")));
+    assert_eq!(field(&event, "source.port"), Some(&FieldValue::UInt(60111)));
+    assert_eq!(field(&event, "host.name").unwrap(), "web01");
+}
+
+#[test]
+fn test_configured_mappings_take_precedence() {
+    let config: AppConfig = AppConfig::parse(
+        r#"
+        [normalize.mappings.line]
+        "user.name" = ["login"]
+        "#,
+    )
+    .unwrap();
+    let normalizer = Normalizer::new(&config.normalize);
+
+    let mut event = AuditEvent::new(1, 0);
+    event.fields.insert("message".to_string(), "hello".into());
+    event.fields.insert("login".to_string(), "carol".into());
+    event.fields.insert("user".to_string(), "ignored".into());
+    normalizer.normalize(&SourceFormat::Line, &mut event);
+    assert_eq!(field(&event, "user.name").unwrap(), "carol");
+    assert_eq!(field(&event, "message").unwrap(), "hello");
+
+    assert!(!NormalizeConfig::default().enabled);
+    assert!(!AppConfig::parse("").unwrap().normalize.enabled);
+    assert!(AppConfig::parse("[normalize]\nenabled = true").unwrap().normalize.enabled);
+}
+
+#[test]
+fn test_collector_normalizes_auto_detected_events() {
+    let data = vec![
+        br#"{"processImagePath":"/usr/libexec/sshd","processID":42,"eventMessage":"hi"}"#.to_vec(),
+        b"type=1300 msg=audit(1.0:5): syscall=2 pid=9 exe=/bin/cat".to_vec(),
+    ];
+    let (tx, rx) = unbounded();
+    let collector = Collector::new(Arc::new(MockAuditSource::new(data)), tx)
+        .with_normalizer(Arc::new(Normalizer::default()));
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+
+    let mac = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event");
+    assert_eq!(field(&mac, "event.module").unwrap(), "mac_json");
+    assert_eq!(field(&mac, "process.executable").unwrap(), "/usr/libexec/sshd");
+    assert_eq!(field(&mac, "process.pid"), Some(&FieldValue::UInt(42)));
+
+    let linux = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event");
+    assert_eq!(field(&linux, "event.category").unwrap(), "file");
+    assert_eq!(field(&linux, "event.action").unwrap(), "open");
+    assert_eq!(field(&linux, "process.executable").unwrap(), "/bin/cat");
+}
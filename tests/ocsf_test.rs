@@ -0,0 +1,160 @@
+use audit_collector::model::AuditEvent;
+use audit_collector::normalize::Normalizer;
+use audit_collector::output::{ocsf, OutputFormat};
+use audit_collector::parser::{ParserRegistry, SourceFormat};
+use serde_json::Value;
+
+fn ocsf_event(format: SourceFormat, raw: &str) -> Value {
+    let mut event = ParserRegistry::default().parser_for(&format).unwrap().parse(raw.as_bytes()).unwrap();
+    Normalizer::default().normalize(&format, &mut event);
+    ocsf::to_ocsf(&event)
+}
+
+fn windows(event_id: u16, data: &[(&str, &str)]) -> String {
+    let data: String = data
+        .iter()
+        .map(|(name, value)| format!(r#"<Data Name="{}">{}</Data>"#, name, value))
+        .collect();
+    format!(
+        r#"<Event xmlns="http://schemas.microsoft.com/win/2004/08/events/event"><System><EventID>{}</EventID><Computer>DC01</Computer></System><EventData>{}</EventData></Event>"#,
+        event_id, data
+    )
+}
+
+fn class(v: &Value) -> (u64, u64, u64) {
+    (
+        v["class_uid"].as_u64().unwrap(),
+        v["activity_id"].as_u64().unwrap(),
+        v["type_uid"].as_u64().unwrap(),
+    )
+}
+
+#[test]
+fn test_execve_is_process_launch() {
+    let v = ocsf_event(
+        SourceFormat::Auditd,
+        r#"type=SYSCALL msg=audit(1700000000.123:77): syscall=59 success=yes pid=4321 ppid=1 uid=1000 comm="cat" exe="/usr/bin/cat""#,
+    );
+    assert_eq!(class(&v), (1007, 1, 100701));
+    assert_eq!(v["category_uid"], 1);
+    assert_eq!(v["status_id"], 1);
+    assert_eq!(v["process"]["pid"], 4321);
+    assert_eq!(v["process"]["name"], "cat");
+    assert_eq!(v["process"]["file"]["path"], "/usr/bin/cat");
+    assert_eq!(v["process"]["parent_process"]["pid"], 1);
+    assert_eq!(v["actor"]["user"]["uid"], "1000");
+    assert_eq!(v["metadata"]["version"], ocsf::OCSF_VERSION);
+    assert_eq!(v["unmapped"]["syscall"], 59);
+}
+
+#[test]
+fn test_authentication_sources() {
+    let login = ocsf_event(
+        SourceFormat::Auditd,
+        "type=USER_LOGIN msg=audit(1700000000.5:90): pid=812 uid=0 msg='op=login acct=\"alice\" exe=\"/usr/sbin/sshd\" addr=10.0.0.5 res=success'",
+    );
+    assert_eq!(class(&login), (3002, 1, 300201));
+    assert_eq!(login["user"]["name"], "alice");
+    assert_eq!(login["src_endpoint"]["ip"], "10.0.0.5");
+    assert_eq!(login["is_remote"], true);
+
+    let success = ocsf_event(SourceFormat::WindowsXml, &windows(4624, &[("TargetUserName", "bob"), ("LogonType", "10"), ("IpAddress", "192.0.2.4")]));
+    assert_eq!(class(&success), (3002, 1, 300201));
+    assert_eq!(success["status_id"], 1);
+    assert_eq!(success["logon_type"], "10");
+    assert_eq!(success["device"]["hostname"], "DC01");
+
+    let failure = ocsf_event(SourceFormat::WindowsXml, &windows(4625, &[("TargetUserName", "bob")]));
+    assert_eq!(class(&failure), (3002, 1, 300201));
+    assert_eq!(failure["status_id"], 2);
+    assert_eq!(failure["status"], "Failure");
+
+    let sshd = ocsf_event(
+        SourceFormat::Syslog,
+        "Jan  2 03:04:05 web01 sshd[1201]: Accepted publickey for deploy from 10.0.0.9 port 50022 ssh2",
+    );
+    assert_eq!(class(&sshd), (3002, 1, 300201));
+    assert_eq!(sshd["user"]["name"], "deploy");
+    assert_eq!(sshd["auth_protocol"], "publickey");
+    assert_eq!(sshd["src_endpoint"]["port"], 50022);
+    assert_eq!(sshd["actor"]["process"]["name"], "sshd");
+}
+
+#[test]
+fn test_file_system_activity() {
+    let path = ocsf_event(
+        SourceFormat::Auditd,
+        r#"type=PATH msg=audit(1700000000.123:77): item=0 name="/etc/shadow" inode=1234 mode=0100640"#,
+    );
+    assert_eq!(class(&path), (1001, 14, 100114));
+    assert_eq!(path["file"]["path"], "/etc/shadow");
+    assert_eq!(path["file"]["name"], "shadow");
+
+    let open = ocsf_event(
+        SourceFormat::Auditd,
+        r#"type=SYSCALL msg=audit(1700000000.123:78): syscall=257 success=no exit=-13 pid=50 exe="/usr/bin/less""#,
+    );
+    assert_eq!(class(&open), (1001, 14, 100114));
+    assert_eq!(open["status_id"], 2);
+    assert_eq!(open["actor"]["process"]["file"]["name"], "less");
+}
+
+#[test]
+fn test_account_change() {
+    let useradd = ocsf_event(
+        SourceFormat::Syslog,
+        "Jan  2 03:04:05 web01 useradd[900]: new user: name=bob, UID=1001, GID=1001, home=/home/bob, shell=/bin/bash, from=/dev/pts/0",
+    );
+    assert_eq!(class(&useradd), (3001, 1, 300101));
+    assert_eq!(useradd["user"]["name"], "bob");
+    assert_eq!(useradd["user"]["uid"], "1001");
+
+    let created = ocsf_event(SourceFormat::WindowsXml, &windows(4720, &[("TargetUserName", "carol"), ("SubjectUserName", "admin")]));
+    assert_eq!(class(&created), (3001, 1, 300101));
+    assert_eq!(created["user"]["name"], "carol");
+    assert_eq!(created["actor"]["user"]["name"], "admin");
+}
+
+#[test]
+fn test_unmapped_events_and_output_format() {
+    let mut event = AuditEvent::new(1, 3);
+    event.fields.insert("message".to_string(), "hello".into());
+
+    // Not normalized yet: the transform normalizes a copy itself.
+    let v = OutputFormat::Ocsf.render(&event);
+    assert_eq!(class(&v), (0, 99, 99));
+    assert_eq!(v["message"], "hello");
+    assert!(v.get("device").is_none(), "empty objects are pruned");
+
+    let native = OutputFormat::Native.render(&event);
+    assert_eq!(native["fields"]["message"], "hello");
+    assert_eq!(serde_json::from_str::<OutputFormat>("\"ocsf\"").unwrap(), OutputFormat::Ocsf);
+}
+
+#[test]
+fn test_events_that_were_not_normalized() {
+    let parse = |format: SourceFormat, raw: &str| {
+        let event = ParserRegistry::default().parser_for(&format).unwrap().parse(raw.as_bytes()).unwrap();
+        assert!(!event.fields.contains_key("raw"));
+        ocsf::to_ocsf(&event)
+    };
+
+    let windows = parse(SourceFormat::WindowsXml, &windows(4624, &[("TargetUserName", "bob"), ("IpAddress", "192.0.2.4")]));
+    assert_eq!(class(&windows), (3002, 1, 300201));
+    assert_eq!(windows["metadata"]["log_name"], "windows_xml");
+    assert_eq!(windows["user"]["name"], "bob");
+
+    let auditd = parse(
+        SourceFormat::Auditd,
+        "type=USER_LOGIN msg=audit(1700000000.5:90): pid=812 uid=0 msg='op=login acct=\"alice\" exe=\"/usr/sbin/sshd\" addr=10.0.0.5 res=success'",
+    );
+    assert_eq!(class(&auditd), (3002, 1, 300201));
+    assert_eq!(auditd["metadata"]["log_name"], "auditd");
+
+    let sshd = parse(
+        SourceFormat::Syslog,
+        "Jan  2 03:04:05 web01 sshd[1201]: Failed password for root from 203.0.113.9 port 60111 ssh2",
+    );
+    assert_eq!(class(&sshd), (3002, 1, 300201));
+    assert_eq!(sshd["status_id"], 2);
+}
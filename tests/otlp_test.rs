@@ -0,0 +1,227 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::otlp::log_record;
+use audit_collector::output::{OtlpSink, OtlpSinkConfig, Sink};
+use axum::body::Bytes;
+use axum::extract::State;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::post;
+use axum::Router;
+use opentelemetry_proto::tonic::collector::logs::v1::logs_service_server::{LogsService, LogsServiceServer};
+use opentelemetry_proto::tonic::collector::logs::v1::{ExportLogsServiceRequest, ExportLogsServiceResponse};
+use opentelemetry_proto::tonic::common::v1::any_value::Value;
+use opentelemetry_proto::tonic::common::v1::KeyValue;
+use prost::Message;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// An OTLP receiver for both HTTP and gRPC. Scripted failures are answered in
+/// order before it starts accepting.
+#[derive(Default)]
+struct Receiver {
+    failures: Mutex<VecDeque<Failure>>,
+    requests: Mutex<Vec<(HeaderMap, ExportLogsServiceRequest)>>,
+    attempts: Mutex<usize>,
+}
+
+enum Failure {
+    Http(u16, Option<&'static str>),
+    Grpc(tonic::Code),
+}
+
+async fn http_logs(State(receiver): State<Arc<Receiver>>, headers: HeaderMap, body: Bytes) -> impl IntoResponse {
+    *receiver.attempts.lock().unwrap() += 1;
+    if let Some(Failure::Http(status, retry_after)) = receiver.failures.lock().unwrap().pop_front() {
+        let mut response_headers = HeaderMap::new();
+        if let Some(after) = retry_after {
+            response_headers.insert("retry-after", after.parse().unwrap());
+        }
+        return (StatusCode::from_u16(status).unwrap(), response_headers, Vec::new());
+    }
+    let json = headers["content-type"] == "application/json";
+    let request = if json {
+        serde_json::from_slice(&body).unwrap()
+    } else {
+        ExportLogsServiceRequest::decode(body).unwrap()
+    };
+    receiver.requests.lock().unwrap().push((headers, request));
+    let response = ExportLogsServiceResponse::default();
+    let body = if json { serde_json::to_vec(&response).unwrap() } else { response.encode_to_vec() };
+    (StatusCode::OK, HeaderMap::new(), body)
+}
+
+struct GrpcReceiver(Arc<Receiver>);
+
+#[tonic::async_trait]
+impl LogsService for GrpcReceiver {
+    async fn export(
+        &self,
+        request: tonic::Request<ExportLogsServiceRequest>,
+    ) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+        *self.0.attempts.lock().unwrap() += 1;
+        if let Some(Failure::Grpc(code)) = self.0.failures.lock().unwrap().pop_front() {
+            return Err(tonic::Status::new(code, "scripted failure"));
+        }
+        let headers = request.metadata().clone().into_headers();
+        self.0.requests.lock().unwrap().push((headers, request.into_inner()));
+        Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+    }
+}
+
+fn serve(failures: Vec<Failure>, grpc: bool) -> (SocketAddr, Arc<Receiver>) {
+    let receiver = Arc::new(Receiver { failures: Mutex::new(failures.into()), ..Default::default() });
+    if !grpc {
+        let app = Router::new().route("/v1/logs", post(http_logs)).with_state(receiver.clone());
+        return (common::serve_app(app), receiver);
+    }
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let addr = listener.local_addr().unwrap();
+    let state = receiver.clone();
+    thread::spawn(move || {
+        tokio::runtime::Runtime::new().unwrap().block_on(async move {
+            let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+            tonic::transport::Server::builder()
+                .add_service(LogsServiceServer::new(GrpcReceiver(state)))
+                .serve_with_incoming(tonic::transport::server::TcpIncoming::from(listener))
+                .await
+                .unwrap();
+        })
+    });
+    (addr, receiver)
+}
+
+fn config(addr: SocketAddr, extra: &str) -> OtlpSinkConfig {
+    let text = format!("[[output.otlp]]\nendpoint = \"http://{}\"\n{}", addr, extra);
+    AppConfig::parse(&text).unwrap().output.otlp.remove(0)
+}
+
+fn host() -> Arc<HostMetadata> {
+    Arc::new(HostMetadata {
+        hostname: "web-1".to_string(),
+        os_name: "Linux".to_string(),
+        machine_id: Some("abc123".to_string()),
+        ..Default::default()
+    })
+}
+
+fn event(sequence: u64) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.record_type = 1112;
+    event.timestamp = "2026-01-02T03:04:05.5Z".parse().unwrap();
+    event.fields.insert("message".to_string(), "login failed".into());
+    event.fields.insert("event.outcome".to_string(), "failure".into());
+    event.fields.insert("event.action".to_string(), "user-login".into());
+    event.fields.insert("process.pid".to_string(), FieldValue::UInt(4242));
+    event
+}
+
+fn wait_for(receiver: &Receiver, count: usize) -> Vec<(HeaderMap, ExportLogsServiceRequest)> {
+    common::wait_for(&format!("{} exports", count), || receiver.requests.lock().unwrap().len() >= count);
+    receiver.requests.lock().unwrap().clone()
+}
+
+fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a Value> {
+    attributes.iter().find(|kv| kv.key == key)?.value.as_ref()?.value.as_ref()
+}
+
+#[test]
+fn test_event_maps_to_log_record() {
+    let mut event = event(u64::MAX);
+    event.fields.insert("severity".to_string(), "crit".into());
+    let record = log_record(&event);
+
+    assert_eq!(record.time_unix_nano, 1_767_323_045_500_000_000);
+    assert_eq!(record.severity_number, 19);
+    assert_eq!(record.severity_text, "crit");
+    assert_eq!(record.body.unwrap().value, Some(Value::StringValue("login failed".to_string())));
+    assert_eq!(record.event_name, "user-login");
+    assert_eq!(attribute(&record.attributes, "process.pid"), Some(&Value::IntValue(4242)));
+    assert_eq!(attribute(&record.attributes, "audit.record_type"), Some(&Value::IntValue(1112)));
+    assert_eq!(attribute(&record.attributes, "audit.sequence"), Some(&Value::StringValue(u64::MAX.to_string())));
+    assert!(attribute(&record.attributes, "message").is_none());
+
+    // Without a severity field, failures are warnings.
+    assert_eq!(log_record(&self::event(1)).severity_number, 13);
+}
+
+#[test]
+fn test_http_protobuf_export_with_resource_and_headers() {
+    let (addr, receiver) = serve(Vec::new(), false);
+    let extra = "headers = { Authorization = \"Bearer s3cret\" }\nbatch = { max_events = 2 }";
+    let tx = OtlpSink::new(config(addr, extra), host()).unwrap().spawn();
+    tx.send(event(1)).unwrap();
+    tx.send(event(2)).unwrap();
+
+    let requests = wait_for(&receiver, 1);
+    let (headers, request) = &requests[0];
+    assert_eq!(headers["content-type"], "application/x-protobuf");
+    assert_eq!(headers["authorization"], "Bearer s3cret");
+    let resource_logs = &request.resource_logs[0];
+    let resource = &resource_logs.resource.as_ref().unwrap().attributes;
+    assert_eq!(attribute(resource, "host.name"), Some(&Value::StringValue("web-1".to_string())));
+    assert_eq!(attribute(resource, "host.id"), Some(&Value::StringValue("abc123".to_string())));
+    assert_eq!(attribute(resource, "service.name"), Some(&Value::StringValue("audit_collector".to_string())));
+    assert_eq!(resource_logs.scope_logs[0].log_records.len(), 2);
+}
+
+#[test]
+fn test_http_json_retries_throttled_and_unavailable() {
+    let failures = vec![Failure::Http(429, Some("0")), Failure::Http(503, None)];
+    let (addr, receiver) = serve(failures, false);
+    let extra = "protocol = \"http/json\"\nbatch = { max_events = 1 }\nretry = { initial_backoff_ms = 1 }";
+    let tx = OtlpSink::new(config(addr, extra), host()).unwrap().spawn();
+    tx.send(event(1)).unwrap();
+
+    let requests = wait_for(&receiver, 1);
+    assert_eq!(*receiver.attempts.lock().unwrap(), 3);
+    assert_eq!(requests[0].0["content-type"], "application/json");
+    assert_eq!(requests[0].1.resource_logs[0].scope_logs[0].log_records[0].severity_text, "warning");
+}
+
+#[test]
+fn test_http_client_errors_are_dead_lettered() {
+    let dir = tempfile::tempdir().unwrap();
+    let (addr, receiver) = serve(vec![Failure::Http(400, None)], false);
+    let extra = format!("dead_letter_dir = {:?}\nbatch = {{ max_events = 1 }}", dir.path().to_str().unwrap());
+    let tx = OtlpSink::new(config(addr, &extra), host()).unwrap().spawn();
+    tx.send(event(1)).unwrap();
+
+    common::wait_for("a dead letter", || std::fs::read_dir(dir.path()).map(|d| d.count()).unwrap_or(0) > 0);
+    assert_eq!(*receiver.attempts.lock().unwrap(), 1);
+}
+
+#[test]
+fn test_grpc_export_retries_unavailable() {
+    let (addr, receiver) = serve(vec![Failure::Grpc(tonic::Code::Unavailable)], true);
+    let extra = "protocol = \"grpc\"\nheaders = { \"X-Scope-OrgID\" = \"tenant-1\" }\nbatch = { max_events = 1 }\nretry = { initial_backoff_ms = 1 }";
+    let tx = OtlpSink::new(config(addr, extra), host()).unwrap().spawn();
+    tx.send(event(7)).unwrap();
+
+    let requests = wait_for(&receiver, 1);
+    assert_eq!(*receiver.attempts.lock().unwrap(), 2);
+    assert_eq!(requests[0].0["x-scope-orgid"], "tenant-1");
+    let record = &requests[0].1.resource_logs[0].scope_logs[0].log_records[0];
+    assert_eq!(attribute(&record.attributes, "log.record.uid"), Some(&Value::StringValue(format!("{:032x}", 7))));
+}
+
+#[test]
+fn test_grpc_invalid_argument_is_not_retried() {
+    let (addr, receiver) = serve(vec![Failure::Grpc(tonic::Code::InvalidArgument)], true);
+    let extra = "protocol = \"grpc\"\nbatch = { max_events = 1 }\nretry = { initial_backoff_ms = 1 }";
+    let tx = OtlpSink::new(config(addr, extra), host()).unwrap().spawn();
+    tx.send(event(1)).unwrap();
+    tx.send(event(2)).unwrap();
+
+    // The first batch is dropped after one attempt; the second goes through.
+    let requests = wait_for(&receiver, 1);
+    assert_eq!(*receiver.attempts.lock().unwrap(), 2);
+    let record = &requests[0].1.resource_logs[0].scope_logs[0].log_records[0];
+    assert_eq!(attribute(&record.attributes, "audit.sequence"), Some(&Value::IntValue(2)));
+}
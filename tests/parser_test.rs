@@ -0,0 +1,184 @@
+use audit_collector::collector::Collector;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::parser::{EventParser, ParserRegistry, SourceFormat};
+use audit_collector::source::MockAuditSource;
+use anyhow::Result;
+use crossbeam_channel::unbounded;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const WINDOWS_4624: &str = r#"<Event xmlns='http://schemas.microsoft.com/win/2004/08/events/event'><System><Provider Name='Microsoft-Windows-Security-Auditing' Guid='{54849625-5478-4994-A5BA-3E3B0328C30D}'/><EventID>4624</EventID><Version>2</Version><Level>0</Level><Task>12544</Task><Opcode>0</Opcode><Keywords>0x8020000000000000</Keywords><TimeCreated SystemTime='2024-03-01T12:34:56.7890123Z'/><EventRecordID>987654</EventRecordID><Correlation/><Execution ProcessID='788' ThreadID='1234'/><Channel>Security</Channel><Computer>DC01.corp.example.com</Computer><Security/></System><EventData><Data Name='SubjectUserSid'>S-1-5-18</Data><Data Name='TargetUserName'>alice &amp; bob</Data><Data Name='LogonType'>10</Data><Data Name='IpAddress'>10.1.2.3</Data></EventData></Event>"#;
+
+fn parse(format: SourceFormat, raw: &str) -> Result<AuditEvent> {
+    ParserRegistry::default().parser_for(&format)?.parse(raw.as_bytes())
+}
+
+fn field<'a>(event: &'a AuditEvent, key: &str) -> Option<&'a FieldValue> {
+    event.fields.get(key)
+}
+
+#[test]
+fn test_registry_has_builtin_formats() {
+    let registry = ParserRegistry::default();
+    for format in [
+        SourceFormat::Auto,
+        SourceFormat::Auditd,
+        SourceFormat::MacJson,
+        SourceFormat::WindowsXml,
+        SourceFormat::Syslog,
+        SourceFormat::Journald,
+        SourceFormat::Line,
+    ] {
+        let parser = registry.parser_for(&format).unwrap();
+        assert_eq!(parser.name(), format.name());
+    }
+    assert!(registry.parser_for(&SourceFormat::Custom { name: "nope".to_string() }).is_err());
+}
+
+#[test]
+fn test_auditd_parser_rejects_other_formats() {
+    let event = parse(SourceFormat::Auditd, "type=1300 msg=audit(1674390000.123:100): pid=9 comm=\"cat\"").unwrap();
+    assert_eq!(event.record_type, 1300);
+    assert_eq!(event.sequence, 100);
+    assert!(parse(SourceFormat::Auditd, "hello world").is_err());
+}
+
+#[test]
+fn test_mac_json_parser() {
+    let event = parse(
+        SourceFormat::MacJson,
+        r#"{"processImagePath":"/usr/libexec/sshd","processID":42,"eventMessage":"hello","subsystem":"com.apple.x"}"#,
+    )
+    .unwrap();
+    assert_eq!(field(&event, "process").unwrap(), "/usr/libexec/sshd");
+    assert_eq!(field(&event, "pid"), Some(&FieldValue::UInt(42)));
+    assert!(parse(SourceFormat::MacJson, "{ not json").is_err());
+}
+
+#[test]
+fn test_windows_xml_parser() {
+    let event = parse(SourceFormat::WindowsXml, WINDOWS_4624).unwrap();
+    assert_eq!(event.record_type, 4624);
+    assert_eq!(event.sequence, 987654);
+    assert_eq!(event.timestamp.to_rfc3339(), "2024-03-01T12:34:56.789012300+00:00");
+    assert_eq!(field(&event, "event_id"), Some(&FieldValue::UInt(4624)));
+    assert_eq!(field(&event, "provider").unwrap(), "Microsoft-Windows-Security-Auditing");
+    assert_eq!(field(&event, "computer").unwrap(), "DC01.corp.example.com");
+    assert_eq!(field(&event, "channel").unwrap(), "Security");
+    assert_eq!(field(&event, "pid"), Some(&FieldValue::UInt(788)));
+    assert_eq!(field(&event, "TargetUserName").unwrap(), "alice & bob");
+    assert_eq!(field(&event, "LogonType").unwrap(), "10");
+    assert!(parse(SourceFormat::WindowsXml, "<Event><System></System></Event>").is_err());
+}
+
+#[test]
+fn test_journald_parser() {
+    let event = parse(
+        SourceFormat::Journald,
+        r#"{"__REALTIME_TIMESTAMP":"1674390000123456","MESSAGE":"Accepted password for alice from 10.0.0.5 port 51234 ssh2","_PID":"1201","_COMM":"sshd","SYSLOG_IDENTIFIER":"sshd","_HOSTNAME":"web01","PRIORITY":"6"}"#,
+    )
+    .unwrap();
+    assert_eq!(event.timestamp.timestamp_micros(), 1674390000123456);
+    assert_eq!(field(&event, "pid"), Some(&FieldValue::UInt(1201)));
+    assert_eq!(field(&event, "comm").unwrap(), "sshd");
+    assert_eq!(field(&event, "hostname").unwrap(), "web01");
+    assert_eq!(field(&event, "PRIORITY").unwrap(), "6");
+    assert_eq!(field(&event, "action").unwrap(), "ssh_login");
+    assert_eq!(field(&event, "user").unwrap(), "alice");
+
+    let event = parse(SourceFormat::Journald, r#"{"MESSAGE":[104,105]}"#).unwrap();
+    assert_eq!(field(&event, "message").unwrap(), "hi");
+    assert!(parse(SourceFormat::Journald, "[1,2]").is_err());
+}
+
+#[test]
+fn test_syslog_and_line_parsers() {
+    let event = parse(SourceFormat::Syslog, "<38>Jan 22 10:15:01 web01 sshd[1201]: Invalid user bob from 10.9.9.9 port 2222").unwrap();
+    assert_eq!(field(&event, "facility").unwrap(), "auth");
+    assert_eq!(field(&event, "action").unwrap(), "ssh_invalid_user");
+    assert!(parse(SourceFormat::Syslog, "free text").is_err());
+
+    let event = parse(SourceFormat::Line, "free text").unwrap();
+    assert_eq!(field(&event, "message").unwrap(), "free text");
+}
+
+#[test]
+fn test_regex_parser() {
+    let format = SourceFormat::Regex { pattern: r"^(?P<level>\w+) user=(?P<user>\S+)".to_string() };
+    let event = parse(format.clone(), "WARN user=alice logged in").unwrap();
+    assert_eq!(field(&event, "level").unwrap(), "WARN");
+    assert_eq!(field(&event, "user").unwrap(), "alice");
+    assert_eq!(field(&event, "message").unwrap(), "WARN user=alice logged in");
+    assert!(parse(format, "no match").is_err());
+
+    let registry = ParserRegistry::default();
+    assert!(registry.parser_for(&SourceFormat::Regex { pattern: "(unclosed".to_string() }).is_err());
+    assert!(registry.parser_for(&SourceFormat::Regex { pattern: "no groups".to_string() }).is_err());
+}
+
+struct UpperParser;
+
+impl EventParser for UpperParser {
+    fn name(&self) -> &str {
+        "upper"
+    }
+
+    fn parse(&self, raw: &[u8]) -> Result<AuditEvent> {
+        let mut event = AuditEvent::new(7, 0);
+        event.fields = HashMap::from([("message".to_string(), String::from_utf8_lossy(raw).to_uppercase().into())]);
+        Ok(event)
+    }
+}
+
+#[test]
+fn test_custom_parser_registration() {
+    let mut registry = ParserRegistry::default();
+    registry.register("upper", Arc::new(UpperParser));
+
+    let source = Arc::new(MockAuditSource::with_format(
+        vec![b"shout".to_vec()],
+        SourceFormat::Custom { name: "upper".to_string() },
+    ));
+    let (tx, rx) = unbounded();
+    let collector = Collector::with_registry(source, tx, Arc::new(registry));
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+
+    let event = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event");
+    assert_eq!(event.record_type, 7);
+    assert_eq!(field(&event, "message").unwrap(), "SHOUT");
+}
+
+#[test]
+fn test_parse_failures_are_counted_and_dead_lettered() {
+    let source = Arc::new(MockAuditSource::with_format(
+        vec![
+            b"not an audit record".to_vec(),
+            b"type=1300 msg=audit(1674390000.123:5): pid=1".to_vec(),
+        ],
+        SourceFormat::Auditd,
+    ));
+    let (tx, rx) = unbounded();
+    let (tx_dead, rx_dead) = unbounded();
+    let collector = Collector::new(source, tx).with_dead_letter(tx_dead);
+    let stats = collector.stats();
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+
+    let dead = rx_dead.recv_timeout(Duration::from_secs(2)).expect("Failed to receive dead letter");
+    assert_eq!(dead.parser, "auditd");
+    assert_eq!(dead.raw, "not an audit record");
+    assert!(dead.error.contains("Not an auditd record"));
+
+    let event = rx.recv_timeout(Duration::from_secs(2)).expect("Failed to receive event");
+    assert_eq!(event.sequence, 5);
+
+    let snapshot = stats.snapshot();
+    assert_eq!(snapshot.received, 2);
+    assert_eq!(snapshot.parsed, 1);
+    assert_eq!(snapshot.parse_failures, 1);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::AuditEvent;
+use audit_collector::output::{Sink, SplunkSink, SplunkSinkConfig};
+use axum::body::Bytes;
+use axum::extract::{Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A fake HTTP Event Collector. Every batch gets the next ack ID; the first
+/// `drop_acks` of them are never acknowledged, as if the indexer had lost them.
+#[derive(Default)]
+struct Hec {
+    token: String,
+    drop_acks: u64,
+    next_ack: Mutex<u64>,
+    batches: Mutex<Vec<(HeaderMap, Vec<Value>)>>,
+    channels: Mutex<Vec<String>>,
+    forbidden: Mutex<usize>,
+}
+
+async fn event(State(hec): State<Arc<Hec>>, headers: HeaderMap, body: Bytes) -> (StatusCode, Json<Value>) {
+    if headers.get("authorization").and_then(|v| v.to_str().ok()) != Some(&format!("Splunk {}", hec.token)) {
+        *hec.forbidden.lock().unwrap() += 1;
+        return (StatusCode::FORBIDDEN, Json(json!({ "text": "Invalid token", "code": 4 })));
+    }
+    let text = String::from_utf8(body.to_vec()).unwrap();
+    let events = serde_json::Deserializer::from_str(&text).into_iter::<Value>().map(|v| v.unwrap()).collect();
+    hec.batches.lock().unwrap().push((headers.clone(), events));
+    if !headers.contains_key("x-splunk-request-channel") {
+        return (StatusCode::OK, Json(json!({ "text": "Success", "code": 0 })));
+    }
+    let mut next = hec.next_ack.lock().unwrap();
+    let ack_id = *next;
+    *next += 1;
+    (StatusCode::OK, Json(json!({ "text": "Success", "code": 0, "ackId": ack_id })))
+}
+
+async fn ack(
+    State(hec): State<Arc<Hec>>,
+    Query(query): Query<HashMap<String, String>>,
+    Json(body): Json<Value>,
+) -> Json<Value> {
+    hec.channels.lock().unwrap().push(query["channel"].clone());
+    let acks: serde_json::Map<String, Value> = body["acks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|id| (id.to_string(), (id.as_u64().unwrap() >= hec.drop_acks).into()))
+        .collect();
+    Json(json!({ "acks": acks }))
+}
+
+fn serve(hec: Hec) -> (SocketAddr, Arc<Hec>) {
+    let hec = Arc::new(hec);
+    let app = Router::new()
+        .route("/services/collector/event", post(event))
+        .route("/services/collector/ack", post(ack))
+        .with_state(hec.clone());
+    (common::serve_app(app), hec)
+}
+
+fn config(addr: SocketAddr, extra: &str) -> SplunkSinkConfig {
+    let text = format!("[[output.splunk]]\nurl = \"http://{}/\"\ntoken = \"t0ken\"\n{}", addr, extra);
+    AppConfig::parse(&text).unwrap().output.splunk.remove(0)
+}
+
+fn audit_event(sequence: u64) -> AuditEvent {
+    let mut event = AuditEvent::new(1300, sequence);
+    event.id = format!("{:032x}", sequence);
+    event.timestamp = "2026-01-02T03:04:05.250Z".parse().unwrap();
+    event.fields.insert("event.module".to_string(), "auditd".into());
+    event.fields.insert("host.name".to_string(), "web-1".into());
+    event
+}
+
+fn wait_for(hec: &Hec, count: usize) -> Vec<(HeaderMap, Vec<Value>)> {
+    common::wait_for(&format!("{} batches", count), || hec.batches.lock().unwrap().len() >= count);
+    hec.batches.lock().unwrap().clone()
+}
+
+#[test]
+fn test_envelope_carries_time_host_and_sourcetype() {
+    let addr: SocketAddr = "127.0.0.1:9".parse().unwrap();
+    let sink = SplunkSink::new(config(addr, "index = \"audit\""), Arc::new(HostMetadata::default())).unwrap();
+    let envelope = sink.envelope(&audit_event(7));
+    assert_eq!(envelope["time"], 1767323045.25);
+    assert_eq!(envelope["host"], "web-1");
+    assert_eq!(envelope["source"], "audit_collector:auditd");
+    assert_eq!(envelope["sourcetype"], "linux:audit");
+    assert_eq!(envelope["index"], "audit");
+    assert_eq!(envelope["event"]["sequence"], 7);
+
+    let sink = SplunkSink::new(config(addr, "sourcetype = \"custom\""), Arc::new(HostMetadata::default())).unwrap();
+    let envelope = sink.envelope(&audit_event(7));
+    assert_eq!(envelope["sourcetype"], "custom");
+    assert!(envelope.get("index").is_none());
+}
+
+#[test]
+fn test_batches_are_sent_with_token() {
+    let (addr, hec) = serve(Hec { token: "t0ken".to_string(), ..Default::default() });
+    let tx = SplunkSink::new(config(addr, "batch = { max_events = 2 }"), Arc::new(HostMetadata::default()))
+        .unwrap()
+        .spawn();
+    for i in 0..4 {
+        tx.send(audit_event(i)).unwrap();
+    }
+
+    let batches = wait_for(&hec, 2);
+    assert_eq!(batches[0].1.len(), 2);
+    assert_eq!(batches[1].1[1]["event"]["sequence"], 3);
+    assert!(!batches[0].0.contains_key("x-splunk-request-channel"));
+}
+
+#[test]
+fn test_acknowledged_batches_are_not_resent() {
+    let (addr, hec) = serve(Hec { token: "t0ken".to_string(), ..Default::default() });
+    let extra = "ack = true\nchannel = \"0b7b3ab6-4c1f-4b8e-9a52-0f7c2f1d5e11\"\nack_poll_interval_ms = 10";
+    let sink = SplunkSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap();
+    let client = reqwest::blocking::Client::new();
+
+    sink.deliver(&client, &[audit_event(1), audit_event(2)]).unwrap();
+    let batches = hec.batches.lock().unwrap();
+    assert_eq!(batches.len(), 1);
+    assert_eq!(batches[0].0["x-splunk-request-channel"], "0b7b3ab6-4c1f-4b8e-9a52-0f7c2f1d5e11");
+    assert_eq!(hec.channels.lock().unwrap()[0], "0b7b3ab6-4c1f-4b8e-9a52-0f7c2f1d5e11");
+}
+
+#[test]
+fn test_unacknowledged_batch_is_resent() {
+    let (addr, hec) = serve(Hec { token: "t0ken".to_string(), drop_acks: 1, ..Default::default() });
+    let extra = "ack = true\nack_poll_interval_ms = 10\nack_timeout_ms = 100";
+    let sink = SplunkSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap();
+    let client = reqwest::blocking::Client::new();
+
+    sink.deliver(&client, &[audit_event(1)]).unwrap();
+    let batches = hec.batches.lock().unwrap();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].1, batches[1].1);
+    // Without a configured channel, a random UUID is used for every request.
+    let channel = batches[0].0["x-splunk-request-channel"].to_str().unwrap();
+    assert_eq!(channel.len(), 36);
+    assert_eq!(batches[1].0["x-splunk-request-channel"], channel);
+}
+
+#[test]
+fn test_bad_token_is_dead_lettered_without_retries() {
+    let dir = tempfile::tempdir().unwrap();
+    let (addr, hec) = serve(Hec { token: "other".to_string(), ..Default::default() });
+    let extra = format!("dead_letter_dir = {:?}\nbatch = {{ max_events = 1 }}", dir.path().to_str().unwrap());
+    let tx = SplunkSink::new(config(addr, &extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(audit_event(1)).unwrap();
+
+    common::wait_for("a dead letter", || std::fs::read_dir(dir.path()).map(|d| d.count()).unwrap_or(0) > 0);
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(*hec.forbidden.lock().unwrap(), 1);
+    assert!(hec.batches.lock().unwrap().is_empty());
+}
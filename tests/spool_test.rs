@@ -0,0 +1,311 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::AuditEvent;
+use audit_collector::output::spool::{Spool, SpoolConfig};
+use audit_collector::output::{Sink, SinkStats, WebhookSink, WebhookSinkConfig};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+fn spool_config(dir: &Path, max_bytes: u64, segment_bytes: u64) -> SpoolConfig {
+    SpoolConfig { dir: dir.to_path_buf(), max_bytes, segment_bytes }
+}
+
+fn segments(dir: &Path) -> usize {
+    fs::read_dir(dir).unwrap().filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|x| x == "wal")).count()
+}
+
+fn sequences(events: &[AuditEvent]) -> Vec<u64> {
+    events.iter().map(|e| e.sequence).collect()
+}
+
+#[test]
+fn test_spool_recovers_unacknowledged_events_in_order() {
+    let dir = tempfile::tempdir().unwrap();
+    // Small segments, so that ten events span several of them.
+    let config = spool_config(dir.path(), 1 << 20, 600);
+    let mut spool = Spool::open(config.clone()).unwrap();
+    for sequence in 0..10 {
+        assert!(spool.append(&common::event(sequence)).unwrap());
+    }
+    let before = segments(dir.path());
+    assert!(before > 2, "{} segments", before);
+
+    assert_eq!(sequences(&spool.read(4).unwrap()), vec![0, 1, 2, 3]);
+    spool.ack().unwrap();
+    // Read but never acknowledged, as if the process died mid-send.
+    assert_eq!(sequences(&spool.read(3).unwrap()), vec![4, 5, 6]);
+    assert_eq!((spool.len(), spool.unread()), (6, 3));
+    assert!(segments(dir.path()) < before);
+    drop(spool);
+
+    // A write torn by the crash is cut off.
+    let last = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().path()).filter(|p| p.extension().is_some_and(|x| x == "wal")).max().unwrap();
+    let intact = fs::metadata(&last).unwrap().len();
+    OpenOptions::new().append(true).open(&last).unwrap().write_all(&[200, 0, 0, 0, 1, 2, 3]).unwrap();
+
+    let mut spool = Spool::open(config.clone()).unwrap();
+    assert_eq!(fs::metadata(&last).unwrap().len(), intact);
+    assert_eq!(spool.len(), 6);
+    assert!(spool.append(&common::event(10)).unwrap());
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![4, 5, 6, 7, 8, 9, 10]);
+    spool.ack().unwrap();
+
+    // Caught up: nothing is left on disk, and that survives a restart too.
+    assert_eq!((spool.len(), spool.bytes(), segments(dir.path())), (0, 0, 0));
+    drop(spool);
+    let mut spool = Spool::open(config).unwrap();
+    assert!(spool.is_empty());
+    assert!(spool.append(&common::event(11)).unwrap());
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![11]);
+}
+
+#[test]
+fn test_spool_refuses_events_beyond_max_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut spool = Spool::open(spool_config(dir.path(), 1000, 300)).unwrap();
+    let accepted = (0..20).take_while(|s| spool.append(&common::event(*s)).unwrap()).count();
+    assert!(accepted > 1 && accepted < 20, "{} accepted", accepted);
+    assert!(spool.bytes() <= 1000);
+    assert!(!spool.append(&common::event(99)).unwrap());
+
+    // Acknowledging frees the space again.
+    spool.read(100).unwrap();
+    spool.ack().unwrap();
+    assert!(spool.append(&common::event(99)).unwrap());
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![99]);
+}
+
+/// The only segment file in `dir`.
+fn segment(dir: &Path) -> PathBuf {
+    let mut paths = fs::read_dir(dir).unwrap().map(|e| e.unwrap().path()).filter(|p| p.extension().is_some_and(|x| x == "wal"));
+    let path = paths.next().unwrap();
+    assert!(paths.next().is_none());
+    path
+}
+
+#[test]
+fn test_spool_skips_past_damage() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut spool = Spool::open(spool_config(dir.path(), 1 << 20, 1 << 20)).unwrap();
+    for sequence in 0..6 {
+        spool.append(&common::event(sequence)).unwrap();
+    }
+    // Flip a byte in the third record's payload.
+    let path = segment(dir.path());
+    let mut bytes = fs::read(&path).unwrap();
+    let mut offset = 0;
+    for _ in 0..2 {
+        offset += 8 + u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize;
+    }
+    bytes[offset + 8] ^= 0xff;
+    fs::write(&path, bytes).unwrap();
+
+    // What comes before the damage is read, then the damage is an error.
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![0, 1]);
+    spool.ack().unwrap();
+    assert!(spool.read(100).is_err());
+    assert!(spool.read(100).is_err());
+
+    // Its length still holds, so only the record itself is lost.
+    assert_eq!(spool.skip_damaged().unwrap(), 1);
+    assert_eq!(spool.len(), 3);
+    assert!(spool.append(&common::event(6)).unwrap());
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![3, 4, 5, 6]);
+    spool.ack().unwrap();
+    assert!(!path.exists());
+}
+
+#[test]
+fn test_spool_keeps_records_after_damage_when_reopened() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = spool_config(dir.path(), 1 << 20, 1 << 20);
+    let mut spool = Spool::open(config.clone()).unwrap();
+    for sequence in 0..6 {
+        spool.append(&common::event(sequence)).unwrap();
+    }
+    drop(spool);
+    // Flip a byte in the second record's payload.
+    let path = segment(dir.path());
+    let mut bytes = fs::read(&path).unwrap();
+    let offset = 8 + u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+    bytes[offset + 8] ^= 0xff;
+    fs::write(&path, &bytes).unwrap();
+
+    // Only a torn tail is cut off on opening; the damaged record stays, and so
+    // do the intact ones after it.
+    let mut spool = Spool::open(config).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), bytes.len() as u64);
+    assert_eq!(spool.len(), 6);
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![0]);
+    spool.ack().unwrap();
+    assert!(spool.read(100).is_err());
+    assert_eq!(spool.skip_damaged().unwrap(), 1);
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![2, 3, 4, 5]);
+    spool.ack().unwrap();
+    assert_eq!(spool.len(), 0);
+}
+
+#[test]
+fn test_spool_takes_lengths_past_the_segment_for_damage() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut spool = Spool::open(spool_config(dir.path(), 1 << 20, 1 << 20)).unwrap();
+    for sequence in 0..4 {
+        spool.append(&common::event(sequence)).unwrap();
+    }
+    // Claim the largest length possible for the second record.
+    let path = segment(dir.path());
+    let mut bytes = fs::read(&path).unwrap();
+    let offset = 8 + u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+    bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(&path, bytes).unwrap();
+
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![0]);
+    spool.ack().unwrap();
+    assert!(spool.read(100).is_err());
+    assert_eq!(spool.skip_damaged().unwrap(), 3);
+    assert_eq!(spool.len(), 0);
+}
+
+#[test]
+fn test_spool_skips_damaged_earlier_segments_when_reopened() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = spool_config(dir.path(), 1 << 20, 600);
+    let mut spool = Spool::open(config.clone()).unwrap();
+    for sequence in 0..10 {
+        spool.append(&common::event(sequence)).unwrap();
+    }
+    drop(spool);
+    // Claim the largest length possible for the second record of the first segment.
+    let first = fs::read_dir(dir.path()).unwrap().map(|e| e.unwrap().path()).filter(|p| p.extension().is_some_and(|x| x == "wal")).min().unwrap();
+    let mut bytes = fs::read(&first).unwrap();
+    let offset = 8 + u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+    let in_first = {
+        let (mut count, mut at) = (0, 0);
+        while at < bytes.len() {
+            at += 8 + u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as usize;
+            count += 1;
+        }
+        count
+    };
+    assert!(in_first > 2, "{} records in the first segment", in_first);
+    bytes[offset..offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    fs::write(&first, &bytes).unwrap();
+
+    // Not the segment being written, so it is left whole and the damage counts
+    // as one event; the records after it in that segment are lost.
+    let mut spool = Spool::open(config).unwrap();
+    assert_eq!(fs::metadata(&first).unwrap().len(), bytes.len() as u64);
+    assert_eq!(spool.len(), 10 - in_first + 2);
+    assert_eq!(sequences(&spool.read(100).unwrap()), vec![0]);
+    spool.ack().unwrap();
+    assert!(spool.read(100).is_err());
+    assert_eq!(spool.skip_damaged().unwrap(), 1);
+    assert_eq!(sequences(&spool.read(100).unwrap()), (in_first as u64..10).collect::<Vec<_>>());
+    spool.ack().unwrap();
+    assert_eq!(spool.len(), 0);
+}
+
+fn webhook_config(addr: SocketAddr, dir: &Path) -> WebhookSinkConfig {
+    let text = format!(
+        r#"
+        [[output.webhook]]
+        url = "http://{}/hook"
+        dead_letter_dir = "{}/dead"
+        batch = {{ max_events = 2, flush_interval_ms = 50 }}
+        retry = {{ max_attempts = 2, initial_backoff_ms = 10, max_backoff_ms = 20 }}
+        spool = {{ dir = "{}/spool" }}
+        "#,
+        addr,
+        dir.display(),
+        dir.display()
+    );
+    AppConfig::parse(&text).unwrap().output.webhook.remove(0)
+}
+
+/// Waits until `events` events were accepted, and returns the sequences of
+/// each accepted request.
+fn wait_for(stub: &common::Stub, events: usize) -> Vec<Vec<u64>> {
+    let accepted = || -> Vec<Vec<u64>> {
+        let bodies = stub.accepted.lock().unwrap();
+        bodies.iter().map(|body| sequences(&serde_json::from_slice::<Vec<AuditEvent>>(body).unwrap())).collect()
+    };
+    common::wait_for(&format!("{} events", events), || accepted().iter().map(Vec::len).sum::<usize>() >= events);
+    accepted()
+}
+
+#[test]
+fn test_webhook_spools_through_an_outage() {
+    let dir = tempfile::tempdir().unwrap();
+    // Far more failures than `max_attempts` allows without a spool.
+    let (addr, stub) = common::serve("/hook", &[503; 8]);
+    let config = webhook_config(addr, dir.path());
+    assert_eq!(config.spool.as_ref().unwrap().max_bytes, 1 << 30);
+    let stats = Arc::new(SinkStats::default());
+    let tx = WebhookSink::new(config, Arc::new(HostMetadata::default())).unwrap().with_stats(stats.clone()).spawn();
+    for sequence in 0..6 {
+        tx.send(common::event(sequence)).unwrap();
+    }
+
+    let accepted = wait_for(&stub, 6);
+    assert_eq!(accepted, vec![vec![0, 1], vec![2, 3], vec![4, 5]]);
+    assert!(stub.statuses.lock().unwrap().is_empty());
+    assert!(!dir.path().join("dead").exists());
+
+    common::wait_for("the spool to drain", || stats.spooled.load(Ordering::Relaxed) == 0);
+    assert_eq!(stats.delivered.load(Ordering::Relaxed), 6);
+    assert_eq!(stats.failed.load(Ordering::Relaxed), 0);
+    assert_eq!(stats.spooled_bytes.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn test_webhook_replays_spool_left_by_previous_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let (addr, stub) = common::serve("/hook", &[]);
+    let config = webhook_config(addr, dir.path());
+
+    let mut spool = Spool::open(config.spool.clone().unwrap()).unwrap();
+    for sequence in 0..3 {
+        spool.append(&common::event(sequence)).unwrap();
+    }
+    drop(spool);
+
+    let tx = WebhookSink::new(config, Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(common::event(3)).unwrap();
+    assert_eq!(wait_for(&stub, 4), vec![vec![0, 1], vec![2, 3]]);
+}
+
+#[test]
+fn test_webhook_delivers_past_an_undecodable_record() {
+    let dir = tempfile::tempdir().unwrap();
+    let (addr, stub) = common::serve("/hook", &[]);
+    let config = webhook_config(addr, dir.path());
+    let spool_dir = config.spool.as_ref().unwrap().dir.clone();
+
+    let mut spool = Spool::open(config.spool.clone().unwrap()).unwrap();
+    for sequence in 0..3 {
+        spool.append(&common::event(sequence)).unwrap();
+    }
+    drop(spool);
+    // Intact and checksummed, but not an event.
+    let payload = br#"{"not":"an event"}"#;
+    let mut record = (payload.len() as u32).to_le_bytes().to_vec();
+    record.extend_from_slice(&crc32fast::hash(payload).to_le_bytes());
+    record.extend_from_slice(payload);
+    OpenOptions::new().append(true).open(segment(&spool_dir)).unwrap().write_all(&record).unwrap();
+    let mut spool = Spool::open(config.spool.clone().unwrap()).unwrap();
+    spool.append(&common::event(3)).unwrap();
+    drop(spool);
+
+    let stats = Arc::new(SinkStats::default());
+    let tx = WebhookSink::new(config, Arc::new(HostMetadata::default())).unwrap().with_stats(stats.clone()).spawn();
+    tx.send(common::event(4)).unwrap();
+    let delivered: Vec<u64> = wait_for(&stub, 5).concat();
+    assert_eq!(delivered, vec![0, 1, 2, 3, 4]);
+    assert_eq!(stats.failed.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,118 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::output::sse::Delivery;
+use audit_collector::output::{EventStream, SseClients};
+use futures::{Stream, StreamExt};
+use std::sync::Arc;
+
+/// The stream id and sequence of the next event.
+async fn next(stream: &mut (impl Stream<Item = Delivery> + Unpin)) -> (u64, u64) {
+    match stream.next().await {
+        Some(Delivery::Event(e)) => (e.id, e.event.sequence),
+        other => panic!("expected an event, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn test_lagging_client_is_told_and_carries_on() {
+    let events = Arc::new(EventStream::new(4, 0));
+    let clients = Arc::new(SseClients::default());
+    let mut stream = Box::pin(clients.connect().deliveries(events.subscribe(None)));
+
+    assert!(events.publish(common::event(0)));
+    assert_eq!(next(&mut stream).await.1, 0);
+
+    // Ten more than the channel holds: the oldest six are skipped.
+    for s in 1..=10 {
+        events.publish(common::event(s));
+    }
+    assert_eq!(stream.next().await, Some(Delivery::Lagged(6)));
+    assert_eq!(next(&mut stream).await.1, 7);
+
+    let snapshot = clients.snapshot();
+    assert_eq!(snapshot.len(), 1);
+    assert_eq!((snapshot[0].sent, snapshot[0].pending), (2, 3));
+    assert_eq!((snapshot[0].lagged, snapshot[0].skipped), (1, 6));
+
+    for s in 8..=10 {
+        assert_eq!(next(&mut stream).await.1, s);
+    }
+    drop(events);
+    assert_eq!(stream.next().await, None);
+
+    // Disconnected clients are no longer listed.
+    drop(stream);
+    assert!(clients.snapshot().is_empty());
+}
+
+#[tokio::test]
+async fn test_clients_are_tracked_separately() {
+    let events = EventStream::new(2, 0);
+    let clients = Arc::new(SseClients::default());
+    let mut fast = Box::pin(clients.connect().deliveries(events.subscribe(None)));
+    let slow = Box::pin(clients.connect().deliveries(events.subscribe(None)));
+
+    for s in 0..5 {
+        events.publish(common::event(s));
+        assert_eq!(next(&mut fast).await.1, s);
+    }
+    let snapshot = clients.snapshot();
+    let ids: Vec<u64> = snapshot.iter().map(|c| c.id).collect();
+    assert_eq!(ids, vec![0, 1]);
+    assert_eq!((snapshot[0].sent, snapshot[0].lagged), (5, 0));
+    // The slow client has not been polled yet; its lag shows once it is.
+    assert_eq!((snapshot[1].sent, snapshot[1].lagged), (0, 0));
+    drop(slow);
+    assert_eq!(clients.snapshot().len(), 1);
+}
+
+#[tokio::test]
+async fn test_reconnecting_client_gets_missed_events_then_live_ones() {
+    let events = EventStream::new(100, 5);
+    let clients = Arc::new(SseClients::default());
+    // Nobody is listening yet, but the events are kept.
+    assert!(!events.publish(common::event(0)));
+
+    let mut first = Box::pin(clients.connect().deliveries(events.subscribe(None)));
+    events.publish(common::event(1));
+    let (id, _) = next(&mut first).await;
+    drop(first);
+
+    // Published while the client was away.
+    for s in 2..5 {
+        events.publish(common::event(s));
+    }
+    let mut resumed = Box::pin(clients.connect().deliveries(events.subscribe(Some(id))));
+    events.publish(common::event(5));
+    for s in 2..=5 {
+        assert_eq!(next(&mut resumed).await, (id + s - 1, s));
+    }
+
+    // Ids are consecutive; an id evicted from the ring is reported as missed.
+    for s in 6..12 {
+        events.publish(common::event(s));
+    }
+    let mut late = Box::pin(clients.connect().deliveries(events.subscribe(Some(id))));
+    assert_eq!(late.next().await, Some(Delivery::Lagged(5)));
+    assert_eq!(next(&mut late).await, (id + 6, 7));
+
+    // Ids from another run replay everything kept, without a lagged event.
+    let mut stale = Box::pin(clients.connect().deliveries(events.subscribe(Some(1))));
+    assert_eq!(next(&mut stale).await.1, 7);
+    let mut future = Box::pin(clients.connect().deliveries(events.subscribe(Some(u64::MAX))));
+    assert_eq!(next(&mut future).await.1, 7);
+
+    // Caught up: nothing to replay, straight to live.
+    let mut current = Box::pin(clients.connect().deliveries(events.subscribe(Some(id + 10))));
+    events.publish(common::event(12));
+    assert_eq!(next(&mut current).await, (id + 11, 12));
+}
+
+#[test]
+fn test_stream_settings_are_configurable() {
+    let config = AppConfig::parse("[output.sse]\ncapacity = 5000\nreplay = 20\n").unwrap();
+    assert_eq!((config.output.sse.capacity, config.output.sse.replay), (5000, 20));
+    let default = AppConfig::default().output.sse;
+    assert_eq!((default.capacity, default.replay), (100, 1000));
+}
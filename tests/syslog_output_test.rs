@@ -0,0 +1,222 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::{Sink, SinkStats, SyslogSink, SyslogSinkConfig};
+use audit_collector::source::syslog::SyslogTlsConfig;
+use audit_collector::source::{AuditSource, SyslogConfig, SyslogSource};
+use audit_collector::syslog::{self, FrameDecoder, SdElement, SyslogMessage};
+use crossbeam_channel::TrySendError;
+use std::io::Read;
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn config(extra: &str) -> SyslogSinkConfig {
+    let text = format!("[[output.syslog]]\n{}", extra);
+    AppConfig::parse(&text).unwrap().output.syslog.remove(0)
+}
+
+fn host() -> Arc<HostMetadata> {
+    Arc::new(HostMetadata { hostname: "web01".to_string(), ..Default::default() })
+}
+
+fn event(sequence: u64) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.fields.insert("message".to_string(), format!("event {}", sequence).into());
+    event.fields.insert("pid".to_string(), FieldValue::UInt(4242));
+    event.fields.insert("exe".to_string(), "/usr/bin/\"odd\" name]".into());
+    event.fields.insert("event.outcome".to_string(), "failure".into());
+    event
+}
+
+/// Reads octet-counted frames from `stream` until `count` have arrived.
+fn read_frames(stream: &mut TcpStream, count: usize) -> Vec<SyslogMessage> {
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut decoder = FrameDecoder::new();
+    let mut messages = Vec::new();
+    let mut buf = [0u8; 4096];
+    while messages.len() < count {
+        let n = stream.read(&mut buf).expect("Timed out waiting for syslog frames");
+        assert!(n > 0, "connection closed early");
+        decoder.push(&buf[..n]);
+        while let Some(frame) = decoder.next_frame() {
+            messages.push(syslog::parse(&String::from_utf8(frame).unwrap()).unwrap());
+        }
+    }
+    messages
+}
+
+#[test]
+fn test_rfc5424_round_trip() {
+    let message = SyslogMessage {
+        facility: 10,
+        severity: 4,
+        version: Some(1),
+        timestamp: Some("2026-01-02T03:04:05.123456Z".parse().unwrap()),
+        hostname: Some("web 01".to_string()),
+        app_name: None,
+        procid: Some("42".to_string()),
+        msgid: Some("x".repeat(40)),
+        structured_data: vec![SdElement {
+            id: "audit@32473".to_string(),
+            params: vec![("a=b".to_string(), "q\"uo\\te]".to_string())],
+        }],
+        message: "hello world".to_string(),
+    };
+    let line = message.to_rfc5424();
+    assert!(line.starts_with("<84>1 2026-01-02T03:04:05.123456Z web01 - 42 "), "{}", line);
+
+    let parsed = syslog::parse(&line).unwrap();
+    assert_eq!(parsed.msgid.unwrap().len(), 32);
+    assert_eq!(parsed.structured_data[0].params, vec![("a_b".to_string(), "q\"uo\\te]".to_string())]);
+    assert_eq!(parsed.message, "hello world");
+}
+
+#[test]
+fn test_structured_data_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let tx = SyslogSink::new(config(&format!("address = \"{}\"", address)), host()).unwrap().spawn();
+    tx.send(event(1)).unwrap();
+    tx.send(event(2)).unwrap();
+
+    let (mut stream, _) = listener.accept().unwrap();
+    let messages = read_frames(&mut stream, 2);
+    let first = &messages[0];
+    assert_eq!((first.facility, first.severity), (10, 4));
+    assert_eq!(first.hostname.as_deref(), Some("web01"));
+    assert_eq!(first.app_name.as_deref(), Some("audit_collector"));
+    assert_eq!(first.procid.as_deref(), Some("4242"));
+    assert_eq!(first.msgid.as_deref(), Some("1300"));
+    assert_eq!(first.message, "event 1");
+
+    let sd = &first.structured_data[0];
+    assert_eq!(sd.id, "audit@32473");
+    let param = |name: &str| sd.params.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str());
+    assert_eq!(param("sequence"), Some("1"));
+    assert_eq!(param("exe"), Some("/usr/bin/\"odd\" name]"));
+    assert_eq!(param("message"), None);
+    assert_eq!(messages[1].message, "event 2");
+}
+
+#[test]
+fn test_json_body_over_udp() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let extra = format!(
+        "address = \"{}\"\ntransport = \"udp\"\nbody = \"json\"\nhost_metadata = true\nfacility = \"local3\"",
+        socket.local_addr().unwrap()
+    );
+    let tx = SyslogSink::new(config(&extra), host()).unwrap().spawn();
+    tx.send(event(7)).unwrap();
+
+    let mut buf = [0u8; 8192];
+    let n = socket.recv(&mut buf).unwrap();
+    let message = syslog::parse(std::str::from_utf8(&buf[..n]).unwrap()).unwrap();
+    assert_eq!(message.facility, 19);
+    assert!(message.structured_data.is_empty());
+    let body: serde_json::Value = serde_json::from_str(&message.message).unwrap();
+    assert_eq!(body["sequence"], 7);
+    assert_eq!(body["fields"]["host.hostname"], "web01");
+}
+
+#[test]
+fn test_oversized_datagram_is_dropped() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let extra = format!("address = \"{}\"\ntransport = \"udp\"", socket.local_addr().unwrap());
+    let stats = Arc::new(SinkStats::default());
+    let tx = SyslogSink::new(config(&extra), host()).unwrap().with_stats(stats.clone()).spawn();
+
+    let mut huge = event(1);
+    huge.fields.insert("cmdline".to_string(), "a".repeat(70_000).into());
+    tx.send(huge).unwrap();
+    tx.send(event(2)).unwrap();
+
+    // The long message is skipped rather than retried, so the next one gets through.
+    let mut buf = [0u8; 8192];
+    let n = socket.recv(&mut buf).unwrap();
+    assert_eq!(syslog::parse(std::str::from_utf8(&buf[..n]).unwrap()).unwrap().message, "event 2");
+    assert_eq!(stats.failed.load(Ordering::Relaxed), 1);
+    common::wait_for("the delivery to be counted", || stats.delivered.load(Ordering::Relaxed) == 1);
+}
+
+#[test]
+fn test_reconnects_and_bounds_queue() {
+    // Nothing listens yet: events queue up until the receiver appears.
+    let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let extra = format!("address = \"127.0.0.1:{}\"\nqueue_size = 4\nreconnect_min_ms = 20\nreconnect_max_ms = 100", port);
+    let tx = SyslogSink::new(config(&extra), host()).unwrap().spawn();
+
+    let mut sent = 0;
+    loop {
+        match tx.try_send(event(sent)) {
+            Ok(()) => sent += 1,
+            Err(TrySendError::Full(_)) => break,
+            Err(e) => panic!("{}", e),
+        }
+        assert!(sent < 100, "queue is not bounded");
+    }
+    assert!(sent <= 5, "one event in flight plus queue_size, got {}", sent);
+
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    let messages = read_frames(&mut stream, sent as usize);
+    let expected: Vec<String> = (0..sent).map(|i| format!("event {}", i)).collect();
+    assert_eq!(messages.iter().map(|m| m.message.clone()).collect::<Vec<_>>(), expected);
+
+    // The receiver goes away; the next event is delivered on a new connection.
+    drop(stream);
+    thread::sleep(Duration::from_millis(100));
+    tx.send(event(99)).unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    assert_eq!(read_frames(&mut stream, 1)[0].message, "event 99");
+}
+
+#[test]
+fn test_tls_forwarding() {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let (cert_path, key_path) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+    std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+    std::fs::write(&key_path, cert.signing_key.serialize_pem()).unwrap();
+
+    let source = SyslogSource::new(SyslogConfig {
+        tls: Some(SyslogTlsConfig { listen: "127.0.0.1:0".parse().unwrap(), cert: cert_path.clone(), key: key_path }),
+        ..Default::default()
+    })
+    .unwrap();
+    let address = source.tls_addr().unwrap();
+
+    let extra = format!(
+        "address = \"{}\"\ntransport = \"tls\"\nca = {:?}\nserver_name = \"localhost\"",
+        address,
+        cert_path.to_str().unwrap()
+    );
+    let tx = SyslogSink::new(config(&extra), host()).unwrap().spawn();
+    // Spaced out so that the server's session tickets arrive in between.
+    let mut ports = Vec::new();
+    for sequence in 0..4 {
+        tx.send(event(sequence)).unwrap();
+        let (raw, peer) = source.receive_from().unwrap();
+        let message = syslog::parse(&String::from_utf8(raw).unwrap()).unwrap();
+        assert_eq!(message.message, format!("event {}", sequence));
+        ports.push(peer.unwrap().addr.port());
+        thread::sleep(Duration::from_millis(50));
+    }
+    source.stop();
+    // All over the one connection.
+    assert!(ports.iter().all(|port| *port == ports[0]), "{:?}", ports);
+}
+
+#[test]
+fn test_config_validation() {
+    let host = host();
+    assert!(SyslogSink::new(config("address = \"127.0.0.1:514\"\nfacility = \"nope\""), host.clone()).is_err());
+    assert!(SyslogSink::new(config("address = \"127.0.0.1:514\"\ntransport = \"tls\""), host.clone()).is_err());
+    assert!(AppConfig::parse("[[output.syslog]]\naddress = \"x:1\"\nport = 1").is_err());
+}
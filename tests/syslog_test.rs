@@ -0,0 +1,261 @@
+mod common;
+
+use audit_collector::collector::Collector;
+use audit_collector::model::FieldValue;
+use audit_collector::model::AuditEvent;
+use audit_collector::source::syslog::SyslogTlsConfig;
+use audit_collector::source::{AuditSource, SyslogConfig, SyslogSource};
+use audit_collector::syslog::{self, FrameDecoder};
+use chrono::{Local, TimeZone};
+use crossbeam_channel::{unbounded, Receiver};
+use rustls::pki_types::{CertificateDer, ServerName};
+use std::io::{Read, Write};
+use std::net::{TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const RFC5424: &str = "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog 1234 ID47 [exampleSDID@32473 iut=\"3\" eventSource=\"Application\" eventID=\"1011\"][origin ip=\"192.0.2.1\"] An application event log entry";
+const RFC3164: &str = "<34>Oct 11 22:14:15 mymachine su[230]: 'su root' failed for lonvick on /dev/pts/8";
+
+fn loopback() -> Option<std::net::SocketAddr> {
+    Some("127.0.0.1:0".parse().unwrap())
+}
+
+fn start(source: SyslogSource) -> (Arc<SyslogSource>, Receiver<AuditEvent>) {
+    let source = Arc::new(source);
+    let (tx, rx) = unbounded();
+    let collector = Collector::new(source.clone(), tx);
+    thread::spawn(move || {
+        let _ = collector.run();
+    });
+    (source, rx)
+}
+
+fn recv(rx: &Receiver<AuditEvent>) -> AuditEvent {
+    rx.recv_timeout(Duration::from_secs(5)).expect("Failed to receive syslog event")
+}
+
+#[test]
+fn test_parse_rfc5424() {
+    let msg = syslog::parse(RFC5424).expect("valid RFC 5424 message");
+    assert_eq!(msg.version, Some(1));
+    assert_eq!(msg.facility, 20);
+    assert_eq!(msg.severity, 5);
+    assert_eq!(msg.hostname.as_deref(), Some("mymachine.example.com"));
+    assert_eq!(msg.app_name.as_deref(), Some("evntslog"));
+    assert_eq!(msg.procid.as_deref(), Some("1234"));
+    assert_eq!(msg.msgid.as_deref(), Some("ID47"));
+    assert_eq!(msg.structured_data.len(), 2);
+    assert_eq!(msg.structured_data[0].id, "exampleSDID@32473");
+    assert_eq!(msg.structured_data[0].params[2], ("eventID".to_string(), "1011".to_string()));
+    assert_eq!(msg.message, "An application event log entry");
+    assert_eq!(msg.timestamp.unwrap().to_rfc3339(), "2003-10-11T22:14:15.003+00:00");
+}
+
+#[test]
+fn test_parse_rfc5424_nil_values_and_escapes() {
+    let msg = syslog::parse(r#"<14>1 - - - - - [meta note="a \"quoted\" \] value"]"#).unwrap();
+    assert_eq!(msg.timestamp, None);
+    assert_eq!(msg.hostname, None);
+    assert_eq!(msg.app_name, None);
+    assert_eq!(msg.structured_data[0].params[0].1, r#"a "quoted" ] value"#);
+    assert_eq!(msg.message, "");
+}
+
+#[test]
+fn test_parse_rfc3164() {
+    let msg = syslog::parse(RFC3164).expect("valid RFC 3164 message");
+    assert_eq!(msg.version, None);
+    assert_eq!(msg.facility, 4);
+    assert_eq!(msg.severity, 2);
+    assert!(msg.timestamp.is_some());
+    assert_eq!(msg.hostname.as_deref(), Some("mymachine"));
+    assert_eq!(msg.app_name.as_deref(), Some("su"));
+    assert_eq!(msg.procid.as_deref(), Some("230"));
+    assert_eq!(msg.message, "'su root' failed for lonvick on /dev/pts/8");
+}
+
+#[test]
+fn test_parse_rejects_invalid_pri() {
+    assert!(syslog::parse("no pri here").is_none());
+    assert!(syslog::parse("<192>1 - - - - - -").is_none());
+    assert!(syslog::parse("<abc>hello").is_none());
+}
+
+#[test]
+fn test_bsd_timestamp_from_last_year() {
+    let now = Local.with_ymd_and_hms(2027, 1, 1, 0, 5, 0).unwrap();
+    let local = |s: &str| syslog::parse_bsd_timestamp_at(s, now).unwrap().0.with_timezone(&Local);
+
+    assert_eq!(local("Dec 31 23:59:58 host app: bye"), Local.with_ymd_and_hms(2026, 12, 31, 23, 59, 58).unwrap());
+    // Clocks drift, so a little ahead of now is still this year.
+    assert_eq!(local("Jan  1 12:00:00 host app: hi"), Local.with_ymd_and_hms(2027, 1, 1, 12, 0, 0).unwrap());
+    assert_eq!(local("Feb  2 00:00:00 host app: hi"), Local.with_ymd_and_hms(2026, 2, 2, 0, 0, 0).unwrap());
+    assert_eq!(syslog::parse_bsd_timestamp_at("Dec 31 23:59:58 host", now).unwrap().1, " host");
+}
+
+#[test]
+fn test_frame_decoder_mixed_framing() {
+    let mut decoder = FrameDecoder::new();
+    decoder.push(b"15 <13>hello");
+    assert_eq!(decoder.next_frame(), None);
+    decoder.push(b" world<13>line one\n<13>line two\r\n<13>tail");
+    assert_eq!(decoder.next_frame().unwrap(), b"<13>hello world".to_vec());
+    assert_eq!(decoder.next_frame().unwrap(), b"<13>line one".to_vec());
+    assert_eq!(decoder.next_frame().unwrap(), b"<13>line two".to_vec());
+    assert_eq!(decoder.next_frame(), None);
+    assert_eq!(decoder.finish().unwrap(), b"<13>tail".to_vec());
+}
+
+#[test]
+fn test_frame_decoder_drops_all_of_an_oversized_line() {
+    let mut decoder = FrameDecoder::new();
+    let mut stream = b"<13>".to_vec();
+    stream.extend((0..100 * 1024).map(|i| b'0' + (i % 10) as u8));
+    stream.extend_from_slice(b"\n<13>next\n");
+
+    let mut frames = Vec::new();
+    for chunk in stream.chunks(8 * 1024) {
+        decoder.push(chunk);
+        while let Some(frame) = decoder.next_frame() {
+            frames.push(frame);
+        }
+    }
+    assert_eq!(frames, vec![b"<13>next".to_vec()]);
+    assert_eq!(decoder.finish(), None);
+
+    // A connection closed mid-line leaves nothing behind.
+    decoder.push(&stream[..80 * 1024]);
+    assert_eq!(decoder.next_frame(), None);
+    assert_eq!(decoder.finish(), None);
+}
+
+#[test]
+fn test_udp_receiver() {
+    let source = SyslogSource::new(SyslogConfig { udp: loopback(), ..Default::default() }).unwrap();
+    let addr = source.udp_addr().unwrap();
+    let (source, rx) = start(source);
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.send_to(RFC5424.as_bytes(), addr).unwrap();
+
+    let event = recv(&rx);
+    assert_eq!(event.record_type, 1);
+    assert_eq!(event.fields.get("app_name"), Some(&FieldValue::from("evntslog")));
+    assert_eq!(event.fields.get("facility"), Some(&FieldValue::from("local4")));
+    assert_eq!(event.fields.get("severity"), Some(&FieldValue::from("notice")));
+    assert_eq!(event.fields.get("sd.origin.ip"), Some(&FieldValue::from("192.0.2.1")));
+    assert_eq!(event.fields.get("transport"), Some(&FieldValue::from("udp")));
+    assert_eq!(event.fields.get("peer"), Some(&FieldValue::from(client.local_addr().unwrap().to_string())));
+    source.stop();
+}
+
+#[test]
+fn test_receiver_queue_is_bounded() {
+    let source = SyslogSource::new(SyslogConfig { udp: loopback(), queue_size: 2, ..Default::default() }).unwrap();
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for n in 0..5 {
+        client.send_to(format!("<34>Oct 11 22:14:15 host app: message {}", n).as_bytes(), source.udp_addr().unwrap()).unwrap();
+    }
+
+    common::wait_for("three messages to be dropped", || source.dropped() == 3);
+    for n in 0..2 {
+        let (raw, _) = source.receive_from().unwrap();
+        assert!(String::from_utf8(raw).unwrap().ends_with(&format!("message {}", n)));
+    }
+    assert_eq!(SyslogConfig::default().queue_size, 10_000);
+    source.stop();
+}
+
+#[test]
+fn test_receiver_limits_connections() {
+    let config = SyslogConfig { tcp: loopback(), max_connections: 2, idle_timeout_secs: 1, ..Default::default() };
+    let source = SyslogSource::new(config).unwrap();
+    let addr = source.tcp_addr().unwrap();
+
+    let first = TcpStream::connect(addr).unwrap();
+    let _second = TcpStream::connect(addr).unwrap();
+    common::wait_for("two connections", || source.active_connections() == 2);
+
+    // Past the limit the connection is closed straight away.
+    let mut third = TcpStream::connect(addr).unwrap();
+    third.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    assert_eq!(third.read(&mut [0u8; 1]).unwrap_or(0), 0);
+    assert_eq!(source.rejected_connections(), 1);
+
+    // Closing one frees its slot; idle ones are closed after the timeout.
+    drop(first);
+    common::wait_for("a free slot", || source.active_connections() == 1);
+    let _fourth = TcpStream::connect(addr).unwrap();
+    common::wait_for("the new connection", || source.active_connections() == 2);
+    common::wait_for("idle connections to close", || source.active_connections() == 0);
+    assert_eq!(source.rejected_connections(), 1);
+    source.stop();
+}
+
+#[test]
+fn test_tcp_receiver_octet_counting_and_newline() {
+    let source = SyslogSource::new(SyslogConfig { tcp: loopback(), ..Default::default() }).unwrap();
+    let addr = source.tcp_addr().unwrap();
+    let (source, rx) = start(source);
+
+    let mut client = TcpStream::connect(addr).unwrap();
+    write!(client, "{} {}", RFC5424.len(), RFC5424).unwrap();
+    writeln!(client, "{}", RFC3164).unwrap();
+    client.flush().unwrap();
+
+    let first = recv(&rx);
+    assert_eq!(first.fields.get("msgid"), Some(&FieldValue::from("ID47")));
+    assert_eq!(first.fields.get("transport"), Some(&FieldValue::from("tcp")));
+    assert_eq!(first.fields.get("peer"), Some(&FieldValue::from(client.local_addr().unwrap().to_string())));
+
+    let second = recv(&rx);
+    assert_eq!(second.fields.get("app_name"), Some(&FieldValue::from("su")));
+    assert_eq!(second.fields.get("procid"), Some(&FieldValue::UInt(230)));
+    source.stop();
+}
+
+#[test]
+fn test_tls_receiver() {
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let cert_path = dir.path().join("cert.pem");
+    let key_path = dir.path().join("key.pem");
+    std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+    std::fs::write(&key_path, cert.signing_key.serialize_pem()).unwrap();
+
+    let source = SyslogSource::new(SyslogConfig {
+        tls: Some(SyslogTlsConfig { listen: loopback().unwrap(), cert: cert_path, key: key_path }),
+        ..Default::default()
+    })
+    .unwrap();
+    let addr = source.tls_addr().unwrap();
+    let (source, rx) = start(source);
+
+    let mut roots = rustls::RootCertStore::empty();
+    roots.add(CertificateDer::from(cert.cert.der().to_vec())).unwrap();
+    let client_config = rustls::ClientConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_safe_default_protocol_versions()
+    .unwrap()
+    .with_root_certificates(roots)
+    .with_no_client_auth();
+    let conn = rustls::ClientConnection::new(
+        Arc::new(client_config),
+        ServerName::try_from("localhost").unwrap(),
+    )
+    .unwrap();
+    let tcp = TcpStream::connect(addr).unwrap();
+    let local = tcp.local_addr().unwrap();
+    let mut tls = rustls::StreamOwned::new(conn, tcp);
+    write!(tls, "{} {}", RFC5424.len(), RFC5424).unwrap();
+    tls.flush().unwrap();
+
+    let event = recv(&rx);
+    assert_eq!(event.fields.get("hostname"), Some(&FieldValue::from("mymachine.example.com")));
+    assert_eq!(event.fields.get("transport"), Some(&FieldValue::from("tls")));
+    assert_eq!(event.fields.get("peer"), Some(&FieldValue::from(local.to_string())));
+    source.stop();
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use audit_collector::config::AppConfig;
+use audit_collector::host::HostMetadata;
+use audit_collector::model::AuditEvent;
+use audit_collector::output::batch::RetryConfig;
+use audit_collector::output::{Sink, WebhookSink, WebhookSinkConfig};
+use std::io::Read;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn config(addr: SocketAddr, extra: &str) -> WebhookSinkConfig {
+    let text = format!("[[output.webhook]]\nurl = \"http://{}/hook\"\n{}", addr, extra);
+    AppConfig::parse(&text).unwrap().output.webhook.remove(0)
+}
+
+fn gunzip(body: &[u8]) -> String {
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(body).read_to_string(&mut text).unwrap();
+    text
+}
+
+#[test]
+fn test_ndjson_batches_with_gzip_and_headers() {
+    let (addr, stub) = common::serve("/hook", &[]);
+    let extra = r#"
+        encoding = "ndjson"
+        gzip = true
+        headers = { Authorization = "Bearer s3cret", "X-Source" = "collector" }
+        batch = { max_events = 3, flush_interval_ms = 200 }
+    "#;
+    let tx = WebhookSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    for i in 0..7 {
+        tx.send(common::event(i)).unwrap();
+    }
+
+    // Two full batches right away, the remainder after the flush interval.
+    let requests = stub.requests(3);
+    let sizes: Vec<usize> = requests.iter().map(|(_, body)| gunzip(body).lines().count()).collect();
+    assert_eq!(sizes, vec![3, 3, 1]);
+
+    let (headers, body) = &requests[0];
+    assert_eq!(headers["content-type"], "application/x-ndjson");
+    assert_eq!(headers["content-encoding"], "gzip");
+    assert_eq!(headers["authorization"], "Bearer s3cret");
+    assert_eq!(headers["x-source"], "collector");
+    let first: serde_json::Value = serde_json::from_str(gunzip(body).lines().next().unwrap()).unwrap();
+    assert_eq!(first["sequence"], 0);
+}
+
+#[test]
+fn test_json_array_retries_transient_failures() {
+    let (addr, stub) = common::serve("/hook", &[503, 429]);
+    let extra = "batch = { max_events = 2 }\nretry = { initial_backoff_ms = 10, max_backoff_ms = 50 }";
+    let tx = WebhookSink::new(config(addr, extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(common::event(1)).unwrap();
+    tx.send(common::event(2)).unwrap();
+
+    let requests = stub.requests(3);
+    assert!(requests.iter().all(|(_, body)| body == &requests[0].1), "retries resend the same batch");
+    let batch: serde_json::Value = serde_json::from_slice(&requests[2].1).unwrap();
+    assert_eq!(batch.as_array().unwrap().len(), 2);
+    assert_eq!(requests[0].0["content-type"], "application/json");
+}
+
+#[test]
+fn test_dead_letters_after_exhausting_retries() {
+    let dir = tempfile::tempdir().unwrap();
+    // Three 500s use up the attempts; the 400 is not retried at all.
+    let (addr, stub) = common::serve("/hook", &[500, 500, 500, 400]);
+    let extra = format!(
+        "dead_letter_dir = {:?}\nbatch = {{ max_events = 1 }}\nretry = {{ max_attempts = 3, initial_backoff_ms = 1 }}",
+        dir.path().to_str().unwrap()
+    );
+    let tx = WebhookSink::new(config(addr, &extra), Arc::new(HostMetadata::default())).unwrap().spawn();
+    tx.send(common::event(1)).unwrap();
+    tx.send(common::event(2)).unwrap();
+    tx.send(common::event(3)).unwrap();
+
+    assert_eq!(stub.requests(5).len(), 5);
+    thread::sleep(Duration::from_millis(100));
+    let mut files: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| std::fs::read_to_string(e.unwrap().path()).unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(files.len(), 2);
+    let dead: Vec<AuditEvent> = files.iter().map(|f| serde_json::from_str(f.trim()).unwrap()).collect();
+    assert_eq!(dead.iter().map(|e| e.sequence).collect::<Vec<_>>(), vec![1, 2]);
+}
+
+#[test]
+fn test_retry_delays_are_jittered_and_capped() {
+    let retry = RetryConfig { max_attempts: 10, initial_backoff_ms: 100, max_backoff_ms: 1000 };
+    for attempt in 1..10 {
+        let cap = (100u64 << (attempt - 1)).min(1000);
+        assert!(retry.delay(attempt) <= Duration::from_millis(cap));
+    }
+    let delays: Vec<Duration> = (0..20).map(|_| retry.delay(5)).collect();
+    assert!(delays.iter().any(|d| *d != delays[0]), "delays are randomized");
+}
+
+#[test]
+fn test_invalid_headers_are_rejected() {
+    let addr: SocketAddr = "127.0.0.1:1".parse().unwrap();
+    let host = Arc::new(HostMetadata::default());
+    assert!(WebhookSink::new(config(addr, "headers = { \"bad header\" = \"x\" }"), host.clone()).is_err());
+    let mut bad_url = config(addr, "");
+    bad_url.url = "not a url".to_string();
+    assert!(WebhookSink::new(bad_url, host).is_err());
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use audit_collector::host::HostMetadata;
+use audit_collector::model::{AuditEvent, FieldValue};
+use audit_collector::output::sse::StreamEvent;
+use audit_collector::output::{EventStream, OutputOptions};
+use audit_collector::ws::{self, ServerMessage, Session};
+use axum::extract::ws::WebSocketUpgrade;
+use axum::extract::State;
+use axum::response::Response;
+use axum::routing::get;
+use axum::Router;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+fn event(sequence: u64, outcome: &str) -> AuditEvent {
+    let mut event = common::event(sequence);
+    event.record_type = 1112;
+    event.fields.insert("event.outcome".to_string(), FieldValue::from(outcome));
+    event
+}
+
+fn live(seq: u64, outcome: &str) -> StreamEvent {
+    StreamEvent { id: seq, event: event(seq, outcome) }
+}
+
+fn session() -> Session {
+    Session::new(OutputOptions::default(), Arc::new(HostMetadata::default()))
+}
+
+/// The subscription and sequence of each event message.
+fn events(messages: &[ServerMessage]) -> Vec<(&str, u64)> {
+    messages
+        .iter()
+        .filter_map(|m| match m {
+            ServerMessage::Event { id, seq, .. } => Some((id.as_str(), *seq)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn error_of(messages: Vec<ServerMessage>) -> String {
+    match messages.as_slice() {
+        [ServerMessage::Error { message, .. }] => message.clone(),
+        other => panic!("expected an error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_subscriptions_are_multiplexed_with_their_own_filters() {
+    let stream = EventStream::new(10, 0);
+    let mut session = session();
+    let reply = session.handle(r#"{"type":"subscribe","id":"fails","filter":"event.outcome == 'failure'"}"#, &stream);
+    assert_eq!(reply, vec![ServerMessage::Subscribed { id: "fails".to_string() }]);
+    session.handle(r#"{"type":"subscribe","id":"all","window":0}"#, &stream);
+
+    assert_eq!(events(&session.deliver(&live(1, "success"))), vec![("all", 1)]);
+    assert_eq!(events(&session.deliver(&live(2, "failure"))), vec![("all", 2), ("fails", 2)]);
+
+    session.handle(r#"{"type":"pause","id":"all"}"#, &stream);
+    assert_eq!(events(&session.deliver(&live(3, "failure"))), vec![("fails", 3)]);
+    let reply = session.handle(r#"{"type":"pause"}"#, &stream);
+    assert_eq!(reply, vec![ServerMessage::Paused { ids: vec!["all".to_string(), "fails".to_string()] }]);
+    assert!(session.deliver(&live(4, "failure")).is_empty());
+    session.handle(r#"{"type":"resume"}"#, &stream);
+
+    let reply = session.handle(r#"{"type":"unsubscribe","id":"all"}"#, &stream);
+    assert_eq!(reply, vec![ServerMessage::Unsubscribed { id: "all".to_string() }]);
+    assert_eq!(events(&session.deliver(&live(5, "failure"))), vec![("fails", 5)]);
+}
+
+#[test]
+fn test_sampling_sends_the_requested_fraction() {
+    let stream = EventStream::new(10, 0);
+    let mut session = session();
+    session.handle(r#"{"type":"subscribe","id":"s","sample":0.25,"window":0}"#, &stream);
+    let sent: usize = (0..100).map(|s| events(&session.deliver(&live(s, "success"))).len()).sum();
+    assert_eq!(sent, 25);
+
+    let reply = session.handle(r#"{"type":"sample","id":"s","rate":0}"#, &stream);
+    assert_eq!(reply, vec![ServerMessage::Sampling { id: "s".to_string(), rate: 0.0 }]);
+    assert!((0..10).all(|s| session.deliver(&live(s, "success")).is_empty()));
+    session.handle(r#"{"type":"sample","id":"s","rate":1}"#, &stream);
+    assert!((0..10).all(|s| events(&session.deliver(&live(s, "success"))).len() == 1));
+
+    assert!(error_of(session.handle(r#"{"type":"sample","id":"s","rate":1.5}"#, &stream)).contains("between 0 and 1"));
+}
+
+#[test]
+fn test_unacknowledged_events_apply_backpressure() {
+    let stream = EventStream::new(10, 0);
+    let mut session = session();
+    session.handle(r#"{"type":"subscribe","id":"s","window":2}"#, &stream);
+    assert_eq!(events(&session.deliver(&live(1, "success"))).len(), 1);
+    assert_eq!(events(&session.deliver(&live(2, "success"))).len(), 1);
+
+    // The window is full: one signal, then the events are dropped.
+    let full = session.deliver(&live(3, "success"));
+    let paused = ServerMessage::Backpressure { id: "s".to_string(), paused: true, unacked: 2, dropped: 0 };
+    assert_eq!(full, vec![paused]);
+    assert!(session.deliver(&live(4, "success")).is_empty());
+
+    // Acknowledging the first event makes room and reports what was dropped.
+    let reply = session.handle(r#"{"type":"ack","id":"s","seq":1}"#, &stream);
+    assert_eq!(reply, vec![ServerMessage::Backpressure { id: "s".to_string(), paused: false, unacked: 1, dropped: 2 }]);
+    assert_eq!(events(&session.deliver(&live(5, "success"))), vec![("s", 5)]);
+    assert!(session.handle(r#"{"type":"ack","id":"s","seq":5}"#, &stream).is_empty());
+}
+
+#[test]
+fn test_history_and_bad_commands() {
+    let stream = EventStream::new(10, 10);
+    for s in 0..6 {
+        stream.publish(event(s, if s % 2 == 0 { "failure" } else { "success" }));
+    }
+    let mut session = session();
+    session.handle(r#"{"type":"subscribe","id":"fails","filter":"event.outcome == 'failure'"}"#, &stream);
+    let reply = session.handle(r#"{"type":"history","id":"fails","limit":2}"#, &stream);
+    let sequences: Vec<u64> = reply
+        .iter()
+        .filter_map(|m| match m {
+            ServerMessage::Event { event, history: true, .. } => event["sequence"].as_u64(),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(sequences, vec![2, 4]);
+    assert_eq!(reply.last(), Some(&ServerMessage::HistoryEnd { id: "fails".to_string(), count: 2 }));
+
+    assert!(error_of(session.handle("not json", &stream)).starts_with("Invalid message"));
+    assert!(error_of(session.handle(r#"{"type":"jump"}"#, &stream)).starts_with("Invalid message"));
+    assert_eq!(error_of(session.handle(r#"{"type":"subscribe","id":"fails"}"#, &stream)), "Already subscribed");
+    assert_eq!(error_of(session.handle(r#"{"type":"pause","id":"nope"}"#, &stream)), "No such subscription");
+    assert!(!error_of(session.handle(r#"{"type":"subscribe","id":"x","filter":"uid =="}"#, &stream)).is_empty());
+    for n in 1..ws::MAX_SUBSCRIPTIONS {
+        session.handle(&format!(r#"{{"type":"subscribe","id":"s{}"}}"#, n), &stream);
+    }
+    assert!(error_of(session.handle(r#"{"type":"subscribe","id":"one-too-many"}"#, &stream)).contains("At most"));
+}
+
+async fn upgrade(State(stream): State<Arc<EventStream>>, upgrade: WebSocketUpgrade) -> Response {
+    let host = Arc::new(HostMetadata::default());
+    upgrade.on_upgrade(move |socket| ws::serve(socket, stream, OutputOptions::default(), host))
+}
+
+#[tokio::test]
+async fn test_socket_round_trip() {
+    let stream = Arc::new(EventStream::new(100, 10));
+    let app = Router::new().route("/api/ws", get(upgrade)).with_state(stream.clone());
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let (mut socket, _) = tokio_tungstenite::connect_async(format!("ws://{}/api/ws", addr)).await.unwrap();
+    let command = json!({"type": "subscribe", "id": "fails", "filter": "event.outcome == 'failure'"});
+    socket.send(Message::Text(command.to_string().into())).await.unwrap();
+    let reply = next_json(&mut socket).await;
+    assert_eq!(reply, json!({"type": "subscribed", "id": "fails"}));
+
+    stream.publish(event(1, "success"));
+    stream.publish(event(2, "failure"));
+    let message = next_json(&mut socket).await;
+    assert_eq!((message["type"].as_str(), message["id"].as_str()), (Some("event"), Some("fails")));
+    assert_eq!(message["event"]["sequence"], 2);
+
+    socket.send(Message::Binary(vec![1, 2, 3].into())).await.unwrap();
+    assert_eq!(next_json(&mut socket).await["type"], "error");
+    socket.close(None).await.unwrap();
+}
+
+async fn next_json<S>(socket: &mut S) -> Value
+where
+    S: futures::Stream<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next()).await.unwrap().unwrap().unwrap();
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
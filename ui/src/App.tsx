@@ -8,12 +8,37 @@ const { Header, Content } = Layout;
 const { Title } = Typography;
 
 interface AuditEvent {
+  id: string;
   timestamp: string;
   record_type: number;
   sequence: number;
-  fields: Record<string, string>;
+  native_id?: { kind: string; value: number | string };
+  fields: Record<string, FieldValue>;
 }
 
+// Field values are typed on the backend: strings, numbers, booleans, lists or maps.
+type FieldValue = string | number | boolean | FieldValue[] | { [key: string]: FieldValue };
+
+const formatField = (v: FieldValue | undefined): string =>
+  v === undefined || v === null ? '' : typeof v === 'object' ? JSON.stringify(v) : String(v);
+
+// Normalized events use ECS names and keep the source fields under `raw`;
+// the plain names cover events from collectors with normalization disabled.
+const field = (r: AuditEvent, ...keys: string[]): FieldValue | undefined =>
+  keys.map((k) => r.fields[k]).find((v) => v !== undefined && v !== '?');
+
+// auditd keeps the quotes around string values (`exe="/usr/bin/cat"`).
+const column = (v: FieldValue | undefined): string => formatField(v).replace(/^"(.*)"$/, '$1') || '-';
+
+// auditd's `hostname` is the remote peer of a login, not the machine that logged it.
+const hostOf = (r: AuditEvent): FieldValue | undefined =>
+  field(r, 'host.name', 'computer', 'node') ?? (r.native_id?.kind === 'audit_serial' ? undefined : field(r, 'hostname'));
+
+const PROCESS_KEYS = ['process.executable', 'process.name', 'process', 'exe', 'comm'];
+const USER_KEYS = ['user.name', 'acct', 'user', 'TargetUserName', 'SubjectUserName'];
+
+const SHOWN_FIELDS = ['message', 'process', 'pid', 'process.executable', 'process.name', 'process.pid', 'raw'];
+
 interface FilterConfig {
   process?: string;
   message?: string;
@@ -24,6 +49,20 @@ interface FilterConfig {
   library?: string;
 }
 
+// API token, from `?token=` once and then remembered; none when auth is disabled.
+const params = new URLSearchParams(window.location.search);
+const token = params.get('token') ?? localStorage.getItem('token');
+if (params.has('token')) {
+  // Keep the token out of the address bar, history and bookmarks.
+  params.delete('token');
+  const query = params.toString();
+  window.history.replaceState(null, '', `${window.location.pathname}${query ? `?${query}` : ''}${window.location.hash}`);
+}
+if (token) {
+  localStorage.setItem('token', token);
+  axios.defaults.headers.common['Authorization'] = `Bearer ${token}`;
+}
+
 function App() {
   const [events, setEvents] = useState<AuditEvent[]>([]);
   const [loading, setLoading] = useState(false);
@@ -38,11 +77,16 @@ function App() {
 
   // Event Stream (SSE)
   useEffect(() => {
-    const eventSource = new EventSource('/api/events');
+    // EventSource cannot send headers, so the token goes in the query.
+    const eventSource = new EventSource(token ? `/api/events?access_token=${encodeURIComponent(token)}` : '/api/events');
     eventSource.onmessage = (e) => {
       const newEvent: AuditEvent = JSON.parse(e.data);
       setEvents(prev => [newEvent, ...prev].slice(0, 500)); // Keep last 500
     };
+    eventSource.addEventListener('lagged', (e) => {
+      const { skipped } = JSON.parse((e as MessageEvent).data);
+      message.warning(`Stream fell behind; ${skipped} events were skipped`);
+    });
     eventSource.onerror = () => {
       // Retry connection automatically handled by browser usually, but good to know
     };
@@ -67,16 +111,18 @@ function App() {
   const columns = [
     { title: 'Time', dataIndex: 'timestamp', key: 'timestamp', width: 200, render: (t: string) => new Date(t).toLocaleTimeString() },
     { title: 'Type', dataIndex: 'record_type', key: 'record_type', width: 80 },
-    { title: 'Process', key: 'process', render: (_: any, r: AuditEvent) => r.fields['process'] || '-' },
-    { title: 'PID', key: 'pid', width: 80, render: (_: any, r: AuditEvent) => r.fields['pid'] || '-' },
+    { title: 'Host', key: 'host', width: 160, render: (_: any, r: AuditEvent) => column(hostOf(r)) },
+    { title: 'User', key: 'user', width: 120, render: (_: any, r: AuditEvent) => column(field(r, ...USER_KEYS)) },
+    { title: 'Process', key: 'process', render: (_: any, r: AuditEvent) => column(field(r, ...PROCESS_KEYS)) },
+    { title: 'PID', key: 'pid', width: 80, render: (_: any, r: AuditEvent) => column(field(r, 'process.pid', 'pid')) },
     {
       title: 'Message / Details', key: 'msg', render: (_: any, r: AuditEvent) => (
         <div>
-          {r.fields['message'] && <div style={{ fontWeight: 500 }}>{r.fields['message']}</div>}
+          {r.fields['message'] !== undefined && <div style={{ fontWeight: 500 }}>{formatField(r.fields['message'])}</div>}
           <div style={{ fontSize: '0.8em', color: '#888' }}>
             {Object.entries(r.fields)
-              .filter(([k]) => !['message', 'process', 'pid'].includes(k))
-              .map(([k, v]) => <span key={k} style={{ marginRight: 8 }}><Tag>{k}: {v}</Tag></span>)}
+              .filter(([k]) => !SHOWN_FIELDS.includes(k))
+              .map(([k, v]) => <span key={k} style={{ marginRight: 8 }}><Tag>{k}: {formatField(v)}</Tag></span>)}
           </div>
         </div>
       )
@@ -91,7 +137,11 @@ function App() {
         <Table
           dataSource={events}
           columns={columns}
-          rowKey={(r) => r.timestamp + r.sequence}
+          rowKey="id"
+          expandable={{
+            rowExpandable: (r) => r.fields['raw'] !== undefined,
+            expandedRowRender: (r) => <pre style={{ margin: 0 }}>{JSON.stringify(r.fields['raw'], null, 2)}</pre>,
+          }}
           pagination={{ pageSize: 15 }}
           size="small"
         />